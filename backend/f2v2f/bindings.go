@@ -7,11 +7,15 @@ package f2v2f
 #include <stdlib.h>
 
 int32_t f2v2f_init();
+int32_t f2v2f_init_with_options(const char* log_level, void* log_callback);
 void* f2v2f_encode_create(uint32_t width, uint32_t height, uint32_t fps, size_t chunk_size, bool use_compression, int32_t compression_level);
 int32_t f2v2f_encode_file(void* handle, const char* input_path, const char* output_path, uint64_t* encoded_size_out, size_t* chunk_size_out, void* progress_callback);
+int32_t f2v2f_encode_set_overwrite(void* handle, bool overwrite);
 void f2v2f_encode_free(void* handle);
 void* f2v2f_decode_create_with_params(uint32_t width, uint32_t height, size_t chunk_size, bool use_compression, uint64_t encoded_size);
-int32_t f2v2f_decode_file(void* handle, const char* input_path, const char* output_path, void* progress_callback);
+void* f2v2f_decode_create_from_video(const char* video_path);
+int32_t f2v2f_decode_file(void* handle, const char* input_path, const char* output_path, uint64_t* extracted_size_out, char** checksum_out, bool* was_compressed_out, void* progress_callback);
+int32_t f2v2f_decode_set_overwrite(void* handle, bool overwrite);
 void f2v2f_decode_free(void* handle);
 char* f2v2f_version();
 char* f2v2f_get_last_error();
@@ -20,6 +24,7 @@ void f2v2f_free_string(char* s);
 import "C"
 import (
 	"errors"
+	"runtime"
 	"unsafe"
 )
 
@@ -27,11 +32,27 @@ func Init() int32 {
 	return int32(C.f2v2f_init())
 }
 
+// InitWithOptions initializes the library logging at logLevel (e.g. "warn"
+// or "f2v2f=debug"); only the first Init call in the process takes effect
+func InitWithOptions(logLevel string) error {
+	runtime.LockOSThread()
+	defer runtime.UnlockOSThread()
+
+	cLevel := C.CString(logLevel)
+	defer C.free(unsafe.Pointer(cLevel))
+	if C.f2v2f_init_with_options(cLevel, nil) != 0 {
+		return getLastError()
+	}
+	return nil
+}
+
 func Version() string {
 	res := C.f2v2f_version()
 	return C.GoString(res)
 }
 
+// getLastError reads the calling thread's last error; callers must hold
+// runtime.LockOSThread across the failing call and this one
 func getLastError() error {
 	ptr := C.f2v2f_get_last_error()
 	if ptr == nil {
@@ -66,6 +87,9 @@ func NewEncoder(width, height, fps uint32, chunkSize int, useCompression bool, c
 }
 
 func (e *Encoder) Encode(inputPath, outputPath string) (*EncodeResult, error) {
+	runtime.LockOSThread()
+	defer runtime.UnlockOSThread()
+
 	cInput := C.CString(inputPath)
 	defer C.free(unsafe.Pointer(cInput))
 	cOutput := C.CString(outputPath)
@@ -83,6 +107,15 @@ func (e *Encoder) Encode(inputPath, outputPath string) (*EncodeResult, error) {
 	}, nil
 }
 
+// SetOverwrite chooses whether later Encode calls replace an existing
+// output (the default) or fail because it exists
+func (e *Encoder) SetOverwrite(overwrite bool) error {
+	if C.f2v2f_encode_set_overwrite(e.handle, C.bool(overwrite)) != 0 {
+		return errors.New("invalid encoder handle")
+	}
+	return nil
+}
+
 func (e *Encoder) Close() {
 	if e.handle != nil {
 		C.f2v2f_encode_free(e.handle)
@@ -108,15 +141,56 @@ func NewDecoder(width, height uint32, chunkSize int, useCompression bool, encode
 	return &Decoder{handle: handle}, nil
 }
 
-func (d *Decoder) Decode(inputPath, outputPath string) error {
+// NewDecoderForVideo creates a decoder configured from the video's manifest or probed resolution
+func NewDecoderForVideo(videoPath string) (*Decoder, error) {
+	runtime.LockOSThread()
+	defer runtime.UnlockOSThread()
+
+	cPath := C.CString(videoPath)
+	defer C.free(unsafe.Pointer(cPath))
+
+	handle := C.f2v2f_decode_create_from_video(cPath)
+	if handle == nil {
+		return nil, getLastError()
+	}
+	return &Decoder{handle: handle}, nil
+}
+
+type DecodeResult struct {
+	ExtractedSize uint64
+	Checksum      string
+	WasCompressed bool
+}
+
+func (d *Decoder) Decode(inputPath, outputPath string) (*DecodeResult, error) {
+	runtime.LockOSThread()
+	defer runtime.UnlockOSThread()
+
 	cInput := C.CString(inputPath)
 	defer C.free(unsafe.Pointer(cInput))
 	cOutput := C.CString(outputPath)
 	defer C.free(unsafe.Pointer(cOutput))
 
-	res := C.f2v2f_decode_file(d.handle, cInput, cOutput, nil)
+	var extractedSize C.uint64_t
+	var checksum *C.char
+	var wasCompressed C.bool
+	res := C.f2v2f_decode_file(d.handle, cInput, cOutput, &extractedSize, &checksum, &wasCompressed, nil)
 	if res != 0 {
-		return getLastError()
+		return nil, getLastError()
+	}
+	defer C.f2v2f_free_string(checksum)
+	return &DecodeResult{
+		ExtractedSize: uint64(extractedSize),
+		Checksum:      C.GoString(checksum),
+		WasCompressed: bool(wasCompressed),
+	}, nil
+}
+
+// SetOverwrite chooses whether later Decode calls replace an existing
+// output (the default) or fail because it exists
+func (d *Decoder) SetOverwrite(overwrite bool) error {
+	if C.f2v2f_decode_set_overwrite(d.handle, C.bool(overwrite)) != 0 {
+		return errors.New("invalid decoder handle")
 	}
 	return nil
 }
@@ -347,7 +347,7 @@ func handleDecode(c *fiber.Ctx) error {
 		}
 		defer decoder.Close()
 
-		if err := decoder.Decode(inputPath, outputPath); err != nil {
+		if _, err := decoder.Decode(inputPath, outputPath); err != nil {
 			job.Status = StatusFailed
 			job.ErrorMessage = err.Error()
 			broadcastJob(job)
@@ -49,7 +49,7 @@ func main() {
 	}
 	defer decoder.Close()
 
-	err = decoder.Decode(videoFile, outputFile)
+	_, err = decoder.Decode(videoFile, outputFile)
 	if err != nil {
 		log.Fatalf("Decode failed: %v", err)
 	}
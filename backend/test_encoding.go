@@ -89,7 +89,7 @@ func main() {
 	fmt.Println("⏱️  Starting decoding...")
 	startTime = time.Now()
 
-	err = decoder.Decode(outputPath, recoveredPath)
+	_, err = decoder.Decode(outputPath, recoveredPath)
 	if err != nil {
 		fmt.Printf("❌ Decoding failed: %v\n", err)
 		os.Exit(1)
@@ -58,7 +58,7 @@ func main() {
 	}
 	defer decoder.Close()
 
-	err = decoder.Decode(encodedVideo, decodedFile)
+	_, err = decoder.Decode(encodedVideo, decodedFile)
 	if err != nil {
 		log.Fatalf("Decode failed: %v", err)
 	}
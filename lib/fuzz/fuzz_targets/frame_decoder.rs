@@ -0,0 +1,27 @@
+//! Frames read back from a video are arbitrary pixels; decoding them must
+//! return data that matches its CRC or an error, never panic
+
+#![no_main]
+
+use f2v2f::codec::FrameCodec;
+use image::RgbaImage;
+use libfuzzer_sys::fuzz_target;
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 64;
+const FRAME_BYTES: usize = (WIDTH * HEIGHT * 4) as usize;
+
+fuzz_target!(|data: &[u8]| {
+    // First byte picks the chunk size, the rest fills whole frames
+    let Some((&chunk, pixels)) = data.split_first() else {
+        return;
+    };
+    let frames: Vec<RgbaImage> = pixels
+        .chunks_exact(FRAME_BYTES)
+        .filter_map(|frame| RgbaImage::from_raw(WIDTH, HEIGHT, frame.to_vec()))
+        .collect();
+    let Ok(codec) = FrameCodec::new(WIDTH, HEIGHT, 0, chunk as usize + 1) else {
+        return;
+    };
+    let _ = codec.decode(&frames);
+});
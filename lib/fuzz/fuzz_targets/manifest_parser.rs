@@ -0,0 +1,17 @@
+//! Sidecars and embedded manifests come from wherever the video came from;
+//! parsing one must fail cleanly, and whatever parses must survive a round trip
+
+#![no_main]
+
+use f2v2f::manifest::Manifest;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(json) = std::str::from_utf8(data) else {
+        return;
+    };
+    if let Ok(manifest) = Manifest::from_json(json) {
+        let again = Manifest::from_json(&manifest.to_json().expect("parsed manifest serializes"));
+        assert_eq!(again.ok(), Some(manifest));
+    }
+});
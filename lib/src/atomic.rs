@@ -0,0 +1,206 @@
+//! Atomic output files
+//!
+//! Outputs are written to a temp file in the destination directory and
+//! renamed over the final path only once complete, so a failed or cancelled
+//! encode/decode never leaves a truncated file behind. The temp file is
+//! removed when dropped without being committed. Existing files are only
+//! replaced when overwriting is allowed.
+//!
+//! Outputs can also be staged in a separate scratch directory; if that is on
+//! another filesystem, commit copies the file next to the target first.
+
+use crate::error::{F2V2FError, Result};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+/// Staging file that replaces `target` on `commit`
+pub struct AtomicOutput {
+    temp: NamedTempFile,
+    target: PathBuf,
+    overwrite: bool,
+}
+
+impl AtomicOutput {
+    /// Create the staging file next to `target`, keeping its extension so
+    /// tools that pick a format from the file name (ffmpeg) still work
+    ///
+    /// Fails with `OutputExists` if `target` exists and `overwrite` is false.
+    pub fn new<P: AsRef<Path>>(target: P, overwrite: bool) -> Result<Self> {
+        Self::new_in(target, overwrite, None)
+    }
+
+    /// Like `new`, but stage in `dir` (if given) instead of next to `target`
+    pub fn new_in<P: AsRef<Path>>(target: P, overwrite: bool, dir: Option<&Path>) -> Result<Self> {
+        let target = target.as_ref().to_path_buf();
+        if !overwrite {
+            ensure_absent(&target)?;
+        }
+        let dir = dir.map_or_else(|| target_dir(&target), Path::to_path_buf);
+        let temp = staging_file(&target, &dir)?;
+        Ok(Self { temp, target, overwrite })
+    }
+
+    /// Path to write to
+    pub fn path(&self) -> &Path {
+        self.temp.path()
+    }
+
+    pub fn as_file(&self) -> &File {
+        self.temp.as_file()
+    }
+
+    /// Atomically move the finished file into place
+    pub fn commit(self) -> Result<()> {
+        match persist(self.temp, &self.target, self.overwrite) {
+            Err((F2V2FError::Io(_), Some(temp))) => {
+                // Staged on another filesystem: copy next to the target, then rename
+                let local = staging_file(&self.target, &target_dir(&self.target))?;
+                std::fs::copy(temp.path(), local.path())?;
+                local.as_file().sync_all()?;
+                drop(temp);
+                persist(local, &self.target, self.overwrite).map_err(|(e, _)| e)
+            }
+            result => result.map_err(|(e, _)| e),
+        }
+    }
+}
+
+fn target_dir(target: &Path) -> PathBuf {
+    match target.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Hidden temp file in `dir`, named after `target` and with its extension
+fn staging_file(target: &Path, dir: &Path) -> Result<NamedTempFile> {
+    let name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let suffix = target
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+
+    let prefix = format!(".{}.", name);
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(&prefix).suffix(&suffix);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        // Regular file permissions (minus umask) rather than 0600
+        builder.permissions(std::fs::Permissions::from_mode(0o666));
+    }
+    Ok(builder.tempfile_in(dir)?)
+}
+
+/// Rename `temp` onto `target`; hands the file back if the rename crossed
+/// filesystems
+fn persist(
+    temp: NamedTempFile,
+    target: &Path,
+    overwrite: bool,
+) -> std::result::Result<(), (F2V2FError, Option<NamedTempFile>)> {
+    let persisted = if overwrite {
+        temp.persist(target)
+    } else {
+        // Don't clobber a file that appeared while we were writing
+        temp.persist_noclobber(target)
+    };
+    persisted.map(|_| ()).map_err(|e| match e.error.kind() {
+        std::io::ErrorKind::AlreadyExists => (F2V2FError::OutputExists(target.display().to_string()), None),
+        std::io::ErrorKind::CrossesDevices => (
+            F2V2FError::Io(format!("Failed to move output into place: {}", e.error)),
+            Some(e.file),
+        ),
+        _ => (F2V2FError::Io(format!("Failed to move output into place: {}", e.error)), None),
+    })
+}
+
+/// Fail with `OutputExists` if something is already at `path`
+pub fn ensure_absent<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref();
+    if std::fs::symlink_metadata(path).is_ok() {
+        return Err(F2V2FError::OutputExists(path.display().to_string()));
+    }
+    Ok(())
+}
+
+/// Write `contents` to `path` atomically, replacing any existing file
+pub fn write_atomic<P: AsRef<Path>>(path: P, contents: &[u8]) -> Result<()> {
+    let staged = AtomicOutput::new(path, true)?;
+    let mut file = staged.as_file();
+    file.write_all(contents)?;
+    file.sync_all()?;
+    staged.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commit_replaces_target() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let target = dir.path().join("out.mp4");
+        std::fs::write(&target, b"old")?;
+
+        assert!(matches!(AtomicOutput::new(&target, false), Err(F2V2FError::OutputExists(_))));
+        let staged = AtomicOutput::new(&target, true)?;
+        assert_eq!(staged.path().extension().unwrap(), "mp4");
+        std::fs::write(staged.path(), b"new")?;
+        // Target is untouched until commit
+        assert_eq!(std::fs::read(&target)?, b"old");
+
+        staged.commit()?;
+        assert_eq!(std::fs::read(&target)?, b"new");
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_noclobber_commit_refuses_new_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let target = dir.path().join("out.bin");
+
+        let staged = AtomicOutput::new(&target, false)?;
+        // Another process creates the target while we're writing
+        std::fs::write(&target, b"theirs")?;
+        assert!(matches!(staged.commit(), Err(F2V2FError::OutputExists(_))));
+        assert_eq!(std::fs::read(&target)?, b"theirs");
+        Ok(())
+    }
+
+    #[test]
+    fn test_staging_in_scratch_dir() -> Result<()> {
+        let out_dir = tempfile::tempdir()?;
+        let scratch = tempfile::tempdir()?;
+        let target = out_dir.path().join("out.mp4");
+
+        let staged = AtomicOutput::new_in(&target, false, Some(scratch.path()))?;
+        assert_eq!(staged.path().parent(), Some(scratch.path()));
+        std::fs::write(staged.path(), b"video")?;
+        assert_eq!(std::fs::read_dir(out_dir.path())?.count(), 0);
+
+        staged.commit()?;
+        assert_eq!(std::fs::read(&target)?, b"video");
+        assert_eq!(std::fs::read_dir(scratch.path())?.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_drop_cleans_up() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let target = dir.path().join("out.bin");
+        {
+            let staged = AtomicOutput::new(&target, false)?;
+            std::fs::write(staged.path(), b"partial")?;
+        }
+        assert!(!target.exists());
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 0);
+        Ok(())
+    }
+}
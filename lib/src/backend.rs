@@ -0,0 +1,1051 @@
+//! Video container backends
+//!
+//! The composer and decoder never touch a video file themselves: a
+//! `VideoBackend` writes the raw RGBA frames they produce into a container
+//! and reads frames back out of one. `FfmpegBackend` shells out to ffmpeg
+//! and ffprobe and is what every front-end uses. `MockBackend` stores the
+//! frames uncompressed in a file of its own, so encode and decode can be
+//! tested end to end on machines without ffmpeg, and without a lossy codec
+//! hiding bugs in the frame logic.
+
+use crate::error::{F2V2FError, Result};
+use crate::extract_format::ExtractFormat;
+use crate::frame_header::FrameHeader;
+use crate::warning::Warning;
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use tracing::{debug, warn};
+
+/// Video codec the ffmpeg backend encodes with (lossless H.264)
+pub const VIDEO_CODEC: &str = "libx264";
+
+/// Lines of ffmpeg's stderr kept for error messages
+const STDERR_TAIL_LINES: usize = 20;
+
+/// Frames crop detection (and the header check before it) looks at
+const CROP_PROBE_FRAMES: u64 = 10;
+
+/// Writes and reads video containers
+pub trait VideoBackend: Send + Sync {
+    /// Start a video at `path`; raw RGBA frames written to the sink become
+    /// its frames
+    fn create(&self, path: &Path, spec: &OutputSpec) -> Result<Box<dyn FrameSink>>;
+
+    /// The frames `request` selects, as `request.width`x`request.height`
+    /// RGBA; recoverable anomalies go to `warnings`
+    fn read_frames(&self, path: &Path, request: &FrameRequest, warnings: &mut Vec<Warning>) -> Result<Vec<RgbaImage>>;
+
+    /// Frame size of the video
+    fn probe_dimensions(&self, path: &Path) -> Result<(u32, u32)>;
+
+    /// Frame count and frame rate
+    fn probe_frames(&self, path: &Path) -> Result<(u64, f64)>;
+
+    /// Duration in seconds
+    fn probe_duration(&self, path: &Path) -> Result<f64>;
+
+    /// Picture area inside letterboxing or pillarboxing, or `None` if the
+    /// picture fills the frame
+    fn detect_content_rect(&self, path: &Path) -> Result<Option<ContentRect>>;
+
+    /// Value of the container metadata tag `key`, if the video has it
+    fn read_tag(&self, path: &Path, key: &str) -> Result<Option<String>>;
+
+    /// Contents of the attachment named `name`, if the video has it (only
+    /// Matroska videos do, see `is_matroska`)
+    fn read_attachment(&self, path: &Path, name: &str) -> Result<Option<Vec<u8>>>;
+
+    /// The first subtitle track as an SRT document, if the video has one
+    fn read_subtitles(&self, path: &Path) -> Result<Option<String>>;
+
+    /// Picture size of a video, ignoring padding bars
+    ///
+    /// Bars are only looked for when no frame header reads at the full
+    /// size: crop detection takes data rows that stay dark for bars.
+    fn probe_resolution(&self, path: &Path) -> Result<(u32, u32)> {
+        let (width, height) = self.probe_dimensions(path)?;
+        let request = FrameRequest {
+            width,
+            height,
+            selection: FrameSelection::Window { start: 0, count: CROP_PROBE_FRAMES },
+            filters: Vec::new(),
+            format: ExtractFormat::default(),
+            ignore_errors: true,
+        };
+        let frames = self.read_frames(path, &request, &mut Vec::new()).unwrap_or_default();
+        if frames.iter().any(|frame| matches!(FrameHeader::try_read_from(frame), Ok(Some(_)))) {
+            return Ok((width, height));
+        }
+        match self.detect_content_rect(path)? {
+            Some(rect) => Ok((rect.width, rect.height)),
+            None => Ok((width, height)),
+        }
+    }
+}
+
+/// Frames being written into a new video
+pub trait FrameSink: Write {
+    /// Close the video, failing if it couldn't be written completely
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+/// Settings of a video being created
+#[derive(Debug, Clone)]
+pub struct OutputSpec<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    /// Frames between keyframes; the codec's default if None
+    pub keyframe_interval: Option<u32>,
+    /// Byte-identical output for identical frames
+    pub deterministic: bool,
+    /// FFMETADATA file with chapters to attach
+    pub metadata: Option<&'a Path>,
+    /// SRT file to mux as a subtitle track
+    pub subtitles: Option<&'a Path>,
+    /// Container metadata tags (key, value) to write
+    pub tags: &'a [(String, String)],
+    /// Files to attach; Matroska outputs only
+    pub attachments: &'a [Attachment],
+}
+
+/// A file carried inside a Matroska video
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    /// File name the attachment is stored and looked up under
+    pub name: String,
+    pub mime: String,
+    pub data: Vec<u8>,
+}
+
+/// Whether `path` names a Matroska video, the only container here that can
+/// hold attachments
+pub fn is_matroska(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ["mkv", "mka", "mk3d"].contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// Which frames of a video to read
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameSelection {
+    All,
+    /// Up to `count` frames starting at frame `start`
+    Window { start: u64, count: u64 },
+    /// The frame shown at this many seconds
+    At(f64),
+    /// The last `count` frames
+    Last(u64),
+}
+
+/// Frames to read from a video and how to convert them
+#[derive(Debug, Clone)]
+pub struct FrameRequest {
+    pub width: u32,
+    pub height: u32,
+    pub selection: FrameSelection,
+    /// ffmpeg filters mapping the picture onto the encoded grid (see
+    /// `VideoComposer::content_filters`)
+    pub filters: Vec<String>,
+    pub format: ExtractFormat,
+    /// Read what can be read of a damaged or cut-off video instead of failing
+    pub ignore_errors: bool,
+}
+
+/// Picture area inside padding bars
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentRect {
+    pub width: u32,
+    pub height: u32,
+    pub x: u32,
+    pub y: u32,
+}
+
+/// Videos encoded and decoded by the ffmpeg and ffprobe in /usr/local/bin
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FfmpegBackend;
+
+impl FfmpegBackend {
+    /// Video encoders the installed ffmpeg was built with
+    pub fn probe_encoders(&self) -> Result<Vec<String>> {
+        let output = Command::new("/usr/local/bin/ffmpeg")
+            .args(["-hide_banner", "-encoders"])
+            .output()
+            .map_err(|e| F2V2FError::VideoError(format!("Failed to start ffmpeg: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(F2V2FError::VideoError("Could not list ffmpeg encoders".to_string()));
+        }
+
+        Ok(parse_encoders(&String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+impl VideoBackend for FfmpegBackend {
+    fn create(&self, path: &Path, spec: &OutputSpec) -> Result<Box<dyn FrameSink>> {
+        let mut command = Command::new("/usr/local/bin/ffmpeg");
+        command.args([
+            "-y",  // Overwrite
+            "-f", "rawvideo",
+            "-pix_fmt", "rgba",
+            "-video_size", &format!("{}x{}", spec.width, spec.height),
+            "-framerate", &spec.fps.to_string(),
+            "-i", "pipe:0",
+        ]);
+
+        // Chapters come from a second FFMETADATA input, subtitles from an SRT one
+        if let Some(metadata_path) = spec.metadata {
+            command.args(["-i", &metadata_path.to_string_lossy()]);
+        }
+        if let Some(subtitles_path) = spec.subtitles {
+            command.args(["-i", &subtitles_path.to_string_lossy()]);
+        }
+        if spec.metadata.is_some() || spec.subtitles.is_some() {
+            command.args(["-map", "0:v"]);
+        }
+        if spec.metadata.is_some() {
+            command.args(["-map_metadata", "1", "-map_chapters", "1"]);
+        }
+        if spec.subtitles.is_some() {
+            let input = if spec.metadata.is_some() { "2:s" } else { "1:s" };
+            // MP4 only takes its own timed-text format
+            let codec = if is_matroska(path) { "srt" } else { "mov_text" };
+            command.args(["-map", input, "-c:s", codec]);
+        }
+
+        command.args([
+            "-c:v", VIDEO_CODEC,  // Use H.264 instead of H.265 for better compatibility
+            "-preset", "ultrafast",  // Faster encoding
+            "-qp", "0",  // LOSSLESS encoding - critical for data integrity!
+            "-pix_fmt", "yuv444p",  // Full chroma resolution (no subsampling)
+        ]);
+
+        // MP4 keeps keys it doesn't know only as udta metadata tags;
+        // Matroska takes any tag as is
+        if !is_matroska(path) {
+            let flags = if spec.tags.is_empty() { "+faststart" } else { "+faststart+use_metadata_tags" };
+            command.args(["-movflags", flags]);
+        }
+        for (key, value) in spec.tags {
+            command.args(["-metadata", &format!("{}={}", key, value)]);
+        }
+
+        // ffmpeg attaches files from disk, so they wait in a scratch
+        // directory that lives as long as the sink
+        let attachment_dir = if spec.attachments.is_empty() { None } else { Some(tempfile::tempdir()?) };
+        if let Some(dir) = &attachment_dir {
+            for (i, attachment) in spec.attachments.iter().enumerate() {
+                let file = dir.path().join(i.to_string());
+                std::fs::write(&file, &attachment.data)?;
+                command.arg("-attach").arg(&file);
+                command.args([
+                    &format!("-metadata:s:t:{}", i), &format!("mimetype={}", attachment.mime),
+                    &format!("-metadata:s:t:{}", i), &format!("filename={}", attachment.name),
+                ]);
+            }
+        }
+
+        if let Some(interval) = spec.keyframe_interval {
+            command.args(["-g", &interval.to_string()]);
+        }
+
+        if spec.deterministic {
+            command.args([
+                "-threads", "1",
+                "-x264-params", "threads=1:sliced-threads=0",
+                "-fflags", "+bitexact",
+                "-flags:v", "+bitexact",
+            ]);
+        }
+
+        let mut child = command
+            .arg(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| F2V2FError::EncodingError(format!("Failed to start ffmpeg: {}", e)))?;
+        let stderr = forward_stderr(&mut child);
+        let stdin = child.stdin.take().ok_or_else(|| F2V2FError::EncodingError("No stdin".to_string()))?;
+        Ok(Box::new(FfmpegSink { child, stdin: Some(stdin), stderr, _attachment_dir: attachment_dir }))
+    }
+
+    fn read_frames(&self, path: &Path, request: &FrameRequest, warnings: &mut Vec<Warning>) -> Result<Vec<RgbaImage>> {
+        let mut args: Vec<String> = Vec::new();
+        if request.ignore_errors {
+            args.extend(["-err_detect".into(), "ignore_err".into()]);
+        }
+        let mut filters = Vec::new();
+        let mut output_args: Vec<String> = Vec::new();
+        match request.selection {
+            FrameSelection::All => {}
+            FrameSelection::Window { start, count } => {
+                // ffmpeg still decodes from the start but stops after the window
+                filters.push(format!("select='between(n,{},{})'", start, start + count - 1));
+                output_args.extend(["-fps_mode".into(), "passthrough".into(), "-frames:v".into(), count.to_string()]);
+            }
+            FrameSelection::At(seconds) => {
+                // Seeking in the input only decodes from the nearest keyframe
+                args.extend(["-ss".into(), format!("{:.6}", seconds.max(0.0))]);
+                output_args.extend(["-frames:v".into(), "1".into()]);
+            }
+            FrameSelection::Last(count) => {
+                let (_, fps) = self.probe_frames(path)?;
+                args.extend(["-sseof".into(), format!("-{:.6}", count as f64 / fps)]);
+            }
+        }
+        filters.extend(request.filters.iter().cloned());
+        args.extend(["-i".into(), path.to_string_lossy().to_string()]);
+        if !filters.is_empty() {
+            args.push("-vf".into());
+            args.push(filters.join(","));
+        }
+        args.extend(output_args);
+        args.extend(request.format.output_args());
+        args.push("-".into());
+
+        let mut child = Command::new("/usr/local/bin/ffmpeg")
+            .args(&args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| F2V2FError::DecodingError(format!("Failed to start ffmpeg: {}", e)))?;
+        let stderr = forward_stderr(&mut child);
+
+        let mut stdout = child.stdout.take().ok_or_else(|| F2V2FError::DecodingError("No stdout".to_string()))?;
+        let mut frames = Vec::new();
+        let pix_fmt = request.format.pix_fmt;
+        let frame_size = request.width as usize * request.height as usize * pix_fmt.bytes_per_pixel();
+
+        loop {
+            let mut buffer = vec![0u8; frame_size];
+            let n = read_chunk(&mut stdout, &mut buffer)
+                .map_err(|e| F2V2FError::DecodingError(format!("Read failed: {}", e)))?;
+            if n == 0 {
+                break;
+            }
+            if n < frame_size {
+                // ffmpeg wrote frames of another size; every frame after the
+                // first would be read misaligned
+                let _ = child.kill();
+                let _ = child.wait();
+                stderr.join();
+                return Err(F2V2FError::InvalidInput(format!(
+                    "Frames from {} are not {}x{} ({} trailing bytes after {} frames)",
+                    path.display(),
+                    request.width,
+                    request.height,
+                    n,
+                    frames.len()
+                )));
+            }
+            let img = pix_fmt
+                .to_rgba(request.width, request.height, buffer)
+                .ok_or_else(|| F2V2FError::DecodingError("Failed to create image from raw bytes".to_string()))?;
+            frames.push(img);
+        }
+
+        let status = child.wait()
+            .map_err(|e| F2V2FError::DecodingError(format!("Wait failed: {}", e)))?;
+        stderr.join();
+
+        if !status.success() {
+            // It might fail if we read all frames but ffmpeg has more to say, or if it's not a video
+            warn!("ffmpeg exited with code {}", status.code().unwrap_or(-1));
+            warnings.push(Warning::FfmpegExit { code: status.code().unwrap_or(-1) });
+        }
+
+        Ok(frames)
+    }
+
+    fn probe_dimensions(&self, path: &Path) -> Result<(u32, u32)> {
+        let output = Command::new("/usr/local/bin/ffprobe")
+            .args([
+                "-v", "error",
+                "-select_streams", "v:0",
+                "-show_entries", "stream=width,height",
+                "-of", "csv=p=0:s=x",
+                &path.to_string_lossy(),
+            ])
+            .output()
+            .map_err(|e| F2V2FError::DecodingError(format!("Failed to start ffprobe: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(F2V2FError::InvalidInput(format!(
+                "Could not probe video dimensions of {}",
+                path.display()
+            )));
+        }
+
+        parse_dimensions(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    fn read_tag(&self, path: &Path, key: &str) -> Result<Option<String>> {
+        let output = Command::new("/usr/local/bin/ffprobe")
+            .args([
+                "-v", "error",
+                "-show_entries", &format!("format_tags={}", key),
+                "-of", "json",
+                &path.to_string_lossy(),
+            ])
+            .output()
+            .map_err(|e| F2V2FError::DecodingError(format!("Failed to start ffprobe: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(F2V2FError::InvalidInput(format!("Could not read the tags of {}", path.display())));
+        }
+
+        Ok(parse_tag(&String::from_utf8_lossy(&output.stdout), key))
+    }
+
+    /// ffmpeg dumps attachments as it opens the input; with no output given
+    /// it then exits with an error, so only the dumped file tells
+    fn read_attachment(&self, path: &Path, name: &str) -> Result<Option<Vec<u8>>> {
+        if !is_matroska(path) {
+            return Ok(None);
+        }
+        let dir = tempfile::tempdir()?;
+        let dump = dir.path().join("attachment");
+        Command::new("/usr/local/bin/ffmpeg")
+            .args(["-v", "error", "-y"])
+            .arg(format!("-dump_attachment:m:filename:{}", name))
+            .arg(&dump)
+            .arg("-i")
+            .arg(path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map_err(|e| F2V2FError::DecodingError(format!("Failed to start ffmpeg: {}", e)))?;
+        match std::fs::read(&dump) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn read_subtitles(&self, path: &Path) -> Result<Option<String>> {
+        let output = Command::new("/usr/local/bin/ffmpeg")
+            .args(["-v", "error", "-i", &path.to_string_lossy(), "-map", "0:s:0", "-f", "srt", "-"])
+            .output()
+            .map_err(|e| F2V2FError::DecodingError(format!("Failed to start ffmpeg: {}", e)))?;
+
+        // No subtitle stream to map is an error too
+        if !output.status.success() || output.stdout.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned()))
+    }
+
+    /// Counts packets rather than decoding, so it only reads the container
+    fn probe_frames(&self, path: &Path) -> Result<(u64, f64)> {
+        let output = Command::new("/usr/local/bin/ffprobe")
+            .args([
+                "-v", "error",
+                "-select_streams", "v:0",
+                "-count_packets",
+                "-show_entries", "stream=r_frame_rate,nb_read_packets",
+                "-of", "default=noprint_wrappers=1",
+                &path.to_string_lossy(),
+            ])
+            .output()
+            .map_err(|e| F2V2FError::VideoError(format!("Failed to start ffprobe: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(F2V2FError::InvalidInput(format!(
+                "Could not count the frames of {}",
+                path.display()
+            )));
+        }
+
+        parse_frames(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    fn probe_duration(&self, path: &Path) -> Result<f64> {
+        let output = Command::new("/usr/local/bin/ffprobe")
+            .args([
+                "-v", "error",
+                "-show_entries", "format=duration",
+                "-of", "csv=p=0",
+                &path.to_string_lossy(),
+            ])
+            .output()
+            .map_err(|e| F2V2FError::VideoError(format!("Failed to start ffprobe: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(F2V2FError::InvalidInput(format!(
+                "Could not probe duration of {}",
+                path.display()
+            )));
+        }
+
+        parse_duration(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    /// Runs ffmpeg's `cropdetect` filter over the first frames
+    fn detect_content_rect(&self, path: &Path) -> Result<Option<ContentRect>> {
+        let (width, height) = self.probe_dimensions(path)?;
+        let output = Command::new("/usr/local/bin/ffmpeg")
+            .args([
+                "-i", &path.to_string_lossy(),
+                "-vf", "cropdetect=limit=16:round=2:reset=0",
+                "-frames:v", &CROP_PROBE_FRAMES.to_string(),
+                "-f", "null",
+                "-",
+            ])
+            .output()
+            .map_err(|e| F2V2FError::DecodingError(format!("Failed to start ffmpeg: {}", e)))?;
+
+        let rect = parse_cropdetect(&String::from_utf8_lossy(&output.stderr));
+        Ok(rect.filter(|r| (r.width, r.height) != (width, height)))
+    }
+}
+
+/// ffmpeg encoding the frames piped to its stdin
+struct FfmpegSink {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stderr: StderrForwarder,
+    /// Files being attached, read by ffmpeg when it writes the header
+    _attachment_dir: Option<tempfile::TempDir>,
+}
+
+impl Write for FfmpegSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match &mut self.stdin {
+            Some(stdin) => stdin.write(buf),
+            None => Err(std::io::ErrorKind::BrokenPipe.into()),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stdin.as_mut().map_or(Ok(()), |stdin| stdin.flush())
+    }
+}
+
+impl FrameSink for FfmpegSink {
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        drop(self.stdin.take());
+        let status = self.child.wait()
+            .map_err(|e| F2V2FError::EncodingError(format!("Wait failed: {}", e)))?;
+        let err_msg = self.stderr.take().join();
+
+        if !status.success() {
+            return Err(F2V2FError::EncodingError(
+                format!("FFmpeg exited with code {}. Details: {}", status.code().unwrap_or(-1), err_msg)
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Magic at the start of a `MockBackend` video
+const MOCK_MAGIC: &[u8; 8] = b"F2V2FRAW";
+
+/// Magic of mock videos with tags or attachments, which follow the header
+/// as a little-endian u32 length and a JSON `MockMetadata`
+const MOCK_TAGGED_MAGIC: &[u8; 8] = b"F2V2FTAG";
+
+/// Magic, then width, height and fps as little-endian u32
+const MOCK_HEADER_LEN: u64 = 20;
+
+/// Stores frames as raw RGBA after a small header and reads them back
+/// exactly, for tests
+///
+/// No filters are applied when reading, so frames of another size are
+/// refused. A trailing partial frame, as in a video cut off mid-write, is
+/// dropped with the warning ffmpeg would give (none when errors are
+/// ignored).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MockBackend;
+
+/// What the header of a mock video says
+struct MockHeader {
+    width: u32,
+    height: u32,
+    fps: u32,
+    /// Where the first frame starts
+    frames_start: u64,
+    /// Whole frames
+    frames: u64,
+    /// Bytes of a trailing partial frame
+    trailing: u64,
+    metadata: MockMetadata,
+}
+
+/// Tags and attachments of a mock video
+#[derive(Default, Serialize, Deserialize)]
+struct MockMetadata {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    tags: BTreeMap<String, String>,
+    /// Attachment contents by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    attachments: BTreeMap<String, Vec<u8>>,
+    /// SRT document of the subtitle track
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    subtitles: Option<String>,
+}
+
+impl MockBackend {
+    fn read_header(path: &Path) -> Result<MockHeader> {
+        let not_mock = || F2V2FError::InvalidInput(format!("{} is not a mock video", path.display()));
+        let mut file = File::open(path)?;
+        let mut header = [0u8; MOCK_HEADER_LEN as usize];
+        file.read_exact(&mut header).map_err(|_| not_mock())?;
+        let tagged = match &header[..8] {
+            magic if magic == MOCK_MAGIC => false,
+            magic if magic == MOCK_TAGGED_MAGIC => true,
+            _ => return Err(not_mock()),
+        };
+        let field = |i: usize| u32::from_le_bytes(header[8 + 4 * i..12 + 4 * i].try_into().unwrap_or_default());
+        let (width, height, fps) = (field(0), field(1), field(2));
+
+        let mut frames_start = MOCK_HEADER_LEN;
+        let mut metadata = MockMetadata::default();
+        if tagged {
+            let mut len = [0u8; 4];
+            file.read_exact(&mut len).map_err(|_| not_mock())?;
+            let mut json = vec![0u8; u32::from_le_bytes(len) as usize];
+            file.read_exact(&mut json).map_err(|_| not_mock())?;
+            metadata = serde_json::from_slice(&json).map_err(|_| not_mock())?;
+            frames_start += 4 + json.len() as u64;
+        }
+
+        let frame_size = width as u64 * height as u64 * 4;
+        let data = file.metadata()?.len() - frames_start;
+        if frame_size == 0 || fps == 0 {
+            return Err(F2V2FError::InvalidInput(format!("{} has an empty frame size or rate", path.display())));
+        }
+        Ok(MockHeader { width, height, fps, frames_start, frames: data / frame_size, trailing: data % frame_size, metadata })
+    }
+}
+
+impl VideoBackend for MockBackend {
+    fn create(&self, path: &Path, spec: &OutputSpec) -> Result<Box<dyn FrameSink>> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let subtitles = spec.subtitles.map(std::fs::read_to_string).transpose()?;
+        let tagged = !spec.tags.is_empty() || !spec.attachments.is_empty() || subtitles.is_some();
+        file.write_all(if tagged { MOCK_TAGGED_MAGIC } else { MOCK_MAGIC })?;
+        for field in [spec.width, spec.height, spec.fps] {
+            file.write_all(&field.to_le_bytes())?;
+        }
+        if tagged {
+            let metadata = MockMetadata {
+                tags: spec.tags.iter().cloned().collect(),
+                attachments: spec.attachments.iter().map(|a| (a.name.clone(), a.data.clone())).collect(),
+                subtitles,
+            };
+            let json = serde_json::to_vec(&metadata)
+                .map_err(|e| F2V2FError::EncodingError(format!("Failed to serialize the metadata: {}", e)))?;
+            file.write_all(&(json.len() as u32).to_le_bytes())?;
+            file.write_all(&json)?;
+        }
+        let frame_size = spec.width as u64 * spec.height as u64 * 4;
+        Ok(Box::new(MockSink { file, frame_size, written: 0 }))
+    }
+
+    fn read_frames(&self, path: &Path, request: &FrameRequest, warnings: &mut Vec<Warning>) -> Result<Vec<RgbaImage>> {
+        if !request.filters.is_empty() {
+            return Err(F2V2FError::VideoError(format!(
+                "The mock backend can't apply filters ({})",
+                request.filters.join(",")
+            )));
+        }
+        let MockHeader { width, height, fps, frames_start, frames, trailing, .. } = Self::read_header(path)?;
+        if (width, height) != (request.width, request.height) {
+            return Err(F2V2FError::InvalidInput(format!(
+                "{} is {}x{} but {}x{} was requested",
+                path.display(),
+                width,
+                height,
+                request.width,
+                request.height
+            )));
+        }
+        if trailing > 0 && !request.ignore_errors {
+            // What ffmpeg reports when a video stops mid-frame
+            warn!("{} ends {} bytes into frame {}", path.display(), trailing, frames);
+            warnings.push(Warning::FfmpegExit { code: 1 });
+        }
+        let range = match request.selection {
+            FrameSelection::All => 0..frames,
+            FrameSelection::Window { start, count } => start.min(frames)..start.saturating_add(count).min(frames),
+            FrameSelection::At(seconds) => {
+                let i = ((seconds.max(0.0) * fps as f64).round() as u64).min(frames);
+                i..(i + 1).min(frames)
+            }
+            FrameSelection::Last(count) => frames.saturating_sub(count)..frames,
+        };
+
+        let frame_size = width as usize * height as usize * 4;
+        let mut reader = BufReader::new(File::open(path)?);
+        std::io::copy(&mut (&mut reader).take(frames_start + range.start * frame_size as u64), &mut std::io::sink())?;
+        range
+            .map(|_| {
+                let mut buffer = vec![0u8; frame_size];
+                reader.read_exact(&mut buffer)?;
+                RgbaImage::from_raw(width, height, buffer)
+                    .ok_or_else(|| F2V2FError::DecodingError("Failed to create image from raw bytes".to_string()))
+            })
+            .collect()
+    }
+
+    fn probe_dimensions(&self, path: &Path) -> Result<(u32, u32)> {
+        let header = Self::read_header(path)?;
+        Ok((header.width, header.height))
+    }
+
+    fn probe_frames(&self, path: &Path) -> Result<(u64, f64)> {
+        let header = Self::read_header(path)?;
+        Ok((header.frames, header.fps as f64))
+    }
+
+    fn probe_duration(&self, path: &Path) -> Result<f64> {
+        let header = Self::read_header(path)?;
+        Ok(header.frames as f64 / header.fps as f64)
+    }
+
+    fn detect_content_rect(&self, _path: &Path) -> Result<Option<ContentRect>> {
+        Ok(None)
+    }
+
+    fn read_tag(&self, path: &Path, key: &str) -> Result<Option<String>> {
+        Ok(Self::read_header(path)?.metadata.tags.remove(key))
+    }
+
+    fn read_attachment(&self, path: &Path, name: &str) -> Result<Option<Vec<u8>>> {
+        Ok(Self::read_header(path)?.metadata.attachments.remove(name))
+    }
+
+    fn read_subtitles(&self, path: &Path) -> Result<Option<String>> {
+        Ok(Self::read_header(path)?.metadata.subtitles)
+    }
+}
+
+/// Frames appended to a mock video
+struct MockSink {
+    file: BufWriter<File>,
+    frame_size: u64,
+    written: u64,
+}
+
+impl Write for MockSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.file.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl FrameSink for MockSink {
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        if !self.written.is_multiple_of(self.frame_size) {
+            return Err(F2V2FError::EncodingError(format!(
+                "{} bytes written do not make whole frames",
+                self.written
+            )));
+        }
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// Reader thread draining a child's stderr
+pub(crate) struct StderrForwarder(Option<std::thread::JoinHandle<String>>);
+
+impl StderrForwarder {
+    /// Wait for the child to close stderr; returns its last lines
+    pub(crate) fn join(self) -> String {
+        self.0.and_then(|handle| handle.join().ok()).unwrap_or_default()
+    }
+
+    pub(crate) fn take(&mut self) -> Self {
+        Self(self.0.take())
+    }
+}
+
+/// Log ffmpeg's stderr line by line (debug level, target `ffmpeg`) while it
+/// runs, so log sinks receive it and the pipe never fills up and stalls it
+pub(crate) fn forward_stderr(child: &mut Child) -> StderrForwarder {
+    StderrForwarder(child.stderr.take().map(|stderr| {
+        std::thread::spawn(move || {
+            let mut tail = std::collections::VecDeque::new();
+            for segment in BufReader::new(stderr).split(b'\n').map_while(|s| s.ok()) {
+                // Progress updates are separated by carriage returns
+                for line in String::from_utf8_lossy(&segment).split('\r') {
+                    let line = line.trim();
+                    if line.is_empty() {
+                        continue;
+                    }
+                    debug!(target: "ffmpeg", "{}", line);
+                    if tail.len() == STDERR_TAIL_LINES {
+                        tail.pop_front();
+                    }
+                    tail.push_back(line.to_string());
+                }
+            }
+            Vec::from(tail).join("\n")
+        })
+    }))
+}
+
+/// Fill `buf` from `reader`, returning fewer bytes only at end of input
+pub(crate) fn read_chunk<R: Read + ?Sized>(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(filled)
+}
+
+/// Parse ffprobe's `WIDTHxHEIGHT` output
+fn parse_dimensions(text: &str) -> Result<(u32, u32)> {
+    let line = text.lines().next().unwrap_or("").trim();
+    let (w, h) = line
+        .split_once('x')
+        .ok_or_else(|| F2V2FError::DecodingError(format!("Unexpected ffprobe output: {:?}", line)))?;
+    let parse = |v: &str| {
+        v.trim()
+            .parse::<u32>()
+            .map_err(|_| F2V2FError::DecodingError(format!("Unexpected ffprobe output: {:?}", line)))
+    };
+    Ok((parse(w)?, parse(h)?))
+}
+
+/// Parse ffprobe's `format=duration` output (seconds)
+fn parse_duration(text: &str) -> Result<f64> {
+    let line = text.lines().next().unwrap_or("").trim();
+    line.parse::<f64>()
+        .map_err(|_| F2V2FError::VideoError(format!("Unexpected ffprobe output: {:?}", line)))
+}
+
+/// Parse ffprobe's `r_frame_rate=30/1` and `nb_read_packets=150` lines
+fn parse_frames(text: &str) -> Result<(u64, f64)> {
+    let unexpected = || F2V2FError::VideoError(format!("Unexpected ffprobe output: {:?}", text.trim()));
+    let value = |key: &str| {
+        text.lines()
+            .find_map(|line| line.trim().strip_prefix(key)?.strip_prefix('='))
+            .ok_or_else(unexpected)
+    };
+    let count = value("nb_read_packets")?.parse::<u64>().map_err(|_| unexpected())?;
+    let (num, den) = value("r_frame_rate")?.split_once('/').ok_or_else(unexpected)?;
+    let (num, den) = (num.parse::<f64>().map_err(|_| unexpected())?, den.parse::<f64>().map_err(|_| unexpected())?);
+    if num <= 0.0 || den <= 0.0 {
+        return Err(unexpected());
+    }
+    Ok((count, num / den))
+}
+
+/// Video encoder names from `ffmpeg -encoders` (lines like ` V....D libx264  ...`)
+/// Tag `key` of the format section in ffprobe's JSON output
+fn parse_tag(json: &str, key: &str) -> Option<String> {
+    let output: serde_json::Value = serde_json::from_str(json).ok()?;
+    output["format"]["tags"][key].as_str().map(str::to_string)
+}
+
+fn parse_encoders(text: &str) -> Vec<String> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let flags = fields.next()?;
+            let name = fields.next()?;
+            (flags.len() == 6 && flags.starts_with('V') && name != "=").then(|| name.to_string())
+        })
+        .collect()
+}
+
+/// Take the last `crop=W:H:X:Y` suggestion from cropdetect's log output
+fn parse_cropdetect(stderr: &str) -> Option<ContentRect> {
+    let last = stderr.rsplit("crop=").next().filter(|_| stderr.contains("crop="))?;
+    let spec: String = last
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == ':' || *c == '-')
+        .collect();
+    let parts: Vec<i64> = spec.split(':').filter_map(|p| p.parse().ok()).collect();
+    match parts.as_slice() {
+        [w, h, x, y] if *w > 0 && *h > 0 && *x >= 0 && *y >= 0 => Some(ContentRect {
+            width: *w as u32,
+            height: *h as u32,
+            x: *x as u32,
+            y: *y as u32,
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forward_stderr_keeps_tail() {
+        let mut child = Command::new("sh")
+            .args(["-c", "printf 'frame=1\\rframe=2\\n' >&2; seq 1 30 >&2"])
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stderr = forward_stderr(&mut child);
+        child.wait().unwrap();
+        let tail = stderr.join();
+        assert_eq!(tail.lines().count(), STDERR_TAIL_LINES);
+        assert!(tail.ends_with("29\n30"));
+    }
+
+    #[test]
+    fn test_parse_dimensions() {
+        assert_eq!(parse_dimensions("1280x720\n").unwrap(), (1280, 720));
+        assert!(parse_dimensions("").is_err());
+        assert!(parse_dimensions("widexhigh").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("12.466667\n").unwrap(), 12.466667);
+        assert!(parse_duration("N/A").is_err());
+    }
+
+    #[test]
+    fn test_parse_frames() {
+        assert_eq!(parse_frames("r_frame_rate=30000/1001\nnb_read_packets=150\n").unwrap(), (150, 30000.0 / 1001.0));
+        assert!(parse_frames("r_frame_rate=0/0\nnb_read_packets=150\n").is_err());
+        assert!(parse_frames("nb_read_packets=N/A\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_encoders() {
+        let listing = "Encoders:\n V..... = Video\n A..... = Audio\n ------\n V....D libx264              libx264 H.264 (codec h264)\n V....D ffv1                 FFmpeg video codec #1\n A....D aac                  AAC (Advanced Audio Coding)\n";
+        assert_eq!(parse_encoders(listing), ["libx264", "ffv1"]);
+    }
+
+    #[test]
+    fn test_parse_tag() {
+        let output = r#"{"format": {"tags": {"major_brand": "isom", "f2v2f_manifest": "{\"width\": 64}"}}}"#;
+        assert_eq!(parse_tag(output, "f2v2f_manifest").as_deref(), Some(r#"{"width": 64}"#));
+        assert_eq!(parse_tag(output, "title"), None);
+        assert_eq!(parse_tag(r#"{"format": {}}"#, "title"), None);
+    }
+
+    #[test]
+    fn test_parse_cropdetect() {
+        let log = "[Parsed_cropdetect_0 @ 0x1] x1:0 x2:1919 y1:140 y2:939 w:1920 h:800 x:0 y:140 pts:0 t:0.000000 crop=1920:800:0:140\n\
+                   [Parsed_cropdetect_0 @ 0x1] x1:0 x2:1919 y1:140 y2:939 w:1920 h:800 x:0 y:140 pts:1 t:0.033333 crop=1920:800:0:140\n";
+        assert_eq!(
+            parse_cropdetect(log),
+            Some(ContentRect { width: 1920, height: 800, x: 0, y: 140 })
+        );
+        assert_eq!(parse_cropdetect("no detection here"), None);
+    }
+
+    #[test]
+    fn test_mock_backend_round_trip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("video.f2v2fraw");
+        let spec = OutputSpec { width: 4, height: 2, fps: 10, keyframe_interval: None, deterministic: true, metadata: None, subtitles: None, tags: &[], attachments: &[] };
+        let frames: Vec<RgbaImage> = (0..5u8).map(|i| RgbaImage::from_pixel(4, 2, image::Rgba([i, 1, 2, 3]))).collect();
+        let mut sink = MockBackend.create(&path, &spec)?;
+        for frame in &frames {
+            sink.write_all(frame.as_raw())?;
+        }
+        sink.finish()?;
+
+        let backend = MockBackend;
+        assert_eq!(backend.probe_resolution(&path)?, (4, 2));
+        assert_eq!(backend.probe_frames(&path)?, (5, 10.0));
+        assert_eq!(backend.probe_duration(&path)?, 0.5);
+        let request = |selection| FrameRequest {
+            width: 4,
+            height: 2,
+            selection,
+            filters: Vec::new(),
+            format: ExtractFormat::default(),
+            ignore_errors: false,
+        };
+        let mut warnings = Vec::new();
+        let mut read = |selection| backend.read_frames(&path, &request(selection), &mut warnings);
+        assert_eq!(read(FrameSelection::All)?, frames);
+        assert_eq!(read(FrameSelection::Window { start: 3, count: 10 })?, frames[3..]);
+        assert_eq!(read(FrameSelection::At(0.2))?, frames[2..3]);
+        assert_eq!(read(FrameSelection::Last(2))?, frames[3..]);
+
+        assert!(warnings.is_empty());
+
+        // Cut off mid-frame: the partial frame is dropped
+        let file = std::fs::OpenOptions::new().write(true).open(&path)?;
+        file.set_len(MOCK_HEADER_LEN + 4 * 32 + 5)?;
+        assert_eq!(backend.read_frames(&path, &request(FrameSelection::All), &mut warnings)?, frames[..4]);
+        assert_eq!(warnings, [Warning::FfmpegExit { code: 1 }]);
+        let request = FrameRequest { ignore_errors: true, ..request(FrameSelection::Last(1)) };
+        assert_eq!(backend.read_frames(&path, &request, &mut warnings)?, frames[3..4]);
+        assert_eq!(warnings.len(), 1);
+        Ok(())
+    }
+    #[test]
+    fn test_mock_backend_tags() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (plain, tagged) = (dir.path().join("plain.f2v2fraw"), dir.path().join("tagged.f2v2fraw"));
+        let tags = [("f2v2f_manifest".to_string(), "{}".to_string())];
+        let spec = OutputSpec { width: 4, height: 2, fps: 10, keyframe_interval: None, deterministic: true, metadata: None, subtitles: None, tags: &[], attachments: &[] };
+        for (path, tags) in [(&plain, &[][..]), (&tagged, &tags[..])] {
+            let mut sink = MockBackend.create(path, &OutputSpec { tags, ..spec.clone() })?;
+            sink.write_all(&[7; 32])?;
+            sink.finish()?;
+        }
+
+        assert_eq!(MockBackend.read_tag(&plain, "f2v2f_manifest")?, None);
+        assert_eq!(MockBackend.read_tag(&tagged, "f2v2f_manifest")?.as_deref(), Some("{}"));
+        // The tags don't shift the frames
+        assert_eq!(MockBackend.probe_frames(&tagged)?, (1, 10.0));
+        let request = FrameRequest {
+            width: 4,
+            height: 2,
+            selection: FrameSelection::All,
+            filters: Vec::new(),
+            format: ExtractFormat::default(),
+            ignore_errors: false,
+        };
+        let frames = MockBackend.read_frames(&tagged, &request, &mut Vec::new())?;
+        assert_eq!(frames[0].as_raw(), &[7; 32]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mock_backend_attachments_and_subtitles() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let video = dir.path().join("attached.mkv");
+        let attachments = [Attachment { name: "notes.json".into(), mime: "application/json".into(), data: b"[1]".to_vec() }];
+        let srt = dir.path().join("track.srt");
+        std::fs::write(&srt, "1\n00:00:00,000 --> 00:00:00,001\nab\n")?;
+        let spec = OutputSpec { width: 4, height: 2, fps: 10, keyframe_interval: None, deterministic: true, metadata: None, subtitles: Some(&srt), tags: &[], attachments: &attachments };
+        let mut sink = MockBackend.create(&video, &spec)?;
+        sink.write_all(&[7; 32])?;
+        sink.finish()?;
+
+        assert_eq!(MockBackend.read_attachment(&video, "notes.json")?.as_deref(), Some(&b"[1]"[..]));
+        assert_eq!(MockBackend.read_attachment(&video, "other.json")?, None);
+        assert_eq!(MockBackend.read_tag(&video, "notes.json")?, None);
+        assert_eq!(MockBackend.read_subtitles(&video)?.as_deref(), Some("1\n00:00:00,000 --> 00:00:00,001\nab\n"));
+        assert_eq!(MockBackend.probe_frames(&video)?, (1, 10.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_matroska() {
+        assert!(is_matroska(Path::new("out.mkv")));
+        assert!(is_matroska(Path::new("dir/.OUT.MKV")));
+        assert!(!is_matroska(Path::new("out.mp4")));
+        assert!(!is_matroska(Path::new("mkv")));
+    }
+}
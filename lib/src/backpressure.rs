@@ -0,0 +1,192 @@
+//! Bounded byte channel between frame extraction and the output writer
+//!
+//! With `DecodeConfig::high_watermark` set, the decoder writes the file while
+//! frames are still being extracted. Extracted payload goes through this
+//! channel; once `high_watermark` bytes are waiting, writes block until the
+//! output catches up, which pauses extraction instead of buffering without
+//! bound when the destination is slow.
+//!
+//! Dropping the writer without calling `finish` makes the reader fail, so a
+//! decode that went wrong never looks like a complete payload; dropping the
+//! reader makes further writes fail.
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+#[derive(Default)]
+struct State {
+    chunks: VecDeque<Vec<u8>>,
+    /// Bytes in `chunks`
+    buffered: usize,
+    /// Set by the writer: `Some(true)` when finished, `Some(false)` if dropped early
+    writer_done: Option<bool>,
+    reader_gone: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+    high_watermark: usize,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn wait<'a>(&self, guard: MutexGuard<'a, State>) -> MutexGuard<'a, State> {
+        self.changed.wait(guard).unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Channel that holds up to about `high_watermark` bytes
+pub fn channel(high_watermark: usize) -> (ChannelWriter, ChannelReader) {
+    let shared = Arc::new(Shared { state: Mutex::default(), changed: Condvar::new(), high_watermark });
+    (
+        ChannelWriter { shared: shared.clone(), pauses: 0 },
+        ChannelReader { shared, current: Vec::new(), offset: 0 },
+    )
+}
+
+pub struct ChannelWriter {
+    shared: Arc<Shared>,
+    pauses: u64,
+}
+
+impl ChannelWriter {
+    /// Times a write had to wait for the reader
+    pub fn pauses(&self) -> u64 {
+        self.pauses
+    }
+
+    /// Whether the reader has gone away (further writes fail)
+    pub fn is_closed(&self) -> bool {
+        self.shared.lock().reader_gone
+    }
+
+    /// Mark the data complete; the reader sees end of file once it's drained
+    pub fn finish(self) {
+        self.close(true);
+    }
+
+    fn close(&self, complete: bool) {
+        let mut state = self.shared.lock();
+        state.writer_done.get_or_insert(complete);
+        self.shared.changed.notify_all();
+    }
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut state = self.shared.lock();
+        if state.buffered >= self.shared.high_watermark && !state.reader_gone {
+            self.pauses += 1;
+            while state.buffered >= self.shared.high_watermark && !state.reader_gone {
+                state = self.shared.wait(state);
+            }
+        }
+        if state.reader_gone {
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, "Output writer stopped"));
+        }
+        state.chunks.push_back(buf.to_vec());
+        state.buffered += buf.len();
+        self.shared.changed.notify_all();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for ChannelWriter {
+    fn drop(&mut self) {
+        self.close(false);
+    }
+}
+
+pub struct ChannelReader {
+    shared: Arc<Shared>,
+    current: Vec<u8>,
+    offset: usize,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.offset == self.current.len() {
+            let mut state = self.shared.lock();
+            loop {
+                if let Some(chunk) = state.chunks.pop_front() {
+                    state.buffered -= chunk.len();
+                    self.shared.changed.notify_all();
+                    self.current = chunk;
+                    self.offset = 0;
+                    break;
+                }
+                match state.writer_done {
+                    Some(true) => return Ok(0),
+                    Some(false) => {
+                        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Payload stream ended early"))
+                    }
+                    None => state = self.shared.wait(state),
+                }
+            }
+        }
+        let n = buf.len().min(self.current.len() - self.offset);
+        buf[..n].copy_from_slice(&self.current[self.offset..self.offset + n]);
+        self.offset += n;
+        Ok(n)
+    }
+}
+
+impl Drop for ChannelReader {
+    fn drop(&mut self) {
+        self.shared.lock().reader_gone = true;
+        self.shared.changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_writer_pauses_at_high_watermark() -> io::Result<()> {
+        let (mut tx, mut rx) = channel(8);
+        let producer = std::thread::spawn(move || -> io::Result<u64> {
+            for i in 0..10u8 {
+                tx.write_all(&[i; 4])?;
+            }
+            let pauses = tx.pauses();
+            tx.finish();
+            Ok(pauses)
+        });
+
+        // Give the producer time to fill the channel before reading
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        let mut out = Vec::new();
+        rx.read_to_end(&mut out)?;
+        assert!(producer.join().unwrap()? > 0);
+        assert_eq!(out, (0..10u8).flat_map(|i| [i; 4]).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    fn test_early_drop_fails_the_other_side() {
+        let (mut tx, rx) = channel(8);
+        tx.write_all(b"data").unwrap();
+        drop(tx);
+        let mut out = Vec::new();
+        let err = { rx }.read_to_end(&mut out).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let (mut tx, rx) = channel(8);
+        drop(rx);
+        assert!(tx.is_closed());
+        assert_eq!(tx.write(b"data").unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+    }
+}
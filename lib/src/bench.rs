@@ -0,0 +1,317 @@
+//! Codec damage matrix
+//!
+//! Renders random data at each resolution and density, puts the frames
+//! through every codec/CRF combination with ffmpeg and decodes them again,
+//! measuring throughput and how many bits came back wrong. Lossy runs (CRF
+//! above 0) use yuv420p chroma like most video platforms, so the matrix
+//! shows which settings would survive an upload.
+
+use crate::config::EncodeConfig;
+use crate::error::{F2V2FError, Result};
+use crate::frame_header::FrameHeader;
+use crate::image_generator::{GeometricArtGenerator, DEFAULT_SEED};
+use crate::backend::forward_stderr;
+use crate::video_composer::VideoComposer;
+use image::RgbaImage;
+use rand::{RngCore, SeedableRng};
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Instant;
+use tracing::debug;
+
+/// What to sweep
+#[derive(Debug, Clone)]
+pub struct MatrixConfig {
+    pub resolutions: Vec<(u32, u32)>,
+    /// ffmpeg encoder names (libx264, libx265, libvpx-vp9, ...)
+    pub codecs: Vec<String>,
+    pub crfs: Vec<u32>,
+    /// Chunk size as a share of frame capacity, in (0, 1]
+    pub densities: Vec<f32>,
+    /// Frames rendered per combination
+    pub frames: usize,
+    pub seed: u64,
+}
+
+impl Default for MatrixConfig {
+    fn default() -> Self {
+        Self {
+            resolutions: vec![(640, 360), (1280, 720), (1920, 1080)],
+            codecs: vec!["libx264".to_string()],
+            crfs: vec![0, 18, 23, 28],
+            densities: vec![0.25, 1.0],
+            frames: 10,
+            seed: DEFAULT_SEED,
+        }
+    }
+}
+
+impl MatrixConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.frames == 0 {
+            return Err(F2V2FError::ConfigError("Frames must be at least 1".to_string()));
+        }
+        if let Some(density) = self.densities.iter().find(|d| !(**d > 0.0 && **d <= 1.0)) {
+            return Err(F2V2FError::ConfigError(format!(
+                "Density must be above 0 and at most 1, got {}",
+                density
+            )));
+        }
+        for &(width, height) in &self.resolutions {
+            EncodeConfig::parse_resolution(&format!("{}x{}", width, height))?;
+        }
+        Ok(())
+    }
+}
+
+/// Results for one combination
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MatrixRow {
+    pub width: u32,
+    pub height: u32,
+    pub codec: String,
+    pub crf: u32,
+    pub density: f32,
+    pub chunk_size: usize,
+    /// Payload MB/s rendering frames
+    pub generate_mb_s: f64,
+    /// Payload MB/s through ffmpeg encoding
+    pub encode_mb_s: f64,
+    /// Payload MB/s extracting frames and reading the bytes back
+    pub decode_mb_s: f64,
+    pub video_bytes: u64,
+    /// Share of payload bits that decoded wrong (lost frames count as all wrong)
+    pub bit_error_rate: f64,
+    pub crc_failures: usize,
+}
+
+impl MatrixRow {
+    pub const CSV_HEADER: &'static str = "width,height,codec,crf,density,chunk_size,generate_mb_s,\
+        encode_mb_s,decode_mb_s,video_bytes,bit_error_rate,crc_failures";
+
+    pub fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{:.2},{:.2},{:.2},{},{:.6},{}",
+            self.width,
+            self.height,
+            self.codec,
+            self.crf,
+            self.density,
+            self.chunk_size,
+            self.generate_mb_s,
+            self.encode_mb_s,
+            self.decode_mb_s,
+            self.video_bytes,
+            self.bit_error_rate,
+            self.crc_failures
+        )
+    }
+}
+
+/// Run every combination, handing each row to `on_row` as it finishes
+pub async fn run_matrix(config: &MatrixConfig, mut on_row: impl FnMut(&MatrixRow)) -> Result<Vec<MatrixRow>> {
+    config.validate()?;
+    let dir = tempfile::tempdir()?;
+    let mut rows = Vec::new();
+
+    for &(width, height) in &config.resolutions {
+        let generator = GeometricArtGenerator::new(width, height, config.seed);
+        let capacity = EncodeConfig { width, height, seed: config.seed, ..EncodeConfig::default() }.frame_capacity();
+
+        for &density in &config.densities {
+            let chunk_size = ((capacity as f32 * density) as usize).clamp(1, capacity);
+            let chunks = random_chunks(config.seed, config.frames, chunk_size);
+            let payload_mb = (chunk_size * config.frames) as f64 / (1024.0 * 1024.0);
+
+            let started = Instant::now();
+            let frames = chunks
+                .iter()
+                .enumerate()
+                .map(|(i, chunk)| generator.generate_frame(&FrameHeader::new(i as u32, chunk), chunk))
+                .collect::<Result<Vec<_>>>()?;
+            let generate_secs = started.elapsed().as_secs_f64();
+
+            for codec in &config.codecs {
+                for &crf in &config.crfs {
+                    debug!("Matrix: {}x{} density {} {} crf {}", width, height, density, codec, crf);
+                    let video = dir.path().join("matrix.mkv");
+
+                    let started = Instant::now();
+                    encode_frames(&frames, width, height, codec, crf, &video)?;
+                    let encode_secs = started.elapsed().as_secs_f64();
+
+                    let started = Instant::now();
+                    let decoded = VideoComposer::new(width, height, 30).extract_frames(&video).await?;
+                    let damage = measure_damage(&generator, &chunks, &decoded)?;
+                    let decode_secs = started.elapsed().as_secs_f64();
+
+                    let row = MatrixRow {
+                        width,
+                        height,
+                        codec: codec.clone(),
+                        crf,
+                        density,
+                        chunk_size,
+                        generate_mb_s: rate(payload_mb, generate_secs),
+                        encode_mb_s: rate(payload_mb, encode_secs),
+                        decode_mb_s: rate(payload_mb, decode_secs),
+                        video_bytes: std::fs::metadata(&video)?.len(),
+                        bit_error_rate: damage.bit_error_rate,
+                        crc_failures: damage.crc_failures,
+                    };
+                    on_row(&row);
+                    rows.push(row);
+                }
+            }
+        }
+    }
+    Ok(rows)
+}
+
+fn rate(megabytes: f64, secs: f64) -> f64 {
+    if secs > 0.0 {
+        megabytes / secs
+    } else {
+        0.0
+    }
+}
+
+fn random_chunks(seed: u64, count: usize, chunk_size: usize) -> Vec<Vec<u8>> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    (0..count)
+        .map(|_| {
+            let mut chunk = vec![0u8; chunk_size];
+            rng.fill_bytes(&mut chunk);
+            chunk
+        })
+        .collect()
+}
+
+/// Encode raw RGBA frames with `codec` at `crf`
+fn encode_frames(frames: &[RgbaImage], width: u32, height: u32, codec: &str, crf: u32, output: &Path) -> Result<()> {
+    let mut command = Command::new("/usr/local/bin/ffmpeg");
+    command.args([
+        "-y",
+        "-f", "rawvideo",
+        "-pix_fmt", "rgba",
+        "-video_size", &format!("{}x{}", width, height),
+        "-framerate", "30",
+        "-i", "pipe:0",
+        "-c:v", codec,
+        "-crf", &crf.to_string(),
+        "-pix_fmt", if crf == 0 { "yuv444p" } else { "yuv420p" },
+    ]);
+    if codec.starts_with("libvpx") {
+        // Constant quality mode
+        command.args(["-b:v", "0"]);
+    }
+    let mut child = command
+        .arg(output)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| F2V2FError::EncodingError(format!("Failed to start ffmpeg: {}", e)))?;
+    let stderr = forward_stderr(&mut child);
+
+    let mut stdin = child.stdin.take().ok_or_else(|| F2V2FError::EncodingError("No stdin".to_string()))?;
+    for frame in frames {
+        stdin
+            .write_all(frame.as_raw())
+            .map_err(|e| F2V2FError::EncodingError(format!("Write failed: {}", e)))?;
+    }
+    drop(stdin);
+
+    let status = child.wait().map_err(|e| F2V2FError::EncodingError(format!("Wait failed: {}", e)))?;
+    let details = stderr.join();
+    if !status.success() {
+        return Err(F2V2FError::EncodingError(format!(
+            "{} at crf {} failed (code {}): {}",
+            codec,
+            crf,
+            status.code().unwrap_or(-1),
+            details
+        )));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Damage {
+    bit_error_rate: f64,
+    crc_failures: usize,
+}
+
+/// Compare decoded frames with the chunks they were rendered from
+fn measure_damage(generator: &GeometricArtGenerator, chunks: &[Vec<u8>], frames: &[RgbaImage]) -> Result<Damage> {
+    let (mut wrong_bits, mut total_bits, mut crc_failures) = (0u64, 0u64, 0);
+    for (i, chunk) in chunks.iter().enumerate() {
+        total_bits += chunk.len() as u64 * 8;
+        let Some(frame) = frames.get(i) else {
+            wrong_bits += chunk.len() as u64 * 8;
+            crc_failures += 1;
+            continue;
+        };
+        let decoded = generator.decode_from_image(frame, chunk.len())?;
+        wrong_bits += chunk.iter().zip(&decoded).map(|(a, b)| (a ^ b).count_ones() as u64).sum::<u64>();
+        if !FrameHeader::new(i as u32, chunk).verify(&decoded) {
+            crc_failures += 1;
+        }
+    }
+    Ok(Damage {
+        bit_error_rate: if total_bits > 0 { wrong_bits as f64 / total_bits as f64 } else { 0.0 },
+        crc_failures,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_measure_damage() -> Result<()> {
+        let generator = GeometricArtGenerator::new(256, 256, 7);
+        let chunks = random_chunks(1, 3, 500);
+        let mut frames = chunks
+            .iter()
+            .enumerate()
+            .map(|(i, chunk)| generator.generate_frame(&FrameHeader::new(i as u32, chunk), chunk))
+            .collect::<Result<Vec<_>>>()?;
+
+        let clean = measure_damage(&generator, &chunks, &frames)?;
+        assert_eq!(clean, Damage { bit_error_rate: 0.0, crc_failures: 0 });
+
+        // A dropped frame counts as entirely wrong
+        frames.pop();
+        let dropped = measure_damage(&generator, &chunks, &frames)?;
+        assert_eq!(dropped.crc_failures, 1);
+        assert!((dropped.bit_error_rate - 1.0 / 3.0).abs() < 1e-9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_and_csv() {
+        assert!(MatrixConfig::default().validate().is_ok());
+        let bad = MatrixConfig { densities: vec![1.5], ..MatrixConfig::default() };
+        assert!(matches!(bad.validate(), Err(F2V2FError::ConfigError(_))));
+
+        let row = MatrixRow {
+            width: 640,
+            height: 360,
+            codec: "libx264".to_string(),
+            crf: 23,
+            density: 0.25,
+            chunk_size: 1000,
+            generate_mb_s: 12.0,
+            encode_mb_s: 3.5,
+            decode_mb_s: 8.25,
+            video_bytes: 4096,
+            bit_error_rate: 0.0125,
+            crc_failures: 2,
+        };
+        assert_eq!(row.to_csv().split(',').count(), MatrixRow::CSV_HEADER.split(',').count());
+        assert_eq!(row.to_csv(), "640,360,libx264,23,0.25,1000,12.00,3.50,8.25,4096,0.012500,2");
+    }
+}
@@ -0,0 +1,280 @@
+//! Duplicate-encode detection
+//!
+//! With a cache directory configured, every finished encode is recorded under
+//! a key made from the input's checksum and the settings that affect the
+//! video. Encoding identical input with identical settings again (watch
+//! folders, re-run batches) reuses the earlier video instead: nothing is done
+//! if it is already at the output path, otherwise it is hard-linked there
+//! (copied across filesystems).
+
+use crate::atomic::{write_atomic, AtomicOutput};
+use crate::checksum::{hash_file, HashAlgorithm};
+use crate::config::EncodeConfig;
+use crate::encoder::EncodedFileInfo;
+use crate::error::{F2V2FError, Result};
+use crate::file_metadata::FileMetadata;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Settings that change the produced video, hashed into the cache key
+#[derive(Serialize)]
+struct KeyFields<'a> {
+    input_checksum: &'a str,
+    /// Output extension: Matroska takes the manifest as attachments, others as tags
+    container: String,
+    width: u32,
+    height: u32,
+    fps: u32,
+    chunk_size: usize,
+    art_style: &'a str,
+    use_compression: bool,
+    compression_level: i32,
+    hash_algo: HashAlgorithm,
+    /// Checksum of the dictionary contents, not its path
+    dictionary: Option<String>,
+    seed: u64,
+    deterministic: bool,
+    max_frames: Option<u64>,
+    /// The overlay shows the input's file name
+    overlay_label: Option<String>,
+    transition_frames: u32,
+    entropy_style: bool,
+    merkle_block_size: Option<u64>,
+    keyframe_interval: Option<u32>,
+    chunk_chapters: Option<u32>,
+    embed_manifest: bool,
+    manifest_tag: bool,
+    subtitle_manifest: bool,
+    adaptive_fps: bool,
+    /// ID and checksum of the contents of each extra stream
+    streams: Vec<(u8, String)>,
+    /// Recorded in the manifest, which the video embeds
+    file_metadata: Option<FileMetadata>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    /// Absolute path of the recorded video
+    video: PathBuf,
+    info: EncodedFileInfo,
+}
+
+/// Record of finished encodes, one JSON file per key
+pub struct EncodeCache {
+    dir: PathBuf,
+}
+
+impl EncodeCache {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self { dir: dir.as_ref().to_path_buf() }
+    }
+
+    /// Cache key for encoding `input` to `output` with `config` (reads the
+    /// whole input)
+    pub fn key(input: &Path, output: &Path, config: &EncodeConfig) -> Result<String> {
+        let input_checksum = hash_file(input, config.hash_algo)?;
+        let dictionary = match &config.dictionary {
+            Some(path) => Some(hash_file(path, HashAlgorithm::Sha256)?),
+            None => None,
+        };
+        let streams = config
+            .streams
+            .iter()
+            .map(|stream| Ok((stream.id, hash_file(&stream.path, HashAlgorithm::Sha256)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let overlay_label = config
+            .overlay
+            .then(|| input.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default());
+
+        let fields = KeyFields {
+            input_checksum: &input_checksum,
+            container: output.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default(),
+            width: config.width,
+            height: config.height,
+            fps: config.fps,
+            chunk_size: config.chunk_size,
+            art_style: &config.art_style,
+            use_compression: config.use_compression,
+            compression_level: config.compression_level,
+            hash_algo: config.hash_algo,
+            dictionary,
+            seed: config.seed,
+            deterministic: config.deterministic,
+            max_frames: config.max_frames,
+            overlay_label,
+            transition_frames: config.transition_frames,
+            entropy_style: config.entropy_style,
+            merkle_block_size: config.merkle_block_size,
+            keyframe_interval: config.keyframe_interval,
+            chunk_chapters: config.chunk_chapters,
+            embed_manifest: config.embed_manifest,
+            manifest_tag: config.manifest_tag,
+            subtitle_manifest: config.subtitle_manifest,
+            adaptive_fps: config.adaptive_fps,
+            streams,
+            file_metadata: config.preserve_metadata.then(|| FileMetadata::read(input)).transpose()?,
+        };
+        let json = serde_json::to_vec(&fields)
+            .map_err(|e| F2V2FError::EncodingError(format!("Failed to serialize cache key: {}", e)))?;
+        Ok(HashAlgorithm::Sha256.digest(&json))
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    /// Previous encode for `key`, if its video is still there and unchanged
+    pub fn lookup(&self, key: &str) -> Result<Option<(PathBuf, EncodedFileInfo)>> {
+        let path = self.entry_path(key);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let entry: CacheEntry = match serde_json::from_slice(&std::fs::read(&path)?) {
+            Ok(entry) => entry,
+            Err(e) => {
+                debug!("Ignoring unreadable cache entry {}: {}", path.display(), e);
+                return Ok(None);
+            }
+        };
+
+        // Moved, deleted or replaced since it was recorded
+        match std::fs::metadata(&entry.video) {
+            Ok(meta) if meta.is_file() && meta.len() == entry.info.video_size_bytes => {
+                Ok(Some((entry.video, entry.info)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Remember that `output` holds the encode for `key`
+    pub fn record(&self, key: &str, output: &Path, info: &EncodedFileInfo) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let entry = CacheEntry { video: std::fs::canonicalize(output)?, info: info.clone() };
+        let json = serde_json::to_vec_pretty(&entry)
+            .map_err(|e| F2V2FError::EncodingError(format!("Failed to serialize cache entry: {}", e)))?;
+        write_atomic(self.entry_path(key), &json)
+    }
+}
+
+/// Put the cached `video` at `output`: a no-op if it's already there,
+/// otherwise a hard link, falling back to a copy
+pub fn reuse_video(video: &Path, output: &Path, overwrite: bool) -> Result<()> {
+    if std::fs::canonicalize(output).is_ok_and(|existing| existing == video) {
+        return Ok(());
+    }
+
+    let staged = AtomicOutput::new(output, overwrite)?;
+    let linked = if overwrite {
+        // Link under a fresh name, then rename over the old output
+        let link = staged.path().with_extension("link");
+        let result = std::fs::hard_link(video, &link).and_then(|_| std::fs::rename(&link, output));
+        let _ = std::fs::remove_file(&link);
+        result
+    } else {
+        // Never replaces an existing file
+        std::fs::hard_link(video, output)
+    };
+    match linked {
+        Ok(()) => return Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            return Err(F2V2FError::OutputExists(output.display().to_string()));
+        }
+        Err(e) => debug!("Could not hard-link {} ({}), copying instead", video.display(), e),
+    }
+
+    std::fs::copy(video, staged.path())?;
+    staged.as_file().sync_all()?;
+    staged.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_info(video_size_bytes: u64) -> EncodedFileInfo {
+        EncodedFileInfo {
+            original_file_size: 5,
+            checksum: "abc".to_string(),
+            hash_algo: HashAlgorithm::Sha256,
+            num_frames: 1,
+            chunk_size: 4096,
+            art_style: "geometric".to_string(),
+            encoded_size: 5,
+            compression_ratio: 1.0,
+            dictionary_id: None,
+            video_size_bytes,
+            duration_secs: 0.1,
+            overhead_ratio: 1.0,
+            holes: Vec::new(),
+            link_target: None,
+            exceeds_max_frames: false,
+            content_type: None,
+            frame_complexity: Vec::new(),
+            warnings: Vec::new(),
+            video_checksum: None,
+            merkle: None,
+            file_metadata: None,
+            frame_index: None,
+            streams: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_key_depends_on_content_and_settings() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let a = dir.path().join("a.bin");
+        let b = dir.path().join("b.bin");
+        std::fs::write(&a, b"same")?;
+        std::fs::write(&b, b"same")?;
+
+        let video = dir.path().join("out.mp4");
+        let config = EncodeConfig::default();
+        // Same content under another name: same key unless the overlay shows
+        // the name or the manifest records it
+        assert_eq!(EncodeCache::key(&a, &video, &config)?, EncodeCache::key(&b, &video, &config)?);
+        let overlay = EncodeConfig { overlay: true, ..config.clone() };
+        assert_ne!(EncodeCache::key(&a, &video, &overlay)?, EncodeCache::key(&b, &video, &overlay)?);
+        let recorded = EncodeConfig { preserve_metadata: true, ..config.clone() };
+        assert_ne!(EncodeCache::key(&a, &video, &recorded)?, EncodeCache::key(&b, &video, &recorded)?);
+
+        let reseeded = EncodeConfig { seed: 1, ..config.clone() };
+        assert_ne!(EncodeCache::key(&a, &video, &config)?, EncodeCache::key(&a, &video, &reseeded)?);
+        // Performance knobs don't change the video
+        let throttled = EncodeConfig { num_threads: 1, buffer_size: 4096, ..config.clone() };
+        assert_eq!(EncodeCache::key(&a, &video, &config)?, EncodeCache::key(&a, &video, &throttled)?);
+
+        // The container decides where the manifest goes
+        let mkv = dir.path().join("out.mkv");
+        assert_ne!(EncodeCache::key(&a, &video, &config)?, EncodeCache::key(&a, &mkv, &config)?);
+
+        std::fs::write(&b, b"diff")?;
+        assert_ne!(EncodeCache::key(&a, &video, &config)?, EncodeCache::key(&b, &video, &config)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_lookup_and_reuse() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = EncodeCache::new(dir.path().join("cache"));
+        let video = dir.path().join("first.mp4");
+        std::fs::write(&video, b"video")?;
+
+        assert!(cache.lookup("k")?.is_none());
+        cache.record("k", &video, &sample_info(5))?;
+        let (cached, info) = cache.lookup("k")?.expect("cache hit");
+        assert_eq!(info.checksum, "abc");
+
+        let copy = dir.path().join("second.mp4");
+        reuse_video(&cached, &copy, false)?;
+        assert_eq!(std::fs::read(&copy)?, b"video");
+        assert!(matches!(reuse_video(&cached, &copy, false), Err(F2V2FError::OutputExists(_))));
+        // Reusing onto the recorded video itself is a no-op
+        reuse_video(&cached, &video, false)?;
+
+        // A changed video is no longer a hit
+        std::fs::write(&video, b"edited video")?;
+        assert!(cache.lookup("k")?.is_none());
+        Ok(())
+    }
+}
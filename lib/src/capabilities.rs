@@ -0,0 +1,91 @@
+//! What this build and the installed ffmpeg can do
+//!
+//! Front-ends use this to fill their option menus instead of hard-coding
+//! lists that drift from the library. Codecs and backends are probed, so the
+//! answer reflects the machine it runs on.
+
+use crate::config::{EncodeConfig, MAX_CHUNK_SIZE};
+use crate::image_generator::ART_STYLES;
+use crate::video_composer::{VideoComposer, VIDEO_CODEC};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use tracing::debug;
+
+/// External tool the library shells out to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Backend {
+    pub name: String,
+    pub path: PathBuf,
+    /// The tool ran when probed
+    pub available: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Capabilities {
+    pub version: String,
+    /// Codecs the composer can encode with on this machine (empty without ffmpeg)
+    pub codecs: Vec<String>,
+    pub art_styles: Vec<String>,
+    pub backends: Vec<Backend>,
+    /// Data bytes one frame carries with the given config
+    pub frame_capacity: usize,
+    /// Largest (compressed) payload that fits in `max_frames` frames, or
+    /// `None` when the config sets no frame limit
+    pub max_payload_bytes: Option<u64>,
+}
+
+fn probe_backend(name: &str) -> Backend {
+    let path = PathBuf::from(format!("/usr/local/bin/{}", name));
+    let available = Command::new(&path)
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    Backend { name: name.to_string(), path, available }
+}
+
+/// Largest payload `config` can put in one video without exceeding `max_frames`
+pub fn max_payload_bytes(config: &EncodeConfig) -> Option<u64> {
+    let chunk = config.frame_capacity().min(MAX_CHUNK_SIZE) as u64;
+    config.max_frames.filter(|&frames| frames > 0).map(|frames| frames.saturating_mul(chunk))
+}
+
+/// Probe codecs and backends and report what `config` allows
+pub fn capabilities(config: &EncodeConfig) -> Capabilities {
+    let codecs = match VideoComposer::probe_encoders() {
+        Ok(encoders) => encoders.into_iter().filter(|e| e == VIDEO_CODEC).collect(),
+        Err(e) => {
+            debug!("Could not probe ffmpeg encoders: {}", e);
+            Vec::new()
+        }
+    };
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        codecs,
+        art_styles: ART_STYLES.iter().map(|s| s.to_string()).collect(),
+        backends: vec![probe_backend("ffmpeg"), probe_backend("ffprobe")],
+        frame_capacity: config.frame_capacity(),
+        max_payload_bytes: max_payload_bytes(config),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_payload_follows_frame_limit() {
+        let config = EncodeConfig { max_frames: Some(10), ..EncodeConfig::default() };
+        let capacity = config.frame_capacity().min(MAX_CHUNK_SIZE) as u64;
+        assert_eq!(max_payload_bytes(&config), Some(10 * capacity));
+        assert_eq!(max_payload_bytes(&EncodeConfig { max_frames: None, ..config }), None);
+
+        let caps = capabilities(&EncodeConfig::default());
+        assert!(caps.art_styles.iter().any(|s| s == "raw"));
+        assert_eq!(caps.backends.len(), 2);
+        // Only codecs the composer actually uses are offered
+        assert!(caps.codecs.iter().all(|c| c == VIDEO_CODEC));
+    }
+}
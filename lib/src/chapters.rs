@@ -0,0 +1,69 @@
+//! Container chapter markers
+//!
+//! Chapters are written through an FFMETADATA file handed to ffmpeg alongside
+//! the raw frame stream, so regular players show named boundaries (e.g. which
+//! file bytes a stretch of frames holds).
+
+/// A named chapter starting at a given frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    pub start_frame: u64,
+    pub title: String,
+}
+
+impl Chapter {
+    pub fn new(start_frame: u64, title: impl Into<String>) -> Self {
+        Self { start_frame, title: title.into() }
+    }
+}
+
+/// Render chapters as an FFMETADATA document
+///
+/// Timestamps use a `1/fps` timebase so chapter boundaries land exactly on
+/// frames. Each chapter ends where the next begins (the last at `total_frames`).
+pub fn to_ffmetadata(chapters: &[Chapter], fps: u32, total_frames: u64) -> String {
+    let mut sorted = chapters.to_vec();
+    sorted.sort_by_key(|c| c.start_frame);
+
+    let mut out = String::from(";FFMETADATA1\n");
+    for (i, chapter) in sorted.iter().enumerate() {
+        let end = sorted
+            .get(i + 1)
+            .map(|next| next.start_frame)
+            .unwrap_or(total_frames)
+            .max(chapter.start_frame + 1);
+        out.push_str("\n[CHAPTER]\n");
+        out.push_str(&format!("TIMEBASE=1/{}\n", fps));
+        out.push_str(&format!("START={}\n", chapter.start_frame));
+        out.push_str(&format!("END={}\n", end));
+        out.push_str(&format!("title={}\n", escape_value(&chapter.title)));
+    }
+    out
+}
+
+/// Escape characters that are special in FFMETADATA values
+fn escape_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        if matches!(ch, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ffmetadata_rendering() {
+        let chapters = vec![Chapter::new(10, "b.txt"), Chapter::new(0, "a=1.txt")];
+        let meta = to_ffmetadata(&chapters, 30, 25);
+
+        assert!(meta.starts_with(";FFMETADATA1\n"));
+        assert!(meta.contains("START=0\nEND=10\ntitle=a\\=1.txt\n"));
+        assert!(meta.contains("START=10\nEND=25\ntitle=b.txt\n"));
+    }
+}
@@ -0,0 +1,211 @@
+use crate::error::{F2V2FError, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Hash algorithm used for the whole-file checksum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    /// SHA-256 (default, compatible with existing checksums)
+    #[default]
+    Sha256,
+    /// BLAKE3: cryptographic and several times faster than SHA-256
+    Blake3,
+    /// XXH3-128: non-cryptographic, for quick corruption checks only
+    Xxh3,
+}
+
+impl HashAlgorithm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Blake3 => "blake3",
+            HashAlgorithm::Xxh3 => "xxh3",
+        }
+    }
+
+    /// Hash a complete buffer and return the lowercase hex digest
+    pub fn digest(&self, data: &[u8]) -> String {
+        let mut hasher = Hasher::new(*self);
+        hasher.update(data);
+        hasher.finalize()
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = F2V2FError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "sha256" | "sha-256" => Ok(HashAlgorithm::Sha256),
+            "blake3" => Ok(HashAlgorithm::Blake3),
+            "xxh3" | "xxhash" => Ok(HashAlgorithm::Xxh3),
+            other => Err(F2V2FError::InvalidInput(format!(
+                "Unknown hash algorithm '{}' (expected sha256, blake3 or xxh3)",
+                other
+            ))),
+        }
+    }
+}
+
+/// Incremental hasher over any supported algorithm
+pub enum Hasher {
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+    Xxh3(Box<xxhash_rust::xxh3::Xxh3>),
+}
+
+impl Hasher {
+    pub fn new(algo: HashAlgorithm) -> Self {
+        match algo {
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            HashAlgorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+            HashAlgorithm::Xxh3 => Hasher::Xxh3(Box::new(xxhash_rust::xxh3::Xxh3::new())),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(h) => h.update(data),
+            Hasher::Blake3(h) => {
+                h.update(data);
+            }
+            Hasher::Xxh3(h) => h.update(data),
+        }
+    }
+
+    /// Hash `len` zero bytes (used for sparse-file holes)
+    pub fn update_zeros(&mut self, mut len: u64) {
+        let zeros = [0u8; 64 * 1024];
+        while len > 0 {
+            let n = len.min(zeros.len() as u64) as usize;
+            self.update(&zeros[..n]);
+            len -= n as u64;
+        }
+    }
+
+    /// Finish hashing and return the lowercase hex digest
+    pub fn finalize(self) -> String {
+        match self {
+            Hasher::Sha256(h) => format!("{:x}", h.finalize()),
+            Hasher::Blake3(h) => h.finalize().to_hex().to_string(),
+            Hasher::Xxh3(h) => format!("{:032x}", h.digest128()),
+        }
+    }
+}
+
+/// Checksum of a whole file, read in 1 MiB blocks
+pub fn hash_file<P: AsRef<Path>>(path: P, algo: HashAlgorithm) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Hasher::new(algo);
+    let mut buf = vec![0u8; 1024 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize())
+}
+
+/// Writer adapter that hashes everything passing through it
+///
+/// Lets the decoder compute the output checksum while writing, without
+/// keeping the data around for a second pass.
+pub struct HashingWriter<W: Write> {
+    inner: W,
+    hasher: Hasher,
+    bytes_written: u64,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub fn new(inner: W, algo: HashAlgorithm) -> Self {
+        Self { inner, hasher: Hasher::new(algo), bytes_written: 0 }
+    }
+
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Return the wrapped writer and the hex digest of everything written
+    pub fn finish(self) -> (W, String) {
+        (self.inner, self.hasher.finalize())
+    }
+}
+
+impl<W: Write + Seek> HashingWriter<W> {
+    /// Leave a hole of `len` zero bytes by seeking past it, hashing it as zeros
+    pub fn skip_zeros(&mut self, len: u64) -> io::Result<()> {
+        self.inner.seek(SeekFrom::Current(len as i64))?;
+        self.hasher.update_zeros(len);
+        self.bytes_written += len;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.bytes_written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha256_matches_known_digest() {
+        assert_eq!(
+            HashAlgorithm::Sha256.digest(b"test data"),
+            "916f0027a575074ce72a331777c3478d6513f786a591bd892da1a577bf2335f9"
+        );
+    }
+
+    #[test]
+    fn test_incremental_matches_one_shot() {
+        for algo in [HashAlgorithm::Sha256, HashAlgorithm::Blake3, HashAlgorithm::Xxh3] {
+            let mut hasher = Hasher::new(algo);
+            hasher.update(b"hello ");
+            hasher.update(b"world");
+            assert_eq!(hasher.finalize(), algo.digest(b"hello world"), "{}", algo);
+        }
+    }
+
+    #[test]
+    fn test_hashing_writer() {
+        let mut writer = HashingWriter::new(Vec::new(), HashAlgorithm::Sha256);
+        writer.write_all(b"test ").unwrap();
+        writer.write_all(b"data").unwrap();
+        assert_eq!(writer.bytes_written(), 9);
+
+        let (inner, digest) = writer.finish();
+        assert_eq!(inner, b"test data");
+        assert_eq!(digest, HashAlgorithm::Sha256.digest(b"test data"));
+    }
+
+    #[test]
+    fn test_parse_algorithm() {
+        assert_eq!("BLAKE3".parse::<HashAlgorithm>().unwrap(), HashAlgorithm::Blake3);
+        assert_eq!("xxhash".parse::<HashAlgorithm>().unwrap(), HashAlgorithm::Xxh3);
+        assert!("md5".parse::<HashAlgorithm>().is_err());
+    }
+}
@@ -0,0 +1,103 @@
+//! Exportable per-chunk hash list
+//!
+//! `Encoder::export_manifest` records the SHA-256 of every payload chunk
+//! (one per data frame) and the video frame it went into;
+//! `Decoder::verify_against_manifest` checks a video against that list frame
+//! by frame, without decompressing or writing the file. Unlike the CRCs in
+//! the frame headers, the list is kept apart from the video, so a video
+//! altered along with its headers still fails.
+
+use crate::checksum::HashAlgorithm;
+use crate::error::{F2V2FError, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Hash of every payload chunk of an encode
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkManifest {
+    /// Payload bytes per data frame
+    pub chunk_size: u64,
+    /// Payload size (after compression)
+    pub payload_size: u64,
+    pub chunks: Vec<ChunkEntry>,
+}
+
+/// One payload chunk, carried by one data frame
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkEntry {
+    pub index: u64,
+    /// Where the chunk starts in the payload
+    pub offset: u64,
+    pub size: u64,
+    pub sha256: String,
+    /// Video frame holding the chunk, if the encode recorded a frame index
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame: Option<u64>,
+}
+
+impl ChunkEntry {
+    /// Whether `payload` (a decoded frame, padding included) holds this chunk
+    pub fn matches(&self, payload: &[u8]) -> bool {
+        payload.len() as u64 >= self.size && HashAlgorithm::Sha256.digest(&payload[..self.size as usize]) == self.sha256
+    }
+}
+
+impl ChunkManifest {
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| F2V2FError::EncodingError(format!("Failed to serialize chunk manifest: {}", e)))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json)
+            .map_err(|e| F2V2FError::InvalidInput(format!("Invalid chunk manifest: {}", e)))
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        std::fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+}
+
+/// Outcome of `Decoder::verify_against_manifest`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChunkVerification {
+    /// Chunks whose frame matched their hash
+    pub verified: u64,
+    /// Chunks whose frame was found but didn't match
+    pub mismatched: Vec<u64>,
+    /// Chunks no frame of the video claims to hold
+    pub missing: Vec<u64>,
+}
+
+impl ChunkVerification {
+    pub fn is_ok(&self) -> bool {
+        self.mismatched.is_empty() && self.missing.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_matches_padded_payload() {
+        let entry = ChunkEntry {
+            index: 0,
+            offset: 0,
+            size: 3,
+            sha256: HashAlgorithm::Sha256.digest(b"abc"),
+            frame: Some(2),
+        };
+        assert!(entry.matches(b"abc\0\0"));
+        assert!(!entry.matches(b"abd\0\0"));
+        assert!(!entry.matches(b"ab"));
+
+        let manifest = ChunkManifest { chunk_size: 5, payload_size: 3, chunks: vec![entry] };
+        assert_eq!(ChunkManifest::from_json(&manifest.to_json().unwrap()).unwrap(), manifest);
+    }
+}
@@ -0,0 +1,113 @@
+//! The codec core without ffmpeg or files
+//!
+//! Payload bytes to frame images and back, through the same frame headers,
+//! art generator and frame extractor an encode and decode use. Nothing here
+//! touches the disk or spawns a process, and rendering depends only on the
+//! seed, so reconstruction can be tested exhaustively and fuzzed.
+
+use crate::decoder::{decode_frames, zstd_decoder};
+use crate::error::{F2V2FError, Result};
+use crate::frame_header::{FrameHeader, FLAG_RAW};
+use crate::image_generator::GeometricArtGenerator;
+use image::RgbaImage;
+use std::io::Read;
+
+/// Frame geometry and payload settings of a video, in memory
+#[derive(Debug, Clone)]
+pub struct FrameCodec {
+    generator: GeometricArtGenerator,
+    chunk_size: usize,
+    raw: bool,
+    /// Zstd level the payload is compressed with, if any
+    compression_level: Option<i32>,
+}
+
+impl FrameCodec {
+    pub fn new(width: u32, height: u32, seed: u64, chunk_size: usize) -> Result<Self> {
+        if chunk_size == 0 {
+            return Err(F2V2FError::ConfigError("Chunk size must be at least 1 byte".to_string()));
+        }
+        let generator = GeometricArtGenerator::new(width, height, seed);
+        Ok(Self { generator, chunk_size, raw: false, compression_level: None })
+    }
+
+    /// Plain gray-level frames instead of art
+    pub fn with_raw(mut self, enabled: bool) -> Self {
+        self.generator = self.generator.with_raw(enabled);
+        self.raw = enabled;
+        self
+    }
+
+    /// Compress the payload with zstd at `level` before it is framed
+    pub fn with_compression(mut self, level: Option<i32>) -> Self {
+        self.compression_level = level;
+        self
+    }
+
+    /// Frames holding `data`, one chunk each (an empty payload still gets
+    /// one marker frame)
+    pub fn encode(&self, data: &[u8]) -> Result<Vec<RgbaImage>> {
+        let capacity = self.generator.chunk_capacity();
+        if self.chunk_size > capacity {
+            return Err(F2V2FError::ConfigError(format!(
+                "Chunk size {} exceeds the {} bytes a frame decodes reliably",
+                self.chunk_size, capacity
+            )));
+        }
+        let payload = match self.compression_level {
+            Some(level) => zstd::encode_all(data, level)?,
+            None => data.to_vec(),
+        };
+        let mut padded = vec![0u8; self.chunk_size];
+        let chunks: Vec<&[u8]> = if payload.is_empty() { vec![&[]] } else { payload.chunks(self.chunk_size).collect() };
+        chunks
+            .into_iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                let mut header = FrameHeader::new(i as u32, chunk);
+                if self.raw {
+                    header.flags |= FLAG_RAW;
+                }
+                padded[..chunk.len()].copy_from_slice(chunk);
+                padded[chunk.len()..].fill(0);
+                self.generator.generate_frame(&header, &padded)
+            })
+            .collect()
+    }
+
+    /// Data recovered from `frames`, which may be reordered, repeated or
+    /// mixed with foreign frames; fails on a gap or a CRC mismatch
+    pub fn decode(&self, frames: &[RgbaImage]) -> Result<Vec<u8>> {
+        let payload = decode_frames(self.generator, Some(self.chunk_size), frames)?;
+        if self.compression_level.is_none() {
+            return Ok(payload);
+        }
+        let mut data = Vec::new();
+        zstd_decoder(&payload[..], None)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_in_memory() -> Result<()> {
+        let data: Vec<u8> = (0..5000u32).map(|i| (i % 211) as u8).collect();
+        let codec = FrameCodec::new(128, 128, 3, 1000)?;
+        let frames = codec.encode(&data)?;
+        assert_eq!(frames.len(), 5);
+        assert_eq!(codec.decode(&frames)?, data);
+
+        let compressed = codec.clone().with_compression(Some(3));
+        assert!(compressed.encode(&data)?.len() < frames.len());
+        assert_eq!(compressed.decode(&compressed.encode(&data)?)?, data);
+        assert_eq!(codec.decode(&codec.encode(&[])?)?, Vec::<u8>::new());
+
+        assert!(FrameCodec::new(128, 128, 3, 0).is_err());
+        assert!(FrameCodec::new(128, 128, 3, 5000)?.encode(&data).is_err());
+        assert!(FrameCodec::new(128, 128, 3, 5000)?.with_raw(true).encode(&data).is_ok());
+        Ok(())
+    }
+}
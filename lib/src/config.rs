@@ -1,6 +1,218 @@
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use crate::checksum::HashAlgorithm;
+use crate::extract_format::ExtractFormat;
 use crate::error::{F2V2FError, Result};
+use crate::image_generator::{GeometricArtGenerator, ART_STYLES, DEFAULT_SEED, RAW_ART_STYLE, SHOWCASE_ART_STYLE};
+use crate::manifest::Manifest;
+use crate::merkle::MIN_BLOCK_SIZE;
+use crate::streams::{ExtraStream, MAIN_STREAM};
+use crate::payload::DEFAULT_SPILL_THRESHOLD;
+use crate::throttle::Throttle;
+use crate::backend::{FfmpegBackend, VideoBackend};
+
+/// Largest allowed chunk size (10 MB)
+pub const MAX_CHUNK_SIZE: usize = 10 * 1024 * 1024;
+
+/// Zstd compression levels accepted by `EncodeConfig::compression_level`
+pub const COMPRESSION_LEVELS: std::ops::RangeInclusive<i32> = 1..=22;
+
+/// Most zstd worker threads `EncodeConfig::compression_threads` may ask for
+pub const MAX_COMPRESSION_THREADS: u32 = 200;
+
+/// Default frame-count target for large payloads
+pub const DEFAULT_MAX_FRAMES: u64 = 1000;
+
+/// Frame rates `EncodeConfig::adaptive_fps` picks from
+pub const ADAPTIVE_FPS_STEPS: &[u32] = &[1, 2, 5, 10, 15, 24, 30, 60, 120];
+
+/// Playback (seconds) `adaptive_fps` stretches shorter videos to, so each
+/// frame of the art stays up long enough to see
+pub const ADAPTIVE_MIN_SECS: f64 = 10.0;
+
+/// Playback (seconds) `adaptive_fps` squeezes longer videos into, as far as
+/// 120 fps allows
+pub const ADAPTIVE_MAX_SECS: f64 = 3600.0;
+
+/// What to do when the input is a symbolic link
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymlinkPolicy {
+    /// Encode the file the link points to (default)
+    #[default]
+    Follow,
+    /// Store only the link target and recreate the link on decode
+    Preserve,
+    /// Refuse to encode links
+    Skip,
+}
+
+impl FromStr for SymlinkPolicy {
+    type Err = F2V2FError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "follow" => Ok(SymlinkPolicy::Follow),
+            "preserve" => Ok(SymlinkPolicy::Preserve),
+            "skip" => Ok(SymlinkPolicy::Skip),
+            other => Err(F2V2FError::InvalidInput(format!(
+                "Unknown symlink policy '{}' (expected follow, preserve or skip)",
+                other
+            ))),
+        }
+    }
+}
+
+/// Named bundle of encode settings for a common use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// Throughput over looks: small raw-data frames packed to capacity,
+    /// lossless x264 and the fastest zstd level
+    FastData,
+    /// For publishing the video as art: highlight glow and vignette, with
+    /// small chunks so every byte is repeated enough to still decode
+    Showcase,
+}
+
+impl FromStr for Preset {
+    type Err = F2V2FError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "fast-data" => Ok(Preset::FastData),
+            "showcase" => Ok(Preset::Showcase),
+            other => Err(F2V2FError::InvalidInput(format!(
+                "Unknown preset '{}' (expected fast-data or showcase)",
+                other
+            ))),
+        }
+    }
+}
+
+impl Preset {
+    /// Overwrite the settings this preset controls; everything else in
+    /// `config` is kept
+    pub fn apply(self, config: &mut EncodeConfig) {
+        match self {
+            Preset::FastData => {
+                config.width = 256;
+                config.height = 256;
+                config.art_style = RAW_ART_STYLE.to_string();
+                // Level 1 runs at close to lz4 speed, and the decoder already reads zstd
+                config.compression_level = 1;
+                config.chunk_size = config.frame_capacity();
+            }
+            Preset::Showcase => {
+                config.art_style = SHOWCASE_ART_STYLE.to_string();
+                config.chunk_size = config.frame_capacity().min(4096);
+                // Growing chunks to cap the frame count would cost redundancy
+                config.max_frames = None;
+                config.transition_frames = 3;
+            }
+        }
+    }
+}
+
+/// The parts of an `EncodeConfig` that shape the video, recorded in the
+/// manifest so other files can be encoded exactly alike
+///
+/// Performance and filesystem knobs (threads, buffers, temp and cache
+/// directories, throttling) are left out; they don't change the result.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncodeSettings {
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    /// Configured chunk size (the manifest's `chunk_size` is the one used)
+    pub chunk_size: usize,
+    pub art_style: String,
+    pub use_compression: bool,
+    pub compression_level: i32,
+    pub overlay: bool,
+    pub hash_algo: HashAlgorithm,
+    pub dictionary: Option<PathBuf>,
+    pub seed: u64,
+    pub deterministic: bool,
+    pub symlinks: SymlinkPolicy,
+    pub max_frames: Option<u64>,
+    pub transition_frames: u32,
+    pub entropy_style: bool,
+    #[serde(default)]
+    pub merkle_block_size: Option<u64>,
+    #[serde(default)]
+    pub keyframe_interval: Option<u32>,
+    #[serde(default)]
+    pub embed_manifest: bool,
+    /// Left out when off, so videos without the tag are unchanged
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub manifest_tag: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub subtitle_manifest: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub adaptive_fps: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_chapters: Option<u32>,
+}
+
+impl From<&EncodeConfig> for EncodeSettings {
+    fn from(config: &EncodeConfig) -> Self {
+        Self {
+            width: config.width,
+            height: config.height,
+            fps: config.fps,
+            chunk_size: config.chunk_size,
+            art_style: config.art_style.clone(),
+            use_compression: config.use_compression,
+            compression_level: config.compression_level,
+            overlay: config.overlay,
+            hash_algo: config.hash_algo,
+            dictionary: config.dictionary.clone(),
+            seed: config.seed,
+            deterministic: config.deterministic,
+            symlinks: config.symlinks,
+            max_frames: config.max_frames,
+            transition_frames: config.transition_frames,
+            entropy_style: config.entropy_style,
+            merkle_block_size: config.merkle_block_size,
+            keyframe_interval: config.keyframe_interval,
+            embed_manifest: config.embed_manifest,
+            manifest_tag: config.manifest_tag,
+            subtitle_manifest: config.subtitle_manifest,
+            adaptive_fps: config.adaptive_fps,
+            chunk_chapters: config.chunk_chapters,
+        }
+    }
+}
+
+impl EncodeSettings {
+    /// Overwrite the recorded settings in `config`; everything else is kept
+    pub fn apply(&self, config: &mut EncodeConfig) {
+        config.width = self.width;
+        config.height = self.height;
+        config.fps = self.fps;
+        config.chunk_size = self.chunk_size;
+        config.art_style = self.art_style.clone();
+        config.use_compression = self.use_compression;
+        config.compression_level = self.compression_level;
+        config.overlay = self.overlay;
+        config.hash_algo = self.hash_algo;
+        config.dictionary = self.dictionary.clone();
+        config.seed = self.seed;
+        config.deterministic = self.deterministic;
+        config.symlinks = self.symlinks;
+        config.max_frames = self.max_frames;
+        config.transition_frames = self.transition_frames;
+        config.entropy_style = self.entropy_style;
+        config.merkle_block_size = self.merkle_block_size;
+        config.keyframe_interval = self.keyframe_interval;
+        config.embed_manifest = self.embed_manifest;
+        config.manifest_tag = self.manifest_tag;
+        config.subtitle_manifest = self.subtitle_manifest;
+        config.adaptive_fps = self.adaptive_fps;
+        config.chunk_chapters = self.chunk_chapters;
+    }
+}
 
 /// Configuration for encoding operations
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,7 +225,8 @@ pub struct EncodeConfig {
     pub fps: u32,
     /// Chunk size in bytes for processing
     pub chunk_size: usize,
-    /// Art style (geometric, fractal, noise)
+    /// Art style (geometric, fractal, noise, `raw` for plain data frames or
+    /// `showcase` for geometric with glow and vignette)
     pub art_style: String,
     /// Number of worker threads
     pub num_threads: usize,
@@ -23,6 +236,70 @@ pub struct EncodeConfig {
     pub use_compression: bool,
     /// Compression level (1-22, default 11)
     pub compression_level: i32,
+    /// Zstd worker threads; one per CPU if None (doesn't change the output)
+    pub compression_threads: Option<u32>,
+    /// Render a human-readable text overlay in a reserved corner of each frame
+    pub overlay: bool,
+    /// Checksum algorithm for the original file (recorded in the manifest)
+    pub hash_algo: HashAlgorithm,
+    /// Shared zstd dictionary file (see `dictionary::train_from_files`)
+    pub dictionary: Option<PathBuf>,
+    /// Art generator seed; varies the pattern (recorded in the manifest)
+    pub seed: u64,
+    /// Produce a byte-identical video for identical input and config
+    pub deterministic: bool,
+    /// Payloads larger than this many bytes are spilled to a temp file
+    pub spill_threshold: u64,
+    /// How a symlinked input is encoded
+    pub symlinks: SymlinkPolicy,
+    /// Frame-count target: the chunk size is raised (up to frame capacity)
+    /// to stay within it; larger payloads get more frames. `None` keeps the
+    /// configured chunk size regardless of frame count.
+    pub max_frames: Option<u64>,
+    /// Pace frame generation (max payload MB/s or CPU share)
+    pub throttle: Throttle,
+    /// Replace an existing output video (refused by default)
+    pub overwrite: bool,
+    /// Scratch directory for spill files and the partial video; defaults to
+    /// the system temp dir (and the output directory for the video)
+    pub temp_dir: Option<PathBuf>,
+    /// Duplicate-encode cache; identical re-encodes reuse the earlier video
+    pub cache_dir: Option<PathBuf>,
+    /// Crossfade frames between consecutive data frames (skipped on decode)
+    pub transition_frames: u32,
+    /// Frames between keyframes; 1 is all-intra (every frame decodes on its
+    /// own, which helps seeking and limits damage, at a bitrate cost).
+    /// ffmpeg's default if None
+    pub keyframe_interval: Option<u32>,
+    /// Mark a chapter every N data frames, titled with the file byte range
+    /// it covers, or the data frames when the payload is compressed (see
+    /// `VideoComposer::with_chunk_chapters`)
+    pub chunk_chapters: Option<u32>,
+    /// Vary pattern complexity with the input's local entropy: calm for
+    /// compressible stretches, busy for random ones (purely visual)
+    pub entropy_style: bool,
+    /// Record a Merkle tree over blocks of this many bytes, so byte ranges
+    /// of the decoded file can be verified on their own
+    pub merkle_block_size: Option<u64>,
+    /// Extra streams multiplexed alongside the file (see `streams`)
+    pub streams: Vec<ExtraStream>,
+    /// Also write the manifest into the video, before the first and after
+    /// the last data frame, for when the sidecar goes missing
+    pub embed_manifest: bool,
+    /// Also write the manifest into the container's metadata (an MP4 `udta`
+    /// tag, or an attachment in `.mkv` outputs), which copies of the file
+    /// keep without spending frames on it
+    pub manifest_tag: bool,
+    /// Also mux the manifest and the CRC of every data frame into a
+    /// subtitle track (see `subtitles`), a copy that needs no frames
+    pub subtitle_manifest: bool,
+    /// Record the input's name, modification time and permissions in the
+    /// manifest (see `file_metadata`); off by default, since the mtime
+    /// would make otherwise identical encodes (and cache keys) differ
+    pub preserve_metadata: bool,
+    /// Pick the frame rate from `ADAPTIVE_FPS_STEPS` by payload size
+    /// instead of using `fps` as is (see `fps_for`)
+    pub adaptive_fps: bool,
 }
 
 impl Default for EncodeConfig {
@@ -37,6 +314,30 @@ impl Default for EncodeConfig {
             buffer_size: 1024 * 1024, // 1MB
             use_compression: true,    // Enable compression by default
             compression_level: 11,    // Balanced speed/compression
+            compression_threads: None,
+            overlay: false,
+            hash_algo: HashAlgorithm::Sha256,
+            dictionary: None,
+            seed: DEFAULT_SEED,
+            deterministic: false,
+            spill_threshold: DEFAULT_SPILL_THRESHOLD,
+            symlinks: SymlinkPolicy::Follow,
+            max_frames: Some(DEFAULT_MAX_FRAMES),
+            throttle: Throttle::Unlimited,
+            overwrite: false,
+            temp_dir: None,
+            cache_dir: None,
+            transition_frames: 0,
+            keyframe_interval: None,
+            chunk_chapters: None,
+            entropy_style: false,
+            merkle_block_size: None,
+            streams: Vec::new(),
+            embed_manifest: true,
+            manifest_tag: false,
+            subtitle_manifest: false,
+            preserve_metadata: false,
+            adaptive_fps: false,
         }
     }
 }
@@ -71,6 +372,72 @@ impl EncodeConfig {
         Ok((width, height))
     }
 
+    /// Frame rate that shows each frame for `secs` seconds
+    ///
+    /// The frame rate is a whole number from 1 to 120, so durations between
+    /// 1/120 s and 1 s are supported and rounded to the nearest such rate.
+    pub fn fps_for_frame_duration(secs: f64) -> Result<u32> {
+        if !(secs.is_finite() && (1.0 / 120.0..=1.0).contains(&secs)) {
+            return Err(F2V2FError::InvalidInput(format!(
+                "Frame duration must be between 1/120 s and 1 s, got {} s",
+                secs
+            )));
+        }
+        Ok((1.0 / secs).round() as u32)
+    }
+
+    /// Frames in the video for `num_frames` data frames, transitions included
+    pub fn video_frames(&self, num_frames: u64) -> u64 {
+        num_frames + num_frames.saturating_sub(1) * self.transition_frames as u64
+    }
+
+    /// Playback length in seconds of a video with `num_frames` data frames
+    pub fn playback_duration(&self, num_frames: u64) -> f64 {
+        self.video_frames(num_frames) as f64 / self.fps_for(num_frames) as f64
+    }
+
+    /// Frame rate of a video with `num_frames` data frames
+    ///
+    /// `fps`, unless `adaptive_fps` is on and playback at `fps` would fall
+    /// outside `ADAPTIVE_MIN_SECS..=ADAPTIVE_MAX_SECS`: then the step
+    /// nearest `fps` that brings it inside, or the last step trying.
+    pub fn fps_for(&self, num_frames: u64) -> u32 {
+        if !self.adaptive_fps {
+            return self.fps;
+        }
+        let secs = |fps: u32| self.video_frames(num_frames) as f64 / fps as f64;
+        if secs(self.fps) > ADAPTIVE_MAX_SECS {
+            let fastest = ADAPTIVE_FPS_STEPS[ADAPTIVE_FPS_STEPS.len() - 1].max(self.fps);
+            let mut faster = ADAPTIVE_FPS_STEPS.iter().copied().filter(|&fps| fps > self.fps);
+            faster.find(|&fps| secs(fps) <= ADAPTIVE_MAX_SECS).unwrap_or(fastest)
+        } else if secs(self.fps) < ADAPTIVE_MIN_SECS {
+            let slowest = ADAPTIVE_FPS_STEPS[0].min(self.fps);
+            let mut slower = ADAPTIVE_FPS_STEPS.iter().rev().copied().filter(|&fps| fps < self.fps);
+            slower.find(|&fps| secs(fps) >= ADAPTIVE_MIN_SECS).unwrap_or(slowest)
+        } else {
+            self.fps
+        }
+    }
+
+    /// Data bytes one frame can carry at this resolution (and art style:
+    /// art frames need each byte repeated to decode, see `chunk_capacity`)
+    pub fn frame_capacity(&self) -> usize {
+        GeometricArtGenerator::new(self.width, self.height, self.seed)
+            .with_overlay(self.overlay)
+            .with_raw(self.art_style == RAW_ART_STYLE)
+            .with_showcase(self.art_style == SHOWCASE_ART_STYLE)
+            .chunk_capacity()
+    }
+
+    /// `frame_capacity` of every art style in `ART_STYLES`, all else equal,
+    /// to compare styles before encoding
+    pub fn style_capacities(&self) -> Vec<(&'static str, usize)> {
+        ART_STYLES
+            .iter()
+            .map(|&style| (style, EncodeConfig { art_style: style.to_string(), ..self.clone() }.frame_capacity()))
+            .collect()
+    }
+
     pub fn validate(&self) -> Result<()> {
         if self.fps == 0 || self.fps > 120 {
             return Err(F2V2FError::ConfigError(
@@ -78,11 +445,25 @@ impl EncodeConfig {
             ));
         }
 
-        if self.chunk_size == 0 || self.chunk_size > 10 * 1024 * 1024 {
-            // Max 10MB chunks
-            return Err(F2V2FError::ConfigError(
-                "Chunk size must be between 1 and 10485760 bytes".to_string(),
-            ));
+        if self.chunk_size == 0 || self.chunk_size > MAX_CHUNK_SIZE {
+            return Err(F2V2FError::ConfigError(format!(
+                "Chunk size must be between 1 and {} bytes",
+                MAX_CHUNK_SIZE
+            )));
+        }
+
+        let capacity = self.frame_capacity();
+        if self.chunk_size > capacity {
+            return Err(F2V2FError::ConfigError(format!(
+                "Chunk size {} bytes exceeds the {} bytes a {}x{} frame{} can hold; \
+                 use a chunk size of at most {} or a higher resolution",
+                self.chunk_size,
+                capacity,
+                self.width,
+                self.height,
+                if self.overlay { " with overlay" } else { "" },
+                capacity
+            )));
         }
 
         if self.num_threads == 0 {
@@ -91,6 +472,48 @@ impl EncodeConfig {
             ));
         }
 
+        if self.use_compression && !COMPRESSION_LEVELS.contains(&self.compression_level) {
+            return Err(F2V2FError::ConfigError(format!(
+                "Compression level must be between {} and {}, got {}",
+                COMPRESSION_LEVELS.start(),
+                COMPRESSION_LEVELS.end(),
+                self.compression_level
+            )));
+        }
+
+        if let Some(threads) = self.compression_threads {
+            if threads == 0 || threads > MAX_COMPRESSION_THREADS {
+                return Err(F2V2FError::ConfigError(format!(
+                    "Compression threads must be between 1 and {}",
+                    MAX_COMPRESSION_THREADS
+                )));
+            }
+        }
+
+        if self.dictionary.is_some() && !self.use_compression {
+            return Err(F2V2FError::ConfigError(
+                "A compression dictionary requires compression to be enabled".to_string(),
+            ));
+        }
+
+        if self.merkle_block_size.is_some_and(|size| size < MIN_BLOCK_SIZE) {
+            return Err(F2V2FError::ConfigError(format!(
+                "Merkle block size must be at least {} bytes",
+                MIN_BLOCK_SIZE
+            )));
+        }
+
+        if self.keyframe_interval == Some(0) {
+            return Err(F2V2FError::ConfigError("Keyframe interval must be at least 1 frame".to_string()));
+        }
+
+        if self.chunk_chapters == Some(0) {
+            return Err(F2V2FError::ConfigError("Chunk chapters must span at least 1 frame".to_string()));
+        }
+
+        crate::streams::validate(&self.streams)?;
+        validate_temp_dir(self.temp_dir.as_deref())?;
+
         Ok(())
     }
 }
@@ -107,10 +530,42 @@ pub struct DecodeConfig {
     pub num_threads: usize,
     /// Buffer size for processing
     pub buffer_size: usize,
-    /// Verify checksum after decoding
+    /// Fail with `IntegrityError` when the decoded file doesn't match the
+    /// checksum in its manifest; a file output is then never written
     pub verify_checksum: bool,
-    /// Exact encoded data size (to remove padding)
+    /// Exact encoded data size; when unset, the chunk size is read from the frame headers
     pub encoded_data_size: Option<u64>,
+    /// Checksum algorithm, used when the video has no manifest
+    pub hash_algo: HashAlgorithm,
+    /// Zstd dictionary the video was compressed with, if any
+    pub dictionary: Option<PathBuf>,
+    /// Art generator seed, used when the video has no manifest
+    pub seed: u64,
+    /// Replace an existing output file (refused by default)
+    pub overwrite: bool,
+    /// Scratch directory for the partial output; defaults to the output directory
+    pub temp_dir: Option<PathBuf>,
+    /// Extract this many frames at a time (slower, but memory stays bounded
+    /// for long videos); all frames at once if None
+    pub frame_window: Option<usize>,
+    /// Without a manifest, take the resolution from the video itself instead
+    /// of `width`/`height` (turn off to refuse videos of any other size)
+    pub probe_resolution: bool,
+    /// Pixel format, scaler and color range frames are extracted with
+    pub extract_format: ExtractFormat,
+    /// Write the file while frames are extracted, pausing extraction while
+    /// this many payload bytes wait for a slow output; the whole payload is
+    /// collected first if None. Needs `frame_window`.
+    pub high_watermark: Option<usize>,
+    /// Stream to decode: the file (`MAIN_STREAM`) or one of the extra
+    /// streams, which are written out as stored
+    pub stream: u8,
+    /// Give the decoded file the modification time and permissions recorded
+    /// in the manifest
+    pub restore_metadata: bool,
+    /// Keep the frame payloads range reads check here, for later reads of
+    /// the same video (see `frame_cache`)
+    pub frame_cache_dir: Option<PathBuf>,
 }
 
 impl Default for DecodeConfig {
@@ -123,11 +578,56 @@ impl Default for DecodeConfig {
             buffer_size: 1024 * 1024, // 1MB
             verify_checksum: true,
             encoded_data_size: None,
+            hash_algo: HashAlgorithm::Sha256,
+            dictionary: None,
+            seed: DEFAULT_SEED,
+            overwrite: false,
+            temp_dir: None,
+            frame_window: None,
+            probe_resolution: true,
+            extract_format: ExtractFormat::default(),
+            high_watermark: None,
+            stream: MAIN_STREAM,
+            restore_metadata: false,
+            frame_cache_dir: None,
         }
     }
 }
 
 impl DecodeConfig {
+    /// Decode parameters for a specific video
+    ///
+    /// Uses the video's manifest if there is one; otherwise probes the
+    /// picture size (ignoring padding bars) and leaves the chunk size to be
+    /// read from the frame headers.
+    pub fn from_video<P: AsRef<Path>>(video_path: P) -> Result<Self> {
+        let path = video_path.as_ref();
+        let mut config = Self::default();
+
+        if let Some(manifest) = Manifest::read_sidecar(path)? {
+            config.apply_manifest(&manifest);
+            return Ok(config);
+        }
+
+        (config.width, config.height) = Self::probe_resolution(path)?;
+        Ok(config)
+    }
+
+    /// Picture size of a video, ignoring padding bars
+    pub fn probe_resolution<P: AsRef<Path>>(video_path: P) -> Result<(u32, u32)> {
+        FfmpegBackend.probe_resolution(video_path.as_ref())
+    }
+
+    /// Take the parameters recorded in a video's manifest
+    pub fn apply_manifest(&mut self, manifest: &Manifest) {
+        self.width = manifest.width;
+        self.height = manifest.height;
+        self.chunk_size = manifest.chunk_size;
+        self.seed = manifest.seed;
+        self.hash_algo = manifest.hash_algo;
+        self.encoded_data_size = Some(manifest.encoded_size);
+    }
+
     pub fn validate(&self) -> Result<()> {
         if self.num_threads == 0 {
             return Err(F2V2FError::ConfigError(
@@ -135,13 +635,43 @@ impl DecodeConfig {
             ));
         }
 
+        if self.frame_window == Some(0) {
+            return Err(F2V2FError::ConfigError(
+                "Frame window must be at least 1 frame".to_string(),
+            ));
+        }
+
+        match self.high_watermark {
+            Some(0) => {
+                return Err(F2V2FError::ConfigError("High watermark must be at least 1 byte".to_string()));
+            }
+            // Without windows every frame is extracted before anything is written
+            Some(_) if self.frame_window.is_none() => {
+                return Err(F2V2FError::ConfigError("A high watermark needs a frame window".to_string()));
+            }
+            _ => {}
+        }
+
+        validate_temp_dir(self.temp_dir.as_deref())?;
+
         Ok(())
     }
 }
 
+fn validate_temp_dir(dir: Option<&Path>) -> Result<()> {
+    match dir {
+        Some(dir) if !dir.is_dir() => Err(F2V2FError::ConfigError(format!(
+            "Temp directory {} does not exist or is not a directory",
+            dir.display()
+        ))),
+        _ => Ok(()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::image_generator::SHOWCASE_MIN_REPEATS;
 
     #[test]
     fn test_parse_resolution() {
@@ -151,6 +681,37 @@ mod tests {
         assert!(EncodeConfig::parse_resolution("100x100").is_err()); // Too small
     }
 
+    #[test]
+    fn test_fps_for_frame_duration() {
+        assert_eq!(EncodeConfig::fps_for_frame_duration(0.5).unwrap(), 2);
+        assert_eq!(EncodeConfig::fps_for_frame_duration(1.0).unwrap(), 1);
+        assert_eq!(EncodeConfig::fps_for_frame_duration(0.3).unwrap(), 3);
+        assert!(EncodeConfig::fps_for_frame_duration(2.0).is_err());
+        assert!(EncodeConfig::fps_for_frame_duration(0.001).is_err());
+        assert!(EncodeConfig::fps_for_frame_duration(f64::NAN).is_err());
+
+        let config = EncodeConfig { fps: 2, ..EncodeConfig::default() };
+        assert_eq!(config.playback_duration(7), 3.5);
+        let faded = EncodeConfig { transition_frames: 2, ..config };
+        assert_eq!(faded.video_frames(7), 19);
+        assert_eq!(faded.video_frames(0), 0);
+    }
+
+    #[test]
+    fn test_adaptive_fps() {
+        let fixed = EncodeConfig::default();
+        assert_eq!(fixed.fps_for(3), 30);
+        let adaptive = EncodeConfig { adaptive_fps: true, ..fixed };
+        // 3 frames: as slow as it goes; 100 frames: 10 s at 10 fps
+        assert_eq!(adaptive.fps_for(3), 1);
+        assert_eq!(adaptive.fps_for(100), 10);
+        assert_eq!(adaptive.fps_for(1000), 30);
+        // 2 hours at 30 fps: an hour at 60; 10 hours: as fast as it goes
+        assert_eq!(adaptive.fps_for(216_000), 60);
+        assert_eq!(adaptive.fps_for(1_080_000), 120);
+        assert_eq!(adaptive.playback_duration(3), 3.0);
+    }
+
     #[test]
     fn test_validate_config() {
         let config = EncodeConfig::default();
@@ -159,5 +720,121 @@ mod tests {
         let mut bad_config = EncodeConfig::default();
         bad_config.fps = 0;
         assert!(bad_config.validate().is_err());
+
+        let level = EncodeConfig { compression_level: 23, ..EncodeConfig::default() };
+        assert!(matches!(level.validate(), Err(F2V2FError::ConfigError(_))));
+        // Ignored when not compressing
+        assert!(EncodeConfig { use_compression: false, ..level }.validate().is_ok());
+        let threads = EncodeConfig { compression_threads: Some(0), ..EncodeConfig::default() };
+        assert!(threads.validate().is_err());
+        let merkle = EncodeConfig { merkle_block_size: Some(512), ..EncodeConfig::default() };
+        assert!(matches!(merkle.validate(), Err(F2V2FError::ConfigError(_))));
+        let gop = EncodeConfig { keyframe_interval: Some(0), ..EncodeConfig::default() };
+        assert!(matches!(gop.validate(), Err(F2V2FError::ConfigError(_))));
+        assert!(EncodeConfig { keyframe_interval: Some(1), ..gop }.validate().is_ok());
+        let chapters = EncodeConfig { chunk_chapters: Some(0), ..EncodeConfig::default() };
+        assert!(matches!(chapters.validate(), Err(F2V2FError::ConfigError(_))));
+    }
+
+    #[test]
+    fn test_validate_chunk_size_capacity() {
+        let config = EncodeConfig {
+            width: 256,
+            height: 256,
+            chunk_size: 256 * 248 / 8,
+            ..EncodeConfig::default()
+        };
+        assert_eq!(config.frame_capacity(), 256 * 248 / 8);
+        assert!(config.validate().is_ok());
+
+        let too_big = EncodeConfig { chunk_size: 256 * 248 / 8 + 1, ..config.clone() };
+        assert!(matches!(too_big.validate(), Err(F2V2FError::ConfigError(_))));
+        // Raw frames hold one byte per pixel
+        let raw = EncodeConfig { chunk_size: 256 * 248, art_style: RAW_ART_STYLE.to_string(), ..config.clone() };
+        assert!(raw.validate().is_ok());
+
+        // The overlay corner takes capacity away from data
+        let with_overlay = EncodeConfig { overlay: true, ..config.clone() };
+        assert!(with_overlay.validate().is_err());
+
+        let capacities = config.style_capacities();
+        assert_eq!(capacities.len(), ART_STYLES.len());
+        assert!(capacities.contains(&("geometric", 256 * 248 / 8)));
+        assert!(capacities.contains(&(RAW_ART_STYLE, 256 * 248)));
+    }
+
+    #[test]
+    fn test_fast_data_preset() {
+        let mut config = EncodeConfig { seed: 7, ..EncodeConfig::default() };
+        "fast-data".parse::<Preset>().unwrap().apply(&mut config);
+
+        assert_eq!((config.width, config.height), (256, 256));
+        assert_eq!(config.art_style, RAW_ART_STYLE);
+        assert_eq!(config.chunk_size, config.frame_capacity());
+        assert_eq!(config.seed, 7);
+        assert!(config.validate().is_ok());
+        assert!("showcase-ish".parse::<Preset>().is_err());
+    }
+
+    #[test]
+    fn test_showcase_preset_keeps_redundancy() {
+        let mut config = EncodeConfig { width: 640, height: 360, ..EncodeConfig::default() };
+        let full = GeometricArtGenerator::new(640, 360, config.seed).data_capacity();
+        Preset::Showcase.apply(&mut config);
+
+        assert_eq!(config.art_style, SHOWCASE_ART_STYLE);
+        assert_eq!(config.frame_capacity(), full / SHOWCASE_MIN_REPEATS);
+        assert!(config.chunk_size <= config.frame_capacity());
+        assert_eq!(config.max_frames, None);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_settings_round_trip() {
+        let mut original =
+            EncodeConfig { seed: 9, transition_frames: 2, keyframe_interval: Some(1), ..EncodeConfig::default() };
+        Preset::Showcase.apply(&mut original);
+        let settings = EncodeSettings::from(&original);
+
+        let mut replay = EncodeConfig { num_threads: 1, ..EncodeConfig::default() };
+        settings.apply(&mut replay);
+        assert_eq!(EncodeSettings::from(&replay), settings);
+        assert_eq!(replay.num_threads, 1);
+    }
+
+    #[test]
+    fn test_validate_temp_dir() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let config = DecodeConfig { temp_dir: Some(dir.path().to_path_buf()), ..DecodeConfig::default() };
+        assert!(config.validate().is_ok());
+
+        let unwindowed = DecodeConfig { high_watermark: Some(1 << 20), ..DecodeConfig::default() };
+        assert!(matches!(unwindowed.validate(), Err(F2V2FError::ConfigError(_))));
+        assert!(DecodeConfig { frame_window: Some(30), ..unwindowed }.validate().is_ok());
+
+        let missing = EncodeConfig { temp_dir: Some(dir.path().join("missing")), ..EncodeConfig::default() };
+        assert!(matches!(missing.validate(), Err(F2V2FError::ConfigError(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_config_from_manifest() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let video = dir.path().join("clip.mp4");
+        std::fs::write(
+            Manifest::sidecar_path(&video),
+            r#"{"format_version": 1, "width": 640, "height": 480, "fps": 30,
+                "chunk_size": 2048, "num_frames": 3, "original_size": 6000,
+                "encoded_size": 5000, "compressed": true, "hash_algo": "sha256",
+                "checksum": "abc", "seed": 9}"#,
+        )?;
+
+        // No ffprobe needed when the manifest has everything
+        let config = DecodeConfig::from_video(&video)?;
+        assert_eq!((config.width, config.height), (640, 480));
+        assert_eq!(config.chunk_size, 2048);
+        assert_eq!(config.seed, 9);
+        assert_eq!(config.encoded_data_size, Some(5000));
+        Ok(())
     }
 }
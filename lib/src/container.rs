@@ -0,0 +1,162 @@
+//! Payload container header
+//!
+//! Since format version 2 the main stream's payload, before it is chunked
+//! into frames, starts with a fixed header:
+//!
+//! - bytes 0..4: magic `F2VP`
+//! - byte 4: container version (`CONTAINER_VERSION`)
+//! - byte 5: flags (`FLAG_COMPRESSED`, `FLAG_DICTIONARY`)
+//! - bytes 6..8: reserved, zero
+//!
+//! followed by the file, zstd compressed or not. A decoder meeting a
+//! version or flag it doesn't know fails instead of misreading the rest.
+//! Version 1 payloads have no header; their compression is sniffed from the
+//! zstd magic.
+
+use crate::error::{F2V2FError, Result};
+use std::io::{self, Cursor, Read};
+
+pub const CONTAINER_MAGIC: [u8; 4] = *b"F2VP";
+
+/// Container version written by this build
+pub const CONTAINER_VERSION: u8 = 1;
+
+/// First format version (see `MANIFEST_VERSION`) whose payloads carry the header
+pub const FIRST_CONTAINER_FORMAT: u32 = 2;
+
+pub const HEADER_LEN: usize = 8;
+
+/// The file is zstd compressed
+pub const FLAG_COMPRESSED: u8 = 1 << 0;
+/// Compressed with a shared zstd dictionary (see `dictionary`)
+pub const FLAG_DICTIONARY: u8 = 1 << 1;
+
+const KNOWN_FLAGS: u8 = FLAG_COMPRESSED | FLAG_DICTIONARY;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContainerHeader {
+    pub version: u8,
+    pub flags: u8,
+}
+
+impl ContainerHeader {
+    pub fn new(compressed: bool, dictionary: bool) -> Self {
+        let mut flags = 0;
+        if compressed {
+            flags |= FLAG_COMPRESSED;
+        }
+        if dictionary {
+            flags |= FLAG_DICTIONARY;
+        }
+        Self { version: CONTAINER_VERSION, flags }
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.flags & FLAG_COMPRESSED != 0
+    }
+
+    pub fn needs_dictionary(&self) -> bool {
+        self.flags & FLAG_DICTIONARY != 0
+    }
+
+    pub fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0u8; HEADER_LEN];
+        bytes[0..4].copy_from_slice(&CONTAINER_MAGIC);
+        bytes[4] = self.version;
+        bytes[5] = self.flags;
+        bytes
+    }
+
+    /// Parse the header at the start of a payload
+    ///
+    /// `None` when the payload doesn't start with the magic (a version 1
+    /// payload); an error for a header this build can't read.
+    pub fn parse(bytes: &[u8]) -> Result<Option<Self>> {
+        if !bytes.starts_with(&CONTAINER_MAGIC) {
+            return Ok(None);
+        }
+        if bytes.len() < HEADER_LEN {
+            return Err(F2V2FError::DecodingError(format!(
+                "Payload container header cut short ({} of {} bytes)",
+                bytes.len(),
+                HEADER_LEN
+            )));
+        }
+        let header = Self { version: bytes[4], flags: bytes[5] };
+        if header.version == 0 || header.version > CONTAINER_VERSION {
+            return Err(F2V2FError::DecodingError(format!(
+                "Payload uses container version {}, but this f2v2f {} reads up to version {}; upgrade f2v2f to decode it",
+                header.version,
+                env!("CARGO_PKG_VERSION"),
+                CONTAINER_VERSION
+            )));
+        }
+        if header.flags & !KNOWN_FLAGS != 0 {
+            return Err(F2V2FError::DecodingError(format!(
+                "Payload container has unknown flags {:#04x}; upgrade f2v2f to decode it",
+                header.flags & !KNOWN_FLAGS
+            )));
+        }
+        Ok(Some(header))
+    }
+}
+
+/// Rest of a payload after its header (headerless payloads: all of it)
+pub type Body<R> = io::Chain<Cursor<Vec<u8>>, R>;
+
+/// Read the header off the front of `reader`
+///
+/// Returns the header and a reader over the rest of the payload. Without a
+/// header (a version 1 payload) the bytes looked at are put back in front.
+pub fn read_header<R: Read>(mut reader: R) -> Result<(Option<ContainerHeader>, Body<R>)> {
+    let mut head = Vec::with_capacity(HEADER_LEN);
+    reader.by_ref().take(HEADER_LEN as u64).read_to_end(&mut head)?;
+    let header = ContainerHeader::parse(&head)?;
+    if header.is_some() {
+        head.clear();
+    }
+    Ok((header, Cursor::new(head).chain(reader)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_round_trip() -> Result<()> {
+        let header = ContainerHeader::new(true, false);
+        let mut payload = header.to_bytes().to_vec();
+        payload.extend_from_slice(b"body");
+
+        let (parsed, mut rest) = read_header(payload.as_slice())?;
+        assert_eq!(parsed, Some(header));
+        assert!(parsed.is_some_and(|h| h.is_compressed() && !h.needs_dictionary()));
+        let mut body = Vec::new();
+        rest.read_to_end(&mut body)?;
+        assert_eq!(body, b"body");
+        Ok(())
+    }
+
+    #[test]
+    fn test_headerless_payload_is_left_intact() -> Result<()> {
+        let (parsed, mut rest) = read_header(&b"plain old payload"[..])?;
+        assert_eq!(parsed, None);
+        let mut body = Vec::new();
+        rest.read_to_end(&mut body)?;
+        assert_eq!(body, b"plain old payload");
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_version_and_flags_rejected() {
+        let mut newer = ContainerHeader::new(false, false).to_bytes();
+        newer[4] = CONTAINER_VERSION + 1;
+        let err = ContainerHeader::parse(&newer).unwrap_err().to_string();
+        assert!(err.contains("container version"), "{}", err);
+
+        let mut flagged = ContainerHeader::new(false, false).to_bytes();
+        flagged[5] = 0x80;
+        assert!(ContainerHeader::parse(&flagged).is_err());
+        assert!(ContainerHeader::parse(&CONTAINER_MAGIC).is_err());
+    }
+}
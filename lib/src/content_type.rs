@@ -0,0 +1,133 @@
+//! Content-type sniffing from magic bytes
+//!
+//! The detected MIME type is stored in the manifest so `inspect` can show
+//! what a video holds and decode can pick an output extension.
+
+use crate::error::Result;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Bytes read from the start of a file for sniffing
+pub const SNIFF_LEN: usize = 512;
+
+/// Detected content type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentType {
+    pub mime: &'static str,
+    /// Usual file extension, without the dot
+    pub extension: &'static str,
+}
+
+struct Signature {
+    offset: usize,
+    magic: &'static [u8],
+    content_type: ContentType,
+}
+
+const fn sig(offset: usize, magic: &'static [u8], mime: &'static str, extension: &'static str) -> Signature {
+    Signature { offset, magic, content_type: ContentType { mime, extension } }
+}
+
+const SIGNATURES: &[Signature] = &[
+    sig(0, b"\x89PNG\r\n\x1a\n", "image/png", "png"),
+    sig(0, b"\xff\xd8\xff", "image/jpeg", "jpg"),
+    sig(0, b"GIF87a", "image/gif", "gif"),
+    sig(0, b"GIF89a", "image/gif", "gif"),
+    sig(8, b"WEBP", "image/webp", "webp"),
+    sig(0, b"%PDF-", "application/pdf", "pdf"),
+    sig(0, b"PK\x03\x04", "application/zip", "zip"),
+    sig(0, b"\x1f\x8b", "application/gzip", "gz"),
+    sig(0, b"\x28\xb5\x2f\xfd", "application/zstd", "zst"),
+    sig(0, b"BZh", "application/x-bzip2", "bz2"),
+    sig(0, b"\xfd7zXZ\x00", "application/x-xz", "xz"),
+    sig(0, b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed", "7z"),
+    sig(257, b"ustar", "application/x-tar", "tar"),
+    sig(0, b"SQLite format 3\x00", "application/vnd.sqlite3", "sqlite"),
+    sig(0, b"\x7fELF", "application/x-elf", "elf"),
+    sig(0, b"\x00asm", "application/wasm", "wasm"),
+    sig(4, b"ftyp", "video/mp4", "mp4"),
+    sig(0, b"\x1a\x45\xdf\xa3", "video/x-matroska", "mkv"),
+    sig(8, b"WAVE", "audio/wav", "wav"),
+    sig(0, b"ID3", "audio/mpeg", "mp3"),
+    sig(0, b"fLaC", "audio/flac", "flac"),
+    sig(0, b"OggS", "audio/ogg", "ogg"),
+];
+
+const TEXT: ContentType = ContentType { mime: "text/plain", extension: "txt" };
+
+/// Detect the content type of data starting with `header`
+///
+/// Falls back to `text/plain` for NUL-free UTF-8; returns `None` for
+/// unrecognised binary data.
+pub fn sniff(header: &[u8]) -> Option<ContentType> {
+    let known = SIGNATURES.iter().find(|s| {
+        header.len() >= s.offset + s.magic.len() && &header[s.offset..s.offset + s.magic.len()] == s.magic
+    });
+    if let Some(signature) = known {
+        return Some(signature.content_type);
+    }
+
+    if !header.is_empty() && !header.contains(&0) && is_utf8_prefix(header) {
+        return Some(TEXT);
+    }
+    None
+}
+
+/// Valid UTF-8, allowing a multi-byte character cut off at the end
+fn is_utf8_prefix(data: &[u8]) -> bool {
+    match std::str::from_utf8(data) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+/// Sniff the content type of a file
+pub fn sniff_file<P: AsRef<Path>>(path: P) -> Result<Option<ContentType>> {
+    let mut header = Vec::with_capacity(SNIFF_LEN);
+    File::open(path)?.take(SNIFF_LEN as u64).read_to_end(&mut header)?;
+    Ok(sniff(&header))
+}
+
+/// Usual extension for a MIME type recorded in a manifest
+pub fn extension_for_mime(mime: &str) -> Option<&'static str> {
+    SIGNATURES
+        .iter()
+        .map(|s| s.content_type)
+        .chain(std::iter::once(TEXT))
+        .find(|ct| ct.mime == mime)
+        .map(|ct| ct.extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_magic_bytes() {
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap().mime, "image/png");
+        assert_eq!(sniff(b"%PDF-1.7\n").unwrap().extension, "pdf");
+        assert_eq!(sniff(b"RIFF\x24\0\0\0WAVEfmt ").unwrap().mime, "audio/wav");
+        assert_eq!(sniff(b"\0\0\0\x18ftypmp42").unwrap().mime, "video/mp4");
+
+        let mut tar = vec![0u8; 512];
+        tar[257..262].copy_from_slice(b"ustar");
+        assert_eq!(sniff(&tar).unwrap().extension, "tar");
+    }
+
+    #[test]
+    fn test_sniff_text_and_binary() {
+        assert_eq!(sniff(b"hello, world\n"), Some(TEXT));
+        // Multi-byte character cut off by the sniff window
+        assert_eq!(sniff("héllo".as_bytes()[..2].as_ref()), Some(TEXT));
+        assert_eq!(sniff(&[0x00, 0x13, 0x37, 0xff]), None);
+        assert_eq!(sniff(b""), None);
+    }
+
+    #[test]
+    fn test_extension_for_mime() {
+        assert_eq!(extension_for_mime("application/gzip"), Some("gz"));
+        assert_eq!(extension_for_mime("text/plain"), Some("txt"));
+        assert_eq!(extension_for_mime("application/x-unknown"), None);
+    }
+}
@@ -1,14 +1,41 @@
 use crate::error::{F2V2FError, Result};
+use crate::file_metadata::FileMetadata;
+use crate::frame_cache::FrameCache;
+use crate::atomic::{ensure_absent, AtomicOutput};
+use crate::backend::{is_matroska, FfmpegBackend, VideoBackend};
+use crate::backpressure::{self, ChannelWriter};
+use crate::checksum::{HashAlgorithm, Hasher, HashingWriter};
+use crate::chunk_manifest::{ChunkManifest, ChunkVerification};
 use crate::config::DecodeConfig;
-use sha2::{Sha256, Digest};
-use std::io::{Write, Read, Cursor};
-use std::fs::File;
+use crate::container::{self, ContainerHeader, FIRST_CONTAINER_FORMAT, HEADER_LEN};
+use crate::frame_header::{FrameHeader, FLAG_OVERLAY, FLAG_TRANSITION};
+use crate::image_generator::{GeometricArtGenerator, ART_MIN_REPEATS, ART_STYLES, SHOWCASE_MIN_REPEATS};
+use crate::jobs::{self, Job, JobKind};
+use crate::manifest::{Manifest, MANIFEST_ATTACHMENT, MANIFEST_TAG, MANIFEST_VERSION};
+use crate::operation::OperationHandle;
+use crate::partial::{self, ByteRange, ChunkCollector, PartialDecodeInfo};
+use crate::payload::{Payload, SpillWriter, DEFAULT_SPILL_THRESHOLD};
+use crate::sparse::{Hole, SparseWriter};
+use crate::storage::Sink;
+use crate::stream::ZeroFill;
+use crate::streams::{StreamInfo, END_STREAM, MAIN_STREAM, MANIFEST_STREAM};
+use crate::subtitles::SubtitleMetadata;
+use crate::video_composer::VideoComposer;
+use crate::warning::Warning;
+use image::RgbaImage;
+use std::collections::{BTreeMap, HashSet};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::fs::{File, OpenOptions};
 use std::path::Path;
-use tracing::info;
+use std::sync::Arc;
+use tracing::{debug, warn};
 
 /// Decodes a video back to the original file
+#[derive(Clone)]
 pub struct Decoder {
     config: DecodeConfig,
+    operation: OperationHandle,
+    backend: Arc<dyn VideoBackend>,
 }
 
 /// Metadata extracted from encoded video
@@ -16,16 +43,76 @@ pub struct Decoder {
 pub struct DecodedFileInfo {
     pub extracted_size: u64,
     pub checksum: String,
+    pub hash_algo: HashAlgorithm,
     pub was_compressed: bool,
+    /// MIME type recorded in the manifest, if any
+    pub content_type: Option<String>,
+    /// Recoverable anomalies during the decode
+    pub warnings: Vec<Warning>,
+    /// Original name, modification time and permissions, if recorded
+    pub file_metadata: Option<FileMetadata>,
+}
+
+/// Outcome of `Decoder::read_range`
+#[derive(Debug, Clone)]
+pub struct RangeRead {
+    /// Bytes of the original file; fewer than asked for past its end
+    pub data: Vec<u8>,
+    /// Data frames extracted from the video
+    pub frames_extracted: u64,
+    /// Data frames found in the frame cache instead
+    pub frames_cached: u64,
 }
 
 // Zstd magic number: 0x28, 0xB5, 0x2F, 0xFD
 const ZSTD_MAGIC: &[u8] = &[0x28, 0xB5, 0x2F, 0xFD];
 
+/// What the front of a payload holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Framing {
+    /// An extra stream: stored as-is, never decompressed
+    Stored,
+    /// Format version 1: no container header, compression is sniffed
+    Headerless,
+    /// A container header (format version 2 on)
+    Container,
+    /// No manifest says which: a header if there is one, else version 1
+    Detect,
+}
+
+impl Framing {
+    fn of(manifest: Option<&Manifest>, main: bool) -> Self {
+        match manifest {
+            _ if !main => Framing::Stored,
+            Some(m) if m.format_version < FIRST_CONTAINER_FORMAT => Framing::Headerless,
+            Some(_) => Framing::Container,
+            None => Framing::Detect,
+        }
+    }
+}
+
 impl Decoder {
     pub fn new(config: DecodeConfig) -> Result<Self> {
         config.validate()?;
-        Ok(Self { config })
+        Ok(Self { config, operation: OperationHandle::new(), backend: Arc::new(FfmpegBackend) })
+    }
+
+    /// Let `handle` pause and resume writing the decoded output
+    pub fn with_operation(mut self, handle: OperationHandle) -> Self {
+        self.operation = handle;
+        self
+    }
+
+    /// Replace an existing output instead of refusing it
+    pub fn with_overwrite(mut self, overwrite: bool) -> Self {
+        self.config.overwrite = overwrite;
+        self
+    }
+
+    /// Read videos through `backend` instead of ffmpeg
+    pub fn with_backend(mut self, backend: Arc<dyn VideoBackend>) -> Self {
+        self.backend = backend;
+        self
     }
 
     /// Detect if data is zstd compressed by checking magic bytes
@@ -33,6 +120,35 @@ impl Decoder {
         data.len() >= 4 && &data[0..4] == ZSTD_MAGIC
     }
 
+    /// Take the container header (see `container`) off the front of a
+    /// payload; returns whether the rest is zstd compressed, and the rest
+    fn open_payload<R: Read>(
+        mut source: R,
+        framing: Framing,
+        dictionary: Option<&[u8]>,
+    ) -> Result<(bool, container::Body<R>)> {
+        let (header, rest) = match framing {
+            Framing::Stored => return Ok((false, io::Cursor::new(Vec::new()).chain(source))),
+            Framing::Headerless => {
+                let mut head = Vec::with_capacity(ZSTD_MAGIC.len());
+                source.by_ref().take(ZSTD_MAGIC.len() as u64).read_to_end(&mut head)?;
+                (None, io::Cursor::new(head).chain(source))
+            }
+            Framing::Container | Framing::Detect => container::read_header(source)?,
+        };
+        match header {
+            Some(header) if header.needs_dictionary() && dictionary.is_none() => Err(F2V2FError::ConfigError(
+                "Video was compressed with a zstd dictionary but no dictionary was provided".to_string(),
+            )),
+            Some(header) => Ok((header.is_compressed(), rest)),
+            None if framing == Framing::Container => Err(F2V2FError::DecodingError(format!(
+                "Payload has no container header, which format version {} and later always write",
+                FIRST_CONTAINER_FORMAT
+            ))),
+            None => Ok((Self::is_zstd_compressed(rest.get_ref().0.get_ref()), rest)),
+        }
+    }
+
     /// Decode a video back to file with automatic decompression
     /// 
     /// Process:
@@ -42,94 +158,1019 @@ impl Decoder {
     /// 4. Write original file
     /// 5. Verify checksum
     pub async fn decode<P: AsRef<Path>>(&self, input: P, output: P) -> Result<DecodedFileInfo> {
-        let input_path = input.as_ref();
-        let output_path = output.as_ref();
+        let (input_path, output_path) = (input.as_ref(), output.as_ref());
+        let job = jobs::register(
+            JobKind::Decode,
+            input_path.display().to_string(),
+            output_path.display().to_string(),
+            &self.operation,
+        );
+        let result = self.decode_stages(input_path, output_path, &job).await;
+        job.finish(&result);
+        result
+    }
 
-        info!("🎬 Starting video extraction from: {}", input_path.display());
+    async fn decode_stages(&self, input_path: &Path, output_path: &Path, job: &Job) -> Result<DecodedFileInfo> {
 
-        // Extract all frame data from video
-        let extracted_data = self.extract_frame_data(input_path).await?;
-        info!("✅ Extracted {} bytes from video", extracted_data.len());
+        debug!("🎬 Starting video extraction from: {}", input_path.display());
 
-        // CRITICAL: Truncate to exact encoded size to remove padding from last chunk
-        let final_extracted = if let Some(encoded_size) = self.config.encoded_data_size {
-            if extracted_data.len() as u64 > encoded_size {
-                info!("✂️  Truncating from {} to {} bytes (removing padding)", 
-                    extracted_data.len(), encoded_size);
-                extracted_data[..encoded_size as usize].to_vec()
-            } else {
-                extracted_data
+        // Fail before the (slow) extraction rather than after it; a pipe
+        // or descriptor to write into is expected to exist
+        let to_stream = crate::stream::is_stream(output_path);
+        if !self.config.overwrite && !to_stream {
+            ensure_absent(output_path)?;
+        }
+
+        let mut warnings = Vec::new();
+        let (manifest, params, stream) = self.resolve_video(input_path, &mut warnings).await?;
+        let main = params.stream == MAIN_STREAM;
+        let framing = Framing::of(manifest.as_ref(), main);
+        let hash_algo = params.hash_algo;
+
+        if let Some(target) = manifest.as_ref().and_then(|m| m.link_target.as_deref()).filter(|_| main) {
+            if to_stream {
+                return Err(F2V2FError::InvalidInput(format!(
+                    "{} holds a symlink, which can't be written to a stream",
+                    input_path.display()
+                )));
+            }
+            if self.config.overwrite && std::fs::symlink_metadata(output_path).is_ok() {
+                std::fs::remove_file(output_path)?;
             }
+            return Self::restore_link(target, output_path, hash_algo);
+        }
+
+        let (dictionary, holes) = if main {
+            let dictionary = self.load_dictionary(manifest.as_ref().and_then(|m| m.dictionary_id))?;
+            (dictionary, manifest.as_ref().map(|m| m.holes.clone()).unwrap_or_default())
         } else {
-            extracted_data
+            (None, Vec::new())
+        };
+
+        // The file must hash to the checksum its manifest records
+        let expected_checksum = ExpectedChecksum {
+            algo: hash_algo,
+            expected: manifest.as_ref().filter(|_| main && self.config.verify_checksum).map(|m| m.checksum.clone()),
         };
 
-        // Detect compression
-        let was_compressed = Self::is_zstd_compressed(&final_extracted);
-        info!("🔍 Data format: {}", 
+        job.stage("extracting", None);
+        let (written, checksum, was_compressed, frames) = match params.high_watermark {
+            Some(high_watermark) => {
+                let output = StreamOutput {
+                    path: output_path,
+                    dictionary,
+                    holes,
+                    high_watermark,
+                    framing,
+                    checksum: expected_checksum,
+                };
+                self.decode_streaming(&params, input_path, output, &mut warnings).await?
+            }
+            None => {
+                // Extract all frame data from video
+                let (payload, frames, _) = self.extract_frame_data(&params, input_path, &mut warnings).await?;
+                debug!("✅ Extracted {} bytes from video", payload.len());
+
+                // Each frame header records its own payload length, so padding is
+                // already gone; a recorded encoded size is only a cross-check
+                Self::check_payload_size(payload.len(), params.encoded_data_size)?;
+
+                job.stage("writing", Some(payload.len()));
+                let (was_compressed, source) =
+                    Self::open_payload(payload.reader()?, framing, dictionary.as_deref())?;
+
+                // Decompress (if needed) straight into the output file, hashing as we go
+                let (written, checksum) = self.write_payload(
+                    source,
+                    was_compressed,
+                    dictionary.as_deref(),
+                    &holes,
+                    output_path,
+                    &expected_checksum,
+                )?;
+                (written, checksum, was_compressed, frames)
+            }
+        };
+        debug!("🔍 Data format: {}", 
             if was_compressed { "Zstd compressed" } else { "Raw" });
+        let expected_frames = match &stream {
+            Some(stream) => Some(stream.num_frames),
+            None => manifest.as_ref().filter(|_| main).map(|m| m.num_frames),
+        };
+        if let Some(expected) = expected_frames.filter(|&n| n != frames) {
+            let warning = Warning::FrameCountMismatch { expected, found: frames };
+            warn!("{}", warning);
+            warnings.push(warning);
+        }
+
+        debug!("💾 Wrote {} bytes to {}", written, output_path.display());
+        debug!("📋 Checksum ({}): {}", hash_algo, checksum);
+
+        let manifest = manifest.filter(|_| main);
+        let file_metadata = manifest.as_ref().and_then(|m| m.file.clone());
+        if let Some(metadata) = file_metadata.as_ref().filter(|_| self.config.restore_metadata && !to_stream) {
+            metadata.apply(output_path)?;
+            debug!("🕰️  Restored modification time and permissions of {}", output_path.display());
+        }
+
+        Ok(DecodedFileInfo {
+            extracted_size: written,
+            checksum,
+            hash_algo,
+            was_compressed,
+            content_type: manifest.and_then(|m| m.content_type),
+            warnings,
+            file_metadata,
+        })
+    }
+
+    /// Decode a video into `sink`
+    ///
+    /// Sinks with a local path are decoded to like that path. Others (HTTP,
+    /// S3, memory) receive the file once it is decoded and checked, so a
+    /// failed decode uploads nothing; it is staged in `temp_dir` meanwhile.
+    pub async fn decode_to_sink<P: AsRef<Path>>(&self, input: P, sink: &dyn Sink) -> Result<DecodedFileInfo> {
+        if let Some(path) = sink.local_path() {
+            return self.decode(input.as_ref(), path).await;
+        }
+        let dir = match &self.config.temp_dir {
+            Some(dir) => tempfile::tempdir_in(dir)?,
+            None => tempfile::tempdir()?,
+        };
+        let staged = dir.path().join("decoded");
+        let info = self.decode(input.as_ref(), &staged).await?;
+        if std::fs::symlink_metadata(&staged)?.file_type().is_symlink() {
+            return Err(F2V2FError::InvalidInput(format!(
+                "{} holds a symlink, which can't be written to {}",
+                input.as_ref().display(),
+                sink.name()
+            )));
+        }
+
+        debug!("📤 Writing {} bytes to {}", info.extracted_size, sink.name());
+        let mut writer = sink.create()?;
+        io::copy(&mut self.operation.reader(File::open(&staged)?), &mut writer)?;
+        writer.finish()?;
+        Ok(info)
+    }
 
-        // Decompress if needed
-        let final_data = if was_compressed {
-            info!("🗜️  Decompressing with Zstd...");
-            let mut decoder = zstd::stream::read::Decoder::new(Cursor::new(&final_extracted))?;
-            let mut decompressed = Vec::new();
-            decoder.read_to_end(&mut decompressed)?;
-            info!("✅ Decompressed: {} bytes → {} bytes", 
-                final_extracted.len(), decompressed.len());
-            decompressed
+    /// Decode whatever survives of an incomplete or damaged video
+    ///
+    /// Frames that are missing or fail their CRC are left out instead of
+    /// failing the decode, and the output holds zeros in their place. The
+    /// result lists the byte ranges of the original file that were
+    /// recovered (see `partial`). Symlinks aren't restored.
+    pub async fn decode_partial<P: AsRef<Path>>(&self, input: P, output: P) -> Result<PartialDecodeInfo> {
+        let input_path = input.as_ref();
+        let output_path = output.as_ref();
+        debug!("🩹 Recovering what survives of {}", input_path.display());
+
+        if !self.config.overwrite && !crate::stream::is_stream(output_path) {
+            ensure_absent(output_path)?;
+        }
+        let mut warnings = Vec::new();
+        let (manifest, params, stream) = self.resolve_video(input_path, &mut warnings).await?;
+        let main = params.stream == MAIN_STREAM;
+        let manifest = manifest.filter(|_| main);
+
+        let composer = self.frame_reader(&params).with_ignore_errors(true);
+        let filters = composer.content_filters(input_path)?;
+        let generator = GeometricArtGenerator::new(params.width, params.height, params.seed);
+        let mut collector: Option<ChunkCollector<File>> = None;
+        let mut frames_seen = 0u64;
+        loop {
+            let frames = match params.frame_window {
+                Some(window) => composer.extract_frame_window(input_path, &filters, frames_seen, window as u64).await?,
+                None => composer.read_all_frames(input_path, &filters)?,
+            };
+            frames_seen += frames.len() as u64;
+            let collector = match &mut collector {
+                Some(collector) => collector,
+                None => {
+                    let chunk_size = match params.encoded_data_size {
+                        Some(_) => params.chunk_size,
+                        None => {
+                            let own: Vec<_> = frames
+                                .iter()
+                                .map(|frame| FrameHeader::try_read_from(frame).ok().flatten())
+                                .map(|header| header.filter(|h| h.stream == params.stream))
+                                .collect();
+                            let search = search_chunk_size(&generator, &frames, &own);
+                            warnings.push(Warning::InferredChunkSize {
+                                chunk_size: search.chunk_size,
+                                validated: search.validated,
+                            });
+                            search.chunk_size
+                        }
+                    };
+                    // Payload goes to its offset in a scratch file, gaps and all
+                    let sink = match &params.temp_dir {
+                        Some(dir) => tempfile::tempfile_in(dir)?,
+                        None => tempfile::tempfile()?,
+                    };
+                    collector.insert(ChunkCollector::new(generator, chunk_size, params.stream, sink))
+                }
+            };
+            collector.process(&frames)?;
+            if params.frame_window.is_none_or(|window| frames.len() < window) {
+                break;
+            }
+        }
+        warnings.extend(composer.take_warnings());
+
+        let Some(collector) = collector else {
+            return Err(not_f2v2f(frames_seen as usize));
+        };
+        let (frames_recovered, frames_damaged) = (collector.frames_recovered(), collector.frames_damaged());
+        let ranges = collector.ranges();
+        let prefix = collector.prefix_len();
+        debug!("Recovered {} frames, {} damaged", frames_recovered, frames_damaged);
+        if frames_recovered == 0 {
+            return Err(F2V2FError::DecodingError(format!(
+                "No frame of {} survived intact",
+                input_path.display()
+            )));
+        }
+
+        let mut payload = collector.into_sink();
+        let payload_len = params.encoded_data_size.unwrap_or_else(|| ranges.last().map_or(0, |r| r.end));
+        payload.set_len(payload_len)?;
+        payload.rewind()?;
+
+        // The container header can only be read when the first frame survived
+        let framing = Framing::of(manifest.as_ref(), main);
+        let mut head = Vec::with_capacity(HEADER_LEN);
+        (&payload).take(prefix.min(HEADER_LEN as u64)).read_to_end(&mut head)?;
+        let header = match framing {
+            Framing::Container | Framing::Detect if head.len() == HEADER_LEN => ContainerHeader::parse(&head)?,
+            _ => None,
+        };
+        let was_compressed = match (&manifest, header) {
+            _ if !main => false,
+            (Some(m), _) => m.compressed,
+            (None, Some(header)) => header.is_compressed(),
+            (None, None) => Self::is_zstd_compressed(&head),
+        };
+        let skip = if framing == Framing::Container || header.is_some() { HEADER_LEN as u64 } else { 0 };
+        payload.seek(SeekFrom::Start(skip))?;
+        let prefix = prefix.saturating_sub(skip);
+        let ranges: Vec<ByteRange> = ranges
+            .iter()
+            .filter(|range| range.end > skip)
+            .map(|range| ByteRange { start: range.start.saturating_sub(skip), end: range.end - skip })
+            .collect();
+        let holes = manifest.as_ref().map(|m| m.holes.clone()).unwrap_or_default();
+
+        let (output_size, recovered) = if was_compressed {
+            // Decompression can't resume after a gap
+            let dictionary = self.load_dictionary(manifest.as_ref().and_then(|m| m.dictionary_id))?;
+            let decoder = zstd_decoder(BufReader::new(payload.take(prefix)), dictionary.as_deref())?;
+            let (written, _) =
+                self.write_payload(UntilError(decoder), false, None, &holes, output_path, &params.hash_algo.into())?;
+            (written, partial::merge([ByteRange { start: 0, end: written }]))
         } else {
-            final_extracted.clone()
+            let (written, _) =
+                self.write_payload(payload, false, None, &holes, output_path, &params.hash_algo.into())?;
+            (written, partial::file_ranges(&ranges, &holes))
         };
 
-        // Calculate checksum and write file
-        let mut hasher = Sha256::new();
-        hasher.update(&final_data);
-        let checksum = format!("{:x}", hasher.finalize());
+        let info = PartialDecodeInfo {
+            recovered,
+            output_size,
+            original_size: match &stream {
+                Some(stream) => Some(stream.size),
+                None => manifest.as_ref().map(|m| m.original_size),
+            },
+            frames_recovered,
+            frames_expected: match &stream {
+                Some(stream) => Some(stream.num_frames),
+                None => manifest.as_ref().map(|m| m.num_frames),
+            },
+            frames_damaged,
+            was_compressed,
+            warnings,
+        };
+        debug!("💾 Recovered {} of {} bytes", info.recovered_bytes(),
+            info.original_size.map_or("?".to_string(), |size| size.to_string()));
+        Ok(info)
+    }
 
-        let mut output_file = File::create(output_path)?;
-        output_file.write_all(&final_data)?;
-        output_file.sync_all()?;
+    /// Read `len` bytes of the original file from `offset` without decoding
+    /// the whole video
+    ///
+    /// Only the frames holding the range are extracted, located with the
+    /// manifest's frame index (see `index`), and each is checked against
+    /// its CRC. Needs
+    /// the video's manifest and an uncompressed, non-sparse payload (a zstd
+    /// stream can only be read from its start). With `frame_cache_dir` set,
+    /// checked frame payloads are kept for later reads (see `frame_cache`).
+    pub async fn read_range<P: AsRef<Path>>(&self, input: P, offset: u64, len: u64) -> Result<RangeRead> {
+        let input_path = input.as_ref();
+        let mut warnings = Vec::new();
+        let (manifest, params, _) = self.resolve_video(input_path, &mut warnings).await?;
+        let unsupported = |why: &str| {
+            F2V2FError::InvalidInput(format!("Can't read a range of {}: {}", input_path.display(), why))
+        };
+        let manifest = manifest.ok_or_else(|| unsupported("it has no manifest"))?;
+        if params.stream != MAIN_STREAM {
+            return Err(unsupported("only the file itself can be read by range"));
+        }
+        if manifest.link_target.is_some() {
+            return Err(unsupported("it holds a symlink"));
+        }
+        if manifest.compressed {
+            return Err(unsupported("its payload is zstd compressed"));
+        }
+        if !manifest.holes.is_empty() {
+            return Err(unsupported("it was encoded sparse"));
+        }
 
-        info!("💾 Wrote {} bytes to {}", final_data.len(), output_path.display());
-        info!("📋 Checksum: {}", checksum);
+        let end = offset.saturating_add(len).min(manifest.original_size);
+        let offset = offset.min(end);
+        if offset == end {
+            return Ok(RangeRead { data: Vec::new(), frames_extracted: 0, frames_cached: 0 });
+        }
+        let skip = if Framing::of(Some(&manifest), true) == Framing::Container { HEADER_LEN as u64 } else { 0 };
+        let chunk_size = params.chunk_size as u64;
+        let (start, stop) = (skip + offset, skip + end);
+        let (first, last) = ((start / chunk_size) as u32, ((stop - 1) / chunk_size) as u32);
+        debug!("📖 Reading bytes {}..{} of {} from frames {}-{}", offset, end, input_path.display(), first, last);
 
-        Ok(DecodedFileInfo {
-            extracted_size: final_data.len() as u64,
+        let cache = params.frame_cache_dir.as_ref().map(FrameCache::new);
+        let video_key = cache.as_ref().map(|_| FrameCache::video_key(input_path)).transpose()?;
+        let mut chunks = BTreeMap::new();
+        if let (Some(cache), Some(key)) = (&cache, &video_key) {
+            for index in first..=last {
+                if let Some(payload) = cache.load(key, index)? {
+                    chunks.insert(index, payload);
+                }
+            }
+        }
+        let frames_cached = chunks.len() as u64;
+
+        let missing: Vec<u32> = (first..=last).filter(|index| !chunks.contains_key(index)).collect();
+        if let (Some(&from), Some(&to)) = (missing.first(), missing.last()) {
+            let window = manifest.index.as_ref().and_then(|index| index.window(from as u64..to as u64 + 1));
+            let (window_start, window_end) = match window {
+                Some((start, count)) => (start, start + count),
+                None => {
+                    // No index (an older or embedded manifest): data frame i
+                    // follows i frames and their crossfades, and at most every
+                    // frame that isn't one (manifest copies, extra streams,
+                    // the end marker)
+                    let stride = manifest.transition_frames as u64 + 1;
+                    let (video_frames, _) = self.backend.probe_frames(input_path)?;
+                    let extra = video_frames.saturating_sub(manifest.num_frames.saturating_sub(1) * stride + 1);
+                    (from as u64 * stride, to as u64 * stride + extra + 1)
+                }
+            };
+
+            let composer = self.frame_reader(&params);
+            let filters = composer.content_filters(input_path)?;
+            let generator = GeometricArtGenerator::new(params.width, params.height, params.seed);
+            let step = params.frame_window.map_or(window_end - window_start, |window| window as u64);
+            let mut pos = window_start;
+            while pos < window_end && chunks.len() <= (last - first) as usize {
+                let frames = composer.extract_frame_window(input_path, &filters, pos, step.min(window_end - pos)).await?;
+                if frames.is_empty() {
+                    break;
+                }
+                pos += frames.len() as u64;
+                for frame in &frames {
+                    let Ok(Some(header)) = FrameHeader::try_read_from(frame) else {
+                        continue;
+                    };
+                    if header.stream != MAIN_STREAM
+                        || header.has_flag(FLAG_TRANSITION)
+                        || !(from..=to).contains(&header.index)
+                        || chunks.contains_key(&header.index)
+                    {
+                        continue;
+                    }
+                    let payload = partial::checked_payload(&generator, frame, &header, params.chunk_size)
+                        .ok_or_else(|| F2V2FError::DecodingError(format!("Frame {} fails its CRC", header.index)))?;
+                    if let (Some(cache), Some(key)) = (&cache, &video_key) {
+                        cache.store(key, header.index, &payload)?;
+                    }
+                    chunks.insert(header.index, payload);
+                }
+            }
+        }
+        if let Some(index) = (first..=last).find(|index| !chunks.contains_key(index)) {
+            return Err(F2V2FError::DecodingError(format!("Frame {} not found in {}", index, input_path.display())));
+        }
+
+        let frames_extracted = chunks.len() as u64 - frames_cached;
+        let mut data = Vec::with_capacity((end - offset) as usize);
+        for (index, payload) in &chunks {
+            let chunk_start = *index as u64 * chunk_size;
+            let from = start.saturating_sub(chunk_start).min(payload.len() as u64) as usize;
+            let to = (stop - chunk_start).min(payload.len() as u64) as usize;
+            data.extend_from_slice(&payload[from..to]);
+        }
+        if data.len() as u64 != end - offset {
+            return Err(F2V2FError::DecodingError(format!(
+                "Frames {}-{} hold {} of the {} bytes asked for",
+                first,
+                last,
+                data.len(),
+                end - offset
+            )));
+        }
+        debug!("✅ Read {} bytes ({} frames extracted, {} cached)", data.len(), frames_extracted, frames_cached);
+        Ok(RangeRead { data, frames_extracted, frames_cached })
+    }
+
+    /// Check each data frame of a video against exported chunk hashes (see
+    /// `chunk_manifest`) without decompressing or writing the file
+    ///
+    /// Frames are extracted `frame_window` at a time (all at once without
+    /// one). A frame's payload is compared with the hash for its index,
+    /// whatever its CRC says; chunks no frame claims are reported missing.
+    pub async fn verify_against_manifest<P: AsRef<Path>>(
+        &self,
+        input: P,
+        chunks: &ChunkManifest,
+    ) -> Result<ChunkVerification> {
+        let input_path = input.as_ref();
+        let mut warnings = Vec::new();
+        let (_, mut params, _) = self.resolve_video(input_path, &mut warnings).await?;
+        params.chunk_size = chunks.chunk_size as usize;
+        debug!("🧾 Verifying {} chunks of {}", chunks.chunks.len(), input_path.display());
+
+        let composer = self.frame_reader(&params);
+        let filters = composer.content_filters(input_path)?;
+        let generator = GeometricArtGenerator::new(params.width, params.height, params.seed);
+        let (total, _) = self.backend.probe_frames(input_path)?;
+        let step = params.frame_window.map_or(total, |window| window as u64).max(1);
+        let mut seen = HashSet::new();
+        let mut verification = ChunkVerification::default();
+        let mut pos = 0;
+        while pos < total {
+            let frames = composer.extract_frame_window(input_path, &filters, pos, step.min(total - pos)).await?;
+            if frames.is_empty() {
+                break;
+            }
+            pos += frames.len() as u64;
+            for frame in &frames {
+                let Ok(Some(header)) = FrameHeader::try_read_from(frame) else {
+                    continue;
+                };
+                if header.stream != MAIN_STREAM || header.has_flag(FLAG_TRANSITION) {
+                    continue;
+                }
+                let Some(entry) = chunks.chunks.get(header.index as usize) else {
+                    continue;
+                };
+                if !seen.insert(entry.index) {
+                    continue;
+                }
+                let payload = generator.for_header(&header).decode_from_raw(frame.as_raw(), params.chunk_size);
+                if payload.is_ok_and(|payload| entry.matches(&payload)) {
+                    verification.verified += 1;
+                } else {
+                    warn!("Chunk {} doesn't match its hash", entry.index);
+                    verification.mismatched.push(entry.index);
+                }
+            }
+        }
+        verification.missing = chunks.chunks.iter().map(|entry| entry.index).filter(|index| !seen.contains(index)).collect();
+        debug!("✅ {} chunks verified, {} mismatched, {} missing",
+            verification.verified, verification.mismatched.len(), verification.missing.len());
+        Ok(verification)
+    }
+
+    /// Reconstruct the manifest of a video whose sidecar was lost
+    ///
+    /// Any sidecar is ignored. A copy embedded in the video is used when
+    /// one survives; otherwise the video is decoded from its frame headers
+    /// (probing the resolution and trying plausible chunk sizes, see
+    /// `search_chunk_size`) and the manifest rebuilt from what comes out:
+    /// zstd framing of the payload, size, checksum and content type of the
+    /// decoded file. The seed and dictionary are the configured ones. What
+    /// the frames don't record (sparse holes, extra streams, encode
+    /// settings) is left out, so a sparse file comes back with its holes
+    /// filled in and `checksum` describes that file.
+    pub async fn rebuild_manifest<P: AsRef<Path>>(&self, input: P) -> Result<Manifest> {
+        let input_path = input.as_ref();
+        debug!("🔧 Rebuilding the manifest of {}", input_path.display());
+        let mut params = self.config.clone();
+        params.stream = MAIN_STREAM;
+        params.encoded_data_size = None;
+        let with_checksum = |manifest: Manifest| -> Result<Manifest> {
+            let hash_algo = manifest.hash_algo;
+            Ok(Manifest { video_checksum: Some(crate::checksum::hash_file(input_path, hash_algo)?), ..manifest })
+        };
+        if let Some(manifest) = self.read_tagged_manifest(input_path) {
+            return with_checksum(manifest);
+        }
+        if params.probe_resolution {
+            (params.width, params.height) = self.backend.probe_resolution(input_path)?;
+        }
+        let head = self.check_has_headers(&params, input_path).await?;
+        if let Some(manifest) = self.read_embedded_manifest(&params, input_path, &head) {
+            debug!("📄 Recovered the manifest embedded in the video");
+            return with_checksum(manifest);
+        }
+        if let Some(manifest) = self.read_subtitle_manifest(input_path) {
+            return with_checksum(manifest);
+        }
+
+        let mut warnings = Vec::new();
+        let (payload, num_frames, ended) = self.extract_frame_data(&params, input_path, &mut warnings).await?;
+        let chunk_size = warnings
+            .iter()
+            .find_map(|w| match w {
+                Warning::InferredChunkSize { chunk_size, .. } => Some(*chunk_size),
+                _ => None,
+            })
+            .unwrap_or(params.chunk_size);
+        let skipped = warnings
+            .iter()
+            .find_map(|w| match w {
+                Warning::SkippedFrames { count } => Some(*count as u64),
+                _ => None,
+            })
+            .unwrap_or(0);
+
+        let mut head = Vec::with_capacity(HEADER_LEN);
+        payload.reader()?.take(HEADER_LEN as u64).read_to_end(&mut head)?;
+        let header = ContainerHeader::parse(&head)?;
+        let compressed = header.map_or_else(|| Self::is_zstd_compressed(&head), |h| h.is_compressed());
+        let dictionary = if compressed { self.load_dictionary(None)? } else { None };
+        let (_, source) = Self::open_payload(payload.reader()?, Framing::Detect, dictionary.as_deref())?;
+        let start = ZeroFill::new(Head(Vec::with_capacity(crate::content_type::SNIFF_LEN)));
+        let (start, original_size, checksum) =
+            self.copy_payload(source, compressed, dictionary.as_deref(), &[], start, params.hash_algo)?;
+
+        // Whatever isn't a data frame, a skipped one or the end marker is a
+        // crossfade between two
+        let (video_frames, fps) = self.backend.probe_frames(input_path)?;
+        let transition_frames = match num_frames {
+            0 | 1 => 0,
+            n => (video_frames.saturating_sub(n + skipped + ended as u64) / (n - 1)) as u32,
+        };
+        debug!("✅ Rebuilt manifest: {}x{}, {} frames of {} bytes, {}", params.width, params.height, num_frames,
+            chunk_size, if compressed { "zstd compressed" } else { "raw" });
+
+        Ok(Manifest {
+            format_version: if header.is_some() { MANIFEST_VERSION } else { FIRST_CONTAINER_FORMAT - 1 },
+            width: params.width,
+            height: params.height,
+            fps: fps.round() as u32,
+            chunk_size,
+            num_frames,
+            original_size,
+            encoded_size: payload.len(),
+            compressed,
+            compression_level: None,
+            hash_algo: params.hash_algo,
             checksum,
-            was_compressed,
+            dictionary_id: dictionary.as_deref().and_then(crate::dictionary::dictionary_id),
+            seed: params.seed,
+            holes: Vec::new(),
+            link_target: None,
+            content_type: crate::content_type::sniff(&start.into_inner().0).map(|t| t.mime.to_string()),
+            transition_frames,
+            video_checksum: Some(crate::checksum::hash_file(input_path, params.hash_algo)?),
+            settings: None,
+            merkle: None,
+            streams: Vec::new(),
+            file: None,
+            index: None,
         })
     }
 
+    /// Manifest, decode parameters and (when decoding an extra stream) the
+    /// stream's record for the video at `input_path`
+    ///
+    /// The manifest is the sidecar, else the copy embedded in the video.
+    /// Without either, the video must show f2v2f frame headers early on.
+    async fn resolve_video(
+        &self,
+        input_path: &Path,
+        warnings: &mut Vec<Warning>,
+    ) -> Result<(Option<Manifest>, DecodeConfig, Option<StreamInfo>)> {
+        let mut manifest = Manifest::read_sidecar(input_path)?;
+        if manifest.is_none() {
+            manifest = self.read_tagged_manifest(input_path);
+            if manifest.is_some() {
+                warnings.push(Warning::TaggedManifest);
+            }
+        }
+        let mut params = self.resolve_config(manifest.as_ref());
+        if manifest.is_none() {
+            if params.probe_resolution {
+                (params.width, params.height) = self.backend.probe_resolution(input_path)?;
+                debug!("📐 No manifest; decoding at the video's {}x{} resolution", params.width, params.height);
+            }
+            // Nothing vouches for the video; look for frame headers before
+            // extracting all of it
+            let head = self.check_has_headers(&params, input_path).await?;
+            manifest = self.read_embedded_manifest(&params, input_path, &head);
+            let mut source = Warning::EmbeddedManifest;
+            if manifest.is_none() {
+                manifest = self.read_subtitle_manifest(input_path);
+                source = Warning::SubtitleManifest;
+            }
+            match &manifest {
+                Some(m) => {
+                    debug!("📄 Using the manifest copy in the video");
+                    params.apply_manifest(m);
+                    warnings.push(source);
+                }
+                None => warnings.push(Warning::MissingManifest),
+            }
+        }
+        // Extra streams are stored as-is, with a size of their own
+        let stream = match &manifest {
+            Some(m) if params.stream != MAIN_STREAM => {
+                let stream = m.streams.iter().find(|s| s.id == params.stream).cloned().ok_or_else(|| {
+                    F2V2FError::InvalidInput(format!("{} has no stream {}", input_path.display(), params.stream))
+                })?;
+                params.encoded_data_size = Some(stream.size);
+                Some(stream)
+            }
+            _ => None,
+        };
+        if let Some(m) = &manifest {
+            check_style(m)?;
+        }
+        Ok((manifest, params, stream))
+    }
+
+    /// Recreate a preserved symlink at `output_path`
+    #[cfg(unix)]
+    fn restore_link(target: &Path, output_path: &Path, hash_algo: HashAlgorithm) -> Result<DecodedFileInfo> {
+        std::os::unix::fs::symlink(target, output_path)?;
+        debug!("🔗 Restored symlink {} → {}", output_path.display(), target.display());
+        Ok(DecodedFileInfo {
+            extracted_size: 0,
+            checksum: hash_algo.digest(b""),
+            hash_algo,
+            was_compressed: false,
+            content_type: None,
+            warnings: Vec::new(),
+            file_metadata: None,
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn restore_link(target: &Path, _output_path: &Path, _hash_algo: HashAlgorithm) -> Result<DecodedFileInfo> {
+        Err(F2V2FError::InvalidInput(format!(
+            "Video holds a symlink to {}; symlinks can't be recreated on this platform",
+            target.display()
+        )))
+    }
+
+    /// Compare the extracted payload size against the size recorded at encode time
+    fn check_payload_size(extracted: u64, expected: Option<u64>) -> Result<()> {
+        match expected {
+            Some(expected) if expected != extracted => Err(F2V2FError::IntegrityError(
+                "Extracted payload size".to_string(),
+                expected.to_string(),
+                extracted.to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Load the configured dictionary, checking it against the manifest's ID
+    fn load_dictionary(&self, expected_id: Option<u32>) -> Result<Option<Vec<u8>>> {
+        let dict = match &self.config.dictionary {
+            Some(path) => crate::dictionary::load(path)?,
+            None if expected_id.is_some() => {
+                return Err(F2V2FError::ConfigError(format!(
+                    "Video was compressed with zstd dictionary {} but no dictionary was provided",
+                    expected_id.unwrap_or_default()
+                )));
+            }
+            None => return Ok(None),
+        };
+
+        let actual_id = crate::dictionary::dictionary_id(&dict);
+        if let (Some(expected), Some(actual)) = (expected_id, actual_id) {
+            if expected != actual {
+                return Err(F2V2FError::ConfigError(format!(
+                    "Wrong zstd dictionary: video needs {}, got {}",
+                    expected, actual
+                )));
+            }
+        }
+        Ok(Some(dict))
+    }
+
+    /// Stream the payload to `output_path`, decompressing on the fly
+    ///
+    /// The zstd decoder reads from the payload buffer and writes through a
+    /// buffered, hashing writer, so the decompressed file never has to fit in
+    /// memory. libzstd has no multi-threaded decoder; the window limit is
+    /// raised so payloads compressed with long-distance matching still decode.
+    /// `holes` are recreated by seeking, leaving a sparse output file; a
+    /// pipe or other stream gets them written out as zeros instead.
+    /// A file whose checksum differs from `checksum.expected` is never
+    /// committed (a stream has been written to by then, and only fails).
+    /// Returns (bytes written, checksum).
+    fn write_payload<R: Read>(
+        &self,
+        source: R,
+        was_compressed: bool,
+        dictionary: Option<&[u8]>,
+        holes: &[Hole],
+        output_path: &Path,
+        checksum: &ExpectedChecksum,
+    ) -> Result<(u64, String)> {
+        let hash_algo = checksum.algo;
+        if crate::stream::is_stream(output_path) {
+            let file = OpenOptions::new().write(true).open(output_path)?;
+            let out = ZeroFill::new(BufWriter::with_capacity(self.config.buffer_size, file));
+            let (out, written, actual) =
+                self.copy_payload(source, was_compressed, dictionary, holes, out, hash_algo)?;
+            out.into_inner().flush()?;
+            checksum.verify(&actual)?;
+            return Ok((written, actual));
+        }
+
+        // Written to a staging file that only replaces `output_path` on success
+        let staged = AtomicOutput::new_in(output_path, self.config.overwrite, self.config.temp_dir.as_deref())?;
+        let file = staged.as_file().try_clone()?;
+        let out = BufWriter::with_capacity(self.config.buffer_size, file);
+        let (buffered, written, actual) =
+            self.copy_payload(source, was_compressed, dictionary, holes, out, hash_algo)?;
+        checksum.verify(&actual)?;
+        let output_file = buffered
+            .into_inner()
+            .map_err(|e| F2V2FError::Io(e.to_string()))?;
+        // A trailing hole only exists once the file is extended over it
+        output_file.set_len(written)?;
+        output_file.sync_all()?;
+        staged.commit()?;
+
+        Ok((written, actual))
+    }
+
+    /// Decompress (if needed) the payload into `out`, restoring `holes`;
+    /// returns `out`, the bytes written and their checksum
+    fn copy_payload<R: Read, W: Write + Seek>(
+        &self,
+        source: R,
+        was_compressed: bool,
+        dictionary: Option<&[u8]>,
+        holes: &[Hole],
+        out: W,
+        hash_algo: HashAlgorithm,
+    ) -> Result<(W, u64, String)> {
+        let mut writer = SparseWriter::new(HashingWriter::new(out, hash_algo), holes);
+
+        if was_compressed {
+            debug!("🗜️  Decompressing with Zstd...");
+            let mut decoder = zstd_decoder(BufReader::new(self.operation.reader(source)), dictionary)?;
+            io::copy(&mut decoder, &mut writer)?;
+        } else {
+            io::copy(&mut self.operation.reader(source), &mut writer)?;
+        }
+
+        let writer = writer.finish()?;
+        let written = writer.bytes_written();
+        if was_compressed {
+            debug!("✅ Decompressed to {} bytes", written);
+        }
+        let (out, checksum) = writer.finish();
+        Ok((out, written, checksum))
+    }
+
+    /// Decode parameters for a video: values recorded in its manifest take
+    /// precedence over the configured ones
+    fn resolve_config(&self, manifest: Option<&Manifest>) -> DecodeConfig {
+        let mut params = self.config.clone();
+        if let Some(m) = manifest {
+            debug!("📄 Using manifest parameters ({}x{}, chunk {} bytes, seed {})",
+                m.width, m.height, m.chunk_size, m.seed);
+            params.apply_manifest(m);
+        }
+        params
+    }
+
     /// Extract all data from video frames
-    async fn extract_frame_data<P: AsRef<Path>>(&self, video_path: P) -> Result<Vec<u8>> {
-        let path = video_path.as_ref();
-        let composer = crate::video_composer::VideoComposer::new(
-            self.config.width,
-            self.config.height,
-            30,
-        );
+    ///
+    /// Returns the payload, the number of data frames it came from, and
+    /// whether the video has an `END_STREAM` frame.
+    async fn extract_frame_data<P: AsRef<Path>>(
+        &self,
+        params: &DecodeConfig,
+        video_path: P,
+        warnings: &mut Vec<Warning>,
+    ) -> Result<(Payload, u64, bool)> {
+        let sink = SpillWriter::new(DEFAULT_SPILL_THRESHOLD).with_temp_dir(params.temp_dir.clone());
+        let mut extractor = FrameExtractor::for_params(params, sink);
+        self.run_extractor(params, video_path.as_ref(), &mut extractor, warnings).await?;
+        let (frames, ended) = (extractor.frames_done as u64, extractor.end.is_some());
+        Ok((extractor.finish()?, frames, ended))
+    }
 
-        let generator = crate::image_generator::GeometricArtGenerator::new(
-            self.config.width,
-            self.config.height,
-            42,
-        );
+    /// Feed every frame of the video through `extractor`
+    ///
+    /// With `frame_window` set, frames are requested from ffmpeg in windows
+    /// and each window is processed before the next is read, so memory use
+    /// doesn't grow with the length of the video (and a blocked sink pauses
+    /// extraction).
+    async fn run_extractor<W: Write>(
+        &self,
+        params: &DecodeConfig,
+        path: &Path,
+        extractor: &mut FrameExtractor<W>,
+        warnings: &mut Vec<Warning>,
+    ) -> Result<()> {
+        let composer = self.frame_reader(params);
+
+        match params.frame_window {
+            None => {
+                let frames = composer.extract_frames(path).await?;
+                debug!("📸 Extracted {} frames from video", frames.len());
+                extractor.process(&frames)?;
+            }
+            Some(window) => {
+                let window = window as u64;
+                debug!("📸 Extracting frames in windows of {}", window);
+                let filters = composer.content_filters(path)?;
+                loop {
+                    let start = extractor.frames_seen as u64;
+                    let frames = composer.extract_frame_window(path, &filters, start, window).await?;
+                    extractor.process(&frames)?;
+                    if (frames.len() as u64) < window {
+                        break;
+                    }
+                }
+            }
+        }
+
+        warnings.extend(composer.take_warnings());
+        if let Some(search) = extractor.search {
+            warnings.push(Warning::InferredChunkSize { chunk_size: search.chunk_size, validated: search.validated });
+        }
+        if extractor.frames_skipped > 0 {
+            warnings.push(Warning::SkippedFrames { count: extractor.frames_skipped });
+        }
+        if extractor.frames_reordered > 0 {
+            warnings.push(Warning::ReorderedFrames { count: extractor.frames_reordered });
+        }
+        Ok(())
+    }
+
+    /// Composer that reads frames back at the resolution `params` describe
+    fn frame_reader(&self, params: &DecodeConfig) -> VideoComposer {
+        VideoComposer::new(params.width, params.height, 30)
+            .with_backend(self.backend.clone())
+            .with_extract_format(params.extract_format)
+    }
+
+    /// Fail fast unless one of the first `PROBE_FRAMES` frames has an f2v2f
+    /// header; returns the frames read
+    async fn check_has_headers(&self, params: &DecodeConfig, path: &Path) -> Result<Vec<RgbaImage>> {
+        let composer = self.frame_reader(params);
+        let filters = composer.content_filters(path)?;
+        let frames = composer.extract_frame_window(path, &filters, 0, PROBE_FRAMES as u64).await?;
+        if frames.iter().any(|frame| matches!(FrameHeader::try_read_from(frame), Ok(Some(_)))) {
+            return Ok(frames);
+        }
+        debug!("No manifest or frame headers in {}", path.display());
+        Err(not_f2v2f(frames.len()))
+    }
 
-        // Extract frames from video
-        let frames = composer.extract_frames(path).await?;
-        info!("📸 Extracted {} frames from video", frames.len());
+    /// The manifest copy in the container's metadata (`MANIFEST_TAG`, or
+    /// `MANIFEST_ATTACHMENT` in Matroska videos), if the video was encoded
+    /// with one and it survived
+    fn read_tagged_manifest(&self, path: &Path) -> Option<Manifest> {
+        let json = if is_matroska(path) {
+            self.backend
+                .read_attachment(path, MANIFEST_ATTACHMENT)
+                .map(|data| data.map(|data| String::from_utf8_lossy(&data).into_owned()))
+        } else {
+            self.backend.read_tag(path, MANIFEST_TAG)
+        };
+        let json = match json {
+            Ok(json) => json?,
+            Err(e) => {
+                debug!("Could not read the metadata of {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        match Manifest::from_json(&json) {
+            Ok(manifest) => {
+                debug!("📄 Using the manifest in the metadata of {}", path.display());
+                Some(manifest)
+            }
+            Err(e) => {
+                debug!("Ignoring the manifest tag of {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
 
-        let mut all_data = Vec::new();
-        for (i, frame) in frames.iter().enumerate() {
-            let frame_data = generator.decode_from_image(frame, self.config.chunk_size)?;
-            all_data.extend_from_slice(&frame_data);
-            if (i + 1) % 10 == 0 {
-                info!("  Processed {} frames...", i + 1);
+    /// The manifest copy in the video's subtitle track (see `subtitles`), if
+    /// the video was encoded with one and it survived
+    fn read_subtitle_manifest(&self, path: &Path) -> Option<Manifest> {
+        let srt = match self.backend.read_subtitles(path) {
+            Ok(srt) => srt?,
+            Err(e) => {
+                debug!("Could not read the subtitles of {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        match SubtitleMetadata::from_srt(&srt) {
+            Ok(metadata) => {
+                debug!("📄 Using the manifest in the subtitle track of {}", path.display());
+                Some(metadata.manifest)
+            }
+            Err(e) => {
+                debug!("Ignoring the subtitle track of {}: {}", path.display(), e);
+                None
             }
         }
+    }
 
-        Ok(all_data)
+    /// The manifest copy embedded in the video (see `MANIFEST_STREAM`), from
+    /// `head` (its first frames) or else from its last frames
+    ///
+    /// Without one the video still decodes from its frame headers, so a
+    /// missing or damaged copy is only logged.
+    fn read_embedded_manifest(&self, params: &DecodeConfig, path: &Path, head: &[RgbaImage]) -> Option<Manifest> {
+        // The head copy precedes the first data frame, the trailer follows the last
+        let before_data = head.iter().position(is_main_frame).unwrap_or(head.len());
+        if let Some(manifest) = embedded_manifest(params, &head[..before_data]) {
+            return Some(manifest);
+        }
+        debug!("No manifest at the start of {}; trying its last frames", path.display());
+        let composer = self.frame_reader(params);
+        let tail = composer
+            .content_filters(path)
+            .and_then(|filters| composer.extract_last_frames(path, &filters, PROBE_FRAMES as u64));
+        match tail {
+            Ok(tail) => {
+                let after_data = tail.iter().rposition(is_main_frame).map_or(0, |i| i + 1);
+                embedded_manifest(params, &tail[after_data..])
+            }
+            Err(e) => {
+                debug!("Could not read the last frames of {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Extract frames and write the output at the same time
+    ///
+    /// Payload flows to a writer thread through a bounded channel (see
+    /// `backpressure`), so a slow output pauses extraction. The channel is
+    /// only marked complete once the payload passed every check, so the
+    /// output is never committed from a partial payload. Returns (bytes
+    /// written, checksum, was compressed, data frames).
+    async fn decode_streaming(
+        &self,
+        params: &DecodeConfig,
+        input_path: &Path,
+        output: StreamOutput<'_>,
+        warnings: &mut Vec<Warning>,
+    ) -> Result<(u64, String, bool, u64)> {
+        let (tx, rx) = backpressure::channel(output.high_watermark);
+        let writer = self.clone();
+        let StreamOutput { dictionary, holes, framing, checksum, .. } = output;
+        let output_path = output.path.to_path_buf();
+        let handle = std::thread::spawn(move || -> Result<(u64, String, bool)> {
+            let (was_compressed, source) = Self::open_payload(rx, framing, dictionary.as_deref())?;
+            let (written, checksum) =
+                writer.write_payload(source, was_compressed, dictionary.as_deref(), &holes, &output_path, &checksum)?;
+            Ok((written, checksum, was_compressed))
+        });
+
+        let mut extractor = FrameExtractor::for_params(params, tx);
+        let extracted = self.run_extractor(params, input_path, &mut extractor, warnings).await;
+        let output_stopped = extractor.sink.is_closed();
+        let pauses = extractor.sink.pauses();
+        let (frames, bytes) = (extractor.frames_done as u64, extractor.bytes_done);
+        let expected_size = params.encoded_data_size;
+        // Every failure path drops the sink unfinished, which fails the writer
+        let finished = extracted
+            .and_then(|()| extractor.into_sink())
+            .and_then(|sink| Self::check_payload_size(bytes, expected_size).map(|()| sink))
+            .map(ChannelWriter::finish);
+
+        let written = handle
+            .join()
+            .map_err(|_| F2V2FError::DecodingError("Output writer panicked".to_string()))?;
+        match finished {
+            Ok(()) => {
+                let (written, checksum, was_compressed) = written?;
+                if pauses > 0 {
+                    debug!("⏸️  Extraction paused {} times for the output to catch up", pauses);
+                }
+                Ok((written, checksum, was_compressed, frames))
+            }
+            // When the output gave up first, its error is the real cause
+            Err(e) => Err(match written {
+                Err(output_error) if output_stopped => output_error,
+                _ => e,
+            }),
+        }
     }
 
     /// Verify that decoded file matches expected checksum
@@ -140,7 +1181,7 @@ impl Decoder {
     ) -> Result<bool> {
         let path = file_path.as_ref();
         let mut file = File::open(path)?;
-        let mut hasher = Sha256::new();
+        let mut hasher = Hasher::new(self.config.hash_algo);
         let mut buffer = vec![0u8; 1024 * 1024]; // 1MB chunks
 
         loop {
@@ -155,11 +1196,449 @@ impl Decoder {
             }
         }
 
-        let checksum = format!("{:x}", hasher.finalize());
+        let checksum = hasher.finalize();
         Ok(checksum == expected_checksum)
     }
 }
 
+/// How the decoded file is hashed, and what it must hash to
+#[derive(Debug, Clone)]
+struct ExpectedChecksum {
+    algo: HashAlgorithm,
+    /// Checksum of the original file, when it is known and checked
+    expected: Option<String>,
+}
+
+impl ExpectedChecksum {
+    fn verify(&self, actual: &str) -> Result<()> {
+        match &self.expected {
+            Some(expected) if expected != actual => Err(F2V2FError::IntegrityError(
+                format!("Checksum ({}) of the decoded file", self.algo),
+                expected.clone(),
+                actual.to_string(),
+            )),
+            Some(_) => {
+                debug!("✅ Checksum verified against the manifest");
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Hash without checking
+impl From<HashAlgorithm> for ExpectedChecksum {
+    fn from(algo: HashAlgorithm) -> Self {
+        Self { algo, expected: None }
+    }
+}
+
+/// Where `Decoder::decode_streaming` writes, and what it needs to do so
+struct StreamOutput<'a> {
+    path: &'a Path,
+    dictionary: Option<Vec<u8>>,
+    holes: Vec<Hole>,
+    high_watermark: usize,
+    framing: Framing,
+    checksum: ExpectedChecksum,
+}
+
+/// Leading frames that may lack a header before a video is rejected as not
+/// f2v2f-encoded (about a second at 30 fps, well past any lead-in padding)
+pub const PROBE_FRAMES: usize = 30;
+
+fn not_f2v2f(frames: usize) -> F2V2FError {
+    F2V2FError::NotF2V2FVideo(format!("none of its first {} frames carries an f2v2f header", frames))
+}
+
+/// Refuse a video rendered in an art style this build doesn't have, whose
+/// frames the built-in styles would misread
+fn check_style(manifest: &Manifest) -> Result<()> {
+    match &manifest.settings {
+        Some(settings) if !ART_STYLES.contains(&settings.art_style.as_str()) => {
+            Err(F2V2FError::UnsupportedStyle(settings.art_style.clone(), manifest.format_version))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Frames that may arrive ahead of a missing one before it counts as dropped
+pub const MAX_REORDER_FRAMES: usize = 64;
+
+/// Validates frames and collects their payload, one batch at a time
+///
+/// Payload is assembled by the index in each frame's header, not by the
+/// frame's position in the video: frames that arrive early (a remux that
+/// reordered them) are held until the gap before them fills. Frames without
+/// a header (a black frame prepended by a platform) and repeats of a frame
+/// already taken (a duplicated last frame) are skipped. A gap still open
+/// after `MAX_REORDER_FRAMES` later frames, or at the end, is an error, as
+/// are main-stream frames missing before the `END_STREAM` frame's count.
+struct FrameExtractor<W = SpillWriter> {
+    generator: GeometricArtGenerator,
+    /// Known up front from the manifest, otherwise inferred from the first batch
+    chunk_size: Option<usize>,
+    /// Video frames read so far, including skipped ones
+    frames_seen: usize,
+    /// Data frames taken so far (the next expected frame index)
+    frames_done: usize,
+    /// Frames ignored as foreign or duplicate
+    frames_skipped: usize,
+    /// Frames that arrived ahead of their turn
+    frames_reordered: usize,
+    /// Payload bytes written to the sink
+    bytes_done: u64,
+    /// Checked payloads waiting for an earlier frame, by index
+    pending: BTreeMap<u32, Vec<u8>>,
+    /// Data frames the video holds, from its `END_STREAM` frame (videos
+    /// from before it was written have none)
+    end: Option<u32>,
+    /// How the chunk size was found, when it wasn't known up front
+    search: Option<ChunkSearch>,
+    /// Stream whose frames are collected; frames of other streams are passed over
+    stream: u8,
+    sink: W,
+}
+
+impl<W: Write> FrameExtractor<W> {
+    fn new(generator: GeometricArtGenerator, chunk_size: Option<usize>, sink: W) -> Self {
+        Self {
+            generator,
+            chunk_size,
+            frames_seen: 0,
+            frames_done: 0,
+            frames_skipped: 0,
+            frames_reordered: 0,
+            bytes_done: 0,
+            pending: BTreeMap::new(),
+            end: None,
+            search: None,
+            stream: MAIN_STREAM,
+            sink,
+        }
+    }
+
+    /// Check the next batch of frames and append the payload that is now in order
+    fn process(&mut self, frames: &[RgbaImage]) -> Result<()> {
+        let headers = frames
+            .iter()
+            .enumerate()
+            .map(|(i, frame)| {
+                FrameHeader::try_read_from(frame)
+                    .map_err(|e| F2V2FError::DecodingError(format!("Frame {}: {}", self.frames_seen + i, e)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let chunk_size = match self.chunk_size {
+            Some(chunk_size) => chunk_size,
+            None => {
+                let own: Vec<_> = headers.iter().map(|h| h.filter(|h| h.stream == self.stream)).collect();
+                let search = search_chunk_size(&self.generator, frames, &own);
+                debug!("📏 Inferred chunk size {} bytes from frame headers{}", search.chunk_size,
+                    if search.validated { " (CRC checked)" } else { "" });
+                self.chunk_size = Some(search.chunk_size);
+                self.search = Some(search);
+                search.chunk_size
+            }
+        };
+
+        for (frame, header) in frames.iter().zip(&headers) {
+            let position = self.frames_seen;
+            self.frames_seen += 1;
+            let i = self.frames_done;
+
+            let Some(header) = header else {
+                warn!("Skipping frame {} of the video: no f2v2f header", position);
+                self.frames_skipped += 1;
+                // Lead-in padding is a frame or two, not a second of video
+                if self.frames_skipped == self.frames_seen && self.frames_seen >= PROBE_FRAMES {
+                    return Err(not_f2v2f(self.frames_seen));
+                }
+                continue;
+            };
+            if header.stream == END_STREAM && self.stream == MAIN_STREAM {
+                debug!("Frame {} of the video ends the data at {} frames", position, header.index);
+                self.end = Some(header.index);
+                continue;
+            }
+            if header.has_flag(FLAG_TRANSITION) || header.stream != self.stream {
+                continue;
+            }
+            let index = header.index;
+            if self.end.is_some_and(|end| index >= end) {
+                warn!("Skipping frame {} of the video: frame {} is past the end marker", position, index);
+                self.frames_skipped += 1;
+                continue;
+            }
+            if (index as usize) < i || self.pending.contains_key(&index) {
+                warn!("Skipping frame {} of the video: repeat of frame {}", position, index);
+                self.frames_skipped += 1;
+                continue;
+            }
+
+            if header.payload_len as usize > chunk_size {
+                return Err(F2V2FError::DecodingError(format!(
+                    "Frame {} payload length {} exceeds chunk size {}",
+                    index, header.payload_len, chunk_size
+                )));
+            }
+
+            let mut frame_data = self.generator.for_header(header).decode_from_raw(frame.as_raw(), chunk_size)?;
+            frame_data.truncate(header.payload_len as usize);
+
+            if !header.verify(&frame_data) {
+                return Err(F2V2FError::IntegrityError(
+                    format!("Frame {} CRC mismatch", index),
+                    format!("{:08x}", header.crc32),
+                    format!("{:08x}", crc32fast::hash(&frame_data)),
+                ));
+            }
+
+            if index as usize != i {
+                debug!("Frame {} of the video is frame {}, ahead of frame {}", position, index, i);
+                self.frames_reordered += 1;
+                self.pending.insert(index, frame_data);
+                if self.pending.len() > MAX_REORDER_FRAMES {
+                    return Err(self.gap_error());
+                }
+                continue;
+            }
+            self.append(&frame_data)?;
+            while let Some(frame_data) = self.pending.remove(&(self.frames_done as u32)) {
+                self.append(&frame_data)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Extractor for the video `params` describe
+    fn for_params(params: &DecodeConfig, sink: W) -> Self {
+        let mut extractor = Self::new(
+            GeometricArtGenerator::new(params.width, params.height, params.seed),
+            // Without a manifest the chunk size comes from the frame headers
+            params.encoded_data_size.map(|_| params.chunk_size),
+            sink,
+        );
+        extractor.stream = params.stream;
+        extractor
+    }
+
+    fn append(&mut self, frame_data: &[u8]) -> Result<()> {
+        self.sink.write_all(frame_data)?;
+        self.bytes_done += frame_data.len() as u64;
+        self.frames_done += 1;
+        if self.frames_done.is_multiple_of(10) {
+            debug!("  Processed {} frames...", self.frames_done);
+        }
+        Ok(())
+    }
+
+    /// Error naming the frames missing before the ones still pending
+    fn gap_error(&self) -> F2V2FError {
+        let last = self.pending.keys().next_back().copied().unwrap_or_default();
+        let missing: Vec<u32> = (self.frames_done as u32..last).filter(|i| !self.pending.contains_key(i)).collect();
+        let listed = missing.iter().take(10).map(u32::to_string).collect::<Vec<_>>().join(", ");
+        F2V2FError::DecodingError(format!(
+            "Missing frame{} {}{} (frame {} is present)",
+            if missing.len() == 1 { "" } else { "s" },
+            listed,
+            if missing.len() > 10 { format!(" and {} more", missing.len() - 10) } else { String::new() },
+            last
+        ))
+    }
+
+    /// The sink, once every frame is in; fails on a gap, or if the video
+    /// held no f2v2f frames at all
+    fn into_sink(self) -> Result<W> {
+        if !self.pending.is_empty() {
+            return Err(self.gap_error());
+        }
+        if let Some(end) = self.end.filter(|&end| self.frames_done < end as usize) {
+            return Err(F2V2FError::DecodingError(format!(
+                "Missing frames {}-{}: the video ends after {} of its {} data frames",
+                self.frames_done,
+                end - 1,
+                self.frames_done,
+                end
+            )));
+        }
+        if self.frames_done == 0 && self.stream != MAIN_STREAM {
+            return Err(F2V2FError::DecodingError(format!("No frames of stream {} found", self.stream)));
+        }
+        if self.frames_done == 0 {
+            return Err(not_f2v2f(self.frames_seen));
+        }
+        if self.frames_skipped > 0 {
+            warn!("Ignored {} foreign or repeated frames", self.frames_skipped);
+        }
+        Ok(self.sink)
+    }
+}
+
+impl FrameExtractor<SpillWriter> {
+    /// Collected payload (see `into_sink`)
+    fn finish(self) -> Result<Payload> {
+        self.into_sink()?.finish()
+    }
+}
+
+/// Streaming zstd decompressor over `source`
+pub(crate) fn zstd_decoder<'a, R: BufRead>(source: R, dictionary: Option<&'a [u8]>) -> io::Result<zstd::stream::read::Decoder<'a, R>> {
+    let mut decoder = match dictionary {
+        Some(dict) => zstd::stream::read::Decoder::with_dictionary(source, dict)?,
+        None => zstd::stream::read::Decoder::with_buffer(source)?,
+    };
+    decoder.window_log_max(31)?;
+    Ok(decoder)
+}
+
+/// Reader that ends at the first error instead of failing, so a payload cut
+/// short decompresses as far as it goes
+struct UntilError<R>(R);
+
+impl<R: Read> Read for UntilError<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.read(buf) {
+            Err(e) if e.kind() != io::ErrorKind::Interrupted => {
+                debug!("Payload stops decompressing: {}", e);
+                Ok(0)
+            }
+            result => result,
+        }
+    }
+}
+
+/// Writer keeping the first `SNIFF_LEN` bytes written, for sniffing the
+/// content type, and dropping the rest
+struct Head(Vec<u8>);
+
+impl Write for Head {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let keep = buf.len().min(crate::content_type::SNIFF_LEN - self.0.len());
+        self.0.extend_from_slice(&buf[..keep]);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Payload held by the main-stream frames among `frames`, in memory
+pub(crate) fn decode_frames(
+    generator: GeometricArtGenerator,
+    chunk_size: Option<usize>,
+    frames: &[RgbaImage],
+) -> Result<Vec<u8>> {
+    let mut extractor = FrameExtractor::new(generator, chunk_size, Vec::new());
+    extractor.process(frames)?;
+    extractor.into_sink()
+}
+
+/// The frame carries part of the file itself
+fn is_main_frame(frame: &RgbaImage) -> bool {
+    matches!(FrameHeader::try_read_from(frame), Ok(Some(header)) if header.stream == MAIN_STREAM)
+}
+
+/// Manifest held by the `MANIFEST_STREAM` frames among `frames`, if they
+/// are all there and decode
+fn embedded_manifest(params: &DecodeConfig, frames: &[RgbaImage]) -> Option<Manifest> {
+    let generator = GeometricArtGenerator::new(params.width, params.height, params.seed);
+    let mut extractor = FrameExtractor::new(generator, None, Vec::new());
+    extractor.stream = MANIFEST_STREAM;
+    let json = match extractor.process(frames).and_then(|_| extractor.into_sink()) {
+        Ok(json) => json,
+        Err(e) => {
+            debug!("No embedded manifest: {}", e);
+            return None;
+        }
+    };
+    Manifest::from_json(&String::from_utf8_lossy(&json))
+        .map_err(|e| debug!("Embedded manifest doesn't parse: {}", e))
+        .ok()
+}
+
+/// Chunk size a video was encoded with, recovered from its frame headers
+///
+/// Every frame but the last carries a full chunk, so the first header's
+/// payload length is the chunk size. A single-frame video doesn't reveal it;
+/// decoding with the full frame capacity reads each byte from its first
+/// pixel, which is correct for any chunk size up to that capacity.
+fn infer_chunk_size(headers: &[FrameHeader], frame_capacity: usize) -> usize {
+    match headers {
+        [first, _, ..] => first.payload_len as usize,
+        _ => frame_capacity,
+    }
+}
+
+/// Data frames checked when searching for the chunk size
+const SEARCH_FRAMES: usize = 3;
+
+/// Chunk size settled on for a video without a manifest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ChunkSearch {
+    pub chunk_size: usize,
+    /// The first data frames' CRCs validate with it
+    pub validated: bool,
+}
+
+/// Chunk sizes worth trying, smallest first: what the headers suggest, the
+/// library and CLI defaults, showcase redundancy and powers of two
+fn candidate_chunk_sizes(headers: &[FrameHeader], capacity: usize) -> Vec<usize> {
+    let smallest = headers.iter().map(|h| h.payload_len as usize).max().unwrap_or(0).max(1);
+    let mut candidates = vec![
+        infer_chunk_size(headers, capacity),
+        4096,
+        65536,
+        capacity,
+        capacity / ART_MIN_REPEATS,
+        capacity / SHOWCASE_MIN_REPEATS,
+    ];
+    candidates.extend((10..usize::BITS).map(|shift| 1usize << shift).take_while(|&size| size < capacity));
+    let mut seen = HashSet::new();
+    candidates.retain(|&size| (smallest..=capacity).contains(&size) && seen.insert(size));
+    candidates.sort_unstable();
+    candidates
+}
+
+/// Find the chunk size of a video without a manifest
+///
+/// A single frame or a short last frame doesn't reveal the chunk size in
+/// its header, so plausible sizes are tried on the first few data frames
+/// until their CRCs validate. A frame also decodes with any multiple of its
+/// chunk size, so the smallest that validates is taken (it averages the
+/// most repeats). Falls back to the header-based guess.
+pub(crate) fn search_chunk_size(
+    generator: &GeometricArtGenerator,
+    frames: &[RgbaImage],
+    headers: &[Option<FrameHeader>],
+) -> ChunkSearch {
+    let data: Vec<(&RgbaImage, FrameHeader)> = frames
+        .iter()
+        .zip(headers)
+        .filter_map(|(frame, header)| header.filter(|h| !h.has_flag(FLAG_TRANSITION)).map(|h| (frame, h)))
+        .collect();
+    let found: Vec<FrameHeader> = data.iter().map(|&(_, header)| header).collect();
+    let overlay = found.first().is_some_and(|h| h.has_flag(FLAG_OVERLAY));
+    let capacity = generator.with_overlay(overlay).data_capacity();
+
+    let sample = &data[..data.len().min(SEARCH_FRAMES)];
+    let validates = |chunk_size: usize| {
+        !sample.is_empty()
+            && sample.iter().all(|(frame, header)| {
+                generator.for_header(header).decode_from_image(frame, chunk_size).is_ok_and(|mut payload| {
+                    payload.truncate(header.payload_len as usize);
+                    header.verify(&payload)
+                })
+            })
+    };
+    let sample_headers: Vec<FrameHeader> = found.iter().take(SEARCH_FRAMES).copied().collect();
+    match candidate_chunk_sizes(&sample_headers, capacity).into_iter().find(|&size| validates(size)) {
+        Some(chunk_size) => ChunkSearch { chunk_size, validated: true },
+        None => ChunkSearch { chunk_size: infer_chunk_size(&found, capacity), validated: false },
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -171,6 +1650,24 @@ mod tests {
         assert!(decoder.config.validate().is_ok());
     }
 
+    #[test]
+    fn test_unknown_style_is_refused() {
+        let json = r#"{"format_version":3,"width":256,"height":256,"fps":30,"chunk_size":1024,"num_frames":1,
+            "original_size":1,"encoded_size":1,"compressed":false,"hash_algo":"sha256","checksum":"ab"}"#;
+        let mut manifest = Manifest::from_json(json).unwrap();
+        // Videos from before settings were recorded can only use built-in styles
+        assert!(check_style(&manifest).is_ok());
+
+        let config = crate::config::EncodeConfig { art_style: "watercolor".to_string(), ..Default::default() };
+        manifest.settings = Some(crate::config::EncodeSettings::from(&config));
+        let err = check_style(&manifest).unwrap_err();
+        assert!(matches!(&err, F2V2FError::UnsupportedStyle(name, 3) if name == "watercolor"));
+        assert!(err.to_string().contains("geometric, raw, showcase"), "{}", err);
+
+        manifest.settings.as_mut().unwrap().art_style = "raw".to_string();
+        assert!(check_style(&manifest).is_ok());
+    }
+
     #[test]
     fn test_zstd_magic_detection() {
         let zstd_data = vec![0x28, 0xB5, 0x2F, 0xFD, 0x00, 0x00];
@@ -183,6 +1680,409 @@ mod tests {
         assert!(!Decoder::is_zstd_compressed(&empty));
     }
 
+    #[test]
+    fn test_open_payload_by_framing() -> Result<()> {
+        let mut versioned = ContainerHeader::new(true, false).to_bytes().to_vec();
+        versioned.extend_from_slice(ZSTD_MAGIC);
+        let (compressed, mut rest) = Decoder::open_payload(versioned.as_slice(), Framing::Detect, None)?;
+        let mut body = Vec::new();
+        rest.read_to_end(&mut body)?;
+        assert!(compressed);
+        assert_eq!(body, ZSTD_MAGIC);
+
+        // A version 1 file that happens to start like a header stays as it is
+        let (compressed, mut rest) = Decoder::open_payload(versioned.as_slice(), Framing::Headerless, None)?;
+        body.clear();
+        rest.read_to_end(&mut body)?;
+        assert!(!compressed);
+        assert_eq!(body, versioned);
+
+        assert!(Decoder::open_payload(&b"no header"[..], Framing::Container, None).is_err());
+        let needs_dictionary = ContainerHeader::new(true, true).to_bytes();
+        assert!(Decoder::open_payload(&needs_dictionary[..], Framing::Container, None).is_err());
+        let mut newer = ContainerHeader::new(false, false).to_bytes();
+        newer[4] += 1;
+        let err = Decoder::open_payload(&newer[..], Framing::Detect, None).err().map(|e| e.to_string());
+        assert!(err.is_some_and(|e| e.contains("upgrade f2v2f")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_infer_chunk_size() {
+        let full = FrameHeader::new(0, &[1u8; 4096]);
+        let last = FrameHeader::new(1, &[1u8; 100]);
+        assert_eq!(infer_chunk_size(&[full, last], 1_000_000), 4096);
+        assert_eq!(infer_chunk_size(&[last], 1_000_000), 1_000_000);
+
+        assert_eq!(
+            candidate_chunk_sizes(&[last], 63_488),
+            vec![496, 1024, 2048, 4096, 7936, 8192, 16384, 32768, 63_488]
+        );
+        assert_eq!(candidate_chunk_sizes(&[full, last], 63_488)[0], 4096);
+    }
+
+    #[test]
+    fn test_search_finds_chunk_size_of_single_frame() -> Result<()> {
+        // One short frame: its header only says 700 bytes
+        let generator = GeometricArtGenerator::new(256, 256, 7);
+        let data: Vec<u8> = (0..700u32).map(|i| (i * 7 % 256) as u8).collect();
+        let mut chunk = data.clone();
+        chunk.resize(4096, 0);
+        let frame = generator.generate_frame(&FrameHeader::new(0, &data), &chunk)?;
+        let header = FrameHeader::try_read_from(&frame)?;
+
+        let search = search_chunk_size(&generator, std::slice::from_ref(&frame), &[header]);
+        assert_eq!(search, ChunkSearch { chunk_size: 4096, validated: true });
+
+        let mut single = extractor(None);
+        single.process(&[frame])?;
+        assert_eq!(single.finish()?.into_vec()?, data);
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_payload_size() {
+        assert!(Decoder::check_payload_size(100, None).is_ok());
+        assert!(Decoder::check_payload_size(100, Some(100)).is_ok());
+        assert!(matches!(
+            Decoder::check_payload_size(4096, Some(100)),
+            Err(F2V2FError::IntegrityError(..))
+        ));
+    }
+
+    fn extractor(chunk_size: Option<usize>) -> FrameExtractor {
+        FrameExtractor::new(
+            GeometricArtGenerator::new(256, 256, 7),
+            chunk_size,
+            SpillWriter::new(DEFAULT_SPILL_THRESHOLD),
+        )
+    }
+
+    fn test_frames(data: &[u8]) -> Result<Vec<RgbaImage>> {
+        let generator = GeometricArtGenerator::new(256, 256, 7);
+        data.chunks(1000)
+            .enumerate()
+            .map(|(i, chunk)| generator.generate_frame(&FrameHeader::new(i as u32, chunk), chunk))
+            .collect()
+    }
+
+    #[test]
+    fn test_frame_windows_match_single_pass() -> Result<()> {
+        let data: Vec<u8> = (0..4500u32).map(|i| (i % 253) as u8).collect();
+        let frames = test_frames(&data)?;
+
+        let mut whole = extractor(None);
+        whole.process(&frames)?;
+        assert_eq!(whole.chunk_size, Some(1000));
+        assert_eq!(whole.finish()?.into_vec()?, data);
+
+        // Windows of 2 frames, chunk size inferred from the first window
+        let mut windowed = extractor(None);
+        for window in frames.chunks(2) {
+            windowed.process(window)?;
+        }
+        assert_eq!(windowed.frames_done, 5);
+        assert_eq!(windowed.finish()?.into_vec()?, data);
+
+        // A window that skips ahead leaves a gap
+        let mut skipped = extractor(Some(1000));
+        skipped.process(&frames[..2])?;
+        skipped.process(&frames[3..])?;
+        assert_eq!(skipped.frames_done, 2);
+        assert!(matches!(skipped.finish(), Err(F2V2FError::DecodingError(e)) if e.contains("Missing frame 2 ")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_streamed_payload_only_completes_without_gaps() -> Result<()> {
+        let data: Vec<u8> = (0..4500u32).map(|i| (i % 251) as u8).collect();
+        let frames = test_frames(&data)?;
+        let stream = |frames: &[RgbaImage]| {
+            let (tx, mut rx) = backpressure::channel(1500);
+            let reader = std::thread::spawn(move || {
+                let mut out = Vec::new();
+                rx.read_to_end(&mut out).map(|_| out)
+            });
+            let mut extractor = FrameExtractor::new(GeometricArtGenerator::new(256, 256, 7), Some(1000), tx);
+            let finished = extractor.process(frames).and_then(|()| extractor.into_sink()).map(ChannelWriter::finish);
+            (finished, reader.join().unwrap())
+        };
+
+        let (finished, read) = stream(&frames);
+        assert!(finished.is_ok());
+        assert_eq!(read?, data);
+        // A gap leaves the reader with an error, not a short payload
+        let (finished, read) = stream(&[frames[0].clone(), frames[2].clone()]);
+        assert!(finished.is_err());
+        assert_eq!(read.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reordered_frames_reassembled_by_index() -> Result<()> {
+        let data: Vec<u8> = (0..4500u32).map(|i| (i % 247) as u8).collect();
+        let frames = test_frames(&data)?;
+
+        // Remuxed out of order, one frame duplicated while out of place
+        let order = [0, 2, 3, 2, 1, 4];
+        let shuffled: Vec<_> = order.iter().map(|&i| frames[i].clone()).collect();
+        let mut reordered = extractor(Some(1000));
+        reordered.process(&shuffled)?;
+        assert_eq!((reordered.frames_done, reordered.frames_reordered, reordered.frames_skipped), (5, 2, 1));
+        assert_eq!(reordered.finish()?.into_vec()?, data);
+
+        // A dropped frame is an error once too many frames pile up behind it
+        let many: Vec<u8> = (0..(MAX_REORDER_FRAMES as u32 + 3) * 1000).map(|i| i as u8).collect();
+        let frames = test_frames(&many)?;
+        let mut dropped = extractor(Some(1000));
+        let result = dropped.process(&frames[1..]);
+        assert!(matches!(result, Err(F2V2FError::DecodingError(e)) if e.starts_with("Missing frame 0 ")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_end_marker_catches_lost_last_frames() -> Result<()> {
+        let data: Vec<u8> = (0..4500u32).map(|i| (i % 239) as u8).collect();
+        let mut frames = test_frames(&data)?;
+        let mut end = FrameHeader::new(frames.len() as u32, &[]);
+        end.stream = END_STREAM;
+        let end = GeometricArtGenerator::new(256, 256, 7).generate_frame(&end, &[0; 1000])?;
+
+        // Anything after the marker, like a platform's outro frame, is ignored
+        let mut marked = frames.clone();
+        marked.push(end.clone());
+        marked.push(frames[4].clone());
+        let mut complete = extractor(None);
+        complete.process(&marked)?;
+        assert_eq!((complete.end, complete.frames_skipped), (Some(5), 1));
+        assert_eq!(complete.finish()?.into_vec()?, data);
+
+        // Without the last two frames the data would look complete but for the marker
+        frames.truncate(3);
+        frames.push(end);
+        let mut cut = extractor(Some(1000));
+        cut.process(&frames)?;
+        assert!(matches!(cut.finish(), Err(F2V2FError::DecodingError(e)) if e.starts_with("Missing frames 3-4:")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_foreign_and_repeated_frames_ignored() -> Result<()> {
+        let data: Vec<u8> = (0..2500u32).map(|i| (i % 241) as u8).collect();
+        let frames = test_frames(&data)?;
+        let black = RgbaImage::from_pixel(256, 256, image::Rgba([0, 0, 0, 255]));
+
+        // Black lead-in frame and a duplicated final frame
+        let mut padded = vec![black.clone()];
+        padded.extend(frames.iter().cloned());
+        padded.push(frames[2].clone());
+
+        let mut lenient = extractor(None);
+        lenient.process(&padded)?;
+        assert_eq!(lenient.chunk_size, Some(1000));
+        assert_eq!((lenient.frames_seen, lenient.frames_skipped), (5, 2));
+        assert_eq!(lenient.finish()?.into_vec()?, data);
+
+        let mut nothing = extractor(None);
+        nothing.process(&[black])?;
+        assert!(matches!(nothing.finish(), Err(F2V2FError::NotF2V2FVideo(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_foreign_video_rejected_early() -> Result<()> {
+        let black = RgbaImage::from_pixel(256, 256, image::Rgba([0, 0, 0, 255]));
+        let mut foreign = extractor(Some(1000));
+        // Windows of 10: fails at the window that reaches PROBE_FRAMES, not at the end
+        let mut windows = 0;
+        let result = loop {
+            windows += 1;
+            if let Err(e) = foreign.process(&vec![black.clone(); 10]) {
+                break e;
+            }
+        };
+        assert!(matches!(result, F2V2FError::NotF2V2FVideo(_)));
+        assert_eq!(windows, PROBE_FRAMES.div_ceil(10));
+        Ok(())
+    }
+
+    #[test]
+    fn test_extra_stream_frames_separated() -> Result<()> {
+        let data: Vec<u8> = (0..2500u32).map(|i| (i % 233) as u8).collect();
+        let extra = b"parity bytes".repeat(100);
+        let generator = GeometricArtGenerator::new(256, 256, 7);
+        let mut frames = Vec::new();
+        let mut parts = extra.chunks(1000).enumerate();
+        for (i, chunk) in data.chunks(1000).enumerate() {
+            frames.push(generator.generate_frame(&FrameHeader::new(i as u32, chunk), chunk)?);
+            // Stream 3 frames between data frames, chunked and padded like the composer's
+            if let Some((j, part)) = parts.next() {
+                let mut header = FrameHeader::new(j as u32, part);
+                header.stream = 3;
+                let mut padded = part.to_vec();
+                padded.resize(1000, 0);
+                frames.push(generator.generate_frame(&header, &padded)?);
+            }
+        }
+
+        let mut main = extractor(None);
+        main.process(&frames)?;
+        assert_eq!((main.chunk_size, main.frames_skipped), (Some(1000), 0));
+        assert_eq!(main.finish()?.into_vec()?, data);
+
+        let mut side = extractor(None);
+        side.stream = 3;
+        side.process(&frames)?;
+        assert_eq!(side.chunk_size, Some(1000));
+        assert_eq!(side.finish()?.into_vec()?, extra);
+
+        let mut missing = extractor(Some(1000));
+        missing.stream = 9;
+        missing.process(&frames)?;
+        assert!(matches!(missing.finish(), Err(F2V2FError::DecodingError(e)) if e.contains("stream 9")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncated_payload_decompresses_as_far_as_it_goes() -> Result<()> {
+        // Several zstd blocks, so half the stream still holds whole ones
+        let data: Vec<u8> = (0..1_000_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8 & 0x3f).collect();
+        let compressed = zstd::encode_all(&data[..], 3)?;
+        let cut = &compressed[..compressed.len() / 2];
+        let mut recovered = Vec::new();
+        UntilError(zstd_decoder(cut, None)?).read_to_end(&mut recovered)?;
+        assert!(!recovered.is_empty() && recovered.len() < data.len());
+        assert_eq!(recovered, data[..recovered.len()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_embedded_manifest_from_either_end() -> Result<()> {
+        let manifest = Manifest::from_json(
+            r#"{"format_version": 1, "width": 256, "height": 256, "fps": 30, "chunk_size": 1000,
+                "num_frames": 3, "original_size": 2500, "encoded_size": 2500, "compressed": false,
+                "hash_algo": "sha256", "checksum": "abc", "seed": 7}"#,
+        )?;
+        let json = manifest.to_json()?.into_bytes();
+        let mut header = FrameHeader::new(0, &json);
+        header.stream = MANIFEST_STREAM;
+        let mut padded = json.clone();
+        padded.resize(crate::streams::MANIFEST_CHUNK_SIZE, 0);
+        let copy = GeometricArtGenerator::new(256, 256, 7).generate_frame(&header, &padded)?;
+
+        let data: Vec<u8> = (0..2500u32).map(|i| (i % 241) as u8).collect();
+        let mut frames = vec![copy.clone()];
+        frames.extend(test_frames(&data)?);
+        frames.push(copy);
+        assert_eq!(frames.iter().position(is_main_frame), Some(1));
+        assert_eq!(frames.iter().rposition(is_main_frame), Some(3));
+
+        let params = DecodeConfig { width: 256, height: 256, seed: 7, ..DecodeConfig::default() };
+        assert_eq!(embedded_manifest(&params, &frames[..1]), Some(manifest.clone()));
+        // Start of the video cut off: only the trailer copy is left
+        assert_eq!(embedded_manifest(&params, &frames[4..]), Some(manifest));
+        assert_eq!(embedded_manifest(&params, &frames[1..4]), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_transition_frames_skipped() -> Result<()> {
+        let data: Vec<u8> = (0..2500u32).map(|i| (i % 239) as u8).collect();
+        let frames = test_frames(&data)?;
+
+        // A crossfade frame ahead of each data frame after the first
+        let mut with_transitions = vec![frames[0].clone()];
+        for (i, frame) in frames.iter().enumerate().skip(1) {
+            let mut transition = frames[i - 1].clone();
+            let mut header = FrameHeader::new(i as u32, &[]);
+            header.flags |= FLAG_TRANSITION;
+            header.write_to(&mut transition);
+            with_transitions.push(transition);
+            with_transitions.push(frame.clone());
+        }
+
+        let mut faded = extractor(None);
+        faded.process(&with_transitions)?;
+        assert_eq!(faded.chunk_size, Some(1000));
+        assert_eq!((faded.frames_done, faded.frames_skipped), (3, 0));
+        assert_eq!(faded.finish()?.into_vec()?, data);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_payload_streams_decompression() -> Result<()> {
+        let original = b"streaming decompression ".repeat(1000);
+        let compressed = zstd::encode_all(&original[..], 3)?;
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("out.bin");
+
+        let decoder = Decoder::new(DecodeConfig::default())?;
+        let (written, checksum) =
+            decoder.write_payload(&compressed[..], true, None, &[], &output, &HashAlgorithm::Sha256.into())?;
+
+        assert_eq!(written, original.len() as u64);
+        assert_eq!(checksum, HashAlgorithm::Sha256.digest(&original));
+        assert_eq!(std::fs::read(&output)?, original);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_payload_restores_trailing_hole() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("disk.img");
+        let holes = [Hole { offset: 4, len: 1 << 20 }];
+
+        let decoder = Decoder::new(DecodeConfig::default())?;
+        let (written, checksum) =
+            decoder.write_payload(&b"boot"[..], false, None, &holes, &output, &HashAlgorithm::Sha256.into())?;
+
+        let mut expected = b"boot".to_vec();
+        expected.resize(4 + (1 << 20), 0);
+        assert_eq!(written, expected.len() as u64);
+        assert_eq!(checksum, HashAlgorithm::Sha256.digest(&expected));
+        assert_eq!(std::fs::read(&output)?, expected);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_payload_into_named_pipe() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let fifo = dir.path().join("out.fifo");
+        let c_path = std::ffi::CString::new(fifo.to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+        let reader = std::thread::spawn({
+            let fifo = fifo.clone();
+            move || std::fs::read(fifo)
+        });
+
+        // The hole goes down the pipe as zeros
+        let holes = [Hole { offset: 4, len: 100_000 }];
+        let decoder = Decoder::new(DecodeConfig::default())?;
+        let (written, _) =
+            decoder.write_payload(&b"headtail"[..], false, None, &holes, &fifo, &HashAlgorithm::Sha256.into())?;
+
+        let mut expected = b"head".to_vec();
+        expected.resize(100_004, 0);
+        expected.extend_from_slice(b"tail");
+        assert_eq!(written, expected.len() as u64);
+        assert_eq!(reader.join().unwrap()?, expected);
+        assert!(crate::stream::is_stream(&fifo));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_restore_link() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("link");
+
+        let info = Decoder::restore_link(Path::new("target.txt"), &output, HashAlgorithm::Sha256)?;
+        assert_eq!(std::fs::read_link(&output)?, Path::new("target.txt"));
+        assert_eq!(info.extracted_size, 0);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_verify_checksum() -> Result<()> {
         use tempfile::NamedTempFile;
@@ -0,0 +1,81 @@
+//! Zstd dictionary support for many-small-files workflows
+//!
+//! Small, similar files (configs, logs) compress poorly on their own because
+//! each one starts with an empty history. A dictionary trained on a sample of
+//! them is stored once as a shared file; each video's manifest records the
+//! dictionary ID so decode can confirm the right one is supplied.
+
+use crate::error::{F2V2FError, Result};
+use std::path::Path;
+
+/// Magic number at the start of a zstd dictionary
+const DICT_MAGIC: [u8; 4] = [0x37, 0xA4, 0x30, 0xEC];
+
+/// Default maximum dictionary size (112 KB, zstd's recommended default)
+pub const DEFAULT_DICT_SIZE: usize = 112 * 1024;
+
+/// Train a dictionary from sample files
+pub fn train_from_files<P: AsRef<Path>>(samples: &[P], max_size: usize) -> Result<Vec<u8>> {
+    if samples.is_empty() {
+        return Err(F2V2FError::InvalidInput(
+            "Dictionary training needs at least one sample file".to_string(),
+        ));
+    }
+
+    let paths: Vec<&Path> = samples.iter().map(|p| p.as_ref()).collect();
+    zstd::dict::from_files(paths, max_size)
+        .map_err(|e| F2V2FError::EncodingError(format!("Dictionary training failed: {}", e)))
+}
+
+/// Load a dictionary file and check that it is a zstd dictionary
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<u8>> {
+    let path = path.as_ref();
+    let dict = std::fs::read(path)?;
+    if dictionary_id(&dict).is_none() {
+        return Err(F2V2FError::InvalidInput(format!(
+            "{} is not a zstd dictionary",
+            path.display()
+        )));
+    }
+    Ok(dict)
+}
+
+/// ID stored in a zstd dictionary header (None if not a zstd dictionary)
+pub fn dictionary_id(dict: &[u8]) -> Option<u32> {
+    if dict.len() < 8 || dict[0..4] != DICT_MAGIC {
+        return None;
+    }
+    Some(u32::from_le_bytes([dict[4], dict[5], dict[6], dict[7]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_train_and_identify() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut samples = Vec::new();
+        for i in 0..200 {
+            let path = dir.path().join(format!("config{}.json", i));
+            let mut file = std::fs::File::create(&path)?;
+            write!(
+                file,
+                "{{\"service\":\"worker-{}\",\"replicas\":{},\"log_level\":\"info\",\"region\":\"eu-west-{}\"}}",
+                i, i % 7, i % 3
+            )?;
+            samples.push(path);
+        }
+
+        let dict = train_from_files(&samples, 4096)?;
+        assert!(dictionary_id(&dict).is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_non_dictionary() {
+        assert_eq!(dictionary_id(b"not a dictionary"), None);
+        assert!(train_from_files::<&Path>(&[], 1024).is_err());
+    }
+}
@@ -1,108 +1,499 @@
 use crate::error::{F2V2FError, Result};
-use crate::config::EncodeConfig;
-use sha2::{Sha256, Digest};
+use crate::events::{EncodeEvent, EventSink, EVENT_CHANNEL_CAPACITY};
+use crate::checksum::{HashAlgorithm, Hasher};
+use crate::chunk_manifest::{ChunkEntry, ChunkManifest};
+use crate::config::{EncodeConfig, SymlinkPolicy, MAX_CHUNK_SIZE};
+use crate::container::{ContainerHeader, HEADER_LEN};
+use crate::content_type::{ContentType, SNIFF_LEN};
+use crate::entropy::EntropyMeter;
+use crate::file_metadata::FileMetadata;
+use crate::index::FrameIndex;
+use crate::merkle::{MerkleBuilder, MerkleTree};
+use crate::operation::OperationHandle;
+use crate::payload::{Payload, SpillWriter};
+use crate::sparse::{find_holes, Hole};
+use crate::storage::{Location, Source};
+use crate::streams::StreamInfo;
+use crate::warning::Warning;
+use serde::{Deserialize, Serialize};
 use std::fs::File;
-use std::io::{Read, Write};
-use std::path::Path;
-use tracing::info;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
 use zstd::stream::write::Encoder as ZstdEncoder;
 
 /// Encodes a file into a video with artistic frames
 pub struct Encoder {
     config: EncodeConfig,
+    operation: OperationHandle,
 }
 
 /// Information about encoded file
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EncodedFileInfo {
     pub original_file_size: u64,
     pub checksum: String,
+    pub hash_algo: HashAlgorithm,
     pub num_frames: u64,
     pub chunk_size: usize,
     pub art_style: String,
     pub encoded_size: u64,  // Size after compression (if enabled)
     pub compression_ratio: f32,  // Original / Compressed
+    pub dictionary_id: Option<u32>,  // Zstd dictionary used (if any)
+    pub video_size_bytes: u64,  // Size of the written video (0 until composed)
+    pub duration_secs: f64,  // Playback length of the written video
+    pub overhead_ratio: f32,  // Video size / original size
+    pub holes: Vec<Hole>,  // Sparse-file holes left out of the payload
+    pub link_target: Option<PathBuf>,  // Set when a symlink was preserved instead of encoded
+    pub exceeds_max_frames: bool,  // More frames than `max_frames` were needed
+    pub content_type: Option<String>,  // MIME type sniffed from the input
+    #[serde(skip)]
+    pub frame_complexity: Vec<u8>,  // Per-frame pattern complexity (empty unless entropy-styled)
+    #[serde(default)]
+    pub warnings: Vec<Warning>,  // Recoverable anomalies during the encode
+    #[serde(default)]
+    pub video_checksum: Option<String>,  // Checksum of the written video file (hash_algo)
+    #[serde(default)]
+    pub merkle: Option<MerkleTree>,  // Block hash tree of the original data (if enabled)
+    #[serde(default)]
+    pub streams: Vec<StreamInfo>,  // Extra streams multiplexed into the video (filled in on composing)
+    #[serde(default)]
+    pub file_metadata: Option<FileMetadata>,  // Name, mtime and permissions of the input (if preserved)
+    #[serde(default)]
+    pub frame_index: Option<FrameIndex>,  // Video frame of each data frame (filled in on composing)
+}
+
+/// Everything computed over the original bytes while they stream through
+struct InputDigest {
+    hasher: Hasher,
+    entropy: Option<EntropyMeter>,
+    merkle: Option<MerkleBuilder>,
+    /// Bytes seen, holes included
+    len: u64,
+}
+
+impl InputDigest {
+    fn update(&mut self, data: &[u8]) {
+        self.len += data.len() as u64;
+        self.hasher.update(data);
+        if let Some(meter) = self.entropy.as_mut() {
+            meter.update(data);
+        }
+        if let Some(tree) = self.merkle.as_mut() {
+            tree.update(data);
+        }
+    }
+
+    fn update_zeros(&mut self, len: u64) {
+        self.len += len;
+        self.hasher.update_zeros(len);
+        if let Some(meter) = self.entropy.as_mut() {
+            meter.update_zeros(len);
+        }
+        if let Some(tree) = self.merkle.as_mut() {
+            tree.update_zeros(len);
+        }
+    }
+}
+
+/// Chunk size and frame count chosen for a payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkPlan {
+    pub chunk_size: usize,
+    pub num_frames: u64,
+    /// The payload didn't fit in `max_frames` even with full frames
+    pub exceeds_max_frames: bool,
+}
+
+impl EncodedFileInfo {
+    /// Record the size and duration of the composed video
+    pub fn set_video_stats(&mut self, video_size_bytes: u64, duration_secs: f64) {
+        self.video_size_bytes = video_size_bytes;
+        self.duration_secs = duration_secs;
+        self.overhead_ratio = if self.original_file_size > 0 {
+            video_size_bytes as f32 / self.original_file_size as f32
+        } else {
+            0.0
+        };
+    }
 }
 
 impl Encoder {
     pub fn new(config: EncodeConfig) -> Result<Self> {
         config.validate()?;
-        Ok(Self { config })
+        Ok(Self { config, operation: OperationHandle::new() })
+    }
+
+    /// Let `handle` pause and resume this encoder's operations
+    pub fn with_operation(mut self, handle: OperationHandle) -> Self {
+        self.operation = handle;
+        self
     }
 
     /// Encode a file (BLOCKING, NO ASYNC) - Safe for FFI calls
     /// Returns (metadata, compressed_data)
     pub fn encode_blocking<P: AsRef<Path>>(&self, input: P) -> Result<(EncodedFileInfo, Vec<u8>)> {
+        let (info, payload) = self.encode_payload_blocking(input)?;
+        Ok((info, payload.into_vec()?))
+    }
+
+    /// Encode a file into a `Payload` (BLOCKING)
+    ///
+    /// The input is streamed through the hasher and compressor; the result
+    /// stays in memory unless it exceeds `spill_threshold`, in which case it
+    /// is written to a temp file. Pass it to
+    /// `VideoComposer::compose_from_payload_blocking`.
+    ///
+    /// A pipe or other stream (see `stream::is_stream`) is read exactly once.
+    pub fn encode_payload_blocking<P: AsRef<Path>>(&self, input: P) -> Result<(EncodedFileInfo, Payload)> {
         let input_path = input.as_ref();
+
+        if crate::stream::is_stream(input_path) {
+            return self.encode_stream(input_path);
+        }
+
+        if std::fs::symlink_metadata(input_path)?.file_type().is_symlink() {
+            match self.config.symlinks {
+                SymlinkPolicy::Follow => {}
+                SymlinkPolicy::Preserve => return self.encode_link(input_path),
+                SymlinkPolicy::Skip => {
+                    return Err(F2V2FError::InvalidInput(format!(
+                        "{} is a symlink (skipped by symlink policy)",
+                        input_path.display()
+                    )));
+                }
+            }
+        }
+
         let file_size = std::fs::metadata(input_path)?.len();
-        
-        if file_size == 0 {
-            return Err(F2V2FError::InvalidInput("Cannot encode empty files".to_string()));
+        let content_type = crate::content_type::sniff_file(input_path)?;
+
+        debug!("📁 Encoding file: {} ({} bytes)", input_path.display(), file_size);
+
+        let holes = find_holes(&File::open(input_path)?, file_size)?;
+        if !holes.is_empty() {
+            let hole_bytes: u64 = holes.iter().map(|h| h.len).sum();
+            debug!("🕳️  Sparse file: skipping {} holes ({} bytes)", holes.len(), hole_bytes);
+        }
+
+        let mut reader = BufReader::with_capacity(
+            self.config.buffer_size,
+            self.operation.reader(File::open(input_path)?),
+        );
+        let (mut info, payload) = self.encode_from(Some(file_size), content_type, holes.clone(), |writer, digest| {
+            Self::copy_sparse_hashing(&mut reader, &holes, writer, digest)
+        })?;
+        if self.config.preserve_metadata {
+            info.file_metadata = Some(FileMetadata::read(input_path)?);
+        }
+        Ok((info, payload))
+    }
+
+    /// Encode the input of `source` into a `Payload` (BLOCKING)
+    ///
+    /// Sources with a local path are encoded like that path; others
+    /// (HTTP, S3, memory) are read once, as a stream.
+    pub fn encode_source_blocking(&self, source: &dyn Source) -> Result<(EncodedFileInfo, Payload)> {
+        match source.local_path() {
+            Some(path) => self.encode_payload_blocking(path),
+            None => {
+                debug!("📥 Encoding stream: {}", source.name());
+                self.encode_reader(source.open()?)
+            }
         }
+    }
 
-        info!("📁 Encoding file: {} ({} bytes)", input_path.display(), file_size);
+    /// Encode a pipe or other unseekable input, reading it once
+    fn encode_stream(&self, input_path: &Path) -> Result<(EncodedFileInfo, Payload)> {
+        debug!("📥 Encoding stream: {}", input_path.display());
+        self.encode_reader(File::open(input_path)?)
+    }
+
+    /// Encode everything `input` yields
+    fn encode_reader<R: Read>(&self, input: R) -> Result<(EncodedFileInfo, Payload)> {
+        let mut reader = BufReader::with_capacity(self.config.buffer_size, self.operation.reader(input));
+        let mut head = Vec::with_capacity(SNIFF_LEN);
+        reader.by_ref().take(SNIFF_LEN as u64).read_to_end(&mut head)?;
+        let content_type = crate::content_type::sniff(&head);
+        let mut source = std::io::Cursor::new(head).chain(reader);
+        self.encode_from(None, content_type, Vec::new(), |writer, digest| {
+            Self::copy_hashing(&mut source, writer, digest)
+        })
+    }
 
-        let mut file = File::open(input_path)?;
-        let mut file_data = Vec::new();
-        file.read_to_end(&mut file_data)?;
+    /// Compress the input `copy` feeds through the digest and describe it
+    ///
+    /// `size` is the input size when known up front (entropy styling needs it).
+    fn encode_from(
+        &self,
+        size: Option<u64>,
+        content_type: Option<ContentType>,
+        holes: Vec<Hole>,
+        copy: impl FnOnce(&mut dyn Write, &mut InputDigest) -> Result<()>,
+    ) -> Result<(EncodedFileInfo, Payload)> {
+        if self.config.entropy_style && size.is_none() {
+            warn!("Entropy styling needs the input size up front; not applied to a stream");
+        }
+        let mut digest = InputDigest {
+            hasher: Hasher::new(self.config.hash_algo),
+            entropy: size.filter(|_| self.config.entropy_style).map(EntropyMeter::new),
+            merkle: self.config.merkle_block_size.map(MerkleBuilder::new),
+            len: 0,
+        };
+        let mut sink = SpillWriter::new(self.config.spill_threshold).with_temp_dir(self.config.temp_dir.clone());
 
-        // Calculate checksum of original data
-        let mut hasher = Sha256::new();
-        hasher.update(&file_data);
-        let checksum = format!("{:x}", hasher.finalize());
+        let dictionary = match &self.config.dictionary {
+            Some(path) => Some(crate::dictionary::load(path)?),
+            None => None,
+        };
+        let dictionary_id = dictionary.as_deref().and_then(crate::dictionary::dictionary_id);
+        let header = ContainerHeader::new(self.config.use_compression, self.config.use_compression && dictionary.is_some());
+        sink.write_all(&header.to_bytes())?;
 
         // Compress if enabled
-        let encoded_data = if self.config.use_compression {
-            info!("🗜️  Compressing with Zstd (compression_level={})", self.config.compression_level);
-            let mut encoder = ZstdEncoder::new(Vec::new(), self.config.compression_level)?;
-            encoder.multithread(num_cpus::get() as u32)?;
-            encoder.write_all(&file_data)?;
-            let compressed = encoder.finish()?;
-            info!(
+        let payload = if self.config.use_compression {
+            debug!("🗜️  Compressing with Zstd (compression_level={})", self.config.compression_level);
+            let mut encoder = match &dictionary {
+                Some(dict) => ZstdEncoder::with_dictionary(sink, self.config.compression_level, dict)?,
+                None => ZstdEncoder::new(sink, self.config.compression_level)?,
+            };
+            encoder.multithread(self.config.compression_threads.unwrap_or(num_cpus::get() as u32))?;
+            copy(&mut encoder, &mut digest)?;
+            let compressed = encoder.finish()?.finish()?;
+            debug!(
                 "✅ Compression: {} bytes → {} bytes ({:.2}x ratio)", 
-                file_size, 
-                compressed.len(),
-                (file_size as f32 / compressed.len() as f32)
+                digest.len, 
+                compressed.len() - HEADER_LEN as u64,
+                (digest.len as f32 / (compressed.len() - HEADER_LEN as u64) as f32)
             );
             compressed
         } else {
-            info!("⏭️  Compression disabled, using raw data");
-            file_data.clone()
+            debug!("⏭️  Compression disabled, using raw data");
+            copy(&mut sink, &mut digest)?;
+            sink.finish()?
         };
 
-        let encoded_size = encoded_data.len() as u64;
-        
-        // Calculate optimal chunk size to limit frame count
-        let max_frames = 1000;
-        let optimal_chunk_size = std::cmp::max(
-            self.config.chunk_size as u64,
-            ((encoded_size + (max_frames - 1)) / max_frames) as u64,
-        ) as usize;
+        let file_size = digest.len;
+        if payload.is_spilled() {
+            debug!("💽 Payload spilled to a temp file ({} bytes)", payload.len());
+        }
+
+        // Checksum of the original data, computed while streaming
+        let checksum = digest.hasher.finalize();
+        let encoded_size = payload.len();
         
-        if optimal_chunk_size > self.config.chunk_size {
-            info!("📊 Automatically adjusted chunk size: {} → {} bytes ({} frames)",
-                self.config.chunk_size,
-                optimal_chunk_size,
-                (encoded_size + optimal_chunk_size as u64 - 1) / optimal_chunk_size as u64
-            );
+        let plan = self.plan_chunks(encoded_size);
+        if plan.chunk_size > self.config.chunk_size {
+            debug!("📊 Automatically adjusted chunk size: {} → {} bytes ({} frames)",
+                self.config.chunk_size, plan.chunk_size, plan.num_frames);
+        }
+        let mut warnings = Vec::new();
+        if plan.exceeds_max_frames {
+            warn!("⚠️  {} frames needed, more than the {} frame target; frames are at full capacity",
+                plan.num_frames, self.config.max_frames.unwrap_or_default());
+            warnings.push(Warning::ExceedsMaxFrames {
+                frames: plan.num_frames,
+                target: self.config.max_frames.unwrap_or_default(),
+            });
         }
 
-        let compression_ratio = file_size as f32 / encoded_size as f32;
-        let num_frames = (encoded_size + optimal_chunk_size as u64 - 1) / optimal_chunk_size as u64;
+        // The container header isn't what compression made of the file
+        let body_size = encoded_size - HEADER_LEN as u64;
+        let compression_ratio = if body_size > 0 {
+            file_size as f32 / body_size as f32
+        } else {
+            1.0
+        };
 
         let info = EncodedFileInfo {
             original_file_size: file_size,
             checksum,
-            num_frames,
-            chunk_size: optimal_chunk_size,
+            hash_algo: self.config.hash_algo,
+            num_frames: plan.num_frames,
+            chunk_size: plan.chunk_size,
             art_style: self.config.art_style.clone(),
             encoded_size,
             compression_ratio,
+            dictionary_id,
+            video_size_bytes: 0,
+            duration_secs: 0.0,
+            overhead_ratio: 0.0,
+            holes,
+            link_target: None,
+            exceeds_max_frames: plan.exceeds_max_frames,
+            content_type: content_type.map(|ct| ct.mime.to_string()),
+            frame_complexity: digest.entropy.map(|meter| meter.frame_levels(plan.num_frames)).unwrap_or_default(),
+            warnings,
+            video_checksum: None,
+            merkle: digest.merkle.map(MerkleBuilder::finish),
+            streams: Vec::new(),
+            file_metadata: None,
+            frame_index: None,
         };
 
-        info!("📊 Encoding complete: {} frames needed (ratio: {:.2}x)", num_frames, compression_ratio);
+        debug!("📊 Encoding complete: {} frames needed (ratio: {:.2}x)", plan.num_frames, compression_ratio);
+        debug!("🎬 {} frames at {} fps play for {:.1}s", plan.num_frames, self.config.fps_for(plan.num_frames),
+            self.config.playback_duration(plan.num_frames));
+
+        Ok((info, payload))
+    }
+
+    /// Copy `reader` into `writer`, feeding the bytes read to `digest`
+    fn copy_hashing<R: Read, W: Write + ?Sized>(reader: &mut R, writer: &mut W, digest: &mut InputDigest) -> Result<()> {
+        let mut buffer = vec![0u8; 64 * 1024];
+        loop {
+            let n = reader.read(&mut buffer)?;
+            if n == 0 {
+                return Ok(());
+            }
+            digest.update(&buffer[..n]);
+            writer.write_all(&buffer[..n])?;
+        }
+    }
+
+    /// Run the full encode pipeline (compress, compose, write manifest) on a
+    /// blocking thread, streaming progress events
+    ///
+    /// The last event is always `EncodeEvent::Finished`. Dropping the
+    /// receiver doesn't cancel the encode. Must be called from within a
+    /// Tokio runtime.
+    pub fn encode_with_events<P: Into<PathBuf>, Q: Into<PathBuf>>(
+        &self,
+        input: P,
+        output: Q,
+    ) -> mpsc::Receiver<EncodeEvent> {
+        let (tx, rx) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
+        let config = self.config.clone();
+        let operation = self.operation.clone();
+        let input = input.into();
+        let output = output.into();
+
+        tokio::task::spawn_blocking(move || {
+            let sink_tx = tx.clone();
+            let sink: EventSink = Arc::new(move |event| {
+                // A dropped receiver just means nobody is listening
+                let _ = sink_tx.blocking_send(event);
+            });
+            let result = crate::pipeline::run_encode(&Location::from(input), &output, &config, &operation, Some(sink), Arc::new(crate::backend::FfmpegBackend));
+            let _ = tx.blocking_send(EncodeEvent::Finished(Box::new(result)));
+        });
+
+        rx
+    }
+
+    /// List the SHA-256 of every chunk of `payload` and the video frame it
+    /// went into (see `chunk_manifest`)
+    ///
+    /// `info` is the one returned with `payload`; frames are only known once
+    /// it has been composed (`frame_index`).
+    pub fn export_manifest(&self, payload: &Payload, info: &EncodedFileInfo) -> Result<ChunkManifest> {
+        let chunk_size = info.chunk_size.max(1);
+        let mut reader = payload.reader()?;
+        let mut buffer = vec![0u8; chunk_size];
+        let mut chunks = Vec::new();
+        let mut offset = 0u64;
+        loop {
+            let mut filled = 0;
+            while filled < chunk_size {
+                match reader.read(&mut buffer[filled..])? {
+                    0 => break,
+                    n => filled += n,
+                }
+            }
+            if filled == 0 {
+                break;
+            }
+            let index = chunks.len() as u64;
+            chunks.push(ChunkEntry {
+                index,
+                offset,
+                size: filled as u64,
+                sha256: HashAlgorithm::Sha256.digest(&buffer[..filled]),
+                frame: info.frame_index.as_ref().and_then(|frames| frames.position(index)),
+            });
+            offset += filled as u64;
+        }
+        debug!("🧾 Exported hashes of {} chunks", chunks.len());
+        Ok(ChunkManifest { chunk_size: chunk_size as u64, payload_size: offset, chunks })
+    }
+
+    /// Decide chunk size and frame count for a payload of `encoded_size` bytes
+    ///
+    /// The configured chunk size is raised just enough to stay within
+    /// `max_frames`, but never past one frame's capacity or `MAX_CHUNK_SIZE`.
+    /// When that isn't enough the video simply gets more frames (composition
+    /// streams one frame at a time), and `exceeds_max_frames` is set.
+    pub fn plan_chunks(&self, encoded_size: u64) -> ChunkPlan {
+        let ceiling = self.config.frame_capacity().min(MAX_CHUNK_SIZE) as u64;
+        let chunk_size = match self.config.max_frames {
+            Some(max_frames) if max_frames > 0 => encoded_size
+                .div_ceil(max_frames)
+                .clamp(self.config.chunk_size as u64, ceiling.max(self.config.chunk_size as u64)),
+            _ => self.config.chunk_size as u64,
+        };
+
+        // An empty payload still gets one marker frame so the video is valid
+        let num_frames = encoded_size.div_ceil(chunk_size).max(1);
+        ChunkPlan {
+            chunk_size: chunk_size as usize,
+            num_frames,
+            exceeds_max_frames: self.config.max_frames.is_some_and(|max| max > 0 && num_frames > max),
+        }
+    }
+
+    /// Record a symlink's target instead of the file it points to
+    ///
+    /// The payload is empty (a single marker frame); the target is carried
+    /// in the manifest and the decoder recreates the link.
+    fn encode_link(&self, input_path: &Path) -> Result<(EncodedFileInfo, Payload)> {
+        let target = std::fs::read_link(input_path)?;
+        debug!("🔗 Preserving symlink {} → {}", input_path.display(), target.display());
+
+        let info = EncodedFileInfo {
+            original_file_size: 0,
+            checksum: self.config.hash_algo.digest(b""),
+            hash_algo: self.config.hash_algo,
+            num_frames: 1,
+            chunk_size: self.config.chunk_size,
+            art_style: self.config.art_style.clone(),
+            encoded_size: 0,
+            compression_ratio: 1.0,
+            dictionary_id: None,
+            video_size_bytes: 0,
+            duration_secs: 0.0,
+            overhead_ratio: 0.0,
+            holes: Vec::new(),
+            link_target: Some(target),
+            exceeds_max_frames: false,
+            content_type: None,
+            frame_complexity: Vec::new(),
+            warnings: Vec::new(),
+            video_checksum: None,
+            merkle: None,
+            streams: Vec::new(),
+            file_metadata: None,
+            frame_index: None,
+        };
+        Ok((info, Payload::Memory(Vec::new())))
+    }
 
-        Ok((info, encoded_data))
+    /// Copy the data between `holes` into `writer`, hashing holes as zeros
+    fn copy_sparse_hashing<R: Read + Seek, W: Write + ?Sized>(
+        reader: &mut R,
+        holes: &[Hole],
+        writer: &mut W,
+        digest: &mut InputDigest,
+    ) -> Result<()> {
+        let mut pos = 0;
+        for hole in holes {
+            Self::copy_hashing(&mut reader.by_ref().take(hole.offset - pos), writer, digest)?;
+            digest.update_zeros(hole.len);
+            reader.seek(SeekFrom::Start(hole.end()))?;
+            pos = hole.end();
+        }
+        Self::copy_hashing(reader, writer, digest)
     }
 
     /// Encode a file: read, compress (optional), and return data
@@ -117,6 +508,11 @@ impl Encoder {
         self.encode_blocking(input)
     }
 
+    /// Async variant of `encode_payload_blocking`
+    pub async fn encode_payload<P: AsRef<Path>>(&self, input: P) -> Result<(EncodedFileInfo, Payload)> {
+        self.encode_payload_blocking(input)
+    }
+
     /// Estimate the video file size based on input, accounting for compression
     /// 
     /// **Calculation:**
@@ -145,7 +541,7 @@ impl Encoder {
         let raw_size = num_frames * bytes_per_frame;
         let estimated_video_size = raw_size / 2;
         
-        info!(
+        debug!(
             "📈 Size estimate: {} bytes → ~{} bytes (compressed) → {} frames → ~{} MB video",
             file_size,
             estimated_compressed,
@@ -203,6 +599,30 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_encode_from_named_pipe() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let fifo = dir.path().join("in.fifo");
+        let c_path = std::ffi::CString::new(fifo.to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+        let data = b"%PDF-1.7 piped through f2v2f ".repeat(100);
+        let writer = std::thread::spawn({
+            let (fifo, data) = (fifo.clone(), data.clone());
+            move || std::fs::write(fifo, data)
+        });
+
+        let encoder = Encoder::new(EncodeConfig { cache_dir: None, ..EncodeConfig::default() })?;
+        let (info, payload) = encoder.encode_payload_blocking(&fifo)?;
+        writer.join().unwrap()?;
+
+        assert_eq!(info.original_file_size, data.len() as u64);
+        assert_eq!(info.checksum, HashAlgorithm::Sha256.digest(&data));
+        assert_eq!(info.content_type.as_deref(), Some("application/pdf"));
+        assert_eq!(zstd::decode_all(&payload.into_vec()?[HEADER_LEN..])?, data);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_encode_without_compression() -> Result<()> {
         let config = EncodeConfig {
@@ -218,9 +638,174 @@ mod tests {
         let (info, data) = encoder.encode(file.path()).await?;
         
         assert_eq!(info.original_file_size, 9);
-        assert_eq!(data.len() as u64, 9);  // No compression
+        assert_eq!(data.len(), 9 + HEADER_LEN);  // No compression
         assert_eq!(info.compression_ratio, 1.0);
+        assert_eq!(info.content_type.as_deref(), Some("text/plain"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_encode_empty_file() -> Result<()> {
+        let config = EncodeConfig {
+            use_compression: false,
+            ..EncodeConfig::default()
+        };
+        let encoder = Encoder::new(config)?;
+        let file = NamedTempFile::new()?;
+
+        let (info, data) = encoder.encode(file.path()).await?;
+
+        assert_eq!(info.original_file_size, 0);
+        assert_eq!(data, ContainerHeader::new(false, false).to_bytes());
+        assert_eq!(info.num_frames, 1);
+        assert_eq!(info.checksum, HashAlgorithm::Sha256.digest(b""));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_sparse_hashing_skips_holes() -> Result<()> {
+        let mut data = b"head".to_vec();
+        data.extend_from_slice(&[0u8; 100]);
+        data.extend_from_slice(b"tail");
+        let holes = [Hole { offset: 4, len: 100 }];
+
+        let mut out = Vec::new();
+        let mut digest = InputDigest {
+            hasher: Hasher::new(HashAlgorithm::Sha256),
+            entropy: None,
+            merkle: Some(MerkleBuilder::new(crate::merkle::MIN_BLOCK_SIZE)),
+            len: 0,
+        };
+        Encoder::copy_sparse_hashing(&mut std::io::Cursor::new(&data), &holes, &mut out, &mut digest)?;
+
+        assert_eq!(out, b"headtail");
+        assert_eq!(digest.len, data.len() as u64);
+        assert_eq!(digest.hasher.finalize(), HashAlgorithm::Sha256.digest(&data));
+        let mut whole = MerkleBuilder::new(crate::merkle::MIN_BLOCK_SIZE);
+        whole.update(&data);
+        assert_eq!(digest.merkle.map(MerkleBuilder::finish), Some(whole.finish()));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_symlink_policies() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let target = dir.path().join("target.txt");
+        std::fs::write(&target, b"linked data")?;
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&target, &link)?;
+
+        let encode = |symlinks| {
+            Encoder::new(EncodeConfig { symlinks, ..EncodeConfig::default() })?.encode_payload_blocking(&link)
+        };
+
+        let (info, _) = encode(SymlinkPolicy::Follow)?;
+        assert_eq!(info.original_file_size, 11);
+        assert_eq!(info.link_target, None);
+
+        let (info, payload) = encode(SymlinkPolicy::Preserve)?;
+        assert_eq!(info.link_target.as_deref(), Some(target.as_path()));
+        assert!(payload.is_empty());
+
+        assert!(encode(SymlinkPolicy::Skip).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_chunks() -> Result<()> {
+        let config = EncodeConfig {
+            width: 256,
+            height: 256,
+            chunk_size: 4096,
+            max_frames: Some(10),
+            ..EncodeConfig::default()
+        };
+        let capacity = config.frame_capacity();
+        let encoder = Encoder::new(config.clone())?;
+
+        // Small payloads keep the configured chunk size
+        let plan = encoder.plan_chunks(10_000);
+        assert_eq!((plan.chunk_size, plan.num_frames), (4096, 3));
+
+        // Chunk size grows to stay within max_frames
+        let plan = encoder.plan_chunks(50_000);
+        assert_eq!((plan.chunk_size, plan.num_frames), (5_000, 10));
+        assert!(!plan.exceeds_max_frames);
+
+        // ...but never past frame capacity; more frames are used instead
+        let plan = encoder.plan_chunks(capacity as u64 * 20);
+        assert_eq!((plan.chunk_size, plan.num_frames), (capacity, 20));
+        assert!(plan.exceeds_max_frames);
+
+        // Without a frame target the chunk size is left alone
+        let unlimited = Encoder::new(EncodeConfig { max_frames: None, ..config })?;
+        assert_eq!(unlimited.plan_chunks(100_000).num_frames, 25);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_encode_with_events_ends_with_finished() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let encoder = Encoder::new(EncodeConfig::default())?;
+
+        // Missing input: the pipeline fails during compression
+        let mut events = encoder.encode_with_events(dir.path().join("missing.bin"), dir.path().join("out.mp4"));
+        let mut received = Vec::new();
+        while let Some(event) = events.recv().await {
+            received.push(event);
+        }
+
+        assert!(matches!(received.first(), Some(EncodeEvent::StageStarted(crate::events::EncodeStage::Compressing))));
+        assert!(matches!(received.last(), Some(EncodeEvent::Finished(result)) if result.is_err()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_set_video_stats() -> Result<()> {
+        let encoder = Encoder::new(EncodeConfig::default())?;
+
+        let mut file = NamedTempFile::new()?;
+        file.write_all(&[7u8; 1000])?;
+        file.flush()?;
+
+        let (mut info, _) = encoder.encode(file.path()).await?;
+        assert_eq!(info.video_size_bytes, 0);
+
+        info.set_video_stats(250_000, 2.5);
+        assert_eq!(info.video_size_bytes, 250_000);
+        assert_eq!(info.duration_secs, 2.5);
+        assert_eq!(info.overhead_ratio, 250.0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_encode_with_dictionary() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut samples = Vec::new();
+        for i in 0..200 {
+            let path = dir.path().join(format!("sample{}.log", i));
+            std::fs::write(&path, format!("level=info service=api request_id={} status=200 latency_ms={}", i, i % 50))?;
+            samples.push(path);
+        }
+        let dict = crate::dictionary::train_from_files(&samples, 4096)?;
+        let dict_path = dir.path().join("logs.dict");
+        std::fs::write(&dict_path, &dict)?;
+
+        let encoder = Encoder::new(EncodeConfig {
+            dictionary: Some(dict_path),
+            ..EncodeConfig::default()
+        })?;
+        let (info, data) = encoder.encode(&samples[7]).await?;
 
+        assert_eq!(info.dictionary_id, crate::dictionary::dictionary_id(&dict));
+        let mut decoder = zstd::stream::read::Decoder::with_dictionary(std::io::Cursor::new(&data[HEADER_LEN..]), &dict)?;
+        let mut restored = Vec::new();
+        decoder.read_to_end(&mut restored)?;
+        assert_eq!(restored, std::fs::read(&samples[7])?);
         Ok(())
     }
 }
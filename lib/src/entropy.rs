@@ -0,0 +1,138 @@
+//! Byte entropy of the original input, for entropy-driven styling
+//!
+//! The encoder measures Shannon entropy over fixed windows of the input while
+//! hashing it, then maps the windows onto frames. Each frame's level sets the
+//! pattern complexity (see `GeometricArtGenerator::with_complexity`), so
+//! compressible stretches of a file look calm and random ones look busy.
+//! Frames cover the payload, which only follows the input proportionally when
+//! it is compressed; that's close enough for looks.
+
+/// Windows measured per input at most (windows grow for larger inputs)
+const MAX_WINDOWS: u64 = 4096;
+
+/// Smallest window, so tiny inputs still get a meaningful histogram
+const MIN_WINDOW: u64 = 4096;
+
+/// Highest complexity level; 0 means "not entropy-styled"
+pub const MAX_LEVEL: u8 = 15;
+
+/// Shannon entropy in bits per byte (0-8) of a byte histogram
+pub fn shannon_entropy(counts: &[u64; 256]) -> f32 {
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return 0.0;
+    }
+    counts
+        .iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum::<f64>() as f32
+}
+
+/// Complexity level (1 to `MAX_LEVEL`) for an entropy in bits per byte
+pub fn level_for(bits: f32) -> u8 {
+    1 + ((bits / 8.0).clamp(0.0, 1.0) * (MAX_LEVEL - 1) as f32).round() as u8
+}
+
+/// Streaming per-window entropy measurement
+pub struct EntropyMeter {
+    window: u64,
+    counts: [u64; 256],
+    filled: u64,
+    windows: Vec<f32>,
+}
+
+impl EntropyMeter {
+    /// Meter for an input of `input_size` bytes
+    pub fn new(input_size: u64) -> Self {
+        Self {
+            window: input_size.div_ceil(MAX_WINDOWS).max(MIN_WINDOW),
+            counts: [0; 256],
+            filled: 0,
+            windows: Vec::new(),
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let take = ((self.window - self.filled) as usize).min(data.len());
+            for &b in &data[..take] {
+                self.counts[b as usize] += 1;
+            }
+            self.advance(take as u64);
+            data = &data[take..];
+        }
+    }
+
+    /// Account for `len` zero bytes (sparse-file holes) without a buffer
+    pub fn update_zeros(&mut self, mut len: u64) {
+        while len > 0 {
+            let take = (self.window - self.filled).min(len);
+            self.counts[0] += take;
+            self.advance(take);
+            len -= take;
+        }
+    }
+
+    fn advance(&mut self, n: u64) {
+        self.filled += n;
+        if self.filled == self.window {
+            self.windows.push(shannon_entropy(&self.counts));
+            self.counts = [0; 256];
+            self.filled = 0;
+        }
+    }
+
+    /// Complexity level for each of `num_frames` frames spread evenly over
+    /// the input (each frame gets the mean entropy of the windows it covers)
+    pub fn frame_levels(mut self, num_frames: u64) -> Vec<u8> {
+        if self.filled > 0 {
+            self.windows.push(shannon_entropy(&self.counts));
+        }
+        let windows = self.windows.len() as u64;
+        (0..num_frames)
+            .map(|i| {
+                if windows == 0 {
+                    return 1;
+                }
+                let start = i * windows / num_frames;
+                let end = ((i + 1) * windows / num_frames).max(start + 1).min(windows);
+                let covered = &self.windows[start as usize..end as usize];
+                level_for(covered.iter().sum::<f32>() / covered.len() as f32)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shannon_entropy() {
+        let mut counts = [0u64; 256];
+        assert_eq!(shannon_entropy(&counts), 0.0);
+        counts[7] = 100;
+        assert_eq!(shannon_entropy(&counts), 0.0);
+        counts = [1; 256];
+        assert!((shannon_entropy(&counts) - 8.0).abs() < 1e-4);
+
+        assert_eq!(level_for(0.0), 1);
+        assert_eq!(level_for(8.0), MAX_LEVEL);
+    }
+
+    #[test]
+    fn test_frame_levels_follow_input() {
+        // Calm first half (zeros), busy second half (every byte value)
+        let mut meter = EntropyMeter::new(2 * MIN_WINDOW);
+        meter.update_zeros(MIN_WINDOW);
+        let busy: Vec<u8> = (0..MIN_WINDOW).map(|i| (i * 97 % 256) as u8).collect();
+        meter.update(&busy);
+
+        assert_eq!(meter.frame_levels(4), vec![1, 1, MAX_LEVEL, MAX_LEVEL]);
+        assert_eq!(EntropyMeter::new(0).frame_levels(1), vec![1]);
+    }
+}
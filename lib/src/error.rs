@@ -37,9 +37,23 @@ pub enum F2V2FError {
     #[error("Timeout: {0}")]
     Timeout(String),
 
+    #[error("Output already exists: {0}")]
+    OutputExists(String),
+
+    #[error("Not an f2v2f-encoded video: {0}")]
+    NotF2V2FVideo(String),
+
+    /// Art style name and format version of a video this build can't decode
+    #[error("Video uses the art style {0:?} (format version {1}), which this build doesn't include; available styles: {styles}",
+        styles = crate::image_generator::ART_STYLES.join(", "))]
+    UnsupportedStyle(String, u32),
+
     #[error("Validation failed: {0}")]
     ValidationFailed(String),
 
+    #[error("Job queue full: {0}")]
+    QueueFull(String),
+
     #[error("Unknown error: {0}")]
     Unknown(String),
 }
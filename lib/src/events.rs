@@ -0,0 +1,40 @@
+//! Progress events for async integrations
+//!
+//! `Encoder::encode_with_events` runs the encode pipeline on a blocking
+//! thread and reports progress over a `tokio::sync::mpsc` channel, so async
+//! applications can fold it into their own event loops.
+
+use crate::encoder::EncodedFileInfo;
+use crate::error::Result;
+use std::sync::Arc;
+
+/// Capacity of the event channel; frame events wait for the receiver when full
+pub const EVENT_CHANNEL_CAPACITY: usize = 64;
+
+/// Called with (frames written, total frames) after each frame
+pub type FrameProgress = Arc<dyn Fn(u64, u64) + Send + Sync>;
+
+/// Pipeline stage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodeStage {
+    /// Reading, hashing and compressing the input
+    Compressing,
+    /// Rendering frames and piping them to ffmpeg
+    Composing,
+    /// Writing the manifest sidecar
+    WritingManifest,
+}
+
+/// Progress event emitted during an encode
+#[derive(Debug)]
+pub enum EncodeEvent {
+    StageStarted(EncodeStage),
+    FrameWritten { frame: u64, total: u64 },
+    /// Something worth surfacing that didn't stop the encode
+    Warning(String),
+    /// Always the last event; boxed since `EncodedFileInfo` dwarfs the rest
+    Finished(Box<Result<EncodedFileInfo>>),
+}
+
+/// Receives pipeline events
+pub type EventSink = Arc<dyn Fn(EncodeEvent) + Send + Sync>;
@@ -0,0 +1,227 @@
+//! How ffmpeg converts decoded frames before they are read
+//!
+//! Frames come out of ffmpeg as raw pixels in `pix_fmt`, converted from the
+//! video's YUV with swscale using `scaler` and `color_range`. The defaults
+//! (8-bit RGBA, lanczos, full range) match what the encoder writes; change
+//! them to mirror a video produced with other settings.
+
+use crate::error::{F2V2FError, Result};
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Raw pixel format requested from ffmpeg
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PixelFormat {
+    #[default]
+    Rgba,
+    Rgb24,
+    Gray,
+    /// 16 bits per channel, for high bit depth sources
+    Rgba64,
+    Gray16,
+}
+
+impl PixelFormat {
+    /// Name ffmpeg's `-pix_fmt` takes
+    pub fn ffmpeg_name(&self) -> &'static str {
+        match self {
+            PixelFormat::Rgba => "rgba",
+            PixelFormat::Rgb24 => "rgb24",
+            PixelFormat::Gray => "gray",
+            PixelFormat::Rgba64 => "rgba64le",
+            PixelFormat::Gray16 => "gray16le",
+        }
+    }
+
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            PixelFormat::Rgba => 4,
+            PixelFormat::Rgb24 => 3,
+            PixelFormat::Gray => 1,
+            PixelFormat::Rgba64 => 8,
+            PixelFormat::Gray16 => 2,
+        }
+    }
+
+    /// Convert one raw frame to 8-bit RGBA (16-bit samples are rounded)
+    pub fn to_rgba(&self, width: u32, height: u32, raw: Vec<u8>) -> Option<RgbaImage> {
+        if raw.len() != width as usize * height as usize * self.bytes_per_pixel() {
+            return None;
+        }
+        let wide = |b: &[u8]| ((u16::from_le_bytes([b[0], b[1]]) as u32 * 255 + 32767) / 65535) as u8;
+        match self {
+            PixelFormat::Rgba => RgbaImage::from_raw(width, height, raw),
+            PixelFormat::Rgb24 => Some(pixels(width, height, raw.chunks_exact(3), |p| Rgba([p[0], p[1], p[2], 255]))),
+            PixelFormat::Gray => Some(pixels(width, height, raw.chunks_exact(1), |p| Rgba([p[0], p[0], p[0], 255]))),
+            PixelFormat::Rgba64 => Some(pixels(width, height, raw.chunks_exact(8), |p| {
+                Rgba([wide(&p[0..2]), wide(&p[2..4]), wide(&p[4..6]), wide(&p[6..8])])
+            })),
+            PixelFormat::Gray16 => Some(pixels(width, height, raw.chunks_exact(2), |p| {
+                let v = wide(p);
+                Rgba([v, v, v, 255])
+            })),
+        }
+    }
+}
+
+fn pixels<'a>(
+    width: u32,
+    height: u32,
+    samples: impl Iterator<Item = &'a [u8]>,
+    convert: impl Fn(&[u8]) -> Rgba<u8>,
+) -> RgbaImage {
+    let mut image = RgbaImage::new(width, height);
+    for (pixel, sample) in image.pixels_mut().zip(samples) {
+        *pixel = convert(sample);
+    }
+    image
+}
+
+impl FromStr for PixelFormat {
+    type Err = F2V2FError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "rgba" => Ok(PixelFormat::Rgba),
+            "rgb24" | "rgb" => Ok(PixelFormat::Rgb24),
+            "gray" | "grey" => Ok(PixelFormat::Gray),
+            "rgba64le" | "rgba64" => Ok(PixelFormat::Rgba64),
+            "gray16le" | "gray16" => Ok(PixelFormat::Gray16),
+            other => Err(F2V2FError::InvalidInput(format!(
+                "Unknown pixel format '{}' (expected rgba, rgb24, gray, rgba64le or gray16le)",
+                other
+            ))),
+        }
+    }
+}
+
+/// swscale algorithm for format conversion
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scaler {
+    #[default]
+    Lanczos,
+    Bicubic,
+    Bilinear,
+    Area,
+    Neighbor,
+    Spline,
+}
+
+impl Scaler {
+    /// Name ffmpeg's `flags`/`-sws_flags` take
+    pub fn ffmpeg_name(&self) -> &'static str {
+        match self {
+            Scaler::Lanczos => "lanczos",
+            Scaler::Bicubic => "bicubic",
+            Scaler::Bilinear => "bilinear",
+            Scaler::Area => "area",
+            Scaler::Neighbor => "neighbor",
+            Scaler::Spline => "spline",
+        }
+    }
+}
+
+impl FromStr for Scaler {
+    type Err = F2V2FError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "lanczos" => Ok(Scaler::Lanczos),
+            "bicubic" => Ok(Scaler::Bicubic),
+            "bilinear" => Ok(Scaler::Bilinear),
+            "area" => Ok(Scaler::Area),
+            "neighbor" | "nearest" => Ok(Scaler::Neighbor),
+            "spline" => Ok(Scaler::Spline),
+            other => Err(F2V2FError::InvalidInput(format!(
+                "Unknown scaler '{}' (expected lanczos, bicubic, bilinear, area, neighbor or spline)",
+                other
+            ))),
+        }
+    }
+}
+
+/// Output color range of the YUV to RGB conversion
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorRange {
+    /// Full range (0-255), as the encoder writes
+    #[default]
+    Pc,
+    /// Limited range (16-235)
+    Tv,
+}
+
+impl ColorRange {
+    pub fn ffmpeg_name(&self) -> &'static str {
+        match self {
+            ColorRange::Pc => "pc",
+            ColorRange::Tv => "tv",
+        }
+    }
+}
+
+impl FromStr for ColorRange {
+    type Err = F2V2FError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "pc" | "full" => Ok(ColorRange::Pc),
+            "tv" | "limited" => Ok(ColorRange::Tv),
+            other => Err(F2V2FError::InvalidInput(format!(
+                "Unknown color range '{}' (expected pc or tv)",
+                other
+            ))),
+        }
+    }
+}
+
+/// Everything that controls how extracted frames are converted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ExtractFormat {
+    pub pix_fmt: PixelFormat,
+    pub scaler: Scaler,
+    pub color_range: ColorRange,
+}
+
+impl ExtractFormat {
+    /// ffmpeg output options producing frames in this format
+    pub fn output_args(&self) -> Vec<String> {
+        [
+            "-sws_flags", self.scaler.ffmpeg_name(),
+            "-f", "rawvideo",
+            "-pix_fmt", self.pix_fmt.ffmpeg_name(),
+            "-color_range", self.color_range.ffmpeg_name(),
+        ]
+        .map(String::from)
+        .to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_names() -> Result<()> {
+        assert_eq!("rgba64le".parse::<PixelFormat>()?, PixelFormat::Rgba64);
+        assert_eq!("Bicubic".parse::<Scaler>()?, Scaler::Bicubic);
+        assert_eq!("limited".parse::<ColorRange>()?, ColorRange::Tv);
+        assert!("yuv420p".parse::<PixelFormat>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_rgba() {
+        let gray = PixelFormat::Gray.to_rgba(2, 1, vec![10, 200]).unwrap();
+        assert_eq!(gray.get_pixel(1, 0), &Rgba([200, 200, 200, 255]));
+
+        let wide = PixelFormat::Gray16.to_rgba(1, 1, 0x8080u16.to_le_bytes().to_vec()).unwrap();
+        assert_eq!(wide.get_pixel(0, 0)[0], 128);
+
+        // Wrong size for the format
+        assert!(PixelFormat::Rgb24.to_rgba(2, 2, vec![0; 16]).is_none());
+    }
+}
@@ -0,0 +1,234 @@
+//! Fault injection for robustness testing (feature `fault-injection`)
+//!
+//! `FaultyBackend` wraps another backend and damages the frames on their way
+//! into the video: noise on a share of the pixels, whole frames
+//! dropped, or the video cut off after some frames. Decoding the result
+//! through the wrapped backend shows how much damage a given chunk density
+//! tolerates. Faults are drawn from a seeded RNG, so every run damages the
+//! same pixels.
+
+use crate::backend::{ContentRect, FrameRequest, FrameSink, OutputSpec, VideoBackend};
+use crate::error::{F2V2FError, Result};
+use crate::warning::Warning;
+use image::RgbaImage;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Damage done to the frames of a video as they are written
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Faults {
+    /// Share of pixels (0 to 1) of each frame hit by noise
+    pub corrupt_pixels: f64,
+    /// Largest change noise makes to a channel (255: any value)
+    pub amplitude: u8,
+    /// Share of frames (0 to 1) left out of the video
+    pub drop_frames: f64,
+    /// Frames the video is cut off after, counting dropped ones
+    pub truncate_after: Option<u64>,
+    pub seed: u64,
+}
+
+impl Default for Faults {
+    fn default() -> Self {
+        Self { corrupt_pixels: 0.0, amplitude: u8::MAX, drop_frames: 0.0, truncate_after: None, seed: 0 }
+    }
+}
+
+impl Faults {
+    pub fn with_corrupt_pixels(mut self, share: f64) -> Self {
+        self.corrupt_pixels = share;
+        self
+    }
+
+    pub fn with_amplitude(mut self, amplitude: u8) -> Self {
+        self.amplitude = amplitude;
+        self
+    }
+
+    pub fn with_drop_frames(mut self, share: f64) -> Self {
+        self.drop_frames = share;
+        self
+    }
+
+    pub fn with_truncate_after(mut self, frames: u64) -> Self {
+        self.truncate_after = Some(frames);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for (name, share) in [("Pixel corruption", self.corrupt_pixels), ("Frame drop", self.drop_frames)] {
+            if !(0.0..=1.0).contains(&share) {
+                return Err(F2V2FError::ConfigError(format!("{} share {} is not between 0 and 1", name, share)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Backend writing through `inner` with `faults` applied; reads are untouched
+pub struct FaultyBackend {
+    inner: Arc<dyn VideoBackend>,
+    faults: Faults,
+}
+
+impl FaultyBackend {
+    pub fn new(inner: Arc<dyn VideoBackend>, faults: Faults) -> Result<Self> {
+        faults.validate()?;
+        Ok(Self { inner, faults })
+    }
+}
+
+impl VideoBackend for FaultyBackend {
+    fn create(&self, path: &Path, spec: &OutputSpec) -> Result<Box<dyn FrameSink>> {
+        Ok(Box::new(FaultySink {
+            inner: self.inner.create(path, spec)?,
+            faults: self.faults,
+            rng: StdRng::seed_from_u64(self.faults.seed),
+            frame: Vec::with_capacity(spec.width as usize * spec.height as usize * 4),
+            frame_size: spec.width as usize * spec.height as usize * 4,
+            frames: 0,
+        }))
+    }
+
+    fn read_frames(&self, path: &Path, request: &FrameRequest, warnings: &mut Vec<Warning>) -> Result<Vec<RgbaImage>> {
+        self.inner.read_frames(path, request, warnings)
+    }
+
+    fn probe_dimensions(&self, path: &Path) -> Result<(u32, u32)> {
+        self.inner.probe_dimensions(path)
+    }
+
+    fn probe_frames(&self, path: &Path) -> Result<(u64, f64)> {
+        self.inner.probe_frames(path)
+    }
+
+    fn probe_duration(&self, path: &Path) -> Result<f64> {
+        self.inner.probe_duration(path)
+    }
+
+    fn detect_content_rect(&self, path: &Path) -> Result<Option<ContentRect>> {
+        self.inner.detect_content_rect(path)
+    }
+
+    fn read_tag(&self, path: &Path, key: &str) -> Result<Option<String>> {
+        self.inner.read_tag(path, key)
+    }
+
+    fn read_attachment(&self, path: &Path, name: &str) -> Result<Option<Vec<u8>>> {
+        self.inner.read_attachment(path, name)
+    }
+
+    fn read_subtitles(&self, path: &Path) -> Result<Option<String>> {
+        self.inner.read_subtitles(path)
+    }
+}
+
+/// Collects each frame whole, then damages, drops or passes it on
+struct FaultySink {
+    inner: Box<dyn FrameSink>,
+    faults: Faults,
+    rng: StdRng,
+    frame: Vec<u8>,
+    frame_size: usize,
+    /// Frames seen so far, dropped ones included
+    frames: u64,
+}
+
+impl FaultySink {
+    fn frame_done(&mut self) -> std::io::Result<()> {
+        self.frames += 1;
+        let cut_off = self.faults.truncate_after.is_some_and(|after| self.frames > after);
+        let dropped = self.rng.gen_bool(self.faults.drop_frames);
+        if !cut_off && !dropped {
+            let pixels = self.frame_size / 4;
+            let corrupt = (pixels as f64 * self.faults.corrupt_pixels).round() as usize;
+            let amplitude = self.faults.amplitude as i16;
+            for i in rand::seq::index::sample(&mut self.rng, pixels, corrupt) {
+                for channel in &mut self.frame[i * 4..i * 4 + 3] {
+                    let noise = self.rng.gen_range(-amplitude..=amplitude);
+                    *channel = (*channel as i16 + noise).clamp(0, 255) as u8;
+                }
+            }
+            self.inner.write_all(&self.frame)?;
+        }
+        self.frame.clear();
+        Ok(())
+    }
+}
+
+impl Write for FaultySink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = buf.len().min(self.frame_size - self.frame.len());
+        self.frame.extend_from_slice(&buf[..n]);
+        if self.frame.len() == self.frame_size {
+            self.frame_done()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl FrameSink for FaultySink {
+    fn finish(self: Box<Self>) -> Result<()> {
+        if !self.frame.is_empty() {
+            return Err(F2V2FError::EncodingError(format!("{} bytes left over after the last frame", self.frame.len())));
+        }
+        self.inner.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{FrameSelection, MockBackend};
+    use crate::extract_format::ExtractFormat;
+
+    #[test]
+    fn test_faults_applied_on_write() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("video");
+        let spec = OutputSpec { width: 10, height: 10, fps: 30, keyframe_interval: None, deterministic: true, metadata: None, subtitles: None, tags: &[], attachments: &[] };
+        let write = |faults: Faults| -> Result<Vec<RgbaImage>> {
+            let mut sink = FaultyBackend::new(Arc::new(MockBackend), faults)?.create(&path, &spec)?;
+            for i in 0..20u8 {
+                sink.write_all(RgbaImage::from_pixel(10, 10, image::Rgba([i, i, i, 255])).as_raw())?;
+            }
+            sink.finish()?;
+            let request = FrameRequest {
+                width: 10,
+                height: 10,
+                selection: FrameSelection::All,
+                filters: Vec::new(),
+                format: ExtractFormat::default(),
+                ignore_errors: false,
+            };
+            MockBackend.read_frames(&path, &request, &mut Vec::new())
+        };
+
+        assert_eq!(write(Faults::default())?.len(), 20);
+        assert_eq!(write(Faults::default().with_truncate_after(7))?.len(), 7);
+        let kept = write(Faults::default().with_drop_frames(0.5).with_seed(3))?;
+        assert!(kept.len() > 2 && kept.len() < 18);
+        // Kept frames arrive in order and undamaged
+        assert!(kept.windows(2).all(|w| w[0].get_pixel(0, 0)[0] < w[1].get_pixel(0, 0)[0]));
+
+        let damaged = write(Faults::default().with_corrupt_pixels(0.3))?;
+        let changed = damaged[4].pixels().filter(|p| p.0 != [4, 4, 4, 255]).count();
+        assert!((25..=30).contains(&changed), "{} pixels changed", changed);
+        let damaged = write(Faults::default().with_corrupt_pixels(1.0).with_amplitude(2))?;
+        assert!(damaged[9].pixels().all(|p| p.0[..3].iter().all(|&c| c.abs_diff(9) <= 2) && p.0[3] == 255));
+        assert!(FaultyBackend::new(Arc::new(MockBackend), Faults::default().with_drop_frames(1.5)).is_err());
+        Ok(())
+    }
+}
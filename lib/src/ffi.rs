@@ -2,43 +2,74 @@
 //!
 //! This module provides C-compatible function signatures that can be called
 //! from Python, TypeScript/Node.js, and other languages via FFI.
+//!
+//! # Thread safety
+//!
+//! Handles are `Send + Sync`: every operation takes the handle by shared
+//! reference and keeps its working state on the calling thread, so one
+//! handle may run several encodes/decodes at once from a thread pool.
+//! Pausing a handle pauses all operations running on it. Freeing a handle
+//! must not race with any other call using it.
+//!
+//! The last error is kept per thread, like `errno`: read it with
+//! `f2v2f_get_last_error` on the thread that made the failing call.
 
-use crate::config::{EncodeConfig, DecodeConfig};
-use crate::encoder::Encoder;
+use crate::backend::{FfmpegBackend, VideoBackend};
+use crate::checksum::HashAlgorithm;
+use crate::config::{EncodeConfig, DecodeConfig, SymlinkPolicy, DEFAULT_MAX_FRAMES};
+use crate::image_generator::DEFAULT_SEED;
 use crate::decoder::Decoder;
-use crate::video_composer::VideoComposer;
+use crate::payload::DEFAULT_SPILL_THRESHOLD;
+use crate::error::F2V2FError;
+use crate::logging;
+use crate::operation::OperationHandle;
+use crate::pipeline::run_encode;
+use crate::storage::Location;
+use crate::throttle::Throttle;
+use std::cell::RefCell;
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
-use std::sync::Mutex;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use lazy_static::lazy_static;
 
-lazy_static! {
-    static ref LAST_ERROR: Mutex<Option<String>> = Mutex::new(None);
+thread_local! {
+    static LAST_ERROR: RefCell<Option<String>> = const { RefCell::new(None) };
 }
 
 fn set_last_error(err: String) {
-    if let Ok(mut guard) = LAST_ERROR.lock() {
-        *guard = Some(err);
-    }
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(err));
 }
 
 fn clear_last_error() {
-    if let Ok(mut guard) = LAST_ERROR.lock() {
-        *guard = None;
-    }
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
 }
 
 /// Opaque handle for ongoing encode operations
 pub struct EncodeHandle {
-    encoder: Encoder,
     config: EncodeConfig,
+    operation: OperationHandle,
+    backend: Arc<dyn VideoBackend>,
+    /// Replace existing outputs, see `f2v2f_encode_set_overwrite`
+    overwrite: AtomicBool,
 }
 
 /// Opaque handle for ongoing decode operations
 pub struct DecodeHandle {
     decoder: Decoder,
+    operation: OperationHandle,
+    /// Replace existing outputs, see `f2v2f_decode_set_overwrite`
+    overwrite: AtomicBool,
 }
 
+// Bindings share handles across threads; keep them Send + Sync
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<EncodeHandle>();
+    assert_send_sync::<DecodeHandle>();
+};
+
 lazy_static! {
     static ref ENCODE_HANDLES: Mutex<Vec<Box<EncodeHandle>>> = Mutex::new(Vec::new());
     static ref DECODE_HANDLES: Mutex<Vec<Box<DecodeHandle>>> = Mutex::new(Vec::new());
@@ -63,6 +94,8 @@ pub enum F2V2FErrorCode {
     ConfigError = 5,
     OperationInProgress = 6,
     InvalidHandle = 7,
+    /// The output path is taken and the handle doesn't overwrite
+    OutputExists = 8,
     Unknown = 255,
 }
 
@@ -72,37 +105,103 @@ pub type ProgressCallback = extern "C" fn(u64, u64, *const c_char);
 /// Callback for operation completion
 pub type CompletionCallback = extern "C" fn(i32, *const c_char);
 
-/// Initialize the library (call once at startup)
+/// Log callback: severity (1 error, 2 warn, 3 info, 4 debug, 5 trace) and
+/// the formatted message, valid only during the call
+pub type LogCallback = extern "C" fn(i32, *const c_char);
+
+/// Initialize the library with default logging (info and up, to stderr)
 #[no_mangle]
 pub extern "C" fn f2v2f_init() -> i32 {
-    // Initialize logging
-    let _ = tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO)
-        .try_init();
-    
-    F2V2FErrorCode::Success as i32
+    f2v2f_init_with_options(std::ptr::null(), None)
 }
 
-/// Get the last error message
-/// Returns a pointer to a null-terminated string. The caller must free it.
+/// Initialize the library, choosing how it logs
+///
+/// `log_level` is a level (`"warn"`) or filter directive (`"f2v2f=debug"`);
+/// null means `"info"`. With `log_callback` set, records go to it instead of
+/// stderr. Safe to call from several threads: only the first call (of this
+/// or `f2v2f_init`) takes effect, later ones return Success and change
+/// nothing.
+///
+/// # Safety
+/// - `log_level` must be null or a valid null-terminated UTF-8 string
+/// - `log_callback` may be called from any thread until the process exits
 #[no_mangle]
-pub extern "C" fn f2v2f_get_last_error() -> *mut c_char {
-    if let Ok(guard) = LAST_ERROR.lock() {
-        match guard.as_ref() {
-            Some(err) => {
-                match CString::new(err.as_str()) {
-                    Ok(c_str) => c_str.into_raw(),
-                    Err(_) => std::ptr::null_mut(),
-                }
+pub extern "C" fn f2v2f_init_with_options(log_level: *const c_char, log_callback: Option<LogCallback>) -> i32 {
+    clear_last_error();
+    let level = if log_level.is_null() {
+        "info"
+    } else {
+        match unsafe { CStr::from_ptr(log_level) }.to_str() {
+            Ok(level) => level,
+            Err(_) => {
+                set_last_error("Log level is not valid UTF-8".to_string());
+                return F2V2FErrorCode::InvalidInput as i32;
             }
-            None => std::ptr::null_mut(),
         }
-    } else {
-        std::ptr::null_mut()
+    };
+    let filter = match tracing_subscriber::EnvFilter::try_new(level) {
+        Ok(filter) => filter,
+        Err(e) => {
+            set_last_error(format!("Invalid log level '{}': {}", level, e));
+            return F2V2FErrorCode::ConfigError as i32;
+        }
+    };
+
+    // The callback only applies if this call set up logging
+    if logging::init(filter) {
+        if let Some(callback) = log_callback {
+            logging::set_log_sink(Some(callback_sink(callback)));
+        }
     }
+
+    F2V2FErrorCode::Success as i32
 }
 
-/// Free a string returned by f2v2f_get_last_error
+/// Send log records to `log_callback` instead of stderr, replacing any
+/// previous callback; null goes back to stderr
+///
+/// Only takes effect once the library's logging is set up by `f2v2f_init`
+/// or `f2v2f_init_with_options`.
+///
+/// # Safety
+/// - `log_callback` may be called from any thread until it is replaced
+#[no_mangle]
+pub extern "C" fn f2v2f_set_log_callback(log_callback: Option<LogCallback>) -> i32 {
+    logging::set_log_sink(log_callback.map(callback_sink));
+    F2V2FErrorCode::Success as i32
+}
+
+fn callback_sink(callback: LogCallback) -> logging::LogSink {
+    Arc::new(move |record: &logging::LogRecord| {
+        let level = match record.level {
+            tracing::Level::ERROR => 1,
+            tracing::Level::WARN => 2,
+            tracing::Level::INFO => 3,
+            tracing::Level::DEBUG => 4,
+            tracing::Level::TRACE => 5,
+        };
+        if let Ok(message) = CString::new(record.message.replace('\0', "")) {
+            callback(level, message.as_ptr());
+        }
+    })
+}
+
+/// Get the last error message of the calling thread
+/// Returns a pointer to a null-terminated string. The caller must free it.
+#[no_mangle]
+pub extern "C" fn f2v2f_get_last_error() -> *mut c_char {
+    LAST_ERROR.with(|last| match last.borrow().as_deref() {
+        Some(err) => match CString::new(err) {
+            Ok(c_str) => c_str.into_raw(),
+            Err(_) => std::ptr::null_mut(),
+        },
+        None => std::ptr::null_mut(),
+    })
+}
+
+/// Free a string returned by f2v2f_get_last_error, f2v2f_decode_file or
+/// f2v2f_capabilities
 #[no_mangle]
 pub extern "C" fn f2v2f_free_string(s: *mut c_char) {
     if !s.is_null() {
@@ -134,19 +233,44 @@ pub extern "C" fn f2v2f_encode_create(
         buffer_size: 1024 * 1024,
         use_compression: true,
         compression_level: 11,
+        compression_threads: None,
+        overlay: false,
+        hash_algo: HashAlgorithm::Sha256,
+        dictionary: None,
+        seed: DEFAULT_SEED,
+        deterministic: false,
+        spill_threshold: DEFAULT_SPILL_THRESHOLD,
+        symlinks: SymlinkPolicy::Follow,
+        max_frames: Some(DEFAULT_MAX_FRAMES),
+        throttle: Throttle::Unlimited,
+        // Bindings have always replaced existing outputs
+        overwrite: true,
+        temp_dir: None,
+        cache_dir: None,
+        transition_frames: 0,
+        keyframe_interval: None,
+        chunk_chapters: None,
+        entropy_style: false,
+        merkle_block_size: None,
+        streams: Vec::new(),
+        embed_manifest: true,
+        manifest_tag: false,
+        subtitle_manifest: false,
+        adaptive_fps: false,
+        preserve_metadata: false,
     };
 
     if let Err(_) = config.validate() {
         return std::ptr::null_mut();
     }
 
-    match Encoder::new(config.clone()) {
-        Ok(encoder) => {
-            let handle = Box::new(EncodeHandle { encoder, config });
-            Box::into_raw(handle)
-        }
-        Err(_) => std::ptr::null_mut(),
-    }
+    let handle = Box::new(EncodeHandle {
+        overwrite: AtomicBool::new(config.overwrite),
+        config,
+        operation: OperationHandle::new(),
+        backend: Arc::new(FfmpegBackend),
+    });
+    Box::into_raw(handle)
 }
 
 /// Encode a file to video
@@ -188,12 +312,24 @@ pub extern "C" fn f2v2f_encode_file(
     // IMPORTANT: Call blocking methods directly - NO async runtime!
     // This prevents SIGBUS crashes from Tokio runtime in cgo context
     
-    // Encode the file data (blocking)
-    let (info, compressed_data) = match handle_ref.encoder.encode_blocking(input_path_str) {
-        Ok(result) => result,
+    // Encode, compose and write the manifest in one pass (blocking)
+    let config = EncodeConfig { overwrite: handle_ref.overwrite.load(Ordering::Relaxed), ..handle_ref.config.clone() };
+    let info = match run_encode(
+        &Location::from(Path::new(input_path_str)),
+        Path::new(output_path_str),
+        &config,
+        &handle_ref.operation,
+        None,
+        handle_ref.backend.clone(),
+    ) {
+        Ok(info) => info,
         Err(e) => {
             set_last_error(format!("{}", e));
-            return F2V2FErrorCode::EncodingError as i32;
+            return match e {
+                F2V2FError::OutputExists(_) => F2V2FErrorCode::OutputExists as i32,
+                F2V2FError::Io(_) => F2V2FErrorCode::IoError as i32,
+                _ => F2V2FErrorCode::EncodingError as i32,
+            };
         }
     };
 
@@ -217,27 +353,51 @@ pub extern "C" fn f2v2f_encode_file(
         // Callbacks not yet supported in FFI layer
     }
 
-    // Create video from file data using optimized chunk size (BLOCKING)
-    let composer = VideoComposer::new(
-        handle_ref.config.width,
-        handle_ref.config.height,
-        handle_ref.config.fps,
-    );
+    clear_last_error();
+    F2V2FErrorCode::Success as i32
+}
 
-    match composer.compose_from_file_data_blocking(
-        compressed_data,
-        info.chunk_size,
-        output_path_str,
-    ) {
-        Ok(_) => {
-            clear_last_error();
-            F2V2FErrorCode::Success as i32
-        },
-        Err(e) => {
-            set_last_error(format!("{}", e));
-            F2V2FErrorCode::EncodingError as i32
-        },
+/// Pause an in-flight encode; `f2v2f_encode_file` blocks until resumed
+///
+/// # Safety
+/// - `handle` must be a valid pointer from `f2v2f_encode_create`
+#[no_mangle]
+pub extern "C" fn f2v2f_encode_pause(handle: *mut EncodeHandle) -> i32 {
+    if handle.is_null() {
+        return F2V2FErrorCode::InvalidHandle as i32;
+    }
+    unsafe { &*handle }.operation.pause();
+    F2V2FErrorCode::Success as i32
+}
+
+/// Resume a paused encode
+///
+/// # Safety
+/// - `handle` must be a valid pointer from `f2v2f_encode_create`
+#[no_mangle]
+pub extern "C" fn f2v2f_encode_resume(handle: *mut EncodeHandle) -> i32 {
+    if handle.is_null() {
+        return F2V2FErrorCode::InvalidHandle as i32;
     }
+    unsafe { &*handle }.operation.resume();
+    F2V2FErrorCode::Success as i32
+}
+
+/// Choose whether encodes on this handle replace an existing output (the
+/// default) or fail with `OutputExists`
+///
+/// Applies to encodes started after the call; running ones keep their
+/// setting.
+///
+/// # Safety
+/// - `handle` must be a valid pointer from `f2v2f_encode_create`
+#[no_mangle]
+pub extern "C" fn f2v2f_encode_set_overwrite(handle: *mut EncodeHandle, overwrite: bool) -> i32 {
+    if handle.is_null() {
+        return F2V2FErrorCode::InvalidHandle as i32;
+    }
+    unsafe { &*handle }.overwrite.store(overwrite, Ordering::Relaxed);
+    F2V2FErrorCode::Success as i32
 }
 
 /// Free an encoding handle
@@ -254,22 +414,32 @@ pub extern "C" fn f2v2f_encode_free(handle: *mut EncodeHandle) {
     }
 }
 
+fn new_decode_handle(config: DecodeConfig) -> *mut DecodeHandle {
+    match Decoder::new(config.clone()) {
+        Ok(decoder) => {
+            let operation = OperationHandle::new();
+            let decoder = decoder.with_operation(operation.clone());
+            let overwrite = AtomicBool::new(config.overwrite);
+            let handle = Box::new(DecodeHandle { decoder, operation, overwrite });
+            Box::into_raw(handle)
+        }
+        Err(e) => {
+            set_last_error(format!("{}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
 /// Create a decoding context
 #[no_mangle]
 pub extern "C" fn f2v2f_decode_create() -> *mut DecodeHandle {
-    let config = DecodeConfig::default();
+    let config = DecodeConfig { overwrite: true, ..DecodeConfig::default() };
 
     if let Err(_) = config.validate() {
         return std::ptr::null_mut();
     }
 
-    match Decoder::new(config) {
-        Ok(decoder) => {
-            let handle = Box::new(DecodeHandle { decoder });
-            Box::into_raw(handle)
-        }
-        Err(_) => std::ptr::null_mut(),
-    }
+    new_decode_handle(config)
 }
 
 /// Create a decoding context with parameters
@@ -286,6 +456,8 @@ pub extern "C" fn f2v2f_decode_create_with_params(
         height,
         chunk_size,
         encoded_data_size: if encoded_size > 0 { Some(encoded_size) } else { None },
+        overwrite: true,
+        probe_resolution: false,
         ..DecodeConfig::default()
     };
 
@@ -293,13 +465,39 @@ pub extern "C" fn f2v2f_decode_create_with_params(
         return std::ptr::null_mut();
     }
 
-    match Decoder::new(config) {
-        Ok(decoder) => {
-            let handle = Box::new(DecodeHandle { decoder });
-            Box::into_raw(handle)
-        }
-        Err(_) => std::ptr::null_mut(),
+    new_decode_handle(config)
+}
+
+/// Create a decoding context configured for a specific video
+///
+/// Reads the video's manifest, or probes its resolution when there is none,
+/// so callers don't need to know width/height/chunk size. Returns null on
+/// failure; see `f2v2f_get_last_error`.
+///
+/// # Safety
+/// - `video_path` must be a valid null-terminated UTF-8 string
+#[no_mangle]
+pub extern "C" fn f2v2f_decode_create_from_video(video_path: *const c_char) -> *mut DecodeHandle {
+    if video_path.is_null() {
+        set_last_error("Video path is null".to_string());
+        return std::ptr::null_mut();
     }
+    let path = match unsafe { CStr::from_ptr(video_path) }.to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            set_last_error("Video path is not valid UTF-8".to_string());
+            return std::ptr::null_mut();
+        }
+    };
+
+    let config = match DecodeConfig::from_video(path) {
+        Ok(config) => DecodeConfig { overwrite: true, ..config },
+        Err(e) => {
+            set_last_error(format!("{}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    new_decode_handle(config)
 }
 
 /// Decode a video back to a file
@@ -307,11 +505,18 @@ pub extern "C" fn f2v2f_decode_create_with_params(
 /// # Safety
 /// - `handle` must be a valid pointer from `f2v2f_decode_create`
 /// - `input_path` and `output_path` must be valid null-terminated UTF-8 strings
+/// - `extracted_size_out` must be a valid pointer to u64 (nullable)
+/// - `checksum_out` must be a valid pointer to char* (nullable) - receives the
+///   hex checksum of the decoded file; free it with `f2v2f_free_string`
+/// - `was_compressed_out` must be a valid pointer to bool (nullable)
 #[no_mangle]
 pub extern "C" fn f2v2f_decode_file(
     handle: *mut DecodeHandle,
     input_path: *const c_char,
     output_path: *const c_char,
+    extracted_size_out: *mut u64,
+    checksum_out: *mut *mut c_char,
+    was_compressed_out: *mut bool,
     progress_callback: Option<ProgressCallback>,
 ) -> i32 {
     if handle.is_null() {
@@ -334,8 +539,29 @@ pub extern "C" fn f2v2f_decode_file(
     let handle_ref = unsafe { &*handle };
 
     // Use the global Tokio runtime for consistency
-    match TOKIO_RUNTIME.block_on(handle_ref.decoder.decode(input_path_str, output_path_str)) {
+    let decoder = handle_ref.decoder.clone().with_overwrite(handle_ref.overwrite.load(Ordering::Relaxed));
+    match TOKIO_RUNTIME.block_on(decoder.decode(input_path_str, output_path_str)) {
         Ok(info) => {
+            if !extracted_size_out.is_null() {
+                unsafe {
+                    *extracted_size_out = info.extracted_size;
+                }
+            }
+
+            if !checksum_out.is_null() {
+                // Hex digests never contain NUL
+                let checksum = CString::new(info.checksum.clone()).unwrap_or_default();
+                unsafe {
+                    *checksum_out = checksum.into_raw();
+                }
+            }
+
+            if !was_compressed_out.is_null() {
+                unsafe {
+                    *was_compressed_out = info.was_compressed;
+                }
+            }
+
             if let Some(callback) = progress_callback {
                 let status_msg = CString::new(format!(
                     "Decoded {} bytes with checksum {}",
@@ -349,11 +575,58 @@ pub extern "C" fn f2v2f_decode_file(
         }
         Err(e) => {
             set_last_error(format!("{}", e));
-            F2V2FErrorCode::DecodingError as i32
+            match e {
+                F2V2FError::NotF2V2FVideo(_) | F2V2FError::UnsupportedStyle(..) => F2V2FErrorCode::InvalidInput as i32,
+                F2V2FError::OutputExists(_) => F2V2FErrorCode::OutputExists as i32,
+                _ => F2V2FErrorCode::DecodingError as i32,
+            }
         },
     }
 }
 
+/// Pause an in-flight decode; `f2v2f_decode_file` blocks until resumed
+///
+/// # Safety
+/// - `handle` must be a valid pointer from `f2v2f_decode_create`
+#[no_mangle]
+pub extern "C" fn f2v2f_decode_pause(handle: *mut DecodeHandle) -> i32 {
+    if handle.is_null() {
+        return F2V2FErrorCode::InvalidHandle as i32;
+    }
+    unsafe { &*handle }.operation.pause();
+    F2V2FErrorCode::Success as i32
+}
+
+/// Resume a paused decode
+///
+/// # Safety
+/// - `handle` must be a valid pointer from `f2v2f_decode_create`
+#[no_mangle]
+pub extern "C" fn f2v2f_decode_resume(handle: *mut DecodeHandle) -> i32 {
+    if handle.is_null() {
+        return F2V2FErrorCode::InvalidHandle as i32;
+    }
+    unsafe { &*handle }.operation.resume();
+    F2V2FErrorCode::Success as i32
+}
+
+/// Choose whether decodes on this handle replace an existing output (the
+/// default) or fail with `OutputExists`
+///
+/// Applies to decodes started after the call; running ones keep their
+/// setting.
+///
+/// # Safety
+/// - `handle` must be a valid pointer from a `f2v2f_decode_create*` function
+#[no_mangle]
+pub extern "C" fn f2v2f_decode_set_overwrite(handle: *mut DecodeHandle, overwrite: bool) -> i32 {
+    if handle.is_null() {
+        return F2V2FErrorCode::InvalidHandle as i32;
+    }
+    unsafe { &*handle }.overwrite.store(overwrite, Ordering::Relaxed);
+    F2V2FErrorCode::Success as i32
+}
+
 /// Free a decoding handle
 ///
 /// # Safety
@@ -368,6 +641,33 @@ pub extern "C" fn f2v2f_decode_free(handle: *mut DecodeHandle) {
     }
 }
 
+/// Describe codecs, art styles and backends as JSON, probing ffmpeg
+///
+/// The payload limit is for the default encode config.
+/// Returns a null-terminated string the caller must free with
+/// f2v2f_free_string, or NULL on error.
+#[no_mangle]
+pub extern "C" fn f2v2f_capabilities() -> *mut c_char {
+    let caps = crate::capabilities(&EncodeConfig::default());
+    match serde_json::to_string(&caps).map(CString::new) {
+        Ok(Ok(json)) => json.into_raw(),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// Describe this process's encodes and decodes (running and recently
+/// finished) as a JSON array, see `jobs::JobInfo`
+///
+/// Returns a null-terminated string the caller must free with
+/// f2v2f_free_string, or NULL on error.
+#[no_mangle]
+pub extern "C" fn f2v2f_jobs() -> *mut c_char {
+    match serde_json::to_string(&crate::jobs::list()).map(CString::new) {
+        Ok(Ok(json)) => json.into_raw(),
+        _ => std::ptr::null_mut(),
+    }
+}
+
 /// Get version string
 ///
 /// Returns: Static string with version info
@@ -379,6 +679,45 @@ pub extern "C" fn f2v2f_version() -> *const c_char {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::backend::MockBackend;
+    use crate::manifest::Manifest;
+
+    /// Lets test threads share a handle the way bindings do
+    struct Shared<T>(*mut T);
+    unsafe impl<T> Send for Shared<T> {}
+    unsafe impl<T> Sync for Shared<T> {}
+
+    fn c_path(path: &Path) -> CString {
+        CString::new(path.to_str().unwrap()).unwrap()
+    }
+
+    /// Encode handle writing videos through `MockBackend`
+    fn mock_encode_handle() -> *mut EncodeHandle {
+        let handle = f2v2f_encode_create(256, 256, 30, 4096);
+        assert!(!handle.is_null());
+        unsafe { (*handle).backend = Arc::new(MockBackend) };
+        handle
+    }
+
+    /// Decode handle reading videos through `MockBackend`
+    fn mock_decode_handle() -> *mut DecodeHandle {
+        let handle = f2v2f_decode_create();
+        assert!(!handle.is_null());
+        unsafe {
+            let decoder = Decoder::new(DecodeConfig::default()).unwrap();
+            (*handle).decoder = decoder.with_operation((*handle).operation.clone()).with_backend(Arc::new(MockBackend));
+        }
+        handle
+    }
+
+    /// Encode `data` to `video` through a fresh mock encode handle
+    fn mock_encode(data: &[u8], input: &Path, video: &Path) {
+        std::fs::write(input, data).unwrap();
+        let handle = mock_encode_handle();
+        let code = f2v2f_encode_file(handle, c_path(input).as_ptr(), c_path(video).as_ptr(), std::ptr::null_mut(), std::ptr::null_mut(), None);
+        assert_eq!(code, 0);
+        f2v2f_encode_free(handle);
+    }
 
     #[test]
     fn test_init() {
@@ -386,9 +725,215 @@ mod tests {
         assert_eq!(result, 0);
     }
 
+    #[test]
+    fn test_init_with_options_rejects_bad_level() {
+        let level = CString::new("f2v2f=loud").unwrap();
+        assert_eq!(f2v2f_init_with_options(level.as_ptr(), None), F2V2FErrorCode::ConfigError as i32);
+        assert!(take_last_error().unwrap().contains("f2v2f=loud"));
+
+        let level = CString::new("warn").unwrap();
+        assert_eq!(f2v2f_init_with_options(level.as_ptr(), None), 0);
+    }
+
     #[test]
     fn test_version() {
         let version = unsafe { CStr::from_ptr(f2v2f_version()).to_str().unwrap() };
         assert!(version.contains("f2v2f"));
     }
+
+    fn take_last_error() -> Option<String> {
+        let ptr = f2v2f_get_last_error();
+        if ptr.is_null() {
+            return None;
+        }
+        let err = unsafe { CStr::from_ptr(ptr) }.to_string_lossy().into_owned();
+        f2v2f_free_string(ptr);
+        Some(err)
+    }
+
+    #[test]
+    fn test_last_error_is_per_thread() {
+        let workers: Vec<_> = (0..8)
+            .map(|i| {
+                std::thread::spawn(move || {
+                    set_last_error(format!("error {}", i));
+                    std::thread::yield_now();
+                    take_last_error()
+                })
+            })
+            .collect();
+        for (i, worker) in workers.into_iter().enumerate() {
+            assert_eq!(worker.join().unwrap(), Some(format!("error {}", i)));
+        }
+    }
+
+    #[test]
+    fn test_refusing_to_overwrite_has_its_own_code() {
+        let dir = tempfile::tempdir().unwrap();
+        let (input, video, output) = (dir.path().join("in.bin"), dir.path().join("in.mp4"), dir.path().join("out.bin"));
+        let data = b"payload".repeat(100);
+        mock_encode(&data, &input, &video);
+        let (input, video, output) = (c_path(&input), c_path(&video), c_path(&output));
+
+        let handle = mock_encode_handle();
+        assert_eq!(f2v2f_encode_set_overwrite(handle, false), 0);
+        let code = f2v2f_encode_file(handle, input.as_ptr(), video.as_ptr(), std::ptr::null_mut(), std::ptr::null_mut(), None);
+        assert_eq!(code, F2V2FErrorCode::OutputExists as i32);
+        assert!(take_last_error().unwrap().contains("in.mp4"));
+        assert_eq!(f2v2f_encode_set_overwrite(handle, true), 0);
+        let code = f2v2f_encode_file(handle, input.as_ptr(), video.as_ptr(), std::ptr::null_mut(), std::ptr::null_mut(), None);
+        assert_eq!(code, 0);
+        f2v2f_encode_free(handle);
+
+        std::fs::write(dir.path().join("out.bin"), b"already here").unwrap();
+        let decode = |handle| {
+            f2v2f_decode_file(handle, video.as_ptr(), output.as_ptr(), std::ptr::null_mut(), std::ptr::null_mut(), std::ptr::null_mut(), None)
+        };
+        let handle = mock_decode_handle();
+        assert_eq!(f2v2f_decode_set_overwrite(handle, false), 0);
+        assert_eq!(decode(handle), F2V2FErrorCode::OutputExists as i32);
+        assert!(take_last_error().unwrap().contains("out.bin"));
+        assert_eq!(std::fs::read(dir.path().join("out.bin")).unwrap(), b"already here");
+        assert_eq!(f2v2f_decode_set_overwrite(handle, true), 0);
+        assert_eq!(decode(handle), 0);
+        assert_eq!(std::fs::read(dir.path().join("out.bin")).unwrap(), data);
+        f2v2f_decode_free(handle);
+
+        assert_eq!(f2v2f_encode_set_overwrite(std::ptr::null_mut(), false), F2V2FErrorCode::InvalidHandle as i32);
+        assert_eq!(f2v2f_decode_set_overwrite(std::ptr::null_mut(), false), F2V2FErrorCode::InvalidHandle as i32);
+    }
+
+    #[test]
+    fn test_shared_decode_handle_across_threads() {
+        let dir = tempfile::tempdir().unwrap();
+        let handle = Arc::new(Shared(f2v2f_decode_create()));
+        assert!(!handle.0.is_null());
+
+        let workers: Vec<_> = (0..4)
+            .map(|i| {
+                let handle = handle.clone();
+                let input = CString::new(dir.path().join(format!("missing{}.mp4", i)).to_str().unwrap()).unwrap();
+                let output = CString::new(dir.path().join(format!("out{}.bin", i)).to_str().unwrap()).unwrap();
+                std::thread::spawn(move || {
+                    assert_eq!(f2v2f_decode_pause(handle.0), 0);
+                    assert_eq!(f2v2f_decode_resume(handle.0), 0);
+                    let code = f2v2f_decode_file(
+                        handle.0,
+                        input.as_ptr(),
+                        output.as_ptr(),
+                        std::ptr::null_mut(),
+                        std::ptr::null_mut(),
+                        std::ptr::null_mut(),
+                        None,
+                    );
+                    (code, take_last_error())
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            let (code, err) = worker.join().unwrap();
+            assert_eq!(code, F2V2FErrorCode::DecodingError as i32);
+            assert!(err.is_some());
+        }
+        f2v2f_decode_free(handle.0);
+    }
+
+    #[test]
+    fn test_shared_encode_handle_across_threads() {
+        let dir = Arc::new(tempfile::tempdir().unwrap());
+        let handle = Arc::new(Shared(mock_encode_handle()));
+
+        let workers: Vec<_> = (0..4u32)
+            .map(|i| {
+                let (dir, handle) = (dir.clone(), handle.clone());
+                std::thread::spawn(move || {
+                    let (input, video) = (dir.path().join(format!("in{}.bin", i)), dir.path().join(format!("out{}.mp4", i)));
+                    std::fs::write(&input, (0..30_000u32).map(|b| (b * (i + 3) % 251) as u8).collect::<Vec<_>>()).unwrap();
+                    let (mut encoded_size, mut chunk_size) = (0u64, 0usize);
+                    let code = f2v2f_encode_file(handle.0, c_path(&input).as_ptr(), c_path(&video).as_ptr(), &mut encoded_size, &mut chunk_size, None);
+                    (code, encoded_size, chunk_size, take_last_error(), video)
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            let (code, encoded_size, chunk_size, err, video) = worker.join().unwrap();
+            assert_eq!(code, 0, "{:?}", err);
+            assert!(encoded_size > 0);
+            assert_eq!(chunk_size, 4096);
+            // Each encode kept to its own video and sidecar
+            let manifest = Manifest::from_json(&std::fs::read_to_string(Manifest::sidecar_path(&video)).unwrap()).unwrap();
+            assert_eq!(manifest.encoded_size, encoded_size);
+        }
+        f2v2f_encode_free(handle.0);
+    }
+
+    #[test]
+    fn test_paused_encode_waits_for_resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let (input, video) = (dir.path().join("in.bin"), dir.path().join("out.mp4"));
+        std::fs::write(&input, b"payload".repeat(1000)).unwrap();
+        let handle = Arc::new(Shared(mock_encode_handle()));
+
+        assert_eq!(f2v2f_encode_pause(handle.0), 0);
+        let worker = {
+            let handle = handle.clone();
+            std::thread::spawn(move || {
+                let none = std::ptr::null_mut();
+                f2v2f_encode_file(handle.0, c_path(&input).as_ptr(), c_path(&video).as_ptr(), none, std::ptr::null_mut(), None)
+            })
+        };
+        // However long it is given, a paused encode can't finish
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        assert!(!worker.is_finished());
+
+        assert_eq!(f2v2f_encode_resume(handle.0), 0);
+        assert_eq!(worker.join().unwrap(), 0);
+        f2v2f_encode_free(handle.0);
+    }
+
+    #[test]
+    fn test_shared_decode_handle_decodes_videos_across_threads() {
+        let dir = Arc::new(tempfile::tempdir().unwrap());
+        let originals: Vec<Vec<u8>> = (0..4u32).map(|i| (0..30_000u32).map(|b| (b * (i + 5) % 241) as u8).collect()).collect();
+        for (i, data) in originals.iter().enumerate() {
+            mock_encode(data, &dir.path().join(format!("in{}.bin", i)), &dir.path().join(format!("in{}.mp4", i)));
+        }
+
+        let handle = Arc::new(Shared(mock_decode_handle()));
+
+        let workers: Vec<_> = (0..originals.len())
+            .map(|i| {
+                let (dir, handle) = (dir.clone(), handle.clone());
+                std::thread::spawn(move || {
+                    let (video, output) = (dir.path().join(format!("in{}.mp4", i)), dir.path().join(format!("out{}.bin", i)));
+                    let (mut extracted_size, mut checksum) = (0u64, std::ptr::null_mut());
+                    let code = f2v2f_decode_file(
+                        handle.0,
+                        c_path(&video).as_ptr(),
+                        c_path(&output).as_ptr(),
+                        &mut extracted_size,
+                        &mut checksum,
+                        std::ptr::null_mut(),
+                        None,
+                    );
+                    let err = take_last_error();
+                    assert!(!checksum.is_null(), "{:?}", err);
+                    let checksum_hex = unsafe { CStr::from_ptr(checksum) }.to_string_lossy().into_owned();
+                    f2v2f_free_string(checksum);
+                    (code, err, extracted_size, checksum_hex, std::fs::read(output).unwrap())
+                })
+            })
+            .collect();
+
+        for (worker, original) in workers.into_iter().zip(&originals) {
+            let (code, err, extracted_size, checksum, decoded) = worker.join().unwrap();
+            assert_eq!(code, 0, "{:?}", err);
+            assert_eq!(&decoded, original);
+            assert_eq!(extracted_size, original.len() as u64);
+            assert_eq!(checksum, HashAlgorithm::Sha256.digest(original));
+        }
+        f2v2f_decode_free(handle.0);
+    }
 }
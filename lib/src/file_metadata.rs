@@ -0,0 +1,125 @@
+//! Original file name, modification time and permissions
+//!
+//! Recorded in the manifest at encode time (`EncodeConfig::preserve_metadata`)
+//! and, with `DecodeConfig::restore_metadata`, put back on the decoded file.
+
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::{Component, Path};
+use std::time::SystemTime;
+
+/// Read, write and execute for owner, group and others; setuid, setgid and
+/// sticky bits from an untrusted video are never restored
+pub const PERMISSION_BITS: u32 = 0o777;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileMetadata {
+    /// File name, without its directory (non-UTF-8 names aren't kept)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<SystemTime>,
+    /// Unix permission bits (`mode & PERMISSION_BITS`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+}
+
+impl FileMetadata {
+    /// Name, modification time and permissions of the file at `path`
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let metadata = std::fs::metadata(path)?;
+        #[cfg(unix)]
+        let mode = {
+            use std::os::unix::fs::PermissionsExt;
+            Some(metadata.permissions().mode() & PERMISSION_BITS)
+        };
+        #[cfg(not(unix))]
+        let mode = None;
+        Ok(Self {
+            name: path.file_name().and_then(|name| name.to_str()).map(str::to_string),
+            modified: metadata.modified().ok(),
+            mode,
+        })
+    }
+
+    /// The recorded name, if it is a plain file name that can't reach
+    /// outside the directory it is joined to
+    pub fn safe_name(&self) -> Option<&str> {
+        let name = self.name.as_deref()?;
+        let mut components = Path::new(name).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(part)), None) if part == name && !name.contains(['/', '\\']) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Set the recorded modification time and permissions on `path`
+    ///
+    /// Permissions are set last, so a read-only original still gets its
+    /// modification time. Permissions are skipped off Unix.
+    pub fn apply<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(modified) = self.modified {
+            File::options().write(true).open(path)?.set_modified(modified)?;
+        }
+        #[cfg(unix)]
+        if let Some(mode) = self.mode {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode & PERMISSION_BITS))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_read_and_apply() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (original, restored) = (dir.path().join("report.txt"), dir.path().join("out"));
+        std::fs::write(&original, b"data")?;
+        std::fs::write(&restored, b"data")?;
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        File::options().write(true).open(&original)?.set_modified(modified)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&original, std::fs::Permissions::from_mode(0o640))?;
+        }
+
+        let metadata = FileMetadata::read(&original)?;
+        assert_eq!(metadata.name.as_deref(), Some("report.txt"));
+        assert_eq!(metadata.modified, Some(modified));
+        metadata.apply(&restored)?;
+        assert_eq!(FileMetadata { name: metadata.name.clone(), ..FileMetadata::read(&restored)? }, metadata);
+        #[cfg(unix)]
+        assert_eq!(metadata.mode, Some(0o640));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_special_bits_are_not_restored() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("out");
+        std::fs::write(&path, b"data")?;
+        FileMetadata { mode: Some(0o6755), ..FileMetadata::default() }.apply(&path)?;
+        assert_eq!(std::fs::metadata(&path)?.permissions().mode() & 0o7777, 0o755);
+        Ok(())
+    }
+
+    #[test]
+    fn test_safe_name() {
+        let named = |name: &str| FileMetadata { name: Some(name.to_string()), ..FileMetadata::default() };
+        assert_eq!(named("report.pdf").safe_name(), Some("report.pdf"));
+        for unsafe_name in ["../report.pdf", "/etc/passwd", "a/b", "..", ".", "", "dir\\file"] {
+            assert_eq!(named(unsafe_name).safe_name(), None, "{}", unsafe_name);
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! On-disk cache of decoded frame payloads
+//!
+//! Reading byte ranges of the same video again and again (see
+//! `Decoder::read_range`) would extract and decode the same frames each
+//! time. With `DecodeConfig::frame_cache_dir` set, every frame payload a
+//! range read checks is kept under the video's checksum and the frame's
+//! index, and later reads only extract the frames they don't find there.
+//! Entries carry the payload's CRC32 and are ignored if it no longer
+//! matches, so a damaged entry costs a re-extraction, not a wrong read.
+
+use crate::atomic::write_atomic;
+use crate::checksum::{hash_file, HashAlgorithm};
+use crate::error::Result;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Decoded frame payloads, one file per video and frame
+pub struct FrameCache {
+    dir: PathBuf,
+}
+
+impl FrameCache {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self { dir: dir.as_ref().to_path_buf() }
+    }
+
+    /// Cache key for the frames of `video` (reads the whole video)
+    pub fn video_key(video: &Path) -> Result<String> {
+        hash_file(video, HashAlgorithm::Sha256)
+    }
+
+    fn entry_path(&self, video_key: &str, index: u32) -> PathBuf {
+        self.dir.join(video_key).join(format!("{}.chunk", index))
+    }
+
+    /// Payload of frame `index` of the video, if cached and intact
+    pub fn load(&self, video_key: &str, index: u32) -> Result<Option<Vec<u8>>> {
+        let path = self.entry_path(video_key, index);
+        let entry = match std::fs::read(&path) {
+            Ok(entry) => entry,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let Some((crc, payload)) = entry.split_first_chunk::<4>() else {
+            debug!("Ignoring truncated frame cache entry {}", path.display());
+            return Ok(None);
+        };
+        if crc32fast::hash(payload) != u32::from_le_bytes(*crc) {
+            debug!("Ignoring damaged frame cache entry {}", path.display());
+            return Ok(None);
+        }
+        Ok(Some(payload.to_vec()))
+    }
+
+    /// Remember the (CRC-checked) payload of frame `index` of the video
+    pub fn store(&self, video_key: &str, index: u32, payload: &[u8]) -> Result<()> {
+        let path = self.entry_path(video_key, index);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut entry = Vec::with_capacity(4 + payload.len());
+        entry.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+        entry.extend_from_slice(payload);
+        write_atomic(path, &entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_and_load() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = FrameCache::new(dir.path());
+        assert_eq!(cache.load("video", 3)?, None);
+        cache.store("video", 3, b"payload")?;
+        assert_eq!(cache.load("video", 3)?, Some(b"payload".to_vec()));
+        assert_eq!(cache.load("other", 3)?, None);
+
+        // A damaged entry is a miss
+        let path = cache.entry_path("video", 3);
+        let mut entry = std::fs::read(&path)?;
+        entry[5] ^= 0xff;
+        std::fs::write(&path, entry)?;
+        assert_eq!(cache.load("video", 3)?, None);
+        Ok(())
+    }
+}
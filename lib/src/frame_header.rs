@@ -0,0 +1,281 @@
+use crate::error::{F2V2FError, Result};
+use crate::streams::MAIN_STREAM;
+use image::{ImageBuffer, Rgba};
+use std::ops::{Deref, DerefMut};
+
+/// Magic bytes identifying an f2v2f frame header
+const HEADER_MAGIC: [u8; 2] = *b"FV";
+
+/// Magic bytes of a frame belonging to an extra stream (see `streams`)
+///
+/// Decoders that predate streams don't recognize it and skip the frame as
+/// foreign, so extra streams never break them.
+const STREAM_MAGIC: [u8; 2] = *b"FS";
+
+/// Current frame header layout version
+const HEADER_VERSION: u8 = 1;
+
+/// Serialized header length in bytes
+pub const HEADER_BYTES: usize = 16;
+
+/// Number of pixel rows at the top of each frame reserved for the header strip
+pub const HEADER_ROWS: u32 = 8;
+
+/// Flag: frame has a text overlay in its bottom-left corner (excluded from data)
+pub const FLAG_OVERLAY: u8 = 0x01;
+
+/// Flag: data region holds plain gray-level bytes (`RAW_ART_STYLE`), no pattern
+pub const FLAG_RAW: u8 = 0x02;
+
+/// Flag: data region has the showcase glow and vignette (`SHOWCASE_ART_STYLE`)
+pub const FLAG_SHOWCASE: u8 = 0x04;
+
+/// Flag: not a data frame but a crossfade between its neighbours; the
+/// decoder skips it (index is that of the next data frame, payload empty)
+pub const FLAG_TRANSITION: u8 = 0x08;
+
+/// The high nibble of the flags byte holds the pattern complexity level
+/// (0 = unmodulated, see `entropy`)
+const COMPLEXITY_SHIFT: u8 = 4;
+
+const HEADER_BITS: usize = HEADER_BYTES * 8;
+
+// Bits are drawn as dark/light gray rather than pure black/white so that
+// codec over/undershoot never clips, and read back by thresholding the mean.
+const BIT_LOW: u8 = 32;
+const BIT_HIGH: u8 = 224;
+const BIT_THRESHOLD: f32 = 128.0;
+
+/// Header stored in the reserved strip of every frame
+///
+/// Layout (little-endian):
+/// - bytes 0..2: magic `FV`
+/// - byte 2: layout version
+/// - byte 3: flags (`FLAG_*`), complexity level in the high nibble
+/// - bytes 4..8: frame index
+/// - bytes 8..12: payload length in bytes
+/// - bytes 12..16: CRC32 of the payload
+///
+/// Frames of an extra stream start with `FS` instead, and byte 2 holds the
+/// stream ID in place of the version; the rest of the layout is the same.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    /// Stream the frame belongs to (`MAIN_STREAM` for the file itself);
+    /// `index` counts frames within the stream
+    pub stream: u8,
+    pub index: u32,
+    pub payload_len: u32,
+    pub crc32: u32,
+    pub flags: u8,
+}
+
+impl FrameHeader {
+    /// Build the header for a payload chunk (before any padding is applied)
+    pub fn new(index: u32, payload: &[u8]) -> Self {
+        Self {
+            stream: MAIN_STREAM,
+            index,
+            payload_len: payload.len() as u32,
+            crc32: crc32fast::hash(payload),
+            flags: 0,
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; HEADER_BYTES] {
+        let mut bytes = [0u8; HEADER_BYTES];
+        if self.stream == MAIN_STREAM {
+            bytes[0..2].copy_from_slice(&HEADER_MAGIC);
+            bytes[2] = HEADER_VERSION;
+        } else {
+            bytes[0..2].copy_from_slice(&STREAM_MAGIC);
+            bytes[2] = self.stream;
+        }
+        bytes[3] = self.flags;
+        bytes[4..8].copy_from_slice(&self.index.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.payload_len.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.crc32.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < HEADER_BYTES {
+            return Err(F2V2FError::DecodingError(format!(
+                "Frame header too short: {} bytes",
+                bytes.len()
+            )));
+        }
+        let stream = if bytes[0..2] == STREAM_MAGIC && bytes[2] != MAIN_STREAM {
+            bytes[2]
+        } else if bytes[0..2] == HEADER_MAGIC {
+            MAIN_STREAM
+        } else {
+            return Err(F2V2FError::DecodingError(
+                "Frame header magic not found (not an f2v2f frame?)".to_string(),
+            ));
+        };
+        if stream == MAIN_STREAM && bytes[2] != HEADER_VERSION {
+            return Err(F2V2FError::DecodingError(format!(
+                "Unsupported frame header version {}",
+                bytes[2]
+            )));
+        }
+
+        let read_u32 = |offset: usize| {
+            u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+        };
+
+        Ok(Self {
+            stream,
+            flags: bytes[3],
+            index: read_u32(4),
+            payload_len: read_u32(8),
+            crc32: read_u32(12),
+        })
+    }
+
+    pub fn has_flag(&self, flag: u8) -> bool {
+        self.flags & flag != 0
+    }
+
+    /// Pattern complexity level the frame was rendered with
+    pub fn complexity(&self) -> u8 {
+        self.flags >> COMPLEXITY_SHIFT
+    }
+
+    /// Record the pattern complexity level (0-15)
+    pub fn set_complexity(&mut self, level: u8) {
+        self.flags = (self.flags & 0x0f) | (level.min(0x0f) << COMPLEXITY_SHIFT);
+    }
+
+    /// Check a decoded payload against the stored CRC
+    pub fn verify(&self, payload: &[u8]) -> bool {
+        payload.len() == self.payload_len as usize && crc32fast::hash(payload) == self.crc32
+    }
+
+    /// Render the header into the reserved strip at the top of the frame
+    ///
+    /// Bits are interleaved across the strip (pixel `p` carries bit `p % 128`)
+    /// so localized damage only weakens each bit's vote instead of erasing it.
+    pub fn write_to<C>(&self, img: &mut ImageBuffer<Rgba<u8>, C>)
+    where
+        C: Deref<Target = [u8]> + DerefMut,
+    {
+        let bytes = self.to_bytes();
+        let width = img.width();
+        let rows = HEADER_ROWS.min(img.height());
+
+        for y in 0..rows {
+            for x in 0..width {
+                let bit = ((y * width + x) as usize) % HEADER_BITS;
+                let set = (bytes[bit / 8] >> (bit % 8)) & 1 == 1;
+                let v = if set { BIT_HIGH } else { BIT_LOW };
+                img.put_pixel(x, y, Rgba([v, v, v, 255]));
+            }
+        }
+    }
+
+    /// Read the header back from the reserved strip of a frame
+    pub fn read_from(img: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> Result<Self> {
+        Self::read_from_raw(img.as_raw(), img.width())
+    }
+
+    /// Like `read_from`, but `None` for frames without a header at all
+    /// (black or title frames added by a platform) rather than an error
+    pub fn try_read_from(img: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> Result<Option<Self>> {
+        Self::try_read_from_raw(img.as_raw(), img.width())
+    }
+
+    /// `read_from` on a raw RGBA frame `width` pixels wide
+    pub fn read_from_raw(raw: &[u8], width: u32) -> Result<Self> {
+        Self::from_bytes(&Self::strip_bytes(raw, width))
+    }
+
+    /// `try_read_from` on a raw RGBA frame `width` pixels wide
+    pub fn try_read_from_raw(raw: &[u8], width: u32) -> Result<Option<Self>> {
+        let bytes = Self::strip_bytes(raw, width);
+        if bytes[0..2] != HEADER_MAGIC && bytes[0..2] != STREAM_MAGIC {
+            return Ok(None);
+        }
+        Self::from_bytes(&bytes).map(Some)
+    }
+
+    /// Majority-vote the header bytes out of the strip
+    fn strip_bytes(raw: &[u8], width: u32) -> [u8; HEADER_BYTES] {
+        let stride = width as usize * 4;
+        let mut sums = [0.0f32; HEADER_BITS];
+        let mut counts = [0u32; HEADER_BITS];
+
+        // A pixel's place in the strip picks its bit
+        let strip = raw.chunks_exact(stride.max(1)).take(HEADER_ROWS as usize).flat_map(|row| row.chunks_exact(4));
+        for (i, pixel) in strip.enumerate() {
+            let bit = i % HEADER_BITS;
+            sums[bit] += pixel[0] as f32;
+            counts[bit] += 1;
+        }
+
+        let mut bytes = [0u8; HEADER_BYTES];
+        for bit in 0..HEADER_BITS {
+            if counts[bit] > 0 && sums[bit] / counts[bit] as f32 >= BIT_THRESHOLD {
+                bytes[bit / 8] |= 1 << (bit % 8);
+            }
+        }
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_bytes_roundtrip() {
+        let header = FrameHeader::new(7, b"payload");
+        let parsed = FrameHeader::from_bytes(&header.to_bytes()).unwrap();
+        assert_eq!(parsed, header);
+        assert_eq!(parsed.payload_len, 7);
+        assert!(parsed.verify(b"payload"));
+        assert!(!parsed.verify(b"pay1oad"));
+    }
+
+    #[test]
+    fn test_header_pixel_roundtrip() {
+        let header = FrameHeader::new(123_456, &[0xAB; 300]);
+        let mut img = ImageBuffer::new(256, 256);
+        header.write_to(&mut img);
+        assert_eq!(FrameHeader::read_from(&img).unwrap(), header);
+        assert_eq!(FrameHeader::read_from_raw(img.as_raw(), 256).unwrap(), header);
+    }
+
+    #[test]
+    fn test_complexity_shares_flags_byte() {
+        let mut header = FrameHeader::new(1, b"x");
+        header.flags |= FLAG_OVERLAY | FLAG_TRANSITION;
+        header.set_complexity(11);
+        let parsed = FrameHeader::from_bytes(&header.to_bytes()).unwrap();
+        assert_eq!(parsed.complexity(), 11);
+        assert!(parsed.has_flag(FLAG_OVERLAY) && parsed.has_flag(FLAG_TRANSITION));
+        assert!(!parsed.has_flag(FLAG_RAW));
+    }
+
+    #[test]
+    fn test_stream_frames_use_their_own_magic() {
+        let mut header = FrameHeader::new(3, b"fec");
+        header.stream = 2;
+        let bytes = header.to_bytes();
+        assert_eq!(&bytes[0..3], b"FS\x02");
+        assert_eq!(FrameHeader::from_bytes(&bytes).unwrap(), header);
+
+        let mut img = ImageBuffer::new(256, 256);
+        header.write_to(&mut img);
+        assert_eq!(FrameHeader::try_read_from(&img).unwrap(), Some(header));
+        // What a decoder without streams checks for
+        assert_ne!(&FrameHeader::strip_bytes(img.as_raw(), 256)[0..2], &HEADER_MAGIC);
+    }
+
+    #[test]
+    fn test_missing_magic_rejected() {
+        let img = ImageBuffer::from_pixel(256, 256, Rgba([0, 0, 0, 255]));
+        assert!(FrameHeader::read_from(&img).is_err());
+        assert_eq!(FrameHeader::try_read_from(&img).unwrap(), None);
+    }
+}
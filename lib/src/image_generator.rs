@@ -1,16 +1,174 @@
 use image::{ImageBuffer, Rgba};
-use crate::error::Result;
+use crate::entropy::MAX_LEVEL;
+use crate::error::{F2V2FError, Result};
+use crate::frame_header::{FrameHeader, FLAG_OVERLAY, FLAG_RAW, FLAG_SHOWCASE, HEADER_ROWS};
+use crate::overlay::OverlayRegion;
+use std::ops::{Deref, DerefMut};
+
+/// Default generator seed
+pub const DEFAULT_SEED: u64 = 42;
+
+/// Art style that stores data bytes as plain gray levels, with no pattern
+pub const RAW_ART_STYLE: &str = "raw";
+
+/// Art style with the showcase finish (see `with_showcase`)
+pub const SHOWCASE_ART_STYLE: &str = "showcase";
+
+/// Every art style the encoder accepts
+pub const ART_STYLES: &[&str] = &["geometric", RAW_ART_STYLE, SHOWCASE_ART_STYLE];
+
+/// Times each byte must repeat within a geometric frame to decode reliably:
+/// under the pattern, one pixel's gray level is too coarse to resolve a byte
+/// and only the average of several is
+pub const ART_MIN_REPEATS: usize = 8;
+
+/// Times each byte must repeat within a showcase frame to decode reliably
+pub const SHOWCASE_MIN_REPEATS: usize = 128;
+
+/// Showcase finish: pattern scale leaving headroom for the glow
+const SHOWCASE_SCALE: f32 = 0.85;
+/// Showcase finish: strongest highlight glow (pattern units)
+const SHOWCASE_GLOW: f32 = 0.15;
+/// Showcase finish: brightness lost in the frame corners
+const SHOWCASE_VIGNETTE: f32 = 0.25;
+
+/// Pattern parameters derived from the seed
+///
+/// The decoder subtracts the base pattern to recover data, so it must be
+/// constructed with the same seed the encoder used.
+#[derive(Debug, Clone, Copy)]
+struct PatternParams {
+    center_x: f32,
+    center_y: f32,
+    frequency: f32,
+}
+
+impl PatternParams {
+    fn from_seed(seed: u64) -> Self {
+        // splitmix64 to spread nearby seeds into unrelated parameters
+        let mut state = seed;
+        let mut next = || {
+            state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            ((z ^ (z >> 31)) >> 40) as f32 / (1u64 << 24) as f32
+        };
+        Self {
+            center_x: 0.3 + next() * 0.4,
+            center_y: 0.3 + next() * 0.4,
+            frequency: 0.5 + next(),
+        }
+    }
+}
 
 /// Generates beautiful geometric artwork
+#[derive(Debug, Clone, Copy)]
 pub struct GeometricArtGenerator {
     width: u32,
     height: u32,
     seed: u64,
+    params: PatternParams,
+    /// Reserve the overlay corner so it carries no data
+    overlay: Option<OverlayRegion>,
+    /// Write data bytes directly as gray levels, skipping the art pattern
+    raw: bool,
+    /// Add highlight glow and a vignette on top of the pattern
+    showcase: bool,
+    /// Pattern complexity level (0 = unmodulated, 1 calm to `MAX_LEVEL` busy)
+    complexity: u8,
 }
 
 impl GeometricArtGenerator {
     pub fn new(width: u32, height: u32, seed: u64) -> Self {
-        Self { width, height, seed }
+        Self { width, height, seed, params: PatternParams::from_seed(seed), overlay: None, raw: false, showcase: false, complexity: 0 }
+    }
+
+    /// Store data bytes as plain gray levels instead of mixing them into the
+    /// pattern (faster to render and decode, but not art)
+    pub fn with_raw(mut self, enabled: bool) -> Self {
+        self.raw = enabled;
+        self
+    }
+
+    /// Exclude (or include) the text overlay corner from the data region
+    pub fn with_overlay(mut self, enabled: bool) -> Self {
+        self.overlay = if enabled {
+            Some(OverlayRegion::for_frame(self.width, self.height))
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Glow highlights and darken the corners of the data region
+    ///
+    /// Both effects depend only on position and seed, so the decoder divides
+    /// them back out; the lower contrast is made up for by averaging the
+    /// repeats of each byte, so chunks must stay below
+    /// `data_capacity() / SHOWCASE_MIN_REPEATS`.
+    pub fn with_showcase(mut self, enabled: bool) -> Self {
+        self.showcase = enabled;
+        self
+    }
+
+    /// Scale the pattern's frequency by a complexity level (see
+    /// `entropy::level_for`): level 1 is half as busy as the plain pattern,
+    /// `MAX_LEVEL` twice as busy, 0 leaves it unchanged
+    pub fn with_complexity(mut self, level: u8) -> Self {
+        self.complexity = level.min(MAX_LEVEL);
+        self
+    }
+
+    /// Set up to render or decode a frame with `header` (overlay, raw,
+    /// showcase and complexity as recorded in its flags)
+    pub fn for_header(self, header: &FrameHeader) -> Self {
+        self.with_overlay(header.has_flag(FLAG_OVERLAY))
+            .with_raw(header.has_flag(FLAG_RAW))
+            .with_showcase(header.has_flag(FLAG_SHOWCASE))
+            .with_complexity(header.complexity())
+    }
+
+    fn frequency_scale(&self) -> f32 {
+        match self.complexity {
+            0 => 1.0,
+            level => 0.5 * 4f32.powf((level - 1) as f32 / (MAX_LEVEL - 1) as f32),
+        }
+    }
+
+    /// Number of data bytes a single frame can hold (one byte per data pixel)
+    pub fn data_capacity(&self) -> usize {
+        let data_rows = self.height.saturating_sub(HEADER_ROWS) as usize;
+        let reserved = self.overlay.map_or(0, |region| {
+            let top = region.y.max(HEADER_ROWS);
+            let rows = (region.y + region.height).saturating_sub(top) as usize;
+            rows * region.width.min(self.width) as usize
+        });
+        self.width as usize * data_rows - reserved
+    }
+
+    /// Largest chunk a frame decodes reliably: `data_capacity()` over the
+    /// repeats each byte needs (raw frames need none)
+    pub fn chunk_capacity(&self) -> usize {
+        let repeats = if self.raw {
+            1
+        } else if self.showcase {
+            SHOWCASE_MIN_REPEATS
+        } else {
+            ART_MIN_REPEATS
+        };
+        self.data_capacity() / repeats
+    }
+
+    fn is_data_pixel(&self, x: u32, y: u32) -> bool {
+        y >= HEADER_ROWS && !self.overlay.is_some_and(|region| region.contains(x, y))
+    }
+
+    /// Coordinates of the pixels that carry data, in data order
+    pub fn data_pixels(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        (HEADER_ROWS..self.height)
+            .flat_map(move |y| (0..self.width).map(move |x| (x, y)))
+            .filter(move |&(x, y)| self.is_data_pixel(x, y))
     }
 
     /// Generate a geometric pattern image
@@ -37,18 +195,79 @@ impl GeometricArtGenerator {
         Ok(img)
     }
 
+    /// Bytes in one raw RGBA frame
+    pub fn frame_len(&self) -> usize {
+        self.width as usize * self.height as usize * 4
+    }
+
+    /// View a caller's `frame_len()`-byte buffer as a frame image
+    pub fn frame_view<'a>(&self, buf: &'a mut [u8]) -> Result<ImageBuffer<Rgba<u8>, &'a mut [u8]>> {
+        let len = buf.len();
+        ImageBuffer::from_raw(self.width, self.height, buf).ok_or_else(|| {
+            F2V2FError::EncodingError(format!(
+                "Frame buffer holds {} bytes, a {}x{} frame needs {}",
+                len,
+                self.width,
+                self.height,
+                self.frame_len()
+            ))
+        })
+    }
+
+    /// Generate a complete frame: header strip followed by the data region
+    pub fn generate_frame(&self, header: &FrameHeader, data: &[u8]) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>> {
+        let mut raw = vec![0u8; self.frame_len()];
+        self.render_frame(header, data, &mut raw)?;
+        Ok(ImageBuffer::from_raw(self.width, self.height, raw).expect("buffer is frame_len() bytes"))
+    }
+
+    /// Like `generate_frame`, but render straight into a caller's reusable
+    /// `frame_len()`-byte buffer (every byte is overwritten)
+    pub fn render_frame(&self, header: &FrameHeader, data: &[u8], buf: &mut [u8]) -> Result<()> {
+        let mut img = self.frame_view(buf)?;
+        self.fill_data_region(data, &mut img);
+        header.write_to(&mut img);
+        Ok(())
+    }
+
     /// Generate image from a chunk of binary data
+    ///
+    /// Only the data region below the header strip is filled; the top
+    /// `HEADER_ROWS` rows are left for `FrameHeader::write_to` and the
+    /// overlay corner (if enabled) for `overlay::draw_overlay`.
     pub fn generate_from_data(&self, data: &[u8]) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>> {
         let mut img = ImageBuffer::new(self.width, self.height);
+        self.fill_data_region(data, &mut img);
+        Ok(img)
+    }
 
+    /// Draw `data` into the data region; the header strip and overlay corner
+    /// are cleared, so a reused buffer keeps nothing of its last frame
+    fn fill_data_region<C>(&self, data: &[u8], img: &mut ImageBuffer<Rgba<u8>, C>)
+    where
+        C: Deref<Target = [u8]> + DerefMut,
+    {
         // Use data to seed the pattern generation
         let data_seed = self.bytes_to_seed(data);
 
+        let mut data_pixel = 0usize;
         for y in 0..self.height {
             for x in 0..self.width {
+                if !self.is_data_pixel(x, y) {
+                    img.put_pixel(x, y, Rgba([0, 0, 0, 0]));
+                    continue;
+                }
+                let pixel_idx = data_pixel % data.len();
+                data_pixel += 1;
+
+                if self.raw {
+                    let v = data[pixel_idx];
+                    img.put_pixel(x, y, Rgba([v, v, v, 255]));
+                    continue;
+                }
+
                 let fx = x as f32 / self.width as f32;
                 let fy = y as f32 / self.height as f32;
-                let pixel_idx = ((y * self.width + x) as usize) % data.len();
 
                 // Combine geometric pattern with actual data
                 let pattern = self.compute_pattern_with_data(fx, fy, data[pixel_idx]);
@@ -57,23 +276,23 @@ impl GeometricArtGenerator {
                 img.put_pixel(x, y, color);
             }
         }
-
-        Ok(img)
     }
 
     fn compute_pattern(&self, x: f32, y: f32) -> f32 {
         // Create multiple overlapping geometric patterns
-        let distance = ((x - 0.5).powi(2) + (y - 0.5).powi(2)).sqrt();
+        let PatternParams { center_x, center_y, frequency } = self.params;
+        let frequency = frequency * self.frequency_scale();
+        let distance = ((x - center_x).powi(2) + (y - center_y).powi(2)).sqrt();
         let angle = y.atan2(x);
 
         // Concentric circles
-        let circles = (distance * 10.0).sin();
+        let circles = (distance * 10.0 * frequency).sin();
 
         // Grid patterns
-        let grid = ((x * 5.0).sin() * (y * 5.0).cos()).abs();
+        let grid = ((x * 5.0 * frequency).sin() * (y * 5.0 * frequency).cos()).abs();
 
         // Spiral
-        let spiral = ((distance * 20.0 + angle).sin()).abs();
+        let spiral = ((distance * 20.0 * frequency + angle).sin()).abs();
 
         // Combine patterns
         (circles + grid + spiral) / 3.0
@@ -85,7 +304,33 @@ impl GeometricArtGenerator {
         let data_influence = ((data_byte as f32 + 0.5) / 256.0) * 2.0 - 1.0; 
 
         // Increase data influence for better robustness (90% signal)
-        base_pattern * 0.1 + data_influence * 0.9
+        let pattern = base_pattern * 0.1 + data_influence * 0.9;
+        if self.showcase {
+            self.apply_finish(x, y, base_pattern, pattern)
+        } else {
+            pattern
+        }
+    }
+
+    /// Vignette gain and glow lift at a position
+    fn finish_at(&self, x: f32, y: f32, base_pattern: f32) -> (f32, f32) {
+        // Squared distance from the center, 0 there and 1 in the corners
+        let r2 = ((x - 0.5).powi(2) + (y - 0.5).powi(2)) * 2.0;
+        let gain = 1.0 - SHOWCASE_VIGNETTE * r2;
+        let glow = SHOWCASE_GLOW * base_pattern.clamp(0.0, 1.0).powi(2);
+        (gain, glow)
+    }
+
+    /// Showcase finish, applied to brightness so the vignette fades to black
+    /// (never clips: the result stays in [-1, 1])
+    fn apply_finish(&self, x: f32, y: f32, base_pattern: f32, pattern: f32) -> f32 {
+        let (gain, glow) = self.finish_at(x, y, base_pattern);
+        gain * (pattern * SHOWCASE_SCALE + glow + 1.0) - 1.0
+    }
+
+    fn remove_finish(&self, x: f32, y: f32, base_pattern: f32, pattern: f32) -> f32 {
+        let (gain, glow) = self.finish_at(x, y, base_pattern);
+        ((pattern + 1.0) / gain - 1.0 - glow) / SHOWCASE_SCALE
     }
 
     fn pattern_to_color(&self, pattern: f32, _base_hue: f32) -> Rgba<u8> {
@@ -98,51 +343,99 @@ impl GeometricArtGenerator {
     }
 
 
-    /// Decode data from an image
+    /// Decode data from an image's data region (the header strip is skipped)
     pub fn decode_from_image(&self, img: &ImageBuffer<Rgba<u8>, Vec<u8>>, chunk_size: usize) -> Result<Vec<u8>> {
-        let mut data = vec![0u8; chunk_size];
-        let mut accumulations = vec![0.0f32; chunk_size];
+        self.decode_from_raw(img.as_raw(), chunk_size)
+    }
+
+    /// Like `decode_from_image`, on a raw `frame_len()`-byte RGBA frame as
+    /// ffmpeg writes it
+    pub fn decode_from_raw(&self, raw: &[u8], chunk_size: usize) -> Result<Vec<u8>> {
+        let estimates = self.estimate_bytes_raw(raw, chunk_size)?;
+        // Rounding the average of 500+ pixels should be extremely robust
+        Ok(estimates.iter().map(|v| v.round().max(0.0).min(255.0) as u8).collect())
+    }
+
+    /// Unrounded estimate of each data byte: the mean over all its repeats
+    /// (0 for bytes the frame has no pixel for). How far these sit from whole
+    /// numbers shows how much the codec disturbed the frame.
+    pub fn estimate_bytes(&self, img: &ImageBuffer<Rgba<u8>, Vec<u8>>, chunk_size: usize) -> Result<Vec<f32>> {
+        self.estimate_bytes_raw(img.as_raw(), chunk_size)
+    }
+
+    /// Like `estimate_bytes`, on a raw `frame_len()`-byte RGBA frame, read a
+    /// row at a time
+    pub fn estimate_bytes_raw(&self, raw: &[u8], chunk_size: usize) -> Result<Vec<f32>> {
+        if raw.len() != self.frame_len() {
+            return Err(F2V2FError::DecodingError(format!(
+                "Frame holds {} bytes, a {}x{} frame has {}",
+                raw.len(),
+                self.width,
+                self.height,
+                self.frame_len()
+            )));
+        }
+        // Sums until the end, then the means
+        let mut estimates = vec![0.0f32; chunk_size];
         let mut counts = vec![0u32; chunk_size];
 
-        let base_hue = 0.0; // Consistently ignored in lightness-based extraction
+        let stride = self.width as usize * 4;
+        let mut data_pixel = 0usize;
+        for (y, row) in raw.chunks_exact(stride.max(1)).enumerate().skip(HEADER_ROWS as usize) {
+            let y = y as u32;
+            let fy = y as f32 / self.height as f32;
+            for (x, pixel) in row.chunks_exact(4).enumerate() {
+                let x = x as u32;
+                if !self.is_data_pixel(x, y) {
+                    continue;
+                }
+                let pixel_idx = data_pixel % chunk_size;
+                data_pixel += 1;
+                counts[pixel_idx] += 1;
+
+                if self.raw {
+                    estimates[pixel_idx] += pixel[0] as f32;
+                    continue;
+                }
 
-        for y in 0..self.height {
-            for x in 0..self.width {
-                let pixel = img.get_pixel(x, y);
                 let fx = x as f32 / self.width as f32;
-                let fy = y as f32 / self.height as f32;
-                let pixel_idx = ((y * self.width + x) as usize) % chunk_size;
-
-                // Reverse color to pattern
-                let pattern = self.color_to_pattern(pixel, base_hue);
-                
-                let base_pattern = self.compute_pattern(fx, fy);
-                // pattern = base_pattern * 0.1 + data_influence * 0.9
-                let data_influence = (pattern - base_pattern * 0.1) / 0.9;
-                
-                // data_influence = ((data_byte + 0.5) / 256.0) * 2.0 - 1.0
-                // (data_influence + 1.0) / 2.0 = (data_byte + 0.5) / 256.0
-                // data_byte = ((data_influence + 1.0) / 2.0 * 256.0) - 0.5
-                let data_byte = (data_influence + 1.0) / 2.0 * 256.0 - 0.5;
-
-                accumulations[pixel_idx] += data_byte;
-                counts[pixel_idx] += 1;
+                estimates[pixel_idx] += self.data_byte_at(fx, fy, pixel[0]);
             }
         }
 
-        for i in 0..chunk_size {
-            if counts[i] > 0 {
-                // Rounding the average of 500+ pixels should be extremely robust
-                data[i] = (accumulations[i] / counts[i] as f32).round().max(0.0).min(255.0) as u8;
+        for (estimate, &count) in estimates.iter_mut().zip(&counts) {
+            if count > 0 {
+                *estimate /= count as f32;
             }
         }
 
-        Ok(data)
+        Ok(estimates)
+    }
+
+    /// The data byte (unrounded) a pixel of gray level `value` at `(x, y)`
+    /// stands for, undoing the pattern
+    fn data_byte_at(&self, x: f32, y: f32, value: u8) -> f32 {
+        // Reverse color to pattern
+        let pattern = self.color_to_pattern(value);
+
+        let base_pattern = self.compute_pattern(x, y);
+        let pattern = if self.showcase {
+            self.remove_finish(x, y, base_pattern, pattern)
+        } else {
+            pattern
+        };
+        // pattern = base_pattern * 0.1 + data_influence * 0.9
+        let data_influence = (pattern - base_pattern * 0.1) / 0.9;
+
+        // data_influence = ((data_byte + 0.5) / 256.0) * 2.0 - 1.0
+        // (data_influence + 1.0) / 2.0 = (data_byte + 0.5) / 256.0
+        // data_byte = ((data_influence + 1.0) / 2.0 * 256.0) - 0.5
+        (data_influence + 1.0) / 2.0 * 256.0 - 0.5
     }
 
-    fn color_to_pattern(&self, color: &Rgba<u8>, _base_hue: f32) -> f32 {
-        let v = color[0] as f32 / 255.0;
-        
+    fn color_to_pattern(&self, value: u8) -> f32 {
+        let v = value as f32 / 255.0;
+
         // v = normalized = (pattern + 1.0) / 2.0
         v * 2.0 - 1.0
     }
@@ -171,4 +464,132 @@ mod tests {
         assert!(pattern.is_finite());
         assert!(pattern >= -2.0 && pattern <= 2.0);
     }
+
+    #[test]
+    fn test_frame_roundtrip_with_header() {
+        let gen = GeometricArtGenerator::new(256, 256, 42);
+        let payload: Vec<u8> = (0..=255u8).cycle().take(1000).collect();
+        let header = FrameHeader::new(3, &payload);
+
+        let img = gen.generate_frame(&header, &payload).unwrap();
+        let decoded_header = FrameHeader::read_from(&img).unwrap();
+        let decoded = gen.decode_from_image(&img, payload.len()).unwrap();
+
+        assert_eq!(decoded_header, header);
+        assert!(decoded_header.verify(&decoded));
+    }
+
+    #[test]
+    fn test_render_into_reused_buffer() {
+        let gen = GeometricArtGenerator::new(256, 256, 42).with_overlay(true);
+        let payload: Vec<u8> = (0..=255u8).cycle().take(1000).collect();
+        let header = FrameHeader::new(3, &payload);
+
+        // Leftovers from a previous frame don't show through
+        let mut buf = vec![0xAB; gen.frame_len()];
+        gen.render_frame(&header, &payload, &mut buf).unwrap();
+        assert_eq!(buf, gen.generate_frame(&header, &payload).unwrap().into_raw());
+        assert!(gen.render_frame(&header, &payload, &mut buf[1..]).is_err());
+    }
+
+    #[test]
+    fn test_decode_from_raw_frame() {
+        let gen = GeometricArtGenerator::new(320, 180, 42).with_showcase(true);
+        let payload: Vec<u8> = (0..=255u8).cycle().take(200).collect();
+        let raw = gen.generate_frame(&FrameHeader::new(0, &payload), &payload).unwrap().into_raw();
+        assert_eq!(gen.decode_from_raw(&raw, payload.len()).unwrap(), payload);
+        assert!(gen.decode_from_raw(&raw[4..], payload.len()).is_err());
+    }
+
+    #[test]
+    fn test_seed_changes_pattern_but_not_data() {
+        let a = GeometricArtGenerator::new(256, 256, 1);
+        let b = GeometricArtGenerator::new(256, 256, 2);
+        assert_ne!(a.compute_pattern(0.25, 0.75), b.compute_pattern(0.25, 0.75));
+
+        let payload: Vec<u8> = (0..=255u8).collect();
+        let img = b.generate_from_data(&payload).unwrap();
+        assert_eq!(b.decode_from_image(&img, payload.len()).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_overlay_does_not_affect_data() {
+        let gen = GeometricArtGenerator::new(256, 256, 42).with_overlay(true);
+        let payload: Vec<u8> = (0..=255u8).rev().cycle().take(2000).collect();
+
+        let mut img = gen.generate_from_data(&payload).unwrap();
+        crate::overlay::draw_overlay(&mut img, &["F2V2F".to_string(), "FRAME 1/1".to_string()]);
+
+        assert_eq!(gen.decode_from_image(&img, payload.len()).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_chunk_capacity_leaves_repeats() {
+        let gen = GeometricArtGenerator::new(200, 120, 9);
+        assert_eq!(gen.chunk_capacity(), gen.data_capacity() / ART_MIN_REPEATS);
+        assert_eq!(gen.with_raw(true).chunk_capacity(), gen.data_capacity());
+        assert_eq!(gen.with_showcase(true).chunk_capacity(), gen.data_capacity() / SHOWCASE_MIN_REPEATS);
+
+        // Random bytes at the largest chunk still come back exactly
+        let payload: Vec<u8> = (0..gen.chunk_capacity() as u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8).collect();
+        let img = gen.generate_from_data(&payload).unwrap();
+        assert_eq!(gen.decode_from_image(&img, payload.len()).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_raw_frames_store_plain_bytes() {
+        let gen = GeometricArtGenerator::new(256, 256, 42).with_raw(true);
+        let payload: Vec<u8> = (0..=255u8).cycle().take(gen.data_capacity()).collect();
+
+        let img = gen.generate_from_data(&payload).unwrap();
+        assert_eq!(img.get_pixel(0, HEADER_ROWS)[0], payload[0]);
+        assert_eq!(img.get_pixel(7, HEADER_ROWS)[0], payload[7]);
+        assert_eq!(gen.decode_from_image(&img, payload.len()).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_showcase_finish_still_decodes() {
+        let gen = GeometricArtGenerator::new(640, 360, 42).with_showcase(true);
+        let plain = GeometricArtGenerator::new(640, 360, 42);
+        let payload: Vec<u8> = (0..=255u8).cycle().take(gen.data_capacity() / SHOWCASE_MIN_REPEATS).collect();
+
+        let mut img = gen.generate_from_data(&payload).unwrap();
+        // Corners come out darker than with the plain pattern
+        let corner = |img: &ImageBuffer<Rgba<u8>, Vec<u8>>| img.get_pixel(639, 359)[0];
+        assert!(corner(&img) < corner(&plain.generate_from_data(&payload).unwrap()));
+
+        // Survives the limited-range YUV round trip ffmpeg applies
+        for pixel in img.pixels_mut() {
+            let y = (16.0 + pixel[0] as f32 * 219.0 / 255.0).round();
+            let v = ((y - 16.0) * 255.0 / 219.0).round() as u8;
+            *pixel = Rgba([v, v, v, 255]);
+        }
+        assert_eq!(gen.decode_from_image(&img, payload.len()).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_complexity_changes_pattern_but_not_data() {
+        let calm = GeometricArtGenerator::new(256, 256, 42).with_complexity(1);
+        let busy = calm.with_complexity(MAX_LEVEL);
+        assert_eq!(calm.frequency_scale(), 0.5);
+        assert_eq!(busy.frequency_scale(), 2.0);
+        assert_ne!(calm.compute_pattern(0.25, 0.75), busy.compute_pattern(0.25, 0.75));
+
+        let payload: Vec<u8> = (0..=255u8).collect();
+        let img = busy.generate_from_data(&payload).unwrap();
+        assert_eq!(busy.decode_from_image(&img, payload.len()).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_data_capacity_counts_data_pixels() {
+        for overlay in [false, true] {
+            let gen = GeometricArtGenerator::new(640, 360, 42).with_overlay(overlay);
+            let counted = (0..360)
+                .flat_map(|y| (0..640).map(move |x| (x, y)))
+                .filter(|&(x, y)| gen.is_data_pixel(x, y))
+                .count();
+            assert_eq!(gen.data_capacity(), counted);
+            assert_eq!(gen.data_pixels().count(), counted);
+        }
+    }
 }
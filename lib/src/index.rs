@@ -0,0 +1,101 @@
+//! Frame index: where each data frame sits in the video
+//!
+//! Besides its data frames a video holds manifest copies, crossfades,
+//! extra-stream frames and the end marker, so data frame `i` is rarely
+//! video frame `i`. The index, recorded in the manifest, maps payload bytes
+//! to data frames and data frames to video frames, so a decoder can extract
+//! just the frames a byte range needs (`Decoder::read_range`) or pick a
+//! decode up part way through.
+//!
+//! Data frames come in runs spaced `stride` video frames apart (a frame and
+//! the crossfades leading to the next); a new run starts wherever other
+//! frames are slotted in between, so the index stays small however long
+//! the video.
+
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameIndex {
+    /// Payload bytes per data frame
+    pub chunk_size: u64,
+    /// Video frames from one data frame to the next within a run
+    pub stride: u64,
+    /// Data frames in the video
+    pub frames: u64,
+    /// (first data frame, its video frame) of each run, in order
+    pub runs: Vec<(u64, u64)>,
+}
+
+impl FrameIndex {
+    pub fn new(chunk_size: u64, stride: u64) -> Self {
+        Self { chunk_size, stride: stride.max(1), frames: 0, runs: Vec::new() }
+    }
+
+    /// Record that data frame `frame`, the one after the last recorded, is
+    /// video frame `position`
+    pub fn push(&mut self, frame: u64, position: u64) {
+        if self.extrapolate(frame) != Some(position) {
+            self.runs.push((frame, position));
+        }
+        self.frames = frame + 1;
+    }
+
+    /// Video frame holding data frame `frame`
+    pub fn position(&self, frame: u64) -> Option<u64> {
+        self.extrapolate(frame).filter(|_| frame < self.frames)
+    }
+
+    /// Where data frame `frame` falls if its run goes on that far
+    fn extrapolate(&self, frame: u64) -> Option<u64> {
+        let run = self.runs.partition_point(|&(first, _)| first <= frame).checked_sub(1)?;
+        let (first, position) = self.runs[run];
+        Some(position + (frame - first) * self.stride)
+    }
+
+    /// Data frames holding payload bytes `start..end`
+    pub fn frames_for(&self, start: u64, end: u64) -> Range<u64> {
+        if start >= end || self.chunk_size == 0 {
+            return 0..0;
+        }
+        let first = (start / self.chunk_size).min(self.frames);
+        let last = ((end - 1) / self.chunk_size + 1).min(self.frames);
+        first..last
+    }
+
+    /// Video frames spanning data frames `frames`: the window's first frame
+    /// and length
+    pub fn window(&self, frames: Range<u64>) -> Option<(u64, u64)> {
+        if frames.is_empty() {
+            return None;
+        }
+        let start = self.position(frames.start)?;
+        let end = self.position(frames.end - 1)? + 1;
+        Some((start, end - start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runs_and_lookups() {
+        // Two manifest frames, one crossfade between data frames and an
+        // extra-stream frame after data frame 2
+        let mut index = FrameIndex::new(1000, 2);
+        for (frame, position) in [(0, 2), (1, 4), (2, 6), (3, 9), (4, 11)] {
+            index.push(frame, position);
+        }
+        assert_eq!(index.runs, [(0, 2), (3, 9)]);
+        assert_eq!(index.frames, 5);
+        assert_eq!((0..5).map(|f| index.position(f)).collect::<Vec<_>>(), [2, 4, 6, 9, 11].map(Some));
+        assert_eq!(index.position(5), None);
+
+        assert_eq!(index.frames_for(1500, 3001), 1..4);
+        assert_eq!(index.frames_for(4500, 9000), 4..5);
+        assert_eq!(index.frames_for(10, 10), 0..0);
+        assert_eq!(index.window(1..4), Some((4, 6)));
+        assert_eq!(index.window(2..2), None);
+    }
+}
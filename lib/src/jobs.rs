@@ -0,0 +1,414 @@
+//! Registry of the encodes and decodes running in this process
+//!
+//! Every encode (`pipeline::run_encode`) and decode (`Decoder::decode`)
+//! registers itself while it runs, with an ID, its state, the stage it is
+//! in and how many bytes that stage has read (see `OperationHandle`).
+//! `list` and `get` query the registry, and `pause` and `resume` control a
+//! job by ID. The last `FINISHED_KEPT` finished jobs stay listed with their
+//! outcome.
+//!
+//! Running jobs are also published as JSON files in `jobs_dir()`, refreshed
+//! every `PUBLISH_INTERVAL` and removed when they end, so other processes
+//! (`f2v2f jobs`, a dashboard) see them through `list_all`.
+
+use crate::error::{F2V2FError, Result};
+use crate::operation::OperationHandle;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::debug;
+
+pub type JobId = u64;
+
+/// Finished jobs kept in the registry
+pub const FINISHED_KEPT: usize = 32;
+
+/// How often running jobs are written to `jobs_dir()`
+pub const PUBLISH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobKind {
+    Encode,
+    Decode,
+}
+
+impl fmt::Display for JobKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            JobKind::Encode => "encode",
+            JobKind::Decode => "decode",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Running,
+    Paused,
+    Succeeded,
+    Failed,
+}
+
+impl JobState {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobState::Succeeded | JobState::Failed)
+    }
+}
+
+impl fmt::Display for JobState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            JobState::Running => "running",
+            JobState::Paused => "paused",
+            JobState::Succeeded => "succeeded",
+            JobState::Failed => "failed",
+        })
+    }
+}
+
+/// Snapshot of a job
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobInfo {
+    pub id: JobId,
+    /// Process running the job
+    pub pid: u32,
+    pub kind: JobKind,
+    pub state: JobState,
+    pub input: String,
+    pub output: String,
+    /// Current step (e.g. "compressing", "composing", "extracting")
+    pub stage: String,
+    /// Bytes the current stage has read
+    pub done: u64,
+    /// Bytes the current stage will read, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    /// Unix time the job started, in seconds
+    pub started_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+struct Entry {
+    info: JobInfo,
+    operation: OperationHandle,
+}
+
+impl Entry {
+    fn snapshot(&self) -> JobInfo {
+        let mut info = self.info.clone();
+        if !info.state.is_finished() {
+            info.done = self.operation.bytes_read();
+            if self.operation.is_paused() {
+                info.state = JobState::Paused;
+            }
+        }
+        info
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    jobs: BTreeMap<JobId, Entry>,
+    next_id: JobId,
+    /// Whether the thread publishing running jobs is alive
+    publishing: bool,
+}
+
+lazy_static! {
+    static ref REGISTRY: Mutex<Registry> = Mutex::new(Registry::default());
+}
+
+fn registry() -> std::sync::MutexGuard<'static, Registry> {
+    // Entries stay consistent even if a holder panicked
+    REGISTRY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Registration of a running operation; finishes the job when dropped
+pub(crate) struct Job {
+    id: JobId,
+    operation: OperationHandle,
+}
+
+/// Add a job for an operation controlled by `operation`
+pub(crate) fn register(kind: JobKind, input: String, output: String, operation: &OperationHandle) -> Job {
+    operation.reset_bytes_read();
+    let mut registry = registry();
+    registry.next_id += 1;
+    let id = registry.next_id;
+    let info = JobInfo {
+        id,
+        pid: std::process::id(),
+        kind,
+        state: JobState::Running,
+        input,
+        output,
+        stage: "starting".to_string(),
+        done: 0,
+        total: None,
+        started_at: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+        error: None,
+    };
+    debug!("📋 Job {}: {} {} → {}", id, kind, info.input, info.output);
+    // Published under the lock, so a job that ends at once isn't left behind
+    publish(&jobs_dir(), &info);
+    registry.jobs.insert(id, Entry { info, operation: operation.clone() });
+    if !registry.publishing {
+        registry.publishing = true;
+        std::thread::spawn(publish_running);
+    }
+    Job { id, operation: operation.clone() }
+}
+
+impl Job {
+    /// Enter the next stage, which will read `total` bytes if known
+    pub(crate) fn stage(&self, stage: &str, total: Option<u64>) {
+        self.operation.reset_bytes_read();
+        if let Some(entry) = registry().jobs.get_mut(&self.id) {
+            entry.info.stage = stage.to_string();
+            entry.info.total = total;
+        }
+    }
+
+    /// Record the outcome of the job
+    pub(crate) fn finish<T>(self, result: &Result<T>) {
+        self.end(result.as_ref().err().map(|e| e.to_string()));
+    }
+
+    fn end(&self, error: Option<String>) {
+        let mut registry = registry();
+        let Some(entry) = registry.jobs.get_mut(&self.id) else {
+            return;
+        };
+        if entry.info.state.is_finished() {
+            return;
+        }
+        entry.info = entry.snapshot();
+        entry.info.state = if error.is_some() { JobState::Failed } else { JobState::Succeeded };
+        entry.info.error = error;
+        debug!("📋 Job {} {}", self.id, entry.info.state);
+        unpublish(&jobs_dir(), &entry.info);
+
+        let finished: Vec<JobId> =
+            registry.jobs.iter().filter(|(_, e)| e.info.state.is_finished()).map(|(&id, _)| id).collect();
+        for id in &finished[..finished.len().saturating_sub(FINISHED_KEPT)] {
+            registry.jobs.remove(id);
+        }
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        // A panic or a cancelled future ends the job without a result
+        self.end(Some("Ended without a result".to_string()));
+    }
+}
+
+/// Jobs of this process, oldest first
+pub fn list() -> Vec<JobInfo> {
+    registry().jobs.values().map(Entry::snapshot).collect()
+}
+
+pub fn get(id: JobId) -> Option<JobInfo> {
+    registry().jobs.get(&id).map(Entry::snapshot)
+}
+
+/// Latest job run under `operation`
+pub fn for_operation(operation: &OperationHandle) -> Option<JobInfo> {
+    registry().jobs.values().rev().find(|entry| entry.operation.ptr_eq(operation)).map(Entry::snapshot)
+}
+
+/// Pause a running job of this process
+pub fn pause(id: JobId) -> Result<()> {
+    running_operation(id)?.pause();
+    Ok(())
+}
+
+/// Resume a paused job of this process
+pub fn resume(id: JobId) -> Result<()> {
+    running_operation(id)?.resume();
+    Ok(())
+}
+
+fn running_operation(id: JobId) -> Result<OperationHandle> {
+    match registry().jobs.get(&id) {
+        Some(entry) if !entry.info.state.is_finished() => Ok(entry.operation.clone()),
+        Some(_) => Err(F2V2FError::InvalidInput(format!("Job {} has finished", id))),
+        None => Err(F2V2FError::InvalidInput(format!("No job {}", id))),
+    }
+}
+
+/// Directory running jobs are published in: `F2V2F_JOBS_DIR`, else a
+/// per-user directory under the system temp dir
+pub fn jobs_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("F2V2F_JOBS_DIR") {
+        return PathBuf::from(dir);
+    }
+    #[cfg(unix)]
+    let name = format!("f2v2f-jobs-{}", unsafe { libc::getuid() });
+    #[cfg(not(unix))]
+    let name = "f2v2f-jobs".to_string();
+    std::env::temp_dir().join(name)
+}
+
+/// Running jobs of every process publishing in `jobs_dir()`
+pub fn list_all() -> Result<Vec<JobInfo>> {
+    list_all_in(&jobs_dir())
+}
+
+/// Running jobs published in `dir`; files left behind by processes that
+/// died are removed. This process's jobs come from the registry.
+pub fn list_all_in(dir: &Path) -> Result<Vec<JobInfo>> {
+    let pid = std::process::id();
+    let mut jobs: Vec<JobInfo> = list().into_iter().filter(|job| !job.state.is_finished()).collect();
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(jobs),
+        Err(e) => return Err(e.into()),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        // A job may end between listing and reading
+        let Ok(json) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        match serde_json::from_str::<JobInfo>(&json) {
+            Ok(job) if job.pid == pid => {}
+            Ok(job) if process_alive(job.pid) => jobs.push(job),
+            _ => {
+                debug!("Removing stale job file {}", path.display());
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+    }
+    jobs.sort_by_key(|job| (job.started_at, job.pid, job.id));
+    Ok(jobs)
+}
+
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // Signal 0 only checks that the process exists
+    let exists = unsafe { libc::kill(pid, 0) } == 0;
+    exists || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn process_alive(_pid: u32) -> bool {
+    true
+}
+
+fn job_file(dir: &Path, job: &JobInfo) -> PathBuf {
+    dir.join(format!("{}-{}.json", job.pid, job.id))
+}
+
+/// Write `job` to `dir`; failing to only costs other processes the view
+fn publish(dir: &Path, job: &JobInfo) {
+    let written = std::fs::create_dir_all(dir)
+        .map_err(F2V2FError::from)
+        .and_then(|_| serde_json::to_vec(job).map_err(|e| F2V2FError::Io(e.to_string())))
+        .and_then(|json| crate::atomic::write_atomic(job_file(dir, job), &json));
+    if let Err(e) = written {
+        debug!("Could not publish job {}: {}", job.id, e);
+    }
+}
+
+fn unpublish(dir: &Path, job: &JobInfo) {
+    let _ = std::fs::remove_file(job_file(dir, job));
+}
+
+/// Refresh the files of running jobs until none is left
+fn publish_running() {
+    loop {
+        std::thread::sleep(PUBLISH_INTERVAL);
+        let mut registry = registry();
+        let running: Vec<JobInfo> =
+            registry.jobs.values().filter(|e| !e.info.state.is_finished()).map(Entry::snapshot).collect();
+        if running.is_empty() {
+            registry.publishing = false;
+            return;
+        }
+        let dir = jobs_dir();
+        for job in &running {
+            publish(&dir, job);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_job_lifecycle() -> Result<()> {
+        let operation = OperationHandle::new();
+        let job = register(JobKind::Decode, "in.mp4".into(), "out.bin".into(), &operation);
+        let id = job.id;
+        job.stage("writing", Some(10));
+        operation.reader(&b"abcd"[..]).read_to_end(&mut Vec::new())?;
+        let info = get(id).unwrap();
+        assert_eq!((info.state, info.stage.as_str(), info.done, info.total), (JobState::Running, "writing", 4, Some(10)));
+        assert!(list().iter().any(|job| job.id == id));
+
+        pause(id)?;
+        assert!(operation.is_paused());
+        assert_eq!(get(id).unwrap().state, JobState::Paused);
+        resume(id)?;
+        assert_eq!(get(id).unwrap().state, JobState::Running);
+
+        job.finish(&Err::<(), _>(F2V2FError::DecodingError("bad frame".into())));
+        let info = get(id).unwrap();
+        assert_eq!(info.state, JobState::Failed);
+        assert!(info.error.as_deref().is_some_and(|e| e.contains("bad frame")));
+        assert!(!job_file(&jobs_dir(), &info).exists());
+        assert!(pause(id).is_err());
+
+        // Dropping a job without a result fails it
+        let dropped = register(JobKind::Encode, "a".into(), "b".into(), &OperationHandle::new()).id;
+        assert_eq!(get(dropped).unwrap().state, JobState::Failed);
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_all_skips_dead_processes() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let job = |pid: u32, id: JobId| JobInfo {
+            id,
+            pid,
+            kind: JobKind::Encode,
+            state: JobState::Running,
+            input: "in".into(),
+            output: "out.mp4".into(),
+            stage: "composing".into(),
+            done: 5,
+            total: Some(10),
+            started_at: 0,
+            error: None,
+        };
+        // The parent (the test harness) is alive; no process has the largest pid
+        #[cfg(unix)]
+        let alive = unsafe { libc::getppid() } as u32;
+        #[cfg(not(unix))]
+        let alive = 1;
+        publish(dir.path(), &job(alive, 1));
+        publish(dir.path(), &job(i32::MAX as u32, 2));
+
+        let jobs: Vec<JobInfo> = list_all_in(dir.path())?.into_iter().filter(|j| j.pid != std::process::id()).collect();
+        assert_eq!(jobs, vec![job(alive, 1)]);
+        #[cfg(unix)]
+        assert!(!job_file(dir.path(), &job(i32::MAX as u32, 2)).exists());
+        Ok(())
+    }
+}
@@ -8,13 +8,11 @@
 //! ### Encoding a file:
 //! ```ignore
 //! use f2v2f::config::EncodeConfig;
-//! use f2v2f::encoder::Encoder;
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<()> {
 //!     let config = EncodeConfig::default();
-//!     let encoder = Encoder::new(config)?;
-//!     let (info, data) = encoder.encode("file.bin").await?;
+//!     let info = f2v2f::encode_file_to_video("file.bin", "video.mp4", &config).await?;
 //!     Ok(())
 //! }
 //! ```
@@ -22,26 +20,72 @@
 //! ### Decoding a video:
 //! ```ignore
 //! use f2v2f::config::DecodeConfig;
-//! use f2v2f::decoder::Decoder;
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<()> {
 //!     let config = DecodeConfig::default();
-//!     let decoder = Decoder::new(config)?;
-//!     let info = decoder.decode("video.mp4", "output.bin").await?;
+//!     let info = f2v2f::decode_video_to_file("video.mp4", "output.bin", &config).await?;
 //!     Ok(())
 //! }
 //! ```
 
+pub mod atomic;
+pub mod backend;
+pub mod bench;
+pub mod backpressure;
+pub mod cache;
+pub mod capabilities;
+pub mod chapters;
+pub mod checksum;
+pub mod chunk_manifest;
+pub mod codec;
 pub mod config;
+pub mod container;
+pub mod content_type;
 pub mod decoder;
+pub mod dictionary;
 pub mod encoder;
+pub mod entropy;
 pub mod error;
+pub mod events;
+pub mod extract_format;
+pub mod file_metadata;
+#[cfg(feature = "fault-injection")]
+pub mod faults;
+pub mod frame_cache;
+pub mod frame_header;
 pub mod image_generator;
+pub mod index;
+pub mod jobs;
+pub mod logging;
+pub mod manifest;
+pub mod merkle;
+pub mod operation;
+pub mod overlay;
+pub mod partial;
+pub mod payload;
+pub mod pipeline;
+pub mod queue;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod sparse;
+pub mod stats;
+pub mod storage;
+pub mod stream;
+pub mod streams;
+pub mod subtitles;
+pub mod throttle;
+pub mod transcode;
 pub mod video_composer;
+pub mod warning;
 pub mod ffi;
 
 pub use error::Result;
+pub use capabilities::{capabilities, Capabilities};
 pub use encoder::Encoder;
 pub use decoder::Decoder;
-pub use config::{EncodeConfig, DecodeConfig};
+pub use config::{EncodeConfig, DecodeConfig, Preset};
+pub use pipeline::{
+    decode_partial_to_file, decode_video_to_file, decode_video_to_sink, encode_file_to_video, encode_file_to_video_blocking,
+    encode_source_to_video_blocking, read_video_range, repair_metadata, verify_container, FramePipeline, PayloadPipeline,
+};
@@ -0,0 +1,135 @@
+//! Log routing for applications embedding f2v2f
+//!
+//! f2v2f logs through `tracing`, including ffmpeg's output (target
+//! `ffmpeg`). `init` installs a subscriber that writes to stderr, or, while
+//! a sink is registered with `set_log_sink`, hands each record to the sink
+//! instead, so hosts can feed f2v2f's logs into their own framework. Sinks
+//! only see records if `init` installed the global subscriber (not when the
+//! host already set up its own).
+//!
+//! The library itself only logs problems at `warn` and above; its step by
+//! step progress is at `debug`, so it stays quiet at the usual `info` level.
+
+use std::fmt::Write as _;
+use std::sync::{Arc, Once, RwLock};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::{filter_fn, EnvFilter};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+
+/// One log record as handed to a sink
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogRecord<'a> {
+    pub level: Level,
+    /// Module path, or `ffmpeg` for ffmpeg's own output
+    pub target: &'a str,
+    /// Message followed by any other fields as `key=value`
+    pub message: &'a str,
+}
+
+pub type LogSink = Arc<dyn Fn(&LogRecord) + Send + Sync>;
+
+static SINK: RwLock<Option<LogSink>> = RwLock::new(None);
+static INIT: Once = Once::new();
+
+/// Send log records to `sink` instead of stderr; `None` goes back to stderr
+pub fn set_log_sink(sink: Option<LogSink>) {
+    *SINK.write().unwrap_or_else(|e| e.into_inner()) = sink;
+}
+
+fn current_sink() -> Option<LogSink> {
+    SINK.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Install the global subscriber, filtered by `filter`
+///
+/// Only the first call in a process does anything; returns whether this
+/// call installed the subscriber (false if another one was already set).
+pub fn init(filter: EnvFilter) -> bool {
+    let mut installed = false;
+    INIT.call_once(|| {
+        let stderr = tracing_subscriber::fmt::layer().with_filter(filter_fn(|_| current_sink().is_none()));
+        installed = tracing_subscriber::registry()
+            .with(filter)
+            .with(stderr)
+            .with(SinkLayer)
+            .try_init()
+            .is_ok();
+    });
+    installed
+}
+
+/// Forwards events to the registered sink, if any
+struct SinkLayer;
+
+impl<S: Subscriber> Layer<S> for SinkLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let Some(sink) = current_sink() else {
+            return;
+        };
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        visitor.message.push_str(&visitor.fields);
+        let meta = event.metadata();
+        sink(&LogRecord { level: *meta.level(), target: meta.target(), message: &visitor.message });
+    }
+}
+
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_sink_receives_records() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let seen = received.clone();
+        // Other tests may log through a global subscriber meanwhile
+        let this_thread = std::thread::current().id();
+        set_log_sink(Some(Arc::new(move |record: &LogRecord| {
+            if std::thread::current().id() == this_thread {
+                seen.lock().unwrap().push((record.level, record.target.to_string(), record.message.to_string()));
+            }
+        })));
+
+        let subscriber = tracing_subscriber::registry().with(SinkLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(target: "ffmpeg", "frame {}", 3);
+            tracing::info!(frames = 2, "done");
+        });
+        set_log_sink(None);
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![
+                (Level::WARN, "ffmpeg".to_string(), "frame 3".to_string()),
+                (Level::INFO, "f2v2f::logging::tests".to_string(), "done frames=2".to_string()),
+            ]
+        );
+    }
+}
@@ -1,10 +1,16 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use clap::{Args, Parser, Subcommand};
+use std::path::{Path, PathBuf};
 use tracing_subscriber;
-use f2v2f::config::{EncodeConfig, DecodeConfig};
-use f2v2f::encoder::Encoder;
-use f2v2f::decoder::Decoder;
+use f2v2f::bench::{MatrixConfig, MatrixRow};
+use f2v2f::config::{EncodeConfig, DecodeConfig, Preset, SymlinkPolicy, DEFAULT_MAX_FRAMES};
+use f2v2f::extract_format::{ColorRange, ExtractFormat, PixelFormat, Scaler};
+use f2v2f::image_generator::DEFAULT_SEED;
+use f2v2f::manifest::{Manifest, SIDECAR_EXTENSION};
+use f2v2f::storage::{Location, Sink};
+use f2v2f::stream::fd_path;
+use f2v2f::streams::{ExtraStream, MAIN_STREAM};
+use f2v2f::throttle::Throttle;
 
 #[derive(Parser)]
 #[command(
@@ -27,8 +33,15 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Encode a file into a video
-    Encode {
-        /// Input file path
+    Encode(EncodeArgs),
+
+    /// Encode a file with exactly the settings an existing video was encoded with
+    ReEncode {
+        /// Video whose manifest provides the settings
+        #[arg(long, value_name = "VIDEO")]
+        like: PathBuf,
+
+        /// Input file path, URL or s3://bucket/key object
         #[arg(value_name = "FILE")]
         input: PathBuf,
 
@@ -36,32 +49,182 @@ enum Commands {
         #[arg(value_name = "VIDEO")]
         output: PathBuf,
 
-        /// Video resolution (width x height), default 1920x1080
-        #[arg(long, default_value = "1920x1080")]
-        resolution: String,
+        /// Replace the output video if it already exists
+        #[arg(long)]
+        force: bool,
+    },
 
-        /// Frames per second, default 30
-        #[arg(long, default_value = "30")]
-        fps: u32,
+    /// Decode a video back to a file
+    Decode(DecodeArgs),
+
+    /// Show what an encoded video holds, from its manifest
+    Inspect {
+        /// Input video path
+        #[arg(value_name = "VIDEO")]
+        input: PathBuf,
+    },
+
+    /// Rebuild a lost manifest sidecar from the video itself, so it decodes again
+    RepairMetadata {
+        /// Input video path
+        #[arg(value_name = "VIDEO")]
+        input: PathBuf,
+
+        /// Generator seed the video was encoded with
+        #[arg(long)]
+        seed: Option<u64>,
 
-        /// Chunk size in bytes, default 64KB
-        #[arg(long, default_value = "65536")]
-        chunk_size: usize,
+        /// zstd dictionary the video was compressed with
+        #[arg(long, value_name = "FILE")]
+        dictionary: Option<PathBuf>,
 
-        /// Art style (geometric, fractal, noise)
-        #[arg(long, default_value = "geometric")]
-        style: String,
+        /// Replace the video's manifest if it already has one
+        #[arg(long)]
+        force: bool,
     },
 
-    /// Decode a video back to a file
-    Decode {
+    /// Check that a video is byte-identical to when it was encoded (fast, no decode)
+    VerifyContainer {
         /// Input video path
         #[arg(value_name = "VIDEO")]
         input: PathBuf,
+    },
+
+    /// Check a byte range of a decoded file against the Merkle tree in the video's manifest
+    VerifyRange {
+        /// Encoded video whose manifest holds the tree
+        #[arg(value_name = "VIDEO")]
+        video: PathBuf,
 
-        /// Output file path
+        /// Decoded file to check
         #[arg(value_name = "FILE")]
-        output: PathBuf,
+        file: PathBuf,
+
+        /// First byte of the range
+        #[arg(long, default_value_t = 0)]
+        offset: u64,
+
+        /// Length of the range in bytes (default: to the end of the file)
+        #[arg(long)]
+        length: Option<u64>,
+    },
+
+    /// Read a byte range of the encoded file, extracting only the frames that hold it
+    ReadRange {
+        /// Encoded video (uncompressed, with its manifest)
+        #[arg(value_name = "VIDEO")]
+        video: PathBuf,
+
+        /// First byte of the range
+        #[arg(long, default_value_t = 0)]
+        offset: u64,
+
+        /// Length of the range in bytes (default: to the end of the file)
+        #[arg(long)]
+        length: Option<u64>,
+
+        /// Write the bytes here instead of to stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Keep decoded frames here so later reads of the same video skip extracting them
+        #[arg(long, value_name = "DIR")]
+        frame_cache_dir: Option<PathBuf>,
+    },
+
+    /// Check the CRCs of a random sample of frames for a quick integrity estimate
+    Verify {
+        /// Input video path
+        #[arg(value_name = "VIDEO")]
+        input: PathBuf,
+
+        /// Share of frames to check, as a percentage (e.g. 5%)
+        #[arg(long, default_value = "5%")]
+        sample: String,
+    },
+
+    /// Check every frame's header and CRC and estimate codec damage, without writing the file
+    Stats {
+        /// Input video path
+        #[arg(value_name = "VIDEO")]
+        input: PathBuf,
+    },
+
+    /// Re-encode a video like a video platform would and check it still decodes, before uploading it
+    SimulateTranscode {
+        /// Encoded video path
+        #[arg(value_name = "VIDEO")]
+        input: PathBuf,
+
+        /// Platform to imitate (youtube, vimeo, instagram, tiktok, twitter, whatsapp)
+        #[arg(long, default_value = "youtube")]
+        profile: String,
+
+        /// Decode without the sidecar manifest, as if only the uploaded video were kept
+        #[arg(long)]
+        no_sidecar: bool,
+    },
+
+    /// List the encodes and decodes running on this machine (every f2v2f process and library user)
+    Jobs {
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Serve an HTTP API for submitting encode and decode jobs
+    #[cfg(feature = "server")]
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: std::net::SocketAddr,
+
+        /// Directory that `path` form fields may reference (refused if unset)
+        #[arg(long)]
+        root: Option<PathBuf>,
+
+        /// Keep uploads and results here instead of a temp directory
+        #[arg(long)]
+        work_dir: Option<PathBuf>,
+
+        /// Jobs running at once
+        #[arg(long, default_value_t = 2)]
+        max_jobs: usize,
+
+        /// Encodes running at once (at most --max-jobs)
+        #[arg(long, default_value_t = 2)]
+        max_encodes: usize,
+
+        /// Jobs waiting for a slot; further submissions get 503
+        #[arg(long, default_value_t = 64)]
+        max_queued: usize,
+    },
+
+    /// Sweep resolutions x codecs x CRF x densities on random data and report throughput and bit errors
+    BenchMatrix {
+        /// Resolutions to try (comma separated, WIDTHxHEIGHT)
+        #[arg(long, value_delimiter = ',', default_value = "640x360,1280x720,1920x1080")]
+        resolutions: Vec<String>,
+
+        /// ffmpeg encoders to try (e.g. libx264,libx265,libvpx-vp9)
+        #[arg(long, value_delimiter = ',', default_value = "libx264")]
+        codecs: Vec<String>,
+
+        /// CRF values to try; 0 is lossless yuv444p, others use yuv420p like most platforms
+        #[arg(long, value_delimiter = ',', default_value = "0,18,23,28")]
+        crfs: Vec<u32>,
+
+        /// Chunk sizes to try, as a share of frame capacity (0-1]
+        #[arg(long, value_delimiter = ',', default_value = "0.25,1")]
+        densities: Vec<f32>,
+
+        /// Frames per combination
+        #[arg(long, default_value_t = 10)]
+        frames: usize,
+
+        /// Print JSON instead of CSV
+        #[arg(long)]
+        json: bool,
     },
 
     /// Benchmark encoding/decoding performance
@@ -76,33 +239,373 @@ enum Commands {
     },
 }
 
+#[derive(Args)]
+struct EncodeArgs {
+    /// Input file path; `-` reads stdin, http(s):// URLs and s3://bucket/key objects are
+    /// downloaded, and named pipes are read as a stream
+    #[arg(value_name = "FILE", required_unless_present = "input_fd")]
+    input: Option<PathBuf>,
+
+    /// Output video path
+    #[arg(value_name = "VIDEO")]
+    output: Option<PathBuf>,
+
+    /// Read the input from this inherited file descriptor (then only VIDEO is given)
+    #[arg(long, value_name = "FD")]
+    input_fd: Option<u32>,
+
+    /// Settings bundle: fast-data (256x256 raw-data frames packed full, fastest compression)
+    /// or showcase (glow and vignette for publishing, small redundant chunks)
+    #[arg(long, conflicts_with_all = ["resolution", "chunk_size", "style"])]
+    preset: Option<Preset>,
+
+    /// Video resolution (width x height), default 1920x1080
+    #[arg(long, default_value = "1920x1080")]
+    resolution: String,
+
+    /// Frames per second, default 30
+    #[arg(long, default_value = "30")]
+    fps: u32,
+
+    /// Show each frame for this many seconds instead of setting --fps (e.g. 0.5; at most 1)
+    #[arg(long, value_name = "SECS", conflicts_with = "fps")]
+    duration_per_frame: Option<f64>,
+
+    /// Raise the frame rate (up to 120) for payloads that would play for hours, lower it
+    /// (down to 1) for small ones, starting from --fps
+    #[arg(long)]
+    adaptive_fps: bool,
+
+    /// Crossfade frames between data frames for smoother playback (skipped on decode)
+    #[arg(long, default_value_t = 0)]
+    transition_frames: u32,
+
+    /// Frames between keyframes (ffmpeg -g), default ffmpeg's choice
+    #[arg(long, value_name = "N")]
+    keyframe_interval: Option<u32>,
+
+    /// Make every frame a keyframe: better seeking and damage isolation, larger video
+    #[arg(long, conflicts_with = "keyframe_interval")]
+    all_intra: bool,
+
+    /// Mark a chapter every N data frames, titled with the file bytes it holds (frames if compressed)
+    #[arg(long, value_name = "N")]
+    chunk_chapters: Option<u32>,
+
+    /// Don't write a copy of the manifest into the video itself (sidecar only)
+    #[arg(long)]
+    no_embedded_manifest: bool,
+
+    /// Also write the manifest into the container's metadata (MP4 udta tag,
+    /// or an attachment when the output is .mkv)
+    #[arg(long)]
+    manifest_tag: bool,
+
+    /// Also mux the manifest and per-frame CRCs into a subtitle track
+    #[arg(long)]
+    subtitle_manifest: bool,
+
+    /// Record the input's name, modification time and permissions in the manifest
+    #[arg(long)]
+    file_metadata: bool,
+
+    /// Make compressible parts of the file look calm and random parts busy (visual only)
+    #[arg(long)]
+    entropy_style: bool,
+
+    /// Zstd compression level, 1 (fastest) to 22 (smallest); default 11
+    #[arg(long, value_name = "LEVEL")]
+    compression_level: Option<i32>,
+
+    /// Zstd worker threads, default one per CPU
+    #[arg(long, value_name = "N")]
+    compression_threads: Option<u32>,
+
+    /// Record a Merkle tree over blocks of this many bytes (e.g. 1048576) for verify-range
+    #[arg(long, value_name = "BYTES")]
+    merkle_block_size: Option<u64>,
+
+    /// Multiplex an extra stream into the video, read from FILE (ID 1-253; repeatable)
+    #[arg(long = "stream", value_name = "ID=FILE")]
+    streams: Vec<ExtraStream>,
+
+    /// Chunk size in bytes, default 64KB
+    #[arg(long, default_value = "65536")]
+    chunk_size: usize,
+
+    /// Art style (geometric, fractal, noise, raw, showcase)
+    #[arg(long, default_value = "geometric")]
+    style: String,
+
+    /// Art generator seed; different seeds give different visuals for the same file
+    #[arg(long, default_value_t = DEFAULT_SEED)]
+    seed: u64,
+
+    /// Produce a byte-identical video for the same input and settings
+    #[arg(long)]
+    deterministic: bool,
+
+    /// Frame-count target; chunk size grows (up to frame capacity) to stay within it. 0 = no target
+    #[arg(long, default_value_t = DEFAULT_MAX_FRAMES)]
+    max_frames: u64,
+
+    /// Slow down background encodes: max payload rate (e.g. 20MB/s) or CPU share (e.g. 50%)
+    #[arg(long, default_value = "none")]
+    throttle: Throttle,
+
+    /// Symlinked input: follow, preserve (store the link target) or skip
+    #[arg(long, default_value = "follow")]
+    symlinks: SymlinkPolicy,
+
+    /// Replace the output video if it already exists
+    #[arg(long)]
+    force: bool,
+
+    /// Directory for scratch files and the partial video (e.g. a fast local disk)
+    #[arg(long, value_name = "DIR")]
+    temp_dir: Option<PathBuf>,
+
+    /// Remember finished encodes here and reuse the video when the same input is encoded again with the same settings
+    #[arg(long, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+}
+
+impl EncodeArgs {
+    /// Input and output video path; with --input-fd the only positional is the video
+    fn paths(&self) -> Result<(Location, PathBuf)> {
+        match (self.input_fd, &self.input, &self.output) {
+            (Some(fd), Some(video), None) => Ok((Location::Local(fd_path(fd)), video.clone())),
+            (None, Some(input), Some(video)) => Ok((Location::from_arg(input)?, video.clone())),
+            (Some(_), _, _) => anyhow::bail!("With --input-fd, give only the output VIDEO"),
+            _ => anyhow::bail!("Give an input FILE and an output VIDEO"),
+        }
+    }
+
+    fn to_config(&self) -> Result<EncodeConfig> {
+        let (width, height) = EncodeConfig::parse_resolution(&self.resolution)?;
+        let fps = match self.duration_per_frame {
+            Some(secs) => {
+                let fps = EncodeConfig::fps_for_frame_duration(secs)?;
+                if (1.0 / fps as f64 - secs).abs() > 1e-9 {
+                    tracing::warn!("{} s/frame isn't a whole frame rate; using {} fps ({:.3} s/frame)",
+                        secs, fps, 1.0 / fps as f64);
+                }
+                fps
+            }
+            None => self.fps,
+        };
+        let mut config = EncodeConfig {
+            width,
+            height,
+            fps,
+            chunk_size: self.chunk_size,
+            art_style: self.style.clone(),
+            seed: self.seed,
+            deterministic: self.deterministic,
+            symlinks: self.symlinks,
+            throttle: self.throttle,
+            max_frames: (self.max_frames > 0).then_some(self.max_frames),
+            overwrite: self.force,
+            temp_dir: self.temp_dir.clone(),
+            cache_dir: self.cache_dir.clone(),
+            transition_frames: self.transition_frames,
+            keyframe_interval: if self.all_intra { Some(1) } else { self.keyframe_interval },
+            chunk_chapters: self.chunk_chapters,
+            entropy_style: self.entropy_style,
+            compression_threads: self.compression_threads,
+            merkle_block_size: self.merkle_block_size,
+            streams: self.streams.clone(),
+            embed_manifest: !self.no_embedded_manifest,
+            manifest_tag: self.manifest_tag,
+            subtitle_manifest: self.subtitle_manifest,
+            adaptive_fps: self.adaptive_fps,
+            preserve_metadata: self.file_metadata,
+            ..EncodeConfig::default()
+        };
+        if let Some(preset) = self.preset {
+            preset.apply(&mut config);
+        }
+        // An explicit level wins over the preset's
+        if let Some(level) = self.compression_level {
+            config.compression_level = level;
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Args)]
+struct DecodeArgs {
+    /// Input video path (with --jobs, a glob pattern such as 'videos/*.mp4')
+    #[arg(value_name = "VIDEO")]
+    input: PathBuf,
+
+    /// Output file path (default: the original file name, else the video name with an extension
+    /// matching the content type, next to the video);
+    /// `-` writes to stdout, http(s):// URLs (PUT) and s3://bucket/key objects are uploaded, and
+    /// named pipes are written as a stream. With --jobs, the output directory
+    #[arg(value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Write the decoded file to this inherited file descriptor
+    #[arg(long, value_name = "FD", conflicts_with_all = ["output", "jobs"])]
+    output_fd: Option<u32>,
+
+    /// Decode every video matching VIDEO into the directory FILE, this many at a time
+    #[arg(long, value_name = "N")]
+    jobs: Option<usize>,
+
+    /// Generator seed, only needed if the video's manifest is missing
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Replace the output file if it already exists
+    #[arg(long)]
+    force: bool,
+
+    /// Directory for the partial output (e.g. a fast local disk)
+    #[arg(long, value_name = "DIR")]
+    temp_dir: Option<PathBuf>,
+
+    /// Low-memory mode: extract and process this many frames at a time
+    #[arg(long, value_name = "FRAMES")]
+    frame_window: Option<usize>,
+
+    /// Write the output while extracting, pausing extraction while this many bytes wait
+    /// for a slow destination (network mount, pipe)
+    #[arg(long, value_name = "BYTES", requires = "frame_window")]
+    high_watermark: Option<usize>,
+
+    /// Recover what survives of an incomplete or damaged video instead of failing; missing
+    /// parts are left as zeros and the recovered byte ranges are listed
+    #[arg(long, conflicts_with_all = ["jobs", "high_watermark"])]
+    partial: bool,
+
+    /// Write out extra stream ID (as stored) instead of the file
+    #[arg(long, value_name = "ID", default_value_t = MAIN_STREAM)]
+    stream: u8,
+
+    /// Raw pixel format frames are extracted as (rgba, rgb24, gray, rgba64le, gray16le)
+    #[arg(long, default_value = "rgba")]
+    pix_fmt: PixelFormat,
+
+    /// swscale algorithm for format conversion (lanczos, bicubic, bilinear, area, neighbor, spline)
+    #[arg(long, default_value = "lanczos")]
+    scaler: Scaler,
+
+    /// Color range of the conversion to RGB: pc (full) or tv (limited)
+    #[arg(long, default_value = "pc")]
+    color_range: ColorRange,
+
+    /// Give the decoded file its original modification time and permissions
+    #[arg(long)]
+    restore_metadata: bool,
+
+    /// Write the file even if it doesn't match the checksum in its manifest
+    #[arg(long)]
+    no_verify: bool,
+}
+
+impl DecodeArgs {
+    fn to_config(&self) -> DecodeConfig {
+        DecodeConfig {
+            seed: self.seed.unwrap_or(DEFAULT_SEED),
+            overwrite: self.force,
+            temp_dir: self.temp_dir.clone(),
+            frame_window: self.frame_window,
+            high_watermark: self.high_watermark,
+            stream: self.stream,
+            restore_metadata: self.restore_metadata,
+            verify_checksum: !self.no_verify,
+            extract_format: ExtractFormat {
+                pix_fmt: self.pix_fmt,
+                scaler: self.scaler,
+                color_range: self.color_range,
+            },
+            ..DecodeConfig::default()
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
 
-    // Setup logging
+    // Setup logging; the library logs its steps at debug level, which the
+    // CLI shows by default
     let log_level = cli
         .log_level
         .as_deref()
-        .unwrap_or("info");
+        .unwrap_or("info,f2v2f=debug");
     let filter = tracing_subscriber::filter::EnvFilter::new(log_level);
+    // Logs go to stderr so stdout can carry a decoded file
     tracing_subscriber::fmt()
         .with_env_filter(filter)
+        .with_writer(std::io::stderr)
         .init();
 
     match cli.command {
-        Commands::Encode {
-            input,
-            output,
-            resolution,
-            fps,
-            chunk_size,
-            style,
-        } => {
-            encode_command(input, output, resolution, fps, chunk_size, style).await?;
+        Commands::Encode(args) => {
+            encode_command(args).await?;
+        }
+        Commands::ReEncode { like, input, output, force } => {
+            re_encode_command(like, input, output, force).await?;
+        }
+        Commands::Decode(args) => {
+            decode_command(args).await?;
+        }
+        Commands::Inspect { input } => {
+            inspect_command(input)?;
+        }
+        Commands::RepairMetadata { input, seed, dictionary, force } => {
+            let config = DecodeConfig {
+                seed: seed.unwrap_or(DEFAULT_SEED),
+                dictionary,
+                overwrite: force,
+                ..DecodeConfig::default()
+            };
+            repair_metadata_command(input, config).await?;
+        }
+        Commands::VerifyContainer { input } => {
+            verify_container_command(input)?;
+        }
+        Commands::VerifyRange { video, file, offset, length } => {
+            verify_range_command(video, file, offset, length)?;
+        }
+        Commands::ReadRange { video, offset, length, output, frame_cache_dir } => {
+            let config = DecodeConfig { frame_cache_dir, ..DecodeConfig::default() };
+            read_range_command(video, offset, length, output, config).await?;
         }
-        Commands::Decode { input, output } => {
-            decode_command(input, output).await?;
+        Commands::Verify { input, sample } => {
+            let rate = f2v2f::stats::parse_sample_rate(&sample)?;
+            verify_sample_command(input, rate).await?;
+        }
+        Commands::Stats { input } => {
+            stats_command(input).await?;
+        }
+        Commands::SimulateTranscode { input, profile, no_sidecar } => {
+            simulate_transcode_command(input, &profile, !no_sidecar).await?;
+        }
+        Commands::Jobs { json } => {
+            jobs_command(json)?;
+        }
+        #[cfg(feature = "server")]
+        Commands::Serve { listen, root, work_dir, max_jobs, max_encodes, max_queued } => {
+            let config = f2v2f::server::ServerConfig {
+                listen,
+                root,
+                work_dir,
+                queue: f2v2f::queue::QueueConfig { max_running: max_jobs, max_encodes, max_queued },
+                ..Default::default()
+            };
+            f2v2f::server::serve(config).await?;
+        }
+        Commands::BenchMatrix { resolutions, codecs, crfs, densities, frames, json } => {
+            let resolutions = resolutions
+                .iter()
+                .map(|r| EncodeConfig::parse_resolution(r))
+                .collect::<f2v2f::Result<Vec<_>>>()?;
+            let config = MatrixConfig { resolutions, codecs, crfs, densities, frames, ..MatrixConfig::default() };
+            bench_matrix_command(config, json).await?;
         }
         Commands::Benchmark { input, size } => {
             benchmark_command(input, size).await?;
@@ -112,33 +615,440 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-async fn encode_command(
-    input: PathBuf,
-    output: PathBuf,
-    resolution: String,
-    fps: u32,
-    chunk_size: usize,
-    style: String,
-) -> Result<()> {
+async fn encode_command(args: EncodeArgs) -> Result<()> {
     tracing::info!("Starting encoding process");
-    tracing::info!("Input: {}", input.display());
+    let (input, output) = args.paths()?;
+    tracing::info!("Input: {}", input);
     tracing::info!("Output: {}", output.display());
-    tracing::info!("Resolution: {}, FPS: {}", resolution, fps);
+    let config = args.to_config()?;
+    tracing::info!("Resolution: {}, FPS: {}, Seed: {}", args.resolution, config.fps, args.seed);
+
+    let info = f2v2f::encode_source_to_video_blocking(&input, &output, &config)?;
+    tracing::info!("Encoded {} frames: {} bytes, {:.1}s ({:.2}x original size)",
+        info.num_frames, info.video_size_bytes, info.duration_secs, info.overhead_ratio);
 
-    // TODO: Implement encoding logic
-    tracing::warn!("Encoding not yet implemented");
-    
     Ok(())
 }
 
-async fn decode_command(input: PathBuf, output: PathBuf) -> Result<()> {
+async fn re_encode_command(like: PathBuf, input: PathBuf, output: PathBuf, force: bool) -> Result<()> {
+    let manifest = Manifest::read_sidecar(&like)?.ok_or_else(|| {
+        anyhow::anyhow!("No manifest found at {}", Manifest::sidecar_path(&like).display())
+    })?;
+    let settings = manifest.settings.ok_or_else(|| {
+        anyhow::anyhow!("{} was encoded by a version that didn't record its settings", like.display())
+    })?;
+
+    let mut config = EncodeConfig { overwrite: force, ..EncodeConfig::default() };
+    settings.apply(&mut config);
+    let input = Location::from_arg(&input)?;
+    tracing::info!("Encoding {} like {} ({}x{} @ {} fps, {} style, seed {})", input, like.display(),
+        config.width, config.height, config.fps, config.art_style, config.seed);
+
+    let info = f2v2f::encode_source_to_video_blocking(&input, &output, &config)?;
+    tracing::info!("Encoded {} frames: {} bytes, {:.1}s ({:.2}x original size)",
+        info.num_frames, info.video_size_bytes, info.duration_secs, info.overhead_ratio);
+
+    Ok(())
+}
+
+async fn decode_command(args: DecodeArgs) -> Result<()> {
+    if let Some(jobs) = args.jobs {
+        return decode_many_command(args, jobs).await;
+    }
+    let input = &args.input;
+    let output = match (&args.output, args.output_fd) {
+        (_, Some(fd)) => Location::Local(fd_path(fd)),
+        (Some(output), None) => Location::from_arg(output)?,
+        (None, None) => Location::Local(Manifest::default_output_path(input, Manifest::read_sidecar(input)?.as_ref())),
+    };
+
     tracing::info!("Starting decoding process");
     tracing::info!("Input: {}", input.display());
-    tracing::info!("Output: {}", output.display());
+    tracing::info!("Output: {}", output);
 
-    // TODO: Implement decoding logic
-    tracing::warn!("Decoding not yet implemented");
-    
+    let config = args.to_config();
+
+    if args.partial {
+        let output = Sink::local_path(&output)
+            .ok_or_else(|| anyhow::anyhow!("--partial writes to a local file, not {}", output))?;
+        return decode_partial_command(input, output, &config).await;
+    }
+    let info = f2v2f::decode_video_to_sink(input, &output, &config).await?;
+    tracing::info!("Decoded {} bytes ({}: {})", info.extracted_size, info.hash_algo, info.checksum);
+
+    Ok(())
+}
+
+async fn decode_partial_command(input: &Path, output: &Path, config: &DecodeConfig) -> Result<()> {
+    let info = f2v2f::decode_partial_to_file(input, output, config).await?;
+    for range in &info.recovered {
+        println!("Recovered bytes {}..{} ({} bytes)", range.start, range.end, range.len());
+    }
+    let expected = info.frames_expected.map_or(String::new(), |n| format!(" of {}", n));
+    println!("Frames:    {}{} intact, {} damaged", info.frames_recovered, expected, info.frames_damaged);
+    if info.is_complete() {
+        println!("Recovered the whole file");
+        return Ok(());
+    }
+    let total = info.original_size.map_or("an unknown number of".to_string(), |size| size.to_string());
+    anyhow::bail!(
+        "Recovered {} of {} bytes; the rest of {} is zeros",
+        info.recovered_bytes(),
+        total,
+        output.display()
+    )
+}
+
+/// How a decoded file compares with the checksum in its manifest
+enum Integrity {
+    Verified,
+    Mismatch,
+    /// No manifest to compare with
+    Unchecked,
+}
+
+async fn decode_many_command(args: DecodeArgs, jobs: usize) -> Result<()> {
+    use indicatif::{ProgressBar, ProgressStyle};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    anyhow::ensure!(jobs > 0, "--jobs must be at least 1");
+    let out_dir = args.output.clone().ok_or_else(|| anyhow::anyhow!("--jobs needs an output directory"))?;
+    let pattern = args.input.to_string_lossy().into_owned();
+    let videos = glob::glob(&pattern)?
+        .collect::<std::result::Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter(|p| p.is_file() && p.extension().is_none_or(|ext| ext != SIDECAR_EXTENSION))
+        .collect::<Vec<_>>();
+    anyhow::ensure!(!videos.is_empty(), "No videos match {}", pattern);
+    std::fs::create_dir_all(&out_dir)?;
+
+    // Work out every output name first so two videos never race for one file
+    let mut jobs_list = Vec::new();
+    let mut claimed: HashMap<PathBuf, PathBuf> = HashMap::new();
+    for video in videos {
+        let manifest = Manifest::read_sidecar(&video)?;
+        let name = Manifest::default_output_path(&video, manifest.as_ref());
+        let output = out_dir.join(name.file_name().unwrap_or_default());
+        if let Some(other) = claimed.insert(output.clone(), video.clone()) {
+            anyhow::bail!("{} and {} would both decode to {}", other.display(), video.display(), output.display());
+        }
+        jobs_list.push((video, output, manifest));
+    }
+
+    let total = jobs_list.len();
+    let progress = ProgressBar::new(total as u64);
+    progress.set_style(ProgressStyle::with_template("{bar:40} {pos}/{len} decoded, {elapsed} {msg}")?);
+    let config = args.to_config();
+    let permits = Arc::new(tokio::sync::Semaphore::new(jobs));
+    let handle = tokio::runtime::Handle::current();
+    let mut tasks = tokio::task::JoinSet::new();
+    for (video, output, manifest) in jobs_list {
+        let permit = permits.clone().acquire_owned().await?;
+        let (config, progress, handle) = (config.clone(), progress.clone(), handle.clone());
+        // Decoding blocks on ffmpeg, so each job gets its own thread
+        tasks.spawn_blocking(move || {
+            progress.set_message(format!("{}", video.display()));
+            let result = handle.block_on(f2v2f::decode_video_to_file(&video, &output, &config));
+            let integrity = match (&result, &manifest) {
+                (Ok(info), Some(manifest)) if info.checksum == manifest.checksum => Integrity::Verified,
+                (Ok(_), Some(_)) | (Err(f2v2f::error::F2V2FError::IntegrityError(..)), _) => Integrity::Mismatch,
+                _ => Integrity::Unchecked,
+            };
+            match &result {
+                Ok(info) => progress.println(format!("✓ {} → {} ({} bytes)",
+                    video.display(), output.display(), info.extracted_size)),
+                Err(e) => progress.println(format!("✗ {}: {}", video.display(), e)),
+            }
+            progress.inc(1);
+            drop(permit);
+            (video, result, integrity)
+        });
+    }
+
+    let (mut verified, mut mismatched, mut unchecked, mut failed) = (Vec::new(), Vec::new(), 0, Vec::new());
+    while let Some(joined) = tasks.join_next().await {
+        let (video, result, integrity) = joined?;
+        match (result, integrity) {
+            (_, Integrity::Mismatch) => mismatched.push(video),
+            (Err(e), _) => failed.push((video, e)),
+            (Ok(_), Integrity::Verified) => verified.push(video),
+            (Ok(_), Integrity::Unchecked) => unchecked += 1,
+        }
+    }
+    progress.finish_and_clear();
+
+    println!("Decoded {} of {} videos into {}", total - failed.len(), total, out_dir.display());
+    println!("  Checksum verified:   {}", verified.len());
+    println!("  No manifest checked: {}", unchecked);
+    for video in &mismatched {
+        println!("  Checksum MISMATCH:   {}", video.display());
+    }
+    for (video, e) in &failed {
+        println!("  Failed:              {}: {}", video.display(), e);
+    }
+    anyhow::ensure!(
+        failed.is_empty() && mismatched.is_empty(),
+        "{} failed, {} with checksum mismatches",
+        failed.len(),
+        mismatched.len()
+    );
+    Ok(())
+}
+
+fn inspect_command(input: PathBuf) -> Result<()> {
+    let manifest = Manifest::read_sidecar(&input)?.ok_or_else(|| {
+        anyhow::anyhow!("No manifest found at {}", Manifest::sidecar_path(&input).display())
+    })?;
+
+    println!("Video:        {}", input.display());
+    let adaptive = manifest.settings.as_ref().is_some_and(|s| s.adaptive_fps);
+    println!("Resolution:   {}x{} @ {} fps{}", manifest.width, manifest.height, manifest.fps,
+        if adaptive { " (adaptive)" } else { "" });
+    println!("Frames:       {} (chunk {} bytes)", manifest.num_frames, manifest.chunk_size);
+    if manifest.fps > 0 {
+        println!("Duration:     {:.1}s ({:.3} s/frame)", manifest.duration_secs(), 1.0 / manifest.fps as f64);
+    }
+    println!("Original:     {} bytes", manifest.original_size);
+    if let Some(file) = &manifest.file {
+        if let Some(name) = &file.name {
+            println!("File name:    {}", name);
+        }
+        if let Some(since_epoch) = file.modified.and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok()) {
+            println!("Modified:     {}.{:09} (Unix time)", since_epoch.as_secs(), since_epoch.subsec_nanos());
+        }
+        if let Some(mode) = file.mode {
+            println!("Permissions:  {:04o}", mode);
+        }
+    }
+    println!("Payload:      {} bytes{}", manifest.encoded_size,
+        match manifest.compression_level {
+            Some(level) => format!(" (zstd level {})", level),
+            None if manifest.compressed => " (zstd)".to_string(),
+            None => String::new(),
+        });
+    println!("Content type: {}", manifest.content_type.as_deref().unwrap_or("unknown"));
+    println!("Checksum:     {} {}", manifest.hash_algo, manifest.checksum);
+    if let Some(video_checksum) = &manifest.video_checksum {
+        println!("Video hash:   {} {}", manifest.hash_algo, video_checksum);
+    }
+    if let Some(target) = &manifest.link_target {
+        println!("Symlink to:   {}", target.display());
+    }
+    if let Some(tree) = &manifest.merkle {
+        println!("Merkle root:  {} ({} blocks of {} bytes)", tree.root, tree.leaves.len(), tree.block_size);
+    }
+    if let Some(every) = manifest.settings.as_ref().and_then(|s| s.chunk_chapters) {
+        println!("Chapters:     every {} data frame{}", every, if every == 1 { "" } else { "s" });
+    }
+    if let Some(interval) = manifest.settings.as_ref().and_then(|s| s.keyframe_interval) {
+        println!("Keyframes:    every {} frame{}", interval, if interval == 1 { " (all-intra)" } else { "s" });
+    }
+    if manifest.settings.as_ref().is_some_and(|s| s.embed_manifest) {
+        println!("Embedded:     manifest copy before the first and after the last data frame");
+    }
+    if manifest.settings.as_ref().is_some_and(|s| s.manifest_tag) {
+        println!("Tagged:       manifest copy in the container metadata");
+    }
+    if manifest.settings.as_ref().is_some_and(|s| s.subtitle_manifest) {
+        println!("Subtitles:    manifest copy and frame CRCs in a subtitle track");
+    }
+    for stream in &manifest.streams {
+        println!("Stream {}:     {} bytes in {} frames (crc32 {:08x})", stream.id, stream.size, stream.num_frames,
+            stream.crc32);
+    }
+    // What the same payload would take in each style, at this video's settings
+    if let Some(settings) = &manifest.settings {
+        let mut config = EncodeConfig::default();
+        settings.apply(&mut config);
+        for (style, capacity) in config.style_capacities() {
+            let frames = manifest.encoded_size.div_ceil(capacity.max(1) as u64).max(1);
+            println!("Capacity:     {:<9} {} bytes/frame, {} frames{}", style, capacity, frames,
+                if style == settings.art_style { " (this video)" } else { "" });
+        }
+    }
+
+    Ok(())
+}
+
+async fn repair_metadata_command(input: PathBuf, config: DecodeConfig) -> Result<()> {
+    let (manifest, sidecar) = f2v2f::repair_metadata(&input, &config).await?;
+    println!("✓ Wrote {}", sidecar.display());
+    println!("Resolution:   {}x{} @ {} fps", manifest.width, manifest.height, manifest.fps);
+    println!("Frames:       {} (chunk {} bytes)", manifest.num_frames, manifest.chunk_size);
+    println!("Original:     {} bytes{}", manifest.original_size, if manifest.compressed { " (zstd)" } else { "" });
+    println!("Content type: {}", manifest.content_type.as_deref().unwrap_or("unknown"));
+    println!("Checksum:     {} {}", manifest.hash_algo, manifest.checksum);
+    Ok(())
+}
+
+fn verify_container_command(input: PathBuf) -> Result<()> {
+    let manifest = f2v2f::verify_container(&input)?;
+    println!("✓ {} matches its manifest ({} {})", input.display(), manifest.hash_algo,
+        manifest.video_checksum.unwrap_or_default());
+    Ok(())
+}
+
+fn verify_range_command(video: PathBuf, file: PathBuf, offset: u64, length: Option<u64>) -> Result<()> {
+    let manifest = Manifest::read_sidecar(&video)?.ok_or_else(|| {
+        anyhow::anyhow!("No manifest found at {}", Manifest::sidecar_path(&video).display())
+    })?;
+    let tree = manifest
+        .merkle
+        .ok_or_else(|| anyhow::anyhow!("{} was encoded without --merkle-block-size", video.display()))?;
+    let length = match length {
+        Some(length) => length,
+        None => std::fs::metadata(&file)?.len().saturating_sub(offset),
+    };
+    tree.verify_range(&file, offset, length)?;
+    println!("✓ Bytes {}..{} of {} match the Merkle root {}", offset, offset + length, file.display(), tree.root);
+    Ok(())
+}
+
+async fn read_range_command(
+    video: PathBuf,
+    offset: u64,
+    length: Option<u64>,
+    output: Option<PathBuf>,
+    config: DecodeConfig,
+) -> Result<()> {
+    let read = f2v2f::read_video_range(&video, offset, length.unwrap_or(u64::MAX), &config).await?;
+    match &output {
+        Some(path) => std::fs::write(path, &read.data)?,
+        None => std::io::Write::write_all(&mut std::io::stdout().lock(), &read.data)?,
+    }
+    eprintln!(
+        "✓ Read {} bytes from {} ({} frames extracted, {} from the frame cache)",
+        read.data.len(),
+        video.display(),
+        read.frames_extracted,
+        read.frames_cached
+    );
+    Ok(())
+}
+
+fn jobs_command(json: bool) -> Result<()> {
+    let jobs = f2v2f::jobs::list_all()?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&jobs)?);
+        return Ok(());
+    }
+    if jobs.is_empty() {
+        println!("No jobs running");
+        return Ok(());
+    }
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs();
+    println!("{:>8} {:>5} {:<7} {:<8} {:<17} {:>8}  INPUT -> OUTPUT", "PID", "JOB", "KIND", "STATE", "STAGE", "ELAPSED");
+    for job in jobs {
+        let stage = match job.total.filter(|&total| total > 0) {
+            Some(total) => format!("{} {:.0}%", job.stage, job.done as f64 * 100.0 / total as f64),
+            None => job.stage.clone(),
+        };
+        println!("{:>8} {:>5} {:<7} {:<8} {:<17} {:>7}s  {} -> {}", job.pid, job.id, job.kind.to_string(),
+            job.state.to_string(), stage, now.saturating_sub(job.started_at), job.input, job.output);
+    }
+    Ok(())
+}
+
+async fn verify_sample_command(input: PathBuf, rate: f64) -> Result<()> {
+    let sample = f2v2f::stats::sample_video(&input, rate).await?;
+    let stats = &sample.stats;
+    println!("Video:        {}", input.display());
+    println!("Sampled:      {} of {} frames ({} data, {} transition, {} foreign)", stats.frames.len(),
+        sample.total_frames, stats.data_frames(), stats.transition_frames(), stats.foreign_frames());
+    println!("CRC failures: {}", stats.crc_failures());
+
+    let failed = stats
+        .frames
+        .iter()
+        .filter_map(|f| Some((f.position, f.header?, f.quality?)))
+        .filter(|(_, _, quality)| !quality.crc_ok);
+    for (position, header, _) in failed {
+        println!("  frame {} (index {}): CRC mismatch", position, header.index);
+    }
+
+    if stats.data_frames() == 0 {
+        anyhow::bail!("No data frames among the sampled frames of {}", input.display());
+    }
+    match sample.max_damaged_share() {
+        Some(bound) => {
+            println!("✓ No damage found; with {:.0}% confidence under {:.2}% of frames are damaged",
+                f2v2f::stats::SAMPLE_CONFIDENCE * 100.0, bound * 100.0);
+            Ok(())
+        }
+        None => anyhow::bail!(
+            "{} is damaged: about {:.1}% of sampled frames failed their CRC (run `stats` for details)",
+            input.display(),
+            sample.damaged_share() * 100.0
+        ),
+    }
+}
+
+async fn stats_command(input: PathBuf) -> Result<()> {
+    let stats = f2v2f::stats::analyze_video(&input).await?;
+    let data_frames = stats.data_frames();
+    let percent = |n: usize| if data_frames > 0 { n as f64 * 100.0 / data_frames as f64 } else { 0.0 };
+
+    println!("Video:        {}", input.display());
+    println!("Resolution:   {}x{} (chunk {} bytes)", stats.width, stats.height, stats.chunk_size);
+    println!("Frames:       {} ({} data, {} transition, {} foreign)", stats.frames.len(),
+        data_frames, stats.transition_frames(), stats.foreign_frames());
+    println!("CRC failures: {} ({:.1}%)", stats.crc_failures(), percent(stats.crc_failures()));
+    println!("Confidence:   {:.3} (1.0 = every byte exact)", stats.mean_confidence());
+    println!("Pixel errors: {:.2}% off by more than {} levels", stats.mean_pixel_error_rate() * 100.0,
+        f2v2f::stats::PIXEL_TOLERANCE);
+    println!("Damage:       {:.2} gray levels mean absolute error", stats.mean_abs_error());
+    println!("Density:      {:.3} payload bytes/pixel, {:.3} payload bytes/video byte",
+        stats.density(), stats.storage_efficiency());
+
+    let failed = stats
+        .frames
+        .iter()
+        .filter_map(|f| Some((f.position, f.header?, f.quality?)))
+        .filter(|(_, _, quality)| !quality.crc_ok);
+    for (position, header, quality) in failed {
+        println!("  frame {} (index {}): CRC mismatch, confidence {:.3}, {:.2}% pixel errors",
+            position, header.index, quality.confidence, quality.pixel_error_rate * 100.0);
+    }
+
+    Ok(())
+}
+
+async fn simulate_transcode_command(input: PathBuf, profile: &str, keep_sidecar: bool) -> Result<()> {
+    let profile = f2v2f::transcode::TranscodeProfile::parse(profile)?;
+    let report = f2v2f::transcode::simulate(&input, profile, keep_sidecar).await?;
+    println!("Video:      {} ({} bytes)", input.display(), report.video_size);
+    println!("Profile:    {} ({}, crf {}, at most {}p)", profile.name, profile.codec, profile.crf, profile.max_height);
+    println!("Re-encoded: {} bytes", report.transcoded_size);
+    if report.survived {
+        println!("✓ Survives: all {} bytes decode and match the checksum", report.recovered_bytes);
+        return Ok(());
+    }
+    println!("Damaged:    {} data frames fail their CRC", report.frames_damaged);
+    println!("Recovered:  {} of {} bytes (partial decode)", report.recovered_bytes,
+        report.original_size.map_or("?".to_string(), |size| size.to_string()));
+    anyhow::bail!(
+        "{} would not survive {}: {} (try a lower density or a higher resolution)",
+        input.display(),
+        profile.name,
+        report.error.as_deref().unwrap_or("decode failed")
+    )
+}
+
+async fn bench_matrix_command(config: MatrixConfig, json: bool) -> Result<()> {
+    // CSV rows are printed as they finish; JSON needs them all
+    if !json {
+        println!("{}", MatrixRow::CSV_HEADER);
+    }
+    let rows = f2v2f::bench::run_matrix(&config, |row| {
+        if !json {
+            println!("{}", row.to_csv());
+        }
+    })
+    .await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+    }
     Ok(())
 }
 
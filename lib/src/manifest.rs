@@ -0,0 +1,432 @@
+use crate::checksum::{hash_file, HashAlgorithm};
+use crate::config::{EncodeConfig, EncodeSettings};
+use crate::encoder::EncodedFileInfo;
+use crate::error::{F2V2FError, Result};
+use crate::file_metadata::FileMetadata;
+use crate::image_generator::DEFAULT_SEED;
+use crate::index::FrameIndex;
+use crate::merkle::MerkleTree;
+use crate::sparse::Hole;
+use crate::streams::StreamInfo;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Current format version, recorded in every manifest
+///
+/// The version covers everything a decoder reads: the manifest, the frame
+/// header layout and the art a payload is rendered into. Compatibility
+/// policy:
+///
+/// - A change that leaves old decoders able to read new videos (a new
+///   optional manifest field, a new extra stream) keeps the version.
+/// - Any other change to what an encode writes bumps it, and adds a golden
+///   corpus for the new version under `tests/golden/` (the golden tests fail
+///   until it exists, and fail whenever the current encoder's output drifts
+///   from its corpus).
+/// - Every version from `OLDEST_MANIFEST_VERSION` up keeps decoding; the
+///   golden corpora of all of them are decoded on every test run. Dropping
+///   one means raising `OLDEST_MANIFEST_VERSION` and deleting its corpus, in
+///   a release that says so.
+///
+/// History: 1, the original format; 2, payloads start with a container
+/// header (see `container`); 3, an `END_STREAM` frame follows the last data
+/// frame.
+pub const MANIFEST_VERSION: u32 = 3;
+
+/// Oldest format version this build still decodes
+pub const OLDEST_MANIFEST_VERSION: u32 = 1;
+
+/// Extension of the sidecar file written next to each encoded video
+pub const SIDECAR_EXTENSION: &str = "mp4meta";
+
+/// Container metadata tag holding a copy of the manifest (see
+/// `EncodeConfig::manifest_tag`)
+pub const MANIFEST_TAG: &str = "f2v2f_manifest";
+
+/// Longest manifest written as a tag; a single command-line argument is
+/// capped at 128 KiB on Linux
+pub const MAX_MANIFEST_TAG_LEN: usize = 100 * 1024;
+
+/// Attachment holding a copy of the manifest in Matroska videos, in place of
+/// `MANIFEST_TAG` (no length limit)
+pub const MANIFEST_ATTACHMENT: &str = "f2v2f_manifest.json";
+
+/// Attachment holding the original file's `FileMetadata` in Matroska videos
+pub const FILE_METADATA_ATTACHMENT: &str = "f2v2f_file.json";
+
+/// Everything the decoder needs to know about an encoded video
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format_version: u32,
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    /// Actual chunk size used (may differ from the configured one)
+    pub chunk_size: usize,
+    pub num_frames: u64,
+    pub original_size: u64,
+    /// Payload size after compression
+    pub encoded_size: u64,
+    pub compressed: bool,
+    /// Zstd level the payload was compressed with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression_level: Option<i32>,
+    pub hash_algo: HashAlgorithm,
+    /// Checksum of the original file, computed with `hash_algo`
+    pub checksum: String,
+    /// ID of the shared zstd dictionary needed to decompress, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dictionary_id: Option<u32>,
+    /// Art generator seed the frames were rendered with
+    #[serde(default = "default_seed")]
+    pub seed: u64,
+    /// Sparse-file holes left out of the payload, restored on decode
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub holes: Vec<Hole>,
+    /// Target of a preserved symlink; decode recreates the link
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_target: Option<PathBuf>,
+    /// MIME type sniffed from the original file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    /// Crossfade frames between data frames (`num_frames` counts data frames only)
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub transition_frames: u32,
+    /// Checksum of the video file itself (`hash_algo`), to check a copy
+    /// before decoding it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub video_checksum: Option<String>,
+    /// Settings the video was encoded with, for `re-encode --like`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<EncodeSettings>,
+    /// Block hash tree of the original file, for `MerkleTree::verify_range`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merkle: Option<MerkleTree>,
+    /// Extra streams multiplexed into the video (see `streams`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub streams: Vec<StreamInfo>,
+    /// Name, modification time and permissions of the original file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<FileMetadata>,
+    /// Video frame of each data frame, for random access (not in the copy
+    /// embedded in the video, which is written first)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<FrameIndex>,
+}
+
+fn default_seed() -> u64 {
+    DEFAULT_SEED
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+impl Manifest {
+    pub fn new(info: &EncodedFileInfo, config: &EncodeConfig) -> Self {
+        Self {
+            format_version: MANIFEST_VERSION,
+            width: config.width,
+            height: config.height,
+            fps: config.fps_for(info.num_frames),
+            chunk_size: info.chunk_size,
+            num_frames: info.num_frames,
+            original_size: info.original_file_size,
+            encoded_size: info.encoded_size,
+            compressed: config.use_compression,
+            compression_level: config.use_compression.then_some(config.compression_level),
+            hash_algo: info.hash_algo,
+            checksum: info.checksum.clone(),
+            dictionary_id: info.dictionary_id,
+            seed: config.seed,
+            holes: info.holes.clone(),
+            link_target: info.link_target.clone(),
+            content_type: info.content_type.clone(),
+            transition_frames: config.transition_frames,
+            video_checksum: info.video_checksum.clone(),
+            settings: Some(EncodeSettings::from(config)),
+            merkle: info.merkle.clone(),
+            streams: info.streams.clone(),
+            file: info.file_metadata.clone(),
+            index: info.frame_index.clone(),
+        }
+    }
+
+    /// Check that `video_path` is the exact video this manifest was written for
+    pub fn verify_video<P: AsRef<Path>>(&self, video_path: P) -> Result<()> {
+        let path = video_path.as_ref();
+        let expected = self.video_checksum.as_deref().ok_or_else(|| {
+            F2V2FError::InvalidInput(format!("Manifest of {} has no video checksum", path.display()))
+        })?;
+        let actual = hash_file(path, self.hash_algo)?;
+        if actual != expected {
+            return Err(F2V2FError::IntegrityError(
+                format!("Video {} was modified", path.display()),
+                expected.to_string(),
+                actual,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Playback length in seconds
+    pub fn duration_secs(&self) -> f64 {
+        if self.fps == 0 {
+            return 0.0;
+        }
+        let video_frames = self.num_frames + self.num_frames.saturating_sub(1) * self.transition_frames as u64;
+        video_frames as f64 / self.fps as f64
+    }
+
+    /// Path of the sidecar manifest for a video
+    ///
+    /// The extension is appended to the full file name (`out.mp4` ->
+    /// `out.mp4.mp4meta`), so videos that differ only in extension or have
+    /// dots in their name never share a sidecar.
+    pub fn sidecar_path<P: AsRef<Path>>(video_path: P) -> PathBuf {
+        let video = video_path.as_ref();
+        match video.file_name() {
+            Some(name) => {
+                let mut name = name.to_os_string();
+                name.push(".");
+                name.push(SIDECAR_EXTENSION);
+                video.with_file_name(name)
+            }
+            // Not a file path ("", "..", "dir/.."); nothing sensible to append to
+            None => Self::legacy_sidecar_path(video),
+        }
+    }
+
+    /// Sidecar path used by earlier versions (`out.mp4` -> `out.mp4meta`),
+    /// still read when the current one is missing
+    pub fn legacy_sidecar_path<P: AsRef<Path>>(video_path: P) -> PathBuf {
+        video_path.as_ref().with_extension(SIDECAR_EXTENSION)
+    }
+
+    /// Existing sidecar for a video, preferring the current naming
+    pub fn find_sidecar<P: AsRef<Path>>(video_path: P) -> Option<PathBuf> {
+        let video = video_path.as_ref();
+        [Self::sidecar_path(video), Self::legacy_sidecar_path(video)]
+            .into_iter()
+            .find(|path| path.is_file())
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| F2V2FError::EncodingError(format!("Failed to serialize manifest: {}", e)))
+    }
+
+    /// Single-line JSON, for where space counts (container tags)
+    pub fn to_compact_json(&self) -> Result<String> {
+        serde_json::to_string(self)
+            .map_err(|e| F2V2FError::EncodingError(format!("Failed to serialize manifest: {}", e)))
+    }
+
+    /// Parse a manifest, refusing format versions this build can't decode
+    pub fn from_json(json: &str) -> Result<Self> {
+        let manifest: Self = serde_json::from_str(json)
+            .map_err(|e| F2V2FError::DecodingError(format!("Invalid manifest: {}", e)))?;
+        manifest.check_version()?;
+        Ok(manifest)
+    }
+
+    /// Fail unless `format_version` is one this build decodes
+    pub fn check_version(&self) -> Result<()> {
+        match self.format_version {
+            version if version > MANIFEST_VERSION => Err(F2V2FError::DecodingError(format!(
+                "Video uses format version {}, newer than the {} this f2v2f {} supports; upgrade f2v2f to decode it",
+                version,
+                MANIFEST_VERSION,
+                env!("CARGO_PKG_VERSION")
+            ))),
+            version if version < OLDEST_MANIFEST_VERSION => Err(F2V2FError::DecodingError(format!(
+                "Video uses format version {}, which this f2v2f no longer decodes (oldest supported: {})",
+                version, OLDEST_MANIFEST_VERSION
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Write the manifest next to the video
+    pub fn write_sidecar<P: AsRef<Path>>(&self, video_path: P) -> Result<PathBuf> {
+        let path = Self::sidecar_path(video_path);
+        crate::atomic::write_atomic(&path, self.to_json()?.as_bytes())?;
+        Ok(path)
+    }
+
+    /// Output path for decoding a video when none is given
+    ///
+    /// The original file name next to the video when it was recorded,
+    /// otherwise the video name with the extension of the recorded content
+    /// type (`.bin` if unknown); never the video path itself.
+    pub fn default_output_path<P: AsRef<Path>>(video_path: P, manifest: Option<&Self>) -> PathBuf {
+        let video = video_path.as_ref();
+        if let Some(name) = manifest.and_then(|m| m.file.as_ref()).and_then(FileMetadata::safe_name) {
+            let output = video.with_file_name(name);
+            if output != video {
+                return output;
+            }
+        }
+        let extension = manifest
+            .and_then(|m| m.content_type.as_deref())
+            .and_then(crate::content_type::extension_for_mime)
+            .unwrap_or("bin");
+        let output = video.with_extension(extension);
+        if output == video {
+            video.with_extension(format!("decoded.{}", extension))
+        } else {
+            output
+        }
+    }
+
+    /// Read the manifest stored next to a video, if there is one
+    pub fn read_sidecar<P: AsRef<Path>>(video_path: P) -> Result<Option<Self>> {
+        let Some(path) = Self::find_sidecar(video_path) else {
+            return Ok(None);
+        };
+        let json = std::fs::read_to_string(&path)?;
+        Self::from_json(&json).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Manifest {
+        Manifest {
+            format_version: MANIFEST_VERSION,
+            width: 1920,
+            height: 1080,
+            fps: 30,
+            chunk_size: 4096,
+            num_frames: 3,
+            original_size: 10_000,
+            encoded_size: 9_000,
+            compressed: true,
+            compression_level: Some(11),
+            hash_algo: HashAlgorithm::Blake3,
+            checksum: "abc".to_string(),
+            dictionary_id: None,
+            seed: 7,
+            holes: vec![Hole { offset: 4096, len: 1 << 20 }],
+            link_target: None,
+            content_type: Some("application/pdf".to_string()),
+            transition_frames: 2,
+            video_checksum: None,
+            settings: None,
+            merkle: None,
+            streams: vec![StreamInfo { id: 1, size: 100, num_frames: 1, crc32: 0xdead_beef }],
+            file: None,
+            index: None,
+        }
+    }
+
+    #[test]
+    fn test_rejects_unsupported_format_version() -> Result<()> {
+        let json = sample().to_json()?;
+        assert_eq!(Manifest::from_json(&json)?, sample());
+        for version in [0, MANIFEST_VERSION + 1] {
+            let manifest = Manifest { format_version: version, ..sample() };
+            let err = Manifest::from_json(&manifest.to_json()?).unwrap_err();
+            assert!(err.to_string().contains(&format!("format version {}", version)));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_duration_counts_transitions() {
+        // 3 data frames and 2 crossfades between each pair
+        assert_eq!(sample().duration_secs(), 7.0 / 30.0);
+    }
+
+    #[test]
+    fn test_sidecar_roundtrip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let video = dir.path().join("out.mp4");
+        let manifest = sample();
+
+        let written = manifest.write_sidecar(&video)?;
+        assert_eq!(written, dir.path().join("out.mp4.mp4meta"));
+        assert_eq!(Manifest::read_sidecar(&video)?, Some(manifest));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sidecar_path_odd_names() {
+        let cases = [
+            ("out.mp4", "out.mp4.mp4meta"),
+            ("video", "video.mp4meta"),
+            ("my.backup.tar.mp4", "my.backup.tar.mp4.mp4meta"),
+            (".hidden", ".hidden.mp4meta"),
+            ("./rel/dir/clip.mkv", "./rel/dir/clip.mkv.mp4meta"),
+            ("../vidéo 文件.mp4", "../vidéo 文件.mp4.mp4meta"),
+            ("/abs/trailing.", "/abs/trailing..mp4meta"),
+        ];
+        for (video, sidecar) in cases {
+            assert_eq!(Manifest::sidecar_path(video), PathBuf::from(sidecar), "{}", video);
+        }
+
+        // Same stem, different extensions: distinct sidecars
+        assert_ne!(Manifest::sidecar_path("a.mp4"), Manifest::sidecar_path("a.mkv"));
+        assert_ne!(Manifest::sidecar_path("a.b"), Manifest::sidecar_path("a.c"));
+    }
+
+    #[test]
+    fn test_read_legacy_sidecar() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let video = dir.path().join("old.mp4");
+        let manifest = sample();
+        std::fs::write(Manifest::legacy_sidecar_path(&video), manifest.to_json()?)?;
+
+        assert_eq!(Manifest::find_sidecar(&video), Some(dir.path().join("old.mp4meta")));
+        assert_eq!(Manifest::read_sidecar(&video)?, Some(manifest.clone()));
+
+        // A current-style sidecar wins over the legacy one
+        let newer = Manifest { seed: 99, ..manifest };
+        newer.write_sidecar(&video)?;
+        assert_eq!(Manifest::read_sidecar(&video)?, Some(newer));
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_output_path() {
+        let manifest = sample();
+        assert_eq!(Manifest::default_output_path("out.mp4", Some(&manifest)), PathBuf::from("out.pdf"));
+        assert_eq!(Manifest::default_output_path("out.mp4", None), PathBuf::from("out.bin"));
+
+        let video = Manifest { content_type: Some("video/mp4".to_string()), ..manifest.clone() };
+        assert_eq!(Manifest::default_output_path("out.mp4", Some(&video)), PathBuf::from("out.decoded.mp4"));
+
+        let named = |name: &str| Manifest {
+            file: Some(FileMetadata { name: Some(name.to_string()), ..FileMetadata::default() }),
+            ..manifest.clone()
+        };
+        assert_eq!(Manifest::default_output_path("videos/out.mp4", Some(&named("report.pdf"))), PathBuf::from("videos/report.pdf"));
+        assert_eq!(Manifest::default_output_path("out.mp4", Some(&named("../report.pdf"))), PathBuf::from("out.pdf"));
+    }
+
+    #[test]
+    fn test_missing_sidecar() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        assert_eq!(Manifest::read_sidecar(dir.path().join("none.mp4"))?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_video() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let video = dir.path().join("out.mp4");
+        std::fs::write(&video, b"video bytes")?;
+
+        let mut manifest = sample();
+        assert!(matches!(manifest.verify_video(&video), Err(F2V2FError::InvalidInput(_))));
+
+        manifest.video_checksum = Some(hash_file(&video, manifest.hash_algo)?);
+        manifest.verify_video(&video)?;
+
+        std::fs::write(&video, b"video bytez")?;
+        assert!(matches!(manifest.verify_video(&video), Err(F2V2FError::IntegrityError(..))));
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+//! Merkle tree over fixed-size blocks of the original file
+//!
+//! The whole-file checksum can only be checked by hashing everything. With
+//! a tree recorded in the manifest, any byte range of a decoded file can be
+//! verified by hashing just the blocks it touches: their hashes must match
+//! the recorded leaves, and the leaves must hash up to the recorded root.
+//! Always SHA-256, whatever the file checksum algorithm; leaves and inner
+//! nodes are domain-separated so one can't pass for the other.
+
+use crate::error::{F2V2FError, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Block size used when the config doesn't name one (1 MiB)
+pub const DEFAULT_BLOCK_SIZE: u64 = 1024 * 1024;
+
+/// Smallest block size accepted
+pub const MIN_BLOCK_SIZE: u64 = 4096;
+
+type Hash = [u8; 32];
+
+fn hash_leaf(block: &[u8]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([0u8]);
+    hasher.update(block);
+    hasher.finalize().into()
+}
+
+fn hash_node(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([1u8]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Root over `leaves`; an unpaired node moves up a level unchanged
+fn root_of(leaves: &[Hash]) -> Hash {
+    if leaves.is_empty() {
+        return hash_leaf(&[]);
+    }
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => hash_node(left, right),
+                [single] => *single,
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    level[0]
+}
+
+/// Block hashes and root of one file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleTree {
+    pub block_size: u64,
+    /// Hex SHA-256 leaf hash of each block, in file order
+    pub leaves: Vec<String>,
+    /// Hex root hash
+    pub root: String,
+}
+
+impl MerkleTree {
+    /// Check `len` bytes at `offset` of the decoded `file` against the tree
+    pub fn verify_range<P: AsRef<Path>>(&self, file: P, offset: u64, len: u64) -> Result<()> {
+        let leaves = self
+            .leaves
+            .iter()
+            .map(|leaf| {
+                let bytes = hex::decode(leaf).ok().and_then(|b| Hash::try_from(b).ok());
+                bytes.ok_or_else(|| F2V2FError::DecodingError(format!("Invalid Merkle leaf '{}'", leaf)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let root = hex::encode(root_of(&leaves));
+        if root != self.root {
+            return Err(F2V2FError::IntegrityError(
+                "Merkle leaves don't match the root".to_string(),
+                self.root.clone(),
+                root,
+            ));
+        }
+
+        let end = offset.checked_add(len).ok_or_else(|| F2V2FError::InvalidInput("Range overflows".to_string()))?;
+        let covered = self.leaves.len() as u64 * self.block_size;
+        if len == 0 || end > covered {
+            return Err(F2V2FError::InvalidInput(format!(
+                "Range {}..{} is empty or outside the {} bytes the tree covers",
+                offset, end, covered
+            )));
+        }
+
+        let mut file = File::open(file)?;
+        let first = offset / self.block_size;
+        let last = (end - 1) / self.block_size;
+        file.seek(SeekFrom::Start(first * self.block_size))?;
+        let mut block = Vec::with_capacity(self.block_size as usize);
+        for index in first..=last {
+            block.clear();
+            file.by_ref().take(self.block_size).read_to_end(&mut block)?;
+            let actual = hex::encode(hash_leaf(&block));
+            if actual != self.leaves[index as usize] {
+                return Err(F2V2FError::IntegrityError(
+                    format!("Block {} (bytes {}..) was modified", index, index * self.block_size),
+                    self.leaves[index as usize].clone(),
+                    actual,
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Builds a `MerkleTree` from a stream of bytes
+pub struct MerkleBuilder {
+    block_size: u64,
+    current: Vec<u8>,
+    leaves: Vec<Hash>,
+}
+
+impl MerkleBuilder {
+    pub fn new(block_size: u64) -> Self {
+        Self { block_size, current: Vec::with_capacity(block_size as usize), leaves: Vec::new() }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let take = (self.block_size as usize - self.current.len()).min(data.len());
+            self.current.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.current.len() as u64 == self.block_size {
+                self.leaves.push(hash_leaf(&self.current));
+                self.current.clear();
+            }
+        }
+    }
+
+    /// Account for `len` zero bytes (sparse-file holes)
+    pub fn update_zeros(&mut self, mut len: u64) {
+        let zeros = vec![0u8; (self.block_size.min(len)) as usize];
+        while len > 0 {
+            let take = (self.block_size - self.current.len() as u64).min(len);
+            self.update(&zeros[..take as usize]);
+            len -= take;
+        }
+    }
+
+    pub fn finish(mut self) -> MerkleTree {
+        if !self.current.is_empty() || self.leaves.is_empty() {
+            self.leaves.push(hash_leaf(&self.current));
+        }
+        MerkleTree {
+            block_size: self.block_size,
+            root: hex::encode(root_of(&self.leaves)),
+            leaves: self.leaves.iter().map(hex::encode).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_range() -> Result<()> {
+        let data: Vec<u8> = (0..20_000u32).map(|i| (i % 251) as u8).collect();
+        let mut builder = MerkleBuilder::new(MIN_BLOCK_SIZE);
+        builder.update(&data[..5000]);
+        builder.update(&data[5000..]);
+        let tree = builder.finish();
+        assert_eq!(tree.leaves.len(), 5);
+
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("decoded.bin");
+        std::fs::write(&file, &data)?;
+        tree.verify_range(&file, 0, 20_000)?;
+
+        // Damage block 3; ranges that avoid it still verify
+        let mut damaged = data.clone();
+        damaged[13_000] ^= 1;
+        std::fs::write(&file, &damaged)?;
+        tree.verify_range(&file, 0, 12_288)?;
+        tree.verify_range(&file, 16_384, 100)?;
+        assert!(matches!(tree.verify_range(&file, 12_000, 2000), Err(F2V2FError::IntegrityError(..))));
+        assert!(matches!(tree.verify_range(&file, 19_000, 5000), Err(F2V2FError::InvalidInput(_))));
+
+        // A tampered leaf no longer matches the root
+        let mut forged = tree.clone();
+        forged.leaves[3] = hex::encode(hash_leaf(&damaged[12_288..16_384]));
+        assert!(matches!(forged.verify_range(&file, 12_288, 10), Err(F2V2FError::IntegrityError(..))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_zeros_match_bytes() {
+        let mut zeros = MerkleBuilder::new(MIN_BLOCK_SIZE);
+        zeros.update(b"x");
+        zeros.update_zeros(10_000);
+        let mut bytes = MerkleBuilder::new(MIN_BLOCK_SIZE);
+        bytes.update(b"x");
+        bytes.update(&[0u8; 10_000]);
+        assert_eq!(zeros.finish(), bytes.finish());
+    }
+}
@@ -0,0 +1,123 @@
+//! Control handle for in-flight encode and decode operations
+//!
+//! Pausing blocks the operation at its next read (input file on encode,
+//! payload on compose and decode). ffmpeg keeps running and simply waits for
+//! more frames, so resuming picks up exactly where it stopped. The handle
+//! also counts the bytes read through it, which `jobs` reports as progress.
+
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+
+/// Cloneable handle to pause and resume an operation from another thread
+#[derive(Debug, Clone, Default)]
+pub struct OperationHandle {
+    state: Arc<(Mutex<bool>, Condvar)>,
+    bytes_read: Arc<AtomicU64>,
+}
+
+impl OperationHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn pause(&self) {
+        *self.lock() = true;
+    }
+
+    pub fn resume(&self) {
+        *self.lock() = false;
+        self.state.1.notify_all();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.lock()
+    }
+
+    /// Block the calling thread while the operation is paused
+    pub fn wait_if_paused(&self) {
+        let mut paused = self.lock();
+        while *paused {
+            paused = self.state.1.wait(paused).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Whether both handles control the same operations
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.state, &other.state)
+    }
+
+    /// Bytes read through `reader` wrappers since the last reset (shared by
+    /// operations running on the same handle)
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn reset_bytes_read(&self) {
+        self.bytes_read.store(0, Ordering::Relaxed);
+    }
+
+    /// Wrap a reader so every read waits while paused
+    pub fn reader<R: Read>(&self, inner: R) -> PausableReader<R> {
+        PausableReader { inner, handle: self.clone() }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, bool> {
+        // A poisoned flag is still a valid bool
+        self.state.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Reader that stops at each read while its handle is paused
+pub struct PausableReader<R> {
+    inner: R,
+    handle: OperationHandle,
+}
+
+impl<R: Read> Read for PausableReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.handle.wait_if_paused();
+        let n = self.inner.read(buf)?;
+        self.handle.bytes_read.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for PausableReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn test_pause_blocks_reads_until_resume() {
+        let handle = OperationHandle::new();
+        handle.pause();
+        assert!(handle.is_paused());
+
+        let done = Arc::new(AtomicBool::new(false));
+        let worker = {
+            let handle = handle.clone();
+            let done = done.clone();
+            std::thread::spawn(move || {
+                let mut out = Vec::new();
+                handle.reader(&b"payload"[..]).read_to_end(&mut out).unwrap();
+                done.store(true, Ordering::SeqCst);
+                out
+            })
+        };
+
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!done.load(Ordering::SeqCst));
+
+        handle.resume();
+        assert_eq!(worker.join().unwrap(), b"payload");
+        assert_eq!(handle.bytes_read(), 7);
+    }
+}
@@ -0,0 +1,186 @@
+//! Human-readable text overlay rendered into a reserved corner of each frame
+//!
+//! The overlay lives in the bottom-left corner, outside the data region, so it
+//! never affects decoding. It uses a tiny built-in 5x7 bitmap font to avoid a
+//! font-rendering dependency.
+
+use image::{ImageBuffer, Rgba};
+use std::ops::{Deref, DerefMut};
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+const GLYPH_SPACING: u32 = 1;
+const LINE_SPACING: u32 = 2;
+const PADDING: u32 = 2;
+const LINES: u32 = 2;
+
+const BACKGROUND: Rgba<u8> = Rgba([16, 16, 16, 255]);
+const FOREGROUND: Rgba<u8> = Rgba([240, 240, 240, 255]);
+
+/// Rectangle reserved for the overlay (x, y, width, height)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverlayRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl OverlayRegion {
+    /// Region for a frame of the given size; scales with frame width
+    pub fn for_frame(frame_width: u32, frame_height: u32) -> Self {
+        let scale = Self::scale(frame_width);
+        let height = (PADDING * 2 + GLYPH_HEIGHT * LINES + LINE_SPACING * (LINES - 1)) * scale;
+        let width = frame_width / 2;
+        Self {
+            x: 0,
+            y: frame_height.saturating_sub(height),
+            width,
+            height: height.min(frame_height),
+        }
+    }
+
+    fn scale(frame_width: u32) -> u32 {
+        (frame_width / 640).max(1)
+    }
+
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+
+    /// Maximum characters that fit on one line
+    pub fn max_chars(&self, frame_width: u32) -> usize {
+        let scale = Self::scale(frame_width);
+        let usable = (self.width / scale).saturating_sub(PADDING * 2);
+        (usable / (GLYPH_WIDTH + GLYPH_SPACING)) as usize
+    }
+}
+
+/// Draw up to two lines of text into the overlay region
+///
+/// Unsupported characters render as blanks; lines are truncated to fit.
+pub fn draw_overlay<C>(img: &mut ImageBuffer<Rgba<u8>, C>, lines: &[String])
+where
+    C: Deref<Target = [u8]> + DerefMut,
+{
+    let region = OverlayRegion::for_frame(img.width(), img.height());
+    let scale = OverlayRegion::scale(img.width());
+    let max_chars = region.max_chars(img.width());
+
+    for y in region.y..region.y + region.height {
+        for x in region.x..region.x + region.width {
+            img.put_pixel(x, y, BACKGROUND);
+        }
+    }
+
+    for (line_no, line) in lines.iter().take(LINES as usize).enumerate() {
+        let top = region.y + (PADDING + line_no as u32 * (GLYPH_HEIGHT + LINE_SPACING)) * scale;
+        for (col, ch) in line.chars().take(max_chars).enumerate() {
+            let left = region.x + (PADDING + col as u32 * (GLYPH_WIDTH + GLYPH_SPACING)) * scale;
+            let rows = glyph(ch);
+            for (gy, bits) in rows.iter().enumerate() {
+                for gx in 0..GLYPH_WIDTH {
+                    if bits & (0x10 >> gx) == 0 {
+                        continue;
+                    }
+                    for dy in 0..scale {
+                        for dx in 0..scale {
+                            let (x, y) = (left + gx * scale + dx, top + gy as u32 * scale + dy);
+                            // Frames shorter than the text clip it to the region
+                            if region.contains(x, y) {
+                                img.put_pixel(x, y, FOREGROUND);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// 5x7 glyph rows (bit 4 = leftmost column); lowercase maps to uppercase
+fn glyph(ch: char) -> [u8; 7] {
+    match ch.to_ascii_uppercase() {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '|' => [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        _ => [0x00; 7],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_inside_frame() {
+        let region = OverlayRegion::for_frame(1920, 1080);
+        assert_eq!(region.y + region.height, 1080);
+        assert!(region.contains(0, 1079));
+        assert!(!region.contains(1919, 1079));
+        assert!(region.max_chars(1920) > 20);
+    }
+
+    #[test]
+    fn test_draw_stays_in_region() {
+        let mut img = ImageBuffer::from_pixel(256, 256, Rgba([0, 0, 0, 255]));
+        draw_overlay(&mut img, &["F2V2F V0.1.0".to_string(), "FRAME 1/2".to_string()]);
+
+        let region = OverlayRegion::for_frame(256, 256);
+        for (x, y, pixel) in img.enumerate_pixels() {
+            if !region.contains(x, y) {
+                assert_eq!(pixel[0], 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_draw_clips_to_small_frames() {
+        for (width, height) in [(8, 8), (64, 10), (1, 1)] {
+            let mut img = ImageBuffer::from_pixel(width, height, Rgba([0, 0, 0, 255]));
+            draw_overlay(&mut img, &["F2V2F V0.1.0".to_string(), "FRAME 1/2".to_string()]);
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! Salvaging what survives of an incomplete or damaged video
+//!
+//! A normal decode refuses a video with missing or corrupt frames, since the
+//! output could not match its checksum. A partial decode instead keeps every
+//! frame whose CRC validates, places its payload at the frame's offset and
+//! reports which byte ranges of the original file came back. Missing bytes
+//! are left as zeros.
+//!
+//! Uncompressed payloads are recovered frame by frame. A zstd stream can only
+//! be decompressed from its start, so compressed payloads are recovered up to
+//! the first missing frame.
+
+use crate::error::Result;
+use crate::frame_header::{FrameHeader, FLAG_TRANSITION};
+use crate::image_generator::GeometricArtGenerator;
+use crate::sparse::Hole;
+use crate::warning::Warning;
+use image::RgbaImage;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::{Seek, SeekFrom, Write};
+use tracing::debug;
+
+/// Half-open byte range `start..end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    pub fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// Outcome of `Decoder::decode_partial`
+#[derive(Debug, Clone, Serialize)]
+pub struct PartialDecodeInfo {
+    /// Ranges of the original file that were recovered; the rest of the
+    /// output is zeros
+    pub recovered: Vec<ByteRange>,
+    /// Bytes written to the output
+    pub output_size: u64,
+    /// Size of the original file, if the manifest records it
+    pub original_size: Option<u64>,
+    /// Data frames whose CRC validated
+    pub frames_recovered: u64,
+    /// Data frames the video should hold, if the manifest records it
+    pub frames_expected: Option<u64>,
+    /// Frames present in the video but failing their CRC
+    pub frames_damaged: u64,
+    pub was_compressed: bool,
+    /// Recoverable anomalies during the decode
+    pub warnings: Vec<Warning>,
+}
+
+impl PartialDecodeInfo {
+    pub fn recovered_bytes(&self) -> u64 {
+        self.recovered.iter().map(ByteRange::len).sum()
+    }
+
+    /// Every byte of the original file was recovered
+    pub fn is_complete(&self) -> bool {
+        self.original_size.is_some_and(|size| self.recovered == [ByteRange { start: 0, end: size }])
+    }
+}
+
+/// Collects the frames of one stream that validate, writing each payload at
+/// its offset in `sink`
+pub(crate) struct ChunkCollector<W> {
+    generator: GeometricArtGenerator,
+    chunk_size: usize,
+    stream: u8,
+    /// Payload length of each validated frame, by index
+    frames: BTreeMap<u32, usize>,
+    damaged: u64,
+    sink: W,
+}
+
+impl<W: Write + Seek> ChunkCollector<W> {
+    pub fn new(generator: GeometricArtGenerator, chunk_size: usize, stream: u8, sink: W) -> Self {
+        Self { generator, chunk_size, stream, frames: BTreeMap::new(), damaged: 0, sink }
+    }
+
+    /// Keep the frames in `batch` that belong to the stream and validate;
+    /// damaged, foreign and repeated frames are passed over
+    pub fn process(&mut self, batch: &[RgbaImage]) -> Result<()> {
+        for frame in batch {
+            let Ok(Some(header)) = FrameHeader::try_read_from(frame) else {
+                continue;
+            };
+            if header.has_flag(FLAG_TRANSITION)
+                || header.stream != self.stream
+                || self.frames.contains_key(&header.index)
+            {
+                continue;
+            }
+            let Some(payload) = checked_payload(&self.generator, frame, &header, self.chunk_size) else {
+                debug!("Frame {} fails its CRC; leaving it out", header.index);
+                self.damaged += 1;
+                continue;
+            };
+            self.sink.seek(SeekFrom::Start(header.index as u64 * self.chunk_size as u64))?;
+            self.sink.write_all(&payload)?;
+            self.frames.insert(header.index, payload.len());
+        }
+        Ok(())
+    }
+
+    pub fn frames_recovered(&self) -> u64 {
+        self.frames.len() as u64
+    }
+
+    pub fn frames_damaged(&self) -> u64 {
+        self.damaged
+    }
+
+    /// Payload ranges the validated frames cover
+    pub fn ranges(&self) -> Vec<ByteRange> {
+        let chunk = self.chunk_size as u64;
+        merge(self.frames.iter().map(|(&index, &len)| {
+            let start = index as u64 * chunk;
+            ByteRange { start, end: start + len as u64 }
+        }))
+    }
+
+    /// Bytes from the start of the payload up to the first gap
+    pub fn prefix_len(&self) -> u64 {
+        self.ranges().first().filter(|range| range.start == 0).map_or(0, |range| range.end)
+    }
+
+    pub fn into_sink(self) -> W {
+        self.sink
+    }
+}
+
+/// Payload of `frame`, if it decodes and passes the CRC in its `header`
+pub(crate) fn checked_payload(
+    generator: &GeometricArtGenerator,
+    frame: &RgbaImage,
+    header: &FrameHeader,
+    chunk_size: usize,
+) -> Option<Vec<u8>> {
+    if header.payload_len as usize > chunk_size {
+        return None;
+    }
+    let mut payload = generator.for_header(header).decode_from_raw(frame.as_raw(), chunk_size).ok()?;
+    payload.truncate(header.payload_len as usize);
+    header.verify(&payload).then_some(payload)
+}
+
+/// Sort `ranges` and join those that touch or overlap
+pub fn merge(ranges: impl IntoIterator<Item = ByteRange>) -> Vec<ByteRange> {
+    let mut ranges: Vec<ByteRange> = ranges.into_iter().filter(|r| !r.is_empty()).collect();
+    ranges.sort_by_key(|r| r.start);
+    let mut merged: Vec<ByteRange> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// File offset of payload offset `pos`, skipping the `holes` left out of the payload
+fn file_offset(pos: u64, holes: &[Hole]) -> u64 {
+    let mut offset = pos;
+    for hole in holes {
+        if hole.offset > offset {
+            break;
+        }
+        offset += hole.len;
+    }
+    offset
+}
+
+/// Map recovered payload ranges onto the original file
+///
+/// Sparse holes hold known zeros, so they count as recovered too.
+pub fn file_ranges(payload: &[ByteRange], holes: &[Hole]) -> Vec<ByteRange> {
+    let data = payload.iter().map(|range| ByteRange {
+        start: file_offset(range.start, holes),
+        end: file_offset(range.end - 1, holes) + 1,
+    });
+    let holes = holes.iter().map(|hole| ByteRange { start: hole.offset, end: hole.end() });
+    merge(data.chain(holes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_file_ranges_skip_holes() {
+        let payload = [ByteRange { start: 0, end: 10 }, ByteRange { start: 20, end: 30 }];
+        assert_eq!(file_ranges(&payload, &[]), payload);
+
+        // 100 zero bytes at offset 5 aren't in the payload
+        let holes = [Hole { offset: 5, len: 100 }];
+        assert_eq!(
+            file_ranges(&payload, &holes),
+            [ByteRange { start: 0, end: 110 }, ByteRange { start: 120, end: 130 }]
+        );
+        assert_eq!(merge([ByteRange { start: 4, end: 6 }, ByteRange { start: 0, end: 4 }]), [ByteRange {
+            start: 0,
+            end: 6
+        }]);
+    }
+
+    #[test]
+    fn test_collector_keeps_valid_frames() -> Result<()> {
+        let generator = GeometricArtGenerator::new(256, 256, 7);
+        let data: Vec<u8> = (0..3500u32).map(|i| (i % 251) as u8).collect();
+        let mut frames = data
+            .chunks(1000)
+            .enumerate()
+            .map(|(i, chunk)| generator.generate_frame(&FrameHeader::new(i as u32, chunk), chunk))
+            .collect::<Result<Vec<_>>>()?;
+        // Frame 1 never arrived and frame 2 is corrupt
+        frames.remove(1);
+        for pixel in frames[1].pixels_mut().skip(256 * 8).take(20_000) {
+            pixel.0[0] = pixel.0[0].wrapping_add(97);
+        }
+
+        let mut collector = ChunkCollector::new(generator, 1000, 0, Cursor::new(Vec::new()));
+        collector.process(&frames)?;
+        assert_eq!((collector.frames_recovered(), collector.frames_damaged()), (2, 1));
+        assert_eq!(collector.ranges(), [ByteRange { start: 0, end: 1000 }, ByteRange { start: 3000, end: 3500 }]);
+        assert_eq!(collector.prefix_len(), 1000);
+
+        let out = collector.into_sink().into_inner();
+        assert_eq!(out.len(), 3500);
+        assert_eq!(out[..1000], data[..1000]);
+        assert!(out[1000..3000].iter().all(|&b| b == 0));
+        assert_eq!(out[3000..], data[3000..]);
+        Ok(())
+    }
+}
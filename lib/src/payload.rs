@@ -0,0 +1,176 @@
+//! Encoded payload handed from the encoder to the composer
+//!
+//! Small payloads stay in memory. Once a payload grows past the spill
+//! threshold it is moved to a temp file, so multi-GB inputs don't have to sit
+//! in a `Vec<u8>` between encoding and composition.
+
+use crate::error::{F2V2FError, Result};
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use tempfile::NamedTempFile;
+use tracing::debug;
+
+/// Default size above which payloads are spilled to disk (256 MB)
+pub const DEFAULT_SPILL_THRESHOLD: u64 = 256 * 1024 * 1024;
+
+/// Encoded payload, either in memory or spilled to a temp file
+pub enum Payload {
+    Memory(Vec<u8>),
+    Spilled { file: NamedTempFile, len: u64 },
+}
+
+impl Payload {
+    pub fn len(&self) -> u64 {
+        match self {
+            Payload::Memory(data) => data.len() as u64,
+            Payload::Spilled { len, .. } => *len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_spilled(&self) -> bool {
+        matches!(self, Payload::Spilled { .. })
+    }
+
+    /// Sequential reader over the payload bytes
+    pub fn reader(&self) -> Result<Box<dyn Read + Send + '_>> {
+        match self {
+            Payload::Memory(data) => Ok(Box::new(Cursor::new(data.as_slice()))),
+            Payload::Spilled { file, .. } => {
+                let mut handle = file.reopen()?;
+                handle.seek(SeekFrom::Start(0))?;
+                Ok(Box::new(BufReader::new(handle)))
+            }
+        }
+    }
+
+    /// Load the whole payload into memory
+    pub fn into_vec(self) -> Result<Vec<u8>> {
+        match self {
+            Payload::Memory(data) => Ok(data),
+            Payload::Spilled { .. } => {
+                let mut data = Vec::with_capacity(self.len() as usize);
+                self.reader()?.read_to_end(&mut data)?;
+                Ok(data)
+            }
+        }
+    }
+}
+
+/// Writer that buffers in memory and spills to a temp file past a threshold
+pub struct SpillWriter {
+    threshold: u64,
+    buffer: Vec<u8>,
+    spill: Option<(NamedTempFile, BufWriter<std::fs::File>)>,
+    len: u64,
+    /// Where to create the spill file (system temp dir if None)
+    temp_dir: Option<PathBuf>,
+}
+
+impl SpillWriter {
+    pub fn new(threshold: u64) -> Self {
+        Self { threshold, buffer: Vec::new(), spill: None, len: 0, temp_dir: None }
+    }
+
+    /// Spill into `dir` instead of the system temp dir
+    pub fn with_temp_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.temp_dir = dir;
+        self
+    }
+
+    fn spill_to_disk(&mut self) -> io::Result<()> {
+        let file = match &self.temp_dir {
+            Some(dir) => NamedTempFile::new_in(dir)?,
+            None => NamedTempFile::new()?,
+        };
+        debug!("Spilling payload to {} ({} bytes so far)", file.path().display(), self.len);
+        let mut writer = BufWriter::new(file.reopen()?);
+        writer.write_all(&self.buffer)?;
+        self.buffer = Vec::new();
+        self.spill = Some((file, writer));
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<Payload> {
+        match self.spill {
+            None => Ok(Payload::Memory(self.buffer)),
+            Some((file, writer)) => {
+                writer
+                    .into_inner()
+                    .map_err(|e| F2V2FError::Io(e.to_string()))?
+                    .sync_all()?;
+                Ok(Payload::Spilled { file, len: self.len })
+            }
+        }
+    }
+}
+
+impl Write for SpillWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.spill.is_none() && self.len + buf.len() as u64 > self.threshold {
+            self.spill_to_disk()?;
+        }
+        match &mut self.spill {
+            Some((_, writer)) => writer.write_all(buf)?,
+            None => self.buffer.extend_from_slice(buf),
+        }
+        self.len += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.spill {
+            Some((_, writer)) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_payload_stays_in_memory() -> Result<()> {
+        let mut writer = SpillWriter::new(1024);
+        writer.write_all(b"small")?;
+        let payload = writer.finish()?;
+        assert!(!payload.is_spilled());
+        assert_eq!(payload.into_vec()?, b"small");
+        Ok(())
+    }
+
+    #[test]
+    fn test_large_payload_spills_to_disk() -> Result<()> {
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let mut writer = SpillWriter::new(4096);
+        for chunk in data.chunks(1000) {
+            writer.write_all(chunk)?;
+        }
+        let payload = writer.finish()?;
+
+        assert!(payload.is_spilled());
+        assert_eq!(payload.len(), data.len() as u64);
+        let mut read_back = Vec::new();
+        payload.reader()?.read_to_end(&mut read_back)?;
+        assert_eq!(read_back, data);
+        Ok(())
+    }
+
+    #[test]
+    fn test_spill_into_temp_dir() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut writer = SpillWriter::new(16).with_temp_dir(Some(dir.path().to_path_buf()));
+        writer.write_all(&[7u8; 64])?;
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 1);
+
+        let payload = writer.finish()?;
+        assert!(payload.is_spilled());
+        drop(payload);
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,484 @@
+//! One-call encode and decode pipelines
+//!
+//! Runs the encoder, composer and manifest steps with parameters taken from a
+//! single config, so callers can't pair an `Encoder` with a `VideoComposer`
+//! that disagrees on resolution, seed or chunk size.
+//!
+//! An encode is two stages with one hand-off: `PayloadPipeline` turns the
+//! input into a compressed, chunk-planned `Payload`, and `FramePipeline`
+//! turns that payload into frames and a video. Either can be built and run
+//! on its own.
+
+use crate::atomic::ensure_absent;
+use crate::backend::{is_matroska, FfmpegBackend, VideoBackend};
+use crate::cache::{reuse_video, EncodeCache};
+use crate::checksum::hash_file;
+use crate::config::{DecodeConfig, EncodeConfig, SymlinkPolicy};
+use crate::decoder::{DecodedFileInfo, Decoder, RangeRead};
+use crate::encoder::{EncodedFileInfo, Encoder};
+use crate::error::{F2V2FError, Result};
+use crate::events::{EncodeEvent, EncodeStage, EventSink, FrameProgress};
+use crate::image_generator::{RAW_ART_STYLE, SHOWCASE_ART_STYLE};
+use crate::jobs::{self, Job, JobKind};
+use crate::manifest::{Manifest, FILE_METADATA_ATTACHMENT, MANIFEST_ATTACHMENT, MANIFEST_TAG, MAX_MANIFEST_TAG_LEN};
+use crate::operation::OperationHandle;
+use crate::partial::PartialDecodeInfo;
+use crate::payload::Payload;
+use crate::storage::{Location, Sink, Source};
+use crate::streams::StreamInfo;
+use crate::subtitles::{self, SubtitleMetadata};
+use crate::video_composer::VideoComposer;
+use crate::warning::Warning;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// Payload half of an encode: input file to the compressed, chunk-planned
+/// payload that frames are built from
+///
+/// Needs no ffmpeg, so it can be run and tested on its own.
+pub struct PayloadPipeline {
+    encoder: Encoder,
+}
+
+impl PayloadPipeline {
+    pub fn new(config: &EncodeConfig) -> Result<Self> {
+        Ok(Self { encoder: Encoder::new(config.clone())? })
+    }
+
+    /// Pause and resume reading the input through `handle`
+    pub fn with_operation(mut self, handle: OperationHandle) -> Self {
+        self.encoder = self.encoder.with_operation(handle);
+        self
+    }
+
+    pub fn run(&self, input: &Path) -> Result<(EncodedFileInfo, Payload)> {
+        self.encoder.encode_payload_blocking(input)
+    }
+
+    /// Like `run`, reading the input from any `Source`
+    pub fn run_source(&self, source: &dyn Source) -> Result<(EncodedFileInfo, Payload)> {
+        self.encoder.encode_source_blocking(source)
+    }
+}
+
+/// Frame half of an encode: payload to frames to a finished video
+///
+/// Fills in the video size, duration and checksum of the `EncodedFileInfo`
+/// that came out of the `PayloadPipeline`.
+pub struct FramePipeline {
+    composer: VideoComposer,
+    config: EncodeConfig,
+    backend: Arc<dyn VideoBackend>,
+}
+
+impl FramePipeline {
+    pub fn new(config: &EncodeConfig) -> Self {
+        let composer = VideoComposer::new(config.width, config.height, config.fps)
+            .with_seed(config.seed)
+            .with_deterministic(config.deterministic)
+            .with_raw(config.art_style == RAW_ART_STYLE)
+            .with_showcase(config.art_style == SHOWCASE_ART_STYLE)
+            .with_transitions(config.transition_frames)
+            .with_keyframe_interval(config.keyframe_interval)
+            .with_chunk_chapters(config.chunk_chapters)
+            .with_throttle(config.throttle)
+            .with_overwrite(config.overwrite)
+            .with_temp_dir(config.temp_dir.clone());
+        Self { composer, config: config.clone(), backend: Arc::new(FfmpegBackend) }
+    }
+
+    /// Write the video through `backend` instead of ffmpeg
+    pub fn with_backend(mut self, backend: Arc<dyn VideoBackend>) -> Self {
+        self.composer = self.composer.with_backend(backend.clone());
+        self.backend = backend;
+        self
+    }
+
+    /// Label the overlay with `input`'s file name, if the config enables it
+    pub fn with_overlay_for(mut self, input: &Path) -> Self {
+        if self.config.overlay {
+            let label = input
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            self.composer = self.composer.with_overlay(label);
+        }
+        self
+    }
+
+    /// Pause and resume frame feeding through `handle`
+    pub fn with_operation(mut self, handle: OperationHandle) -> Self {
+        self.composer = self.composer.with_operation(handle);
+        self
+    }
+
+    pub fn with_progress(mut self, progress: FrameProgress) -> Self {
+        self.composer = self.composer.with_progress(progress);
+        self
+    }
+
+    /// Compose `payload` into `output` and record the video's stats in `info`
+    pub fn run(self, payload: &Payload, info: &mut EncodedFileInfo, output: &Path) -> Result<()> {
+        let mut streams = Vec::with_capacity(self.config.streams.len());
+        info.streams.clear();
+        for stream in &self.config.streams {
+            let data = std::fs::read(&stream.path)?;
+            info.streams.push(StreamInfo {
+                id: stream.id,
+                size: data.len() as u64,
+                num_frames: (data.len() as u64).div_ceil(info.chunk_size as u64).max(1),
+                crc32: crc32fast::hash(&data),
+            });
+            streams.push((stream.id, data));
+        }
+        let fps = self.config.fps_for(info.num_frames);
+        if fps != self.config.fps {
+            debug!("⏱️  {} frames play at {} fps instead of {}", info.num_frames, fps, self.config.fps);
+        }
+        let mut composer = self.composer.with_fps(fps).with_streams(streams);
+        // Copies written before the video exists, so without its checksum
+        if self.config.embed_manifest {
+            composer = composer.with_manifest(Manifest::new(info, &self.config).to_json()?.into_bytes());
+        }
+        // Matroska carries the metadata as attachments, which survive remuxing
+        let matroska = is_matroska(output);
+        if let Some(file) = info.file_metadata.as_ref().filter(|_| matroska) {
+            let json = serde_json::to_vec(file)
+                .map_err(|e| F2V2FError::EncodingError(format!("Failed to serialize file metadata: {}", e)))?;
+            composer = composer.with_attachment(FILE_METADATA_ATTACHMENT, "application/json", json);
+        }
+        if self.config.manifest_tag {
+            let json = Manifest::new(info, &self.config).to_compact_json()?;
+            if matroska {
+                composer = composer.with_attachment(MANIFEST_ATTACHMENT, "application/json", json.into_bytes());
+            } else if json.len() <= MAX_MANIFEST_TAG_LEN {
+                composer = composer.with_tag(MANIFEST_TAG, json);
+            } else {
+                let warning = Warning::ManifestTagSkipped { size: json.len() };
+                warn!("{}", warning);
+                info.warnings.push(warning);
+            }
+        }
+        if self.config.subtitle_manifest {
+            let metadata = SubtitleMetadata {
+                manifest: Manifest::new(info, &self.config),
+                frame_crcs: subtitles::frame_crcs(payload, info.chunk_size)?,
+            };
+            composer = composer.with_subtitles(metadata.to_srt()?);
+        }
+        // Compressed or sparse payloads don't hold the file's bytes at its offsets
+        if !self.config.use_compression && info.holes.is_empty() && info.link_target.is_none() {
+            composer = composer.with_chapter_file_size(Some(info.original_file_size));
+        }
+        let composer = composer.with_complexity_levels(std::mem::take(&mut info.frame_complexity));
+        info.frame_index = Some(composer.compose_from_payload_blocking(payload, info.chunk_size, output)?);
+
+        let video_size = std::fs::metadata(output)?.len();
+        let duration = match self.backend.probe_duration(output) {
+            Ok(duration) => duration,
+            Err(e) => {
+                let warning = Warning::DurationEstimated { reason: e.to_string() };
+                warn!("{}", warning);
+                info.warnings.push(warning);
+                self.config.playback_duration(info.num_frames)
+            }
+        };
+        info.set_video_stats(video_size, duration);
+        info.video_checksum = Some(hash_file(output, info.hash_algo)?);
+        debug!("🎞️  Video is {} bytes, {:.1}s ({:.2}x original size)",
+            info.video_size_bytes, info.duration_secs, info.overhead_ratio);
+        Ok(())
+    }
+}
+
+/// Encode a file into a video and write its manifest sidecar (BLOCKING)
+pub fn encode_file_to_video_blocking<P: AsRef<Path>, Q: AsRef<Path>>(
+    input: P,
+    output: Q,
+    config: &EncodeConfig,
+) -> Result<EncodedFileInfo> {
+    run_encode(&Location::from(input.as_ref()), output.as_ref(), config, &OperationHandle::new(), None, Arc::new(FfmpegBackend))
+}
+
+/// Encode the input of `source` (a URL, an S3 object, memory...) into a
+/// video and write its manifest sidecar (BLOCKING)
+pub fn encode_source_to_video_blocking<Q: AsRef<Path>>(
+    source: &dyn Source,
+    output: Q,
+    config: &EncodeConfig,
+) -> Result<EncodedFileInfo> {
+    run_encode(source, output.as_ref(), config, &OperationHandle::new(), None, Arc::new(FfmpegBackend))
+}
+
+/// Like `encode_file_to_video_blocking`, but `operation` can pause and
+/// resume the encode from another thread
+pub fn encode_file_to_video_with_handle<P: AsRef<Path>, Q: AsRef<Path>>(
+    input: P,
+    output: Q,
+    config: &EncodeConfig,
+    operation: &OperationHandle,
+) -> Result<EncodedFileInfo> {
+    run_encode(&Location::from(input.as_ref()), output.as_ref(), config, operation, None, Arc::new(FfmpegBackend))
+}
+
+/// Duplicate-encode cache and key for this input, if caching applies
+fn cache_key(input: &Path, output: &Path, config: &EncodeConfig) -> Result<Option<(EncodeCache, String)>> {
+    let Some(dir) = &config.cache_dir else {
+        return Ok(None);
+    };
+    // The cache key hashes the input, which would use up a stream
+    if crate::stream::is_stream(input) {
+        return Ok(None);
+    }
+    // Preserved or skipped links produce no frames worth caching
+    let is_link = std::fs::symlink_metadata(input).is_ok_and(|m| m.file_type().is_symlink());
+    if is_link && config.symlinks != SymlinkPolicy::Follow {
+        return Ok(None);
+    }
+    Ok(Some((EncodeCache::new(dir), EncodeCache::key(input, output, config)?)))
+}
+
+/// Encode pipeline writing through `backend`, optionally reporting
+/// progress to `events`
+pub(crate) fn run_encode(
+    input: &dyn Source,
+    output: &Path,
+    config: &EncodeConfig,
+    operation: &OperationHandle,
+    events: Option<EventSink>,
+    backend: Arc<dyn VideoBackend>,
+) -> Result<EncodedFileInfo> {
+    let job = jobs::register(JobKind::Encode, input.name(), output.display().to_string(), operation);
+    let result = run_encode_stages(input, output, config, operation, events, backend, &job);
+    job.finish(&result);
+    result
+}
+
+fn run_encode_stages(
+    input: &dyn Source,
+    output: &Path,
+    config: &EncodeConfig,
+    operation: &OperationHandle,
+    events: Option<EventSink>,
+    backend: Arc<dyn VideoBackend>,
+    job: &Job,
+) -> Result<EncodedFileInfo> {
+    let emit = |event: EncodeEvent| {
+        if let Some(sink) = &events {
+            sink(event);
+        }
+    };
+
+    let cache = match input.local_path() {
+        Some(path) => cache_key(path, output, config)?,
+        None => None,
+    };
+    if let Some((cache, key)) = &cache {
+        if let Some((video, info)) = cache.lookup(key)? {
+            debug!("♻️  Identical encode found at {}, reusing it", video.display());
+            reuse_video(&video, output, config.overwrite)?;
+            Manifest::new(&info, config).write_sidecar(output)?;
+            return Ok(info);
+        }
+    }
+
+    // Fail before compressing rather than after
+    if !config.overwrite {
+        ensure_absent(output)?;
+    }
+
+    emit(EncodeEvent::StageStarted(EncodeStage::Compressing));
+    let input_size = input.local_path().and_then(|path| std::fs::metadata(path).ok()).filter(|m| m.is_file());
+    job.stage("compressing", input_size.map(|m| m.len()));
+    let payload_pipeline = PayloadPipeline::new(config)?.with_operation(operation.clone());
+    let (mut info, payload) = payload_pipeline.run_source(input)?;
+    for warning in &info.warnings {
+        emit(EncodeEvent::Warning(warning.to_string()));
+    }
+
+    emit(EncodeEvent::StageStarted(EncodeStage::Composing));
+    job.stage("composing", Some(payload.len()));
+    let mut frames = FramePipeline::new(config)
+        .with_backend(backend)
+        .with_overlay_for(input.local_path().unwrap_or(Path::new(&input.name())))
+        .with_operation(operation.clone());
+    if let Some(sink) = events.clone() {
+        frames = frames.with_progress(Arc::new(move |frame, total| {
+            sink(EncodeEvent::FrameWritten { frame, total })
+        }));
+    }
+    let earlier_warnings = info.warnings.len();
+    frames.run(&payload, &mut info, output)?;
+    drop(payload);
+    for warning in &info.warnings[earlier_warnings..] {
+        emit(EncodeEvent::Warning(warning.to_string()));
+    }
+
+    emit(EncodeEvent::StageStarted(EncodeStage::WritingManifest));
+    job.stage("writing manifest", None);
+    let sidecar = Manifest::new(&info, config).write_sidecar(output)?;
+    debug!("Encoded {} frames, manifest written to {}", info.num_frames, sidecar.display());
+
+    if let Some((cache, key)) = &cache {
+        // The video is done; a cache failure only costs a future re-encode
+        if let Err(e) = cache.record(key, output, &info) {
+            let warning = Warning::CacheNotRecorded { reason: e.to_string() };
+            warn!("{}", warning);
+            emit(EncodeEvent::Warning(warning.to_string()));
+            info.warnings.push(warning);
+        }
+    }
+
+    Ok(info)
+}
+
+/// Encode a file into a video and write its manifest sidecar
+pub async fn encode_file_to_video<P: AsRef<Path>, Q: AsRef<Path>>(
+    input: P,
+    output: Q,
+    config: &EncodeConfig,
+) -> Result<EncodedFileInfo> {
+    encode_file_to_video_blocking(input, output, config)
+}
+
+/// Decode a video back into the original file
+///
+/// Parameters recorded in the video's manifest sidecar take precedence over
+/// those in `config`.
+pub async fn decode_video_to_file<P: AsRef<Path>>(
+    input: P,
+    output: P,
+    config: &DecodeConfig,
+) -> Result<DecodedFileInfo> {
+    Decoder::new(config.clone())?.decode(input, output).await
+}
+
+/// Decode a video into `sink` (a URL, an S3 object, memory...)
+pub async fn decode_video_to_sink<P: AsRef<Path>>(
+    input: P,
+    sink: &dyn Sink,
+    config: &DecodeConfig,
+) -> Result<DecodedFileInfo> {
+    Decoder::new(config.clone())?.decode_to_sink(input, sink).await
+}
+
+/// Recover what survives of an incomplete or damaged video (see `partial`)
+pub async fn decode_partial_to_file<P: AsRef<Path>>(
+    input: P,
+    output: P,
+    config: &DecodeConfig,
+) -> Result<PartialDecodeInfo> {
+    Decoder::new(config.clone())?.decode_partial(input, output).await
+}
+
+/// Read a byte range of the file a video holds (see `Decoder::read_range`)
+pub async fn read_video_range<P: AsRef<Path>>(input: P, offset: u64, len: u64, config: &DecodeConfig) -> Result<RangeRead> {
+    Decoder::new(config.clone())?.read_range(input, offset, len).await
+}
+
+/// Write a new manifest sidecar for a video whose sidecar was lost
+///
+/// The manifest is rebuilt from the video (see `Decoder::rebuild_manifest`);
+/// an existing sidecar is only replaced with `config.overwrite`. Returns
+/// the manifest and the sidecar path.
+pub async fn repair_metadata<P: AsRef<Path>>(video_path: P, config: &DecodeConfig) -> Result<(Manifest, PathBuf)> {
+    let path = video_path.as_ref();
+    if let Some(existing) = Manifest::find_sidecar(path).filter(|_| !config.overwrite) {
+        return Err(F2V2FError::InvalidInput(format!(
+            "{} already has a manifest at {}",
+            path.display(),
+            existing.display()
+        )));
+    }
+    let manifest = Decoder::new(config.clone())?.rebuild_manifest(path).await?;
+    let sidecar = manifest.write_sidecar(path)?;
+    Ok((manifest, sidecar))
+}
+
+/// Check a video against the checksum in its manifest, without decoding it
+///
+/// Catches a video modified or truncated in transit with one read of the
+/// file; returns the manifest so the caller can go on to decode.
+pub fn verify_container<P: AsRef<Path>>(video_path: P) -> Result<Manifest> {
+    let path = video_path.as_ref();
+    let manifest = Manifest::read_sidecar(path)?.ok_or_else(|| {
+        F2V2FError::InvalidInput(format!("No manifest found at {}", Manifest::sidecar_path(path).display()))
+    })?;
+    manifest.verify_video(path)?;
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_input_leaves_no_output() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("out.mp4");
+
+        let result = encode_file_to_video_blocking(
+            dir.path().join("missing.bin"),
+            &output,
+            &EncodeConfig::default(),
+        );
+        assert!(result.is_err());
+        assert!(!output.exists());
+        assert!(!Manifest::sidecar_path(&output).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_payload_pipeline_runs_without_frames() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let input = dir.path().join("input.txt");
+        std::fs::write(&input, b"payload only ".repeat(100))?;
+        let config = EncodeConfig::default();
+
+        let (info, payload) = PayloadPipeline::new(&config)?.run(&input)?;
+        assert_eq!(info.original_file_size, 1300);
+        assert_eq!(payload.len(), info.encoded_size);
+        assert!(info.num_frames >= 1);
+        assert!(info.video_checksum.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cached_encode_is_reused() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let input = dir.path().join("input.txt");
+        std::fs::write(&input, b"cache me")?;
+        let config = EncodeConfig { cache_dir: Some(dir.path().join("cache")), ..EncodeConfig::default() };
+
+        // Stand-in for an earlier encode of the same input and settings
+        let earlier = dir.path().join("earlier.mp4");
+        std::fs::write(&earlier, b"video bytes")?;
+        let mut recorded = Encoder::new(config.clone())?.encode_payload_blocking(&input)?.0;
+        recorded.set_video_stats(11, 1.0);
+        let cache = EncodeCache::new(dir.path().join("cache"));
+        cache.record(&EncodeCache::key(&input, &earlier, &config)?, &earlier, &recorded)?;
+
+        // No ffmpeg involved: the earlier video is linked into place
+        let output = dir.path().join("again.mp4");
+        let info = encode_file_to_video_blocking(&input, &output, &config)?;
+        assert_eq!(info.checksum, recorded.checksum);
+        assert_eq!(std::fs::read(&output)?, b"video bytes");
+        assert_eq!(Manifest::read_sidecar(&output)?.unwrap().checksum, recorded.checksum);
+
+        // An MP4 can't stand in for a Matroska output: this one is encoded,
+        // which without ffmpeg fails rather than reusing the MP4
+        let mkv = dir.path().join("again.mkv");
+        assert!(cache.lookup(&EncodeCache::key(&input, &mkv, &config)?)?.is_none());
+        let installed = crate::capabilities::capabilities(&config).backends;
+        let ffmpeg = installed.iter().filter(|b| b.name == "ffmpeg" || b.name == "ffprobe").all(|b| b.available);
+        match encode_file_to_video_blocking(&input, &mkv, &config) {
+            Ok(_) => {
+                assert!(ffmpeg);
+                assert_ne!(std::fs::read(&mkv)?, b"video bytes");
+            }
+            Err(_) => assert!(!ffmpeg),
+        }
+        Ok(())
+    }
+}
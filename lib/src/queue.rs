@@ -0,0 +1,282 @@
+//! Bounded job queue with concurrency limits and priorities
+//!
+//! Long-running modes (`server`) submit each job here before starting it.
+//! At most `max_running` jobs run at once, and at most `max_encodes` of them
+//! are encodes (each one drives an ffmpeg process and holds its payload).
+//! Up to `max_queued` more wait for a slot; past that, submitting fails with
+//! `F2V2FError::QueueFull` so a burst is turned away instead of piling up.
+//! Waiting jobs start highest priority first, in submission order within a
+//! priority; an encode held back by `max_encodes` doesn't hold up the
+//! decodes behind it.
+
+use crate::error::{F2V2FError, Result};
+use crate::jobs::JobKind;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+use tracing::debug;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl FromStr for Priority {
+    type Err = F2V2FError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "low" => Ok(Priority::Low),
+            "normal" => Ok(Priority::Normal),
+            "high" => Ok(Priority::High),
+            _ => Err(F2V2FError::InvalidInput(format!("Unknown priority '{}' (low, normal, high)", s))),
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Priority::Low => "low",
+            Priority::Normal => "normal",
+            Priority::High => "high",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueueConfig {
+    /// Jobs running at once
+    pub max_running: usize,
+    /// Encodes among them
+    pub max_encodes: usize,
+    /// Jobs waiting for a slot before submissions are refused
+    pub max_queued: usize,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self { max_running: 2, max_encodes: 2, max_queued: 64 }
+    }
+}
+
+impl QueueConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.max_running == 0 || self.max_encodes == 0 {
+            return Err(F2V2FError::ConfigError("Queue limits must allow at least one running job".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Waiting jobs by (priority, submission order)
+type WaitKey = (Reverse<Priority>, u64);
+
+struct Waiter {
+    kind: JobKind,
+    start: oneshot::Sender<Permit>,
+}
+
+#[derive(Default)]
+struct State {
+    running: usize,
+    encodes: usize,
+    next_seq: u64,
+    waiting: BTreeMap<WaitKey, Waiter>,
+}
+
+struct Inner {
+    config: QueueConfig,
+    state: Mutex<State>,
+}
+
+impl Inner {
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Start every waiting job that now fits; returns the permits of jobs
+    /// whose submitter went away, to be dropped once the lock is released
+    fn schedule(self: &Arc<Self>, state: &mut State) -> Vec<Permit> {
+        let mut abandoned = Vec::new();
+        while state.running < self.config.max_running {
+            let encodes_full = state.encodes >= self.config.max_encodes;
+            let Some(key) = state
+                .waiting
+                .iter()
+                .find(|(_, waiter)| !(encodes_full && waiter.kind == JobKind::Encode))
+                .map(|(key, _)| *key)
+            else {
+                break;
+            };
+            let Some(waiter) = state.waiting.remove(&key) else {
+                break;
+            };
+            state.running += 1;
+            if waiter.kind == JobKind::Encode {
+                state.encodes += 1;
+            }
+            let permit = Permit { inner: self.clone(), kind: waiter.kind };
+            if let Err(permit) = waiter.start.send(permit) {
+                abandoned.push(permit);
+            }
+        }
+        abandoned
+    }
+}
+
+/// Queue shared by everything that submits jobs to it
+#[derive(Clone)]
+pub struct JobQueue {
+    inner: Arc<Inner>,
+}
+
+impl JobQueue {
+    pub fn new(config: QueueConfig) -> Result<Self> {
+        config.validate()?;
+        Ok(Self { inner: Arc::new(Inner { config, state: Mutex::new(State::default()) }) })
+    }
+
+    pub fn config(&self) -> QueueConfig {
+        self.inner.config
+    }
+
+    /// Queue a job; fails with `QueueFull` when `max_queued` jobs already wait
+    pub fn submit(&self, kind: JobKind, priority: Priority) -> Result<Ticket> {
+        let (start, started) = oneshot::channel();
+        let (key, abandoned) = {
+            let mut state = self.inner.state();
+            if state.waiting.len() >= self.inner.config.max_queued {
+                return Err(F2V2FError::QueueFull(format!(
+                    "{} jobs already waiting; try again later",
+                    state.waiting.len()
+                )));
+            }
+            state.next_seq += 1;
+            let key = (Reverse(priority), state.next_seq);
+            state.waiting.insert(key, Waiter { kind, start });
+            (key, self.inner.schedule(&mut state))
+        };
+        drop(abandoned);
+        debug!("Queued {} job ({} priority)", kind, priority);
+        Ok(Ticket { queue: self.clone(), key, started })
+    }
+
+    /// Jobs (running, waiting)
+    pub fn load(&self) -> (usize, usize) {
+        let state = self.inner.state();
+        (state.running, state.waiting.len())
+    }
+
+    /// Jobs that start before the waiting job `ticket`, if it still waits
+    pub fn position(&self, ticket: &Ticket) -> Option<usize> {
+        let state = self.inner.state();
+        state.waiting.contains_key(&ticket.key).then(|| state.waiting.range(..ticket.key).count())
+    }
+}
+
+/// A queued job; dropping it before it starts gives up its place
+pub struct Ticket {
+    queue: JobQueue,
+    key: WaitKey,
+    started: oneshot::Receiver<Permit>,
+}
+
+impl Ticket {
+    /// Wait for the job's turn; the job may run while the permit is held
+    pub async fn start(mut self) -> Permit {
+        let started = std::mem::replace(&mut self.started, oneshot::channel().1);
+        // The queue holds the sender until it sends, so this only fails if
+        // the queue itself is gone, which the ticket's own handle prevents
+        started.await.expect("job queue dropped a waiting job")
+    }
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        let abandoned = {
+            let mut state = self.queue.inner.state();
+            if state.waiting.remove(&self.key).is_none() {
+                return;
+            }
+            self.queue.inner.schedule(&mut state)
+        };
+        drop(abandoned);
+    }
+}
+
+/// A running job's slot, released when dropped
+pub struct Permit {
+    inner: Arc<Inner>,
+    kind: JobKind,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let abandoned = {
+            let mut state = self.inner.state();
+            state.running -= 1;
+            if self.kind == JobKind::Encode {
+                state.encodes -= 1;
+            }
+            self.inner.schedule(&mut state)
+        };
+        drop(abandoned);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(max_running: usize, max_encodes: usize, max_queued: usize) -> JobQueue {
+        JobQueue::new(QueueConfig { max_running, max_encodes, max_queued }).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_limits_and_priorities() -> Result<()> {
+        let queue = queue(2, 1, 3);
+        let encode = queue.submit(JobKind::Encode, Priority::Normal)?.start().await;
+        // One encode at a time: the next one waits, the decode behind it doesn't
+        let second_encode = queue.submit(JobKind::Encode, Priority::High)?;
+        let decode = queue.submit(JobKind::Decode, Priority::Low)?.start().await;
+        assert_eq!(queue.load(), (2, 1));
+
+        let low = queue.submit(JobKind::Decode, Priority::Low)?;
+        let high = queue.submit(JobKind::Decode, Priority::High)?;
+        assert!(matches!(queue.submit(JobKind::Decode, Priority::High), Err(F2V2FError::QueueFull(_))));
+        assert_eq!(queue.position(&second_encode), Some(0));
+        assert_eq!(queue.position(&high), Some(1));
+        assert_eq!(queue.position(&low), Some(2));
+
+        // A decode slot frees up: the high priority decode goes ahead of the
+        // encode still blocked by the encode limit
+        drop(decode);
+        let high = high.start().await;
+        assert_eq!(queue.position(&low), Some(1));
+
+        // Giving up a place lets the next job move up
+        drop(second_encode);
+        assert_eq!(queue.position(&low), Some(0));
+        drop((encode, high));
+        let _low = low.start().await;
+        assert_eq!(queue.load(), (1, 0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_priority_parsing() {
+        assert_eq!("HIGH".parse::<Priority>().unwrap(), Priority::High);
+        assert!("urgent".parse::<Priority>().is_err());
+        assert!(Priority::High > Priority::Normal && Priority::Normal > Priority::Low);
+        assert!(JobQueue::new(QueueConfig { max_running: 0, ..QueueConfig::default() }).is_err());
+    }
+}
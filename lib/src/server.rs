@@ -0,0 +1,671 @@
+//! HTTP API for encoding and decoding (feature `server`, `f2v2f serve`)
+//!
+//! Lets services that can't use the FFI submit jobs over REST:
+//!
+//! - `POST /encode`: multipart form with the input as a `file` upload or a
+//!   `path` under the server root, and optional `preset`, `resolution`,
+//!   `fps`, `chunk_size`, `seed` and `compression` (true/false) fields
+//! - `POST /decode`: multipart form with the video as a `video` upload (and
+//!   optionally its `manifest` sidecar) or a `path`, and an optional `seed`
+//! - `GET /jobs`, `GET /jobs/:id`: state, stage and progress (see `jobs`)
+//! - `GET /jobs/:id/result`: the finished video or decoded file;
+//!   `GET /jobs/:id/manifest`: an encode's manifest sidecar
+//! - `DELETE /jobs/:id`: forget a finished job and delete its files
+//!
+//! Submitting answers `202 Accepted` with the job right away; jobs wait in
+//! a `queue::JobQueue` and run in the background within its limits. A
+//! `?priority=low|normal|high` query parameter orders the waiting jobs; a
+//! submission finding the queue full gets `503 Service Unavailable` before
+//! its upload is read. Path references are off unless a `root` is
+//! configured, and can't reach outside it.
+
+use crate::backend::{FfmpegBackend, VideoBackend};
+use crate::config::{DecodeConfig, EncodeConfig, Preset};
+use crate::decoder::Decoder;
+use crate::error::{F2V2FError, Result};
+use crate::jobs::{self, JobKind, JobState};
+use crate::manifest::Manifest;
+use crate::operation::OperationHandle;
+use crate::queue::{JobQueue, Priority, QueueConfig};
+use crate::storage::Location;
+use axum::body::Body;
+use axum::extract::{DefaultBodyLimit, Multipart, Path as UrlPath, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, info};
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub listen: SocketAddr,
+    /// Directory path references are resolved in; `None` refuses them
+    pub root: Option<PathBuf>,
+    /// Where uploads and results are kept (default: a temp directory)
+    pub work_dir: Option<PathBuf>,
+    /// Jobs running at once, encodes among them, and jobs waiting
+    pub queue: QueueConfig,
+    /// Largest request body accepted, in bytes
+    pub max_upload: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            listen: SocketAddr::from(([127, 0, 0, 1], 8080)),
+            root: None,
+            work_dir: None,
+            queue: QueueConfig::default(),
+            max_upload: 4 << 30,
+        }
+    }
+}
+
+/// Serve the API until the listener fails
+pub async fn serve(config: ServerConfig) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(config.listen).await?;
+    let (router, work_dir) = router(&config)?;
+    info!("Serving on http://{} (work dir {})", config.listen, work_dir.path().display());
+    axum::serve(listener, router).await?;
+    Ok(())
+}
+
+/// The API's routes, and the work directory they keep files in (removed
+/// when dropped unless configured)
+pub fn router(config: &ServerConfig) -> Result<(Router, WorkDir)> {
+    router_with_backend(config, Arc::new(FfmpegBackend))
+}
+
+/// Like `router`, but jobs write and read videos through `backend`
+pub fn router_with_backend(config: &ServerConfig, backend: Arc<dyn VideoBackend>) -> Result<(Router, WorkDir)> {
+    let queue = JobQueue::new(config.queue)?;
+    let work_dir = match &config.work_dir {
+        Some(dir) => {
+            std::fs::create_dir_all(dir)?;
+            WorkDir::Kept(dir.clone())
+        }
+        None => WorkDir::Temp(tempfile::Builder::new().prefix("f2v2f-serve-").tempdir()?),
+    };
+    let state = Arc::new(AppState {
+        root: config.root.as_ref().map(|root| root.canonicalize()).transpose()?,
+        work_dir: work_dir.path().to_path_buf(),
+        queue,
+        backend,
+        tasks: Mutex::new(BTreeMap::new()),
+        next_id: Mutex::new(0),
+    });
+    let router = Router::new()
+        .route("/encode", axum::routing::post(submit_encode))
+        .route("/decode", axum::routing::post(submit_decode))
+        .route("/jobs", get(list_tasks))
+        .route("/jobs/:id", get(task_status).delete(delete_task))
+        .route("/jobs/:id/result", get(download_result))
+        .route("/jobs/:id/manifest", get(download_manifest))
+        .layer(DefaultBodyLimit::max(config.max_upload))
+        .with_state(state);
+    Ok((router, work_dir))
+}
+
+/// Directory holding each job's files
+pub enum WorkDir {
+    Temp(tempfile::TempDir),
+    Kept(PathBuf),
+}
+
+impl WorkDir {
+    pub fn path(&self) -> &Path {
+        match self {
+            WorkDir::Temp(dir) => dir.path(),
+            WorkDir::Kept(dir) => dir,
+        }
+    }
+}
+
+struct AppState {
+    root: Option<PathBuf>,
+    work_dir: PathBuf,
+    queue: JobQueue,
+    backend: Arc<dyn VideoBackend>,
+    tasks: Mutex<BTreeMap<u64, Task>>,
+    next_id: Mutex<u64>,
+}
+
+/// A submitted job; `jobs` has its progress once it starts
+struct Task {
+    kind: JobKind,
+    operation: OperationHandle,
+    dir: PathBuf,
+    outcome: Option<std::result::Result<Output, String>>,
+}
+
+/// Files a finished job produced
+struct Output {
+    result: PathBuf,
+    content_type: Option<String>,
+    manifest: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+struct TaskStatus {
+    id: u64,
+    kind: JobKind,
+    state: JobState,
+    stage: String,
+    done: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result_url: Option<String>,
+}
+
+impl Task {
+    fn status(&self, id: u64) -> TaskStatus {
+        let mut status = TaskStatus {
+            id,
+            kind: self.kind,
+            state: JobState::Running,
+            stage: "queued".to_string(),
+            done: 0,
+            total: None,
+            error: None,
+            result_url: None,
+        };
+        match &self.outcome {
+            Some(Ok(_)) => {
+                status.state = JobState::Succeeded;
+                status.stage = "done".to_string();
+                status.result_url = Some(format!("/jobs/{}/result", id));
+            }
+            Some(Err(e)) => {
+                status.state = JobState::Failed;
+                status.stage = "done".to_string();
+                status.error = Some(e.clone());
+            }
+            None => {
+                if let Some(job) = jobs::for_operation(&self.operation) {
+                    (status.state, status.stage, status.done, status.total) = (job.state, job.stage, job.done, job.total);
+                }
+            }
+        }
+        status
+    }
+}
+
+/// Error answered as `{"error": "..."}`
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+impl From<F2V2FError> for ApiError {
+    fn from(err: F2V2FError) -> Self {
+        let status = match &err {
+            F2V2FError::InvalidInput(_)
+            | F2V2FError::ConfigError(_)
+            | F2V2FError::NotF2V2FVideo(_)
+            | F2V2FError::UnsupportedStyle(..) => StatusCode::BAD_REQUEST,
+            F2V2FError::QueueFull(_) => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        ApiError(status, err.to_string())
+    }
+}
+
+impl From<std::io::Error> for ApiError {
+    fn from(err: std::io::Error) -> Self {
+        F2V2FError::from(err).into()
+    }
+}
+
+impl From<axum::extract::multipart::MultipartError> for ApiError {
+    fn from(err: axum::extract::multipart::MultipartError) -> Self {
+        ApiError(StatusCode::BAD_REQUEST, err.to_string())
+    }
+}
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+fn bad_request(message: impl Into<String>) -> ApiError {
+    ApiError(StatusCode::BAD_REQUEST, message.into())
+}
+
+fn not_found(id: u64) -> ApiError {
+    ApiError(StatusCode::NOT_FOUND, format!("No job {}", id))
+}
+
+impl AppState {
+    /// Set up a task and its directory
+    fn new_task(&self, kind: JobKind) -> ApiResult<(u64, PathBuf, OperationHandle)> {
+        let id = {
+            let mut next_id = self.next_id.lock().unwrap_or_else(|e| e.into_inner());
+            *next_id += 1;
+            *next_id
+        };
+        let dir = self.work_dir.join(id.to_string());
+        std::fs::create_dir_all(&dir)?;
+        let operation = OperationHandle::new();
+        let task = Task { kind, operation: operation.clone(), dir: dir.clone(), outcome: None };
+        self.tasks().insert(id, task);
+        Ok((id, dir, operation))
+    }
+
+    fn tasks(&self) -> std::sync::MutexGuard<'_, BTreeMap<u64, Task>> {
+        self.tasks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn finish(&self, id: u64, outcome: Result<Output>) {
+        if let Err(e) = &outcome {
+            debug!("Job {} failed: {}", id, e);
+        }
+        if let Some(task) = self.tasks().get_mut(&id) {
+            task.outcome = Some(outcome.map_err(|e| e.to_string()));
+        }
+    }
+
+    /// Resolve a path reference inside the server root
+    fn resolve(&self, path: &str) -> Result<PathBuf> {
+        let root = self.root.as_ref().ok_or_else(|| {
+            F2V2FError::InvalidInput("Path references are disabled; start the server with a root".to_string())
+        })?;
+        let resolved = root.join(path).canonicalize()?;
+        if !resolved.starts_with(root) {
+            return Err(F2V2FError::InvalidInput(format!("{} is outside the server root", path)));
+        }
+        Ok(resolved)
+    }
+
+    fn accepted(&self, id: u64) -> Response {
+        let status = self.tasks().get(&id).map(|task| task.status(id));
+        (StatusCode::ACCEPTED, Json(status)).into_response()
+    }
+}
+
+/// Query parameters of `POST /encode` and `POST /decode`
+#[derive(Debug, Default, Deserialize)]
+struct SubmitParams {
+    #[serde(default)]
+    priority: Priority,
+}
+
+/// Fields of a submitted form; uploads are written into the task directory
+#[derive(Default)]
+struct Form {
+    fields: BTreeMap<String, String>,
+    uploads: BTreeMap<String, PathBuf>,
+}
+
+impl Form {
+    /// Read the form, saving each upload in `uploads` under `dir` (keeping
+    /// the client's file name for the main one, for overlay labels)
+    async fn read(mut multipart: Multipart, dir: &Path, uploads: &[&str]) -> ApiResult<Self> {
+        let mut form = Form::default();
+        while let Some(mut field) = multipart.next_field().await? {
+            let name = field.name().unwrap_or_default().to_string();
+            if !uploads.contains(&name.as_str()) {
+                form.fields.insert(name, field.text().await?);
+                continue;
+            }
+            let file_name = field
+                .file_name()
+                .and_then(|n| Path::new(n).file_name())
+                .map(|n| n.to_os_string())
+                .unwrap_or_else(|| name.clone().into());
+            let upload_dir = dir.join(&name);
+            tokio::fs::create_dir_all(&upload_dir).await?;
+            let path = upload_dir.join(file_name);
+            let mut file = tokio::fs::File::create(&path).await?;
+            while let Some(chunk) = field.chunk().await? {
+                file.write_all(&chunk).await?;
+            }
+            file.flush().await?;
+            form.uploads.insert(name, path);
+        }
+        Ok(form)
+    }
+
+    fn parsed<T: std::str::FromStr>(&self, name: &str) -> ApiResult<Option<T>> {
+        self.fields
+            .get(name)
+            .map(|value| value.parse().map_err(|_| bad_request(format!("Invalid {}: '{}'", name, value))))
+            .transpose()
+    }
+
+    fn encode_config(&self) -> ApiResult<EncodeConfig> {
+        let mut config = EncodeConfig::default();
+        if let Some(preset) = self.fields.get("preset") {
+            preset.parse::<Preset>()?.apply(&mut config);
+        }
+        if let Some(resolution) = self.fields.get("resolution") {
+            (config.width, config.height) = EncodeConfig::parse_resolution(resolution)?;
+        }
+        config.fps = self.parsed("fps")?.unwrap_or(config.fps);
+        config.chunk_size = self.parsed("chunk_size")?.unwrap_or(config.chunk_size);
+        config.seed = self.parsed("seed")?.unwrap_or(config.seed);
+        config.use_compression = self.parsed("compression")?.unwrap_or(config.use_compression);
+        config.adaptive_fps = self.parsed("adaptive_fps")?.unwrap_or(config.adaptive_fps);
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// The `upload` file, else the `path` reference
+    fn input(&self, state: &AppState, upload: &str) -> ApiResult<PathBuf> {
+        match (self.uploads.get(upload), self.fields.get("path")) {
+            (Some(path), None) => Ok(path.clone()),
+            (None, Some(path)) => Ok(state.resolve(path)?),
+            _ => Err(bad_request(format!("Give either a '{}' upload or a 'path'", upload))),
+        }
+    }
+}
+
+async fn submit_encode(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SubmitParams>,
+    multipart: Multipart,
+) -> ApiResult<Response> {
+    let ticket = state.queue.submit(JobKind::Encode, params.priority)?;
+    let (id, dir, operation) = state.new_task(JobKind::Encode)?;
+    let form = Form::read(multipart, &dir, &["file"]).await;
+    let prepared = form.and_then(|form| Ok((form.input(&state, "file")?, form.encode_config()?)));
+    let (input, config) = match prepared {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            forget(&state, id);
+            return Err(e);
+        }
+    };
+
+    let task_state = state.clone();
+    let backend = state.backend.clone();
+    tokio::spawn(async move {
+        let _permit = ticket.start().await;
+        let output = dir.join("video.mp4");
+        let encoded = tokio::task::spawn_blocking(move || {
+            crate::pipeline::run_encode(&Location::from(input), &output, &config, &operation, None, backend).map(|_| Output {
+                manifest: Some(Manifest::sidecar_path(&output)),
+                result: output,
+                content_type: Some("video/mp4".to_string()),
+            })
+        })
+        .await
+        .unwrap_or_else(|e| Err(F2V2FError::Unknown(format!("Encode task failed: {}", e))));
+        task_state.finish(id, encoded);
+    });
+    Ok(state.accepted(id))
+}
+
+async fn submit_decode(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SubmitParams>,
+    multipart: Multipart,
+) -> ApiResult<Response> {
+    let ticket = state.queue.submit(JobKind::Decode, params.priority)?;
+    let (id, dir, operation) = state.new_task(JobKind::Decode)?;
+    let prepared = async {
+        let form = Form::read(multipart, &dir, &["video", "manifest"]).await?;
+        let video = form.input(&state, "video")?;
+        if let Some(manifest) = form.uploads.get("manifest") {
+            if !form.uploads.contains_key("video") {
+                return Err(bad_request("A 'manifest' upload goes with a 'video' upload"));
+            }
+            // Checked here so a bad upload fails the request, not the job
+            let json = tokio::fs::read_to_string(manifest).await.map_err(|e| bad_request(format!("Unreadable manifest: {}", e)))?;
+            Manifest::from_json(&json).map_err(|e| bad_request(e.to_string()))?;
+            tokio::fs::rename(manifest, Manifest::sidecar_path(&video)).await?;
+        }
+        let mut config = DecodeConfig::default();
+        config.seed = form.parsed("seed")?.unwrap_or(config.seed);
+        Ok::<_, ApiError>((video, config))
+    };
+    let (video, config) = match prepared.await {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            forget(&state, id);
+            return Err(e);
+        }
+    };
+
+    let task_state = state.clone();
+    let backend = state.backend.clone();
+    let runtime = tokio::runtime::Handle::current();
+    tokio::spawn(async move {
+        let _permit = ticket.start().await;
+        let output = dir.join("decoded");
+        // Decoding blocks on ffmpeg, so it gets a thread of its own
+        let decoded = tokio::task::spawn_blocking(move || -> Result<Output> {
+            let decoder = Decoder::new(config)?.with_operation(operation).with_backend(backend);
+            let info = runtime.block_on(decoder.decode(&video, &output))?;
+            Ok(Output { result: output, content_type: info.content_type, manifest: None })
+        })
+        .await
+        .unwrap_or_else(|e| Err(F2V2FError::Unknown(format!("Decode task failed: {}", e))));
+        task_state.finish(id, decoded);
+    });
+    Ok(state.accepted(id))
+}
+
+/// Drop a task that never started, with its files
+fn forget(state: &AppState, id: u64) {
+    if let Some(task) = state.tasks().remove(&id) {
+        let _ = std::fs::remove_dir_all(task.dir);
+    }
+}
+
+async fn list_tasks(State(state): State<Arc<AppState>>) -> Json<Vec<TaskStatus>> {
+    Json(state.tasks().iter().map(|(&id, task)| task.status(id)).collect())
+}
+
+async fn task_status(State(state): State<Arc<AppState>>, UrlPath(id): UrlPath<u64>) -> ApiResult<Json<TaskStatus>> {
+    state.tasks().get(&id).map(|task| Json(task.status(id))).ok_or_else(|| not_found(id))
+}
+
+async fn delete_task(State(state): State<Arc<AppState>>, UrlPath(id): UrlPath<u64>) -> ApiResult<StatusCode> {
+    let task = {
+        let mut tasks = state.tasks();
+        match tasks.get(&id) {
+            None => return Err(not_found(id)),
+            Some(task) if task.outcome.is_none() => {
+                return Err(ApiError(StatusCode::CONFLICT, format!("Job {} is still running", id)));
+            }
+            Some(_) => tasks.remove(&id),
+        }
+    };
+    if let Some(task) = task {
+        tokio::fs::remove_dir_all(&task.dir).await?;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn download_result(State(state): State<Arc<AppState>>, UrlPath(id): UrlPath<u64>) -> ApiResult<Response> {
+    let (path, content_type) = finished_output(&state, id, |output| {
+        Some((output.result.clone(), output.content_type.clone()))
+    })?;
+    let content_type = content_type.unwrap_or_else(|| "application/octet-stream".to_string());
+    send_file(&path, &content_type).await
+}
+
+async fn download_manifest(State(state): State<Arc<AppState>>, UrlPath(id): UrlPath<u64>) -> ApiResult<Response> {
+    let path = finished_output(&state, id, |output| output.manifest.clone())?;
+    send_file(&path, "application/json").await
+}
+
+/// What `pick` takes from a job's output, once the job has succeeded
+fn finished_output<T>(state: &AppState, id: u64, pick: impl FnOnce(&Output) -> Option<T>) -> ApiResult<T> {
+    let tasks = state.tasks();
+    let task = tasks.get(&id).ok_or_else(|| not_found(id))?;
+    match &task.outcome {
+        None => Err(ApiError(StatusCode::CONFLICT, format!("Job {} is still running", id))),
+        Some(Err(e)) => Err(ApiError(StatusCode::CONFLICT, format!("Job {} failed: {}", id, e))),
+        Some(Ok(output)) => pick(output).ok_or_else(|| ApiError(StatusCode::NOT_FOUND, format!("Job {} has no such file", id))),
+    }
+}
+
+async fn send_file(path: &Path, content_type: &str) -> ApiResult<Response> {
+    let file = tokio::fs::File::open(path).await?;
+    let len = file.metadata().await?.len();
+    let body = Body::from_stream(tokio_util::io::ReaderStream::new(file));
+    Ok(([(header::CONTENT_TYPE, content_type.to_string()), (header::CONTENT_LENGTH, len.to_string())], body).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MockBackend;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    const BOUNDARY: &str = "f2v2f-test-boundary";
+
+    /// Multipart body of text `fields` and (field, file name, contents) `uploads`
+    fn form(fields: &[(&str, &str)], uploads: &[(&str, &str, &[u8])]) -> Body {
+        let mut body = Vec::new();
+        for (name, value) in fields {
+            body.extend(format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n", BOUNDARY, name, value).bytes());
+        }
+        for (name, file_name, contents) in uploads {
+            body.extend(
+                format!(
+                    "--{}\r\nContent-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\r\n",
+                    BOUNDARY, name, file_name
+                )
+                .bytes(),
+            );
+            body.extend_from_slice(contents);
+            body.extend(b"\r\n");
+        }
+        body.extend(format!("--{}--\r\n", BOUNDARY).bytes());
+        Body::from(body)
+    }
+
+    async fn send(router: &Router, method: &str, uri: &str, body: Body) -> (StatusCode, Vec<u8>) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, format!("multipart/form-data; boundary={}", BOUNDARY))
+            .body(body)
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        (status, axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec())
+    }
+
+    async fn get(router: &Router, uri: &str) -> (StatusCode, Vec<u8>) {
+        send(router, "GET", uri, Body::empty()).await
+    }
+
+    /// Submit a form and return the job id it was accepted as
+    async fn submit(router: &Router, uri: &str, body: Body) -> u64 {
+        let (status, body) = send(router, "POST", uri, body).await;
+        assert_eq!(status, StatusCode::ACCEPTED, "{}", String::from_utf8_lossy(&body));
+        serde_json::from_slice::<serde_json::Value>(&body).unwrap()["id"].as_u64().unwrap()
+    }
+
+    /// Poll a job until its outcome is in, returning its last status
+    async fn wait_for(router: &Router, id: u64) -> serde_json::Value {
+        for _ in 0..500 {
+            let (status, body) = get(router, &format!("/jobs/{}", id)).await;
+            assert_eq!(status, StatusCode::OK);
+            let job: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            // The job registry can report the end before the outcome is kept
+            if job["stage"] == "done" {
+                return job;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        panic!("job {} did not finish", id);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_encode_and_decode_over_http() -> Result<()> {
+        let (router, _work_dir) = router_with_backend(&ServerConfig::default(), Arc::new(MockBackend))?;
+        let data: Vec<u8> = (0..20_000u32).map(|i| (i * 7 % 253) as u8).collect();
+
+        let fields = [("resolution", "256x256"), ("chunk_size", "4096")];
+        let id = submit(&router, "/encode", form(&fields, &[("file", "in.bin", &data)])).await;
+        let job = wait_for(&router, id).await;
+        assert_eq!(job["state"], "succeeded", "{}", job);
+        assert_eq!(job["result_url"], format!("/jobs/{}/result", id));
+        let (status, video) = get(&router, &format!("/jobs/{}/result", id)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, manifest) = get(&router, &format!("/jobs/{}/manifest", id)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let uploads: [(&str, &str, &[u8]); 2] = [("video", "in.mp4", &video), ("manifest", "in.mp4.f2v2f.json", &manifest)];
+        let id = submit(&router, "/decode?priority=high", form(&[], &uploads)).await;
+        let job = wait_for(&router, id).await;
+        assert_eq!(job["state"], "succeeded", "{}", job);
+        assert_eq!(get(&router, &format!("/jobs/{}/result", id)).await, (StatusCode::OK, data));
+
+        // Finished jobs can be deleted, and then are gone
+        let (status, _) = send(&router, "DELETE", &format!("/jobs/{}", id), Body::empty()).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(get(&router, &format!("/jobs/{}", id)).await.0, StatusCode::NOT_FOUND);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_failed_decode_is_reported() -> Result<()> {
+        let (router, _work_dir) = router_with_backend(&ServerConfig::default(), Arc::new(MockBackend))?;
+        let id = submit(&router, "/decode", form(&[], &[("video", "in.mp4", b"not a video")])).await;
+        let job = wait_for(&router, id).await;
+        assert_eq!(job["state"], "failed");
+        assert!(job["error"].is_string());
+        assert_eq!(get(&router, &format!("/jobs/{}/result", id)).await.0, StatusCode::CONFLICT);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bad_forms_are_rejected_and_forgotten() -> Result<()> {
+        let (router, work_dir) = router_with_backend(&ServerConfig::default(), Arc::new(MockBackend))?;
+        let bad_forms = [
+            ("/encode", form(&[], &[])),
+            ("/encode", form(&[("fps", "fast")], &[("file", "in.bin", b"data")])),
+            ("/encode", form(&[("path", "in.bin")], &[])),
+            ("/decode", form(&[], &[("video", "in.mp4", b"video"), ("manifest", "in.json", b"{not json")])),
+            ("/decode", form(&[], &[("manifest", "in.json", b"{}")])),
+        ];
+        for (uri, body) in bad_forms {
+            let (status, body) = send(&router, "POST", uri, body).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", String::from_utf8_lossy(&body));
+            let error: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert!(error["error"].is_string());
+        }
+
+        // Their tasks and uploads are gone
+        assert_eq!(get(&router, "/jobs").await, (StatusCode::OK, b"[]".to_vec()));
+        assert_eq!(std::fs::read_dir(work_dir.path())?.count(), 0);
+        assert_eq!(get(&router, "/jobs/1").await.0, StatusCode::NOT_FOUND);
+        Ok(())
+    }
+
+    #[test]
+    fn test_path_references_stay_in_root() -> Result<()> {
+        let root = tempfile::tempdir()?;
+        std::fs::write(root.path().join("in.bin"), b"data")?;
+        let config = ServerConfig { root: Some(root.path().to_path_buf()), ..ServerConfig::default() };
+        let (_, work_dir) = router(&config)?;
+        let state = AppState {
+            root: Some(root.path().canonicalize()?),
+            work_dir: work_dir.path().to_path_buf(),
+            queue: JobQueue::new(QueueConfig::default())?,
+            backend: Arc::new(FfmpegBackend),
+            tasks: Mutex::new(BTreeMap::new()),
+            next_id: Mutex::new(0),
+        };
+        assert_eq!(state.resolve("in.bin")?, root.path().canonicalize()?.join("in.bin"));
+        assert!(state.resolve("../in.bin").is_err());
+        assert!(state.resolve("/etc/hostname").is_err());
+        assert!(AppState { root: None, ..state }.resolve("in.bin").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+//! Sparse file support
+//!
+//! Disk images and VM snapshots are mostly holes. The encoder asks the
+//! filesystem where the holes are (`SEEK_HOLE`/`SEEK_DATA`), encodes only the
+//! data between them and records the holes in the manifest. The decoder seeks
+//! over them again, so the output is sparse too.
+
+use crate::checksum::HashingWriter;
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Seek, Write};
+
+/// Holes smaller than this are encoded as ordinary zeros
+pub const MIN_HOLE_SIZE: u64 = 64 * 1024;
+
+/// A run of zero bytes that is not stored in the payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hole {
+    pub offset: u64,
+    pub len: u64,
+}
+
+impl Hole {
+    pub fn end(&self) -> u64 {
+        self.offset + self.len
+    }
+}
+
+/// Find the holes in the first `len` bytes of a file
+///
+/// Returns no holes on filesystems and platforms without `SEEK_HOLE`.
+/// Moves the file offset, so pass a handle that isn't being read.
+#[cfg(target_os = "linux")]
+pub fn find_holes(file: &File, len: u64) -> Result<Vec<Hole>> {
+    use std::os::unix::io::AsRawFd;
+
+    let fd = file.as_raw_fd();
+    let mut holes = Vec::new();
+    let mut pos = 0u64;
+
+    while pos < len {
+        let hole = unsafe { libc::lseek(fd, pos as libc::off_t, libc::SEEK_HOLE) };
+        if hole < 0 || hole as u64 >= len {
+            break;
+        }
+        // ENXIO here means there is no data after the hole
+        let data = unsafe { libc::lseek(fd, hole, libc::SEEK_DATA) };
+        let end = if data < 0 { len } else { (data as u64).min(len) };
+
+        if end - hole as u64 >= MIN_HOLE_SIZE {
+            holes.push(Hole { offset: hole as u64, len: end - hole as u64 });
+        }
+        pos = end;
+    }
+
+    Ok(holes)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn find_holes(_file: &File, _len: u64) -> Result<Vec<Hole>> {
+    Ok(Vec::new())
+}
+
+/// Writer that re-creates holes while writing the data between them
+///
+/// Holes are skipped by seeking, and hashed as zeros, so the checksum
+/// covers the full logical file.
+pub struct SparseWriter<W: Write + Seek> {
+    inner: HashingWriter<W>,
+    holes: VecDeque<Hole>,
+    pos: u64,
+}
+
+impl<W: Write + Seek> SparseWriter<W> {
+    pub fn new(inner: HashingWriter<W>, holes: &[Hole]) -> Self {
+        Self { inner, holes: holes.iter().copied().collect(), pos: 0 }
+    }
+
+    fn skip_holes(&mut self) -> io::Result<()> {
+        while let Some(hole) = self.holes.front().copied() {
+            if hole.offset > self.pos {
+                break;
+            }
+            if hole.offset < self.pos {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Hole at offset {} overlaps data", hole.offset),
+                ));
+            }
+            self.inner.skip_zeros(hole.len)?;
+            self.pos = hole.end();
+            self.holes.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Skip any trailing holes and return the hashing writer
+    ///
+    /// The caller should `set_len` the file to `bytes_written()` so a
+    /// trailing hole still extends it.
+    pub fn finish(mut self) -> io::Result<HashingWriter<W>> {
+        self.skip_holes()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write + Seek> Write for SparseWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.skip_holes()?;
+        let limit = match self.holes.front() {
+            Some(hole) => buf.len().min((hole.offset - self.pos) as usize),
+            None => buf.len(),
+        };
+        let n = self.inner.write(&buf[..limit])?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::HashAlgorithm;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_sparse_writer_restores_holes() -> Result<()> {
+        let holes = [Hole { offset: 0, len: 10 }, Hole { offset: 13, len: 5 }, Hole { offset: 20, len: 4 }];
+        let mut writer = SparseWriter::new(
+            HashingWriter::new(Cursor::new(Vec::new()), HashAlgorithm::Sha256),
+            &holes,
+        );
+        writer.write_all(b"abcde")?;
+        let (cursor, checksum) = writer.finish()?.finish();
+
+        let mut expected = vec![0u8; 10];
+        expected.extend_from_slice(b"abc");
+        expected.extend_from_slice(&[0u8; 5]);
+        expected.extend_from_slice(b"de");
+        expected.extend_from_slice(&[0u8; 4]);
+
+        // Cursor only grows on write; the trailing hole is left to set_len
+        let mut written = cursor.into_inner();
+        written.resize(expected.len(), 0);
+        assert_eq!(written, expected);
+        assert_eq!(checksum, HashAlgorithm::Sha256.digest(&expected));
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_holes_in_sparse_file() -> Result<()> {
+        let mut file = tempfile::tempfile()?;
+        file.write_all(b"data")?;
+        file.set_len(8 * 1024 * 1024)?;
+
+        // Not every filesystem reports holes; any that are found must be valid
+        let holes = find_holes(&file, 8 * 1024 * 1024)?;
+        for hole in &holes {
+            assert!(hole.offset >= 4);
+            assert!(hole.end() <= 8 * 1024 * 1024);
+            assert!(hole.len >= MIN_HOLE_SIZE);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,418 @@
+//! Diagnostics for an encoded video
+//!
+//! `analyze_video` reads every frame's header, decodes its payload and checks
+//! the CRC, without reassembling or writing the file. Each data frame is also
+//! compared against an ideal rendering of the bytes it decoded to, which shows
+//! how much a platform's re-encoding disturbed it and how close it came to
+//! losing data.
+//!
+//! `sample_video` runs the same checks on a random sample of frames, seeking
+//! to each one, for a quick probabilistic check of a long video.
+
+use crate::config::DecodeConfig;
+use crate::decoder::search_chunk_size;
+use crate::error::{F2V2FError, Result};
+use crate::frame_header::{FrameHeader, FLAG_TRANSITION};
+use crate::image_generator::GeometricArtGenerator;
+use crate::video_composer::VideoComposer;
+use image::RgbaImage;
+use rand::Rng;
+use std::path::Path;
+use tracing::debug;
+
+/// Gray levels a data pixel may differ from the ideal rendering before it
+/// counts as an error (lossless encodes still round through YUV)
+pub const PIXEL_TOLERANCE: u8 = 2;
+
+/// How well one data frame survived
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameQuality {
+    pub crc_ok: bool,
+    /// Mean distance of the byte estimates from a rounding boundary: 1.0 when
+    /// every estimate is a whole number, 0.0 when all sit halfway between two
+    pub confidence: f32,
+    /// Share of data pixels more than `PIXEL_TOLERANCE` off the ideal rendering
+    pub pixel_error_rate: f32,
+    /// Mean absolute difference from the ideal rendering, in gray levels
+    pub mean_abs_error: f32,
+}
+
+/// One frame of the video, in playback order
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameStats {
+    /// Position in the video
+    pub position: u64,
+    /// `None` for frames without an f2v2f header
+    pub header: Option<FrameHeader>,
+    /// Only measured for data frames (not transitions or foreign frames)
+    pub quality: Option<FrameQuality>,
+}
+
+/// Per-frame diagnostics for a whole video
+#[derive(Debug, Clone)]
+pub struct VideoStats {
+    pub width: u32,
+    pub height: u32,
+    pub chunk_size: usize,
+    pub video_size_bytes: u64,
+    pub frames: Vec<FrameStats>,
+}
+
+impl VideoStats {
+    fn qualities(&self) -> impl Iterator<Item = &FrameQuality> {
+        self.frames.iter().filter_map(|f| f.quality.as_ref())
+    }
+
+    pub fn data_frames(&self) -> usize {
+        self.qualities().count()
+    }
+
+    pub fn transition_frames(&self) -> usize {
+        self.frames
+            .iter()
+            .filter(|f| f.header.is_some_and(|h| h.has_flag(FLAG_TRANSITION)))
+            .count()
+    }
+
+    /// Frames without an f2v2f header
+    pub fn foreign_frames(&self) -> usize {
+        self.frames.iter().filter(|f| f.header.is_none()).count()
+    }
+
+    pub fn crc_failures(&self) -> usize {
+        self.qualities().filter(|q| !q.crc_ok).count()
+    }
+
+    fn mean(&self, field: impl Fn(&FrameQuality) -> f32) -> f32 {
+        let n = self.data_frames();
+        if n == 0 {
+            return 0.0;
+        }
+        self.qualities().map(field).sum::<f32>() / n as f32
+    }
+
+    pub fn mean_confidence(&self) -> f32 {
+        self.mean(|q| q.confidence)
+    }
+
+    pub fn mean_pixel_error_rate(&self) -> f32 {
+        self.mean(|q| q.pixel_error_rate)
+    }
+
+    pub fn mean_abs_error(&self) -> f32 {
+        self.mean(|q| q.mean_abs_error)
+    }
+
+    /// Payload bytes carried by the data frames
+    pub fn payload_bytes(&self) -> u64 {
+        self.frames
+            .iter()
+            .filter(|f| f.quality.is_some())
+            .filter_map(|f| f.header)
+            .map(|h| h.payload_len as u64)
+            .sum()
+    }
+
+    /// Payload bytes per pixel of video (all frames)
+    pub fn density(&self) -> f64 {
+        let pixels = self.frames.len() as u64 * self.width as u64 * self.height as u64;
+        if pixels == 0 {
+            return 0.0;
+        }
+        self.payload_bytes() as f64 / pixels as f64
+    }
+
+    /// Payload bytes per byte of video file
+    pub fn storage_efficiency(&self) -> f64 {
+        if self.video_size_bytes == 0 {
+            return 0.0;
+        }
+        self.payload_bytes() as f64 / self.video_size_bytes as f64
+    }
+}
+
+/// Measures frames one batch at a time
+struct FrameAnalyzer {
+    generator: GeometricArtGenerator,
+    chunk_size: Option<usize>,
+    frames: Vec<FrameStats>,
+}
+
+impl FrameAnalyzer {
+    fn new(generator: GeometricArtGenerator, chunk_size: Option<usize>) -> Self {
+        Self { generator, chunk_size, frames: Vec::new() }
+    }
+
+    fn process(&mut self, frames: &[RgbaImage]) -> Result<()> {
+        let headers = frames
+            .iter()
+            .map(|frame| FrameHeader::try_read_from(frame).ok().flatten())
+            .collect::<Vec<_>>();
+
+        let chunk_size = match self.chunk_size {
+            Some(chunk_size) => chunk_size,
+            None => {
+                let search = search_chunk_size(&self.generator, frames, &headers);
+                // A guess no CRC confirmed is tried again on the next batch
+                if search.validated {
+                    self.chunk_size = Some(search.chunk_size);
+                }
+                search.chunk_size
+            }
+        };
+
+        for (frame, header) in frames.iter().zip(headers) {
+            let quality = match header {
+                Some(header) if !header.has_flag(FLAG_TRANSITION) => {
+                    Some(self.measure(frame, &header, chunk_size)?)
+                }
+                _ => None,
+            };
+            self.frames.push(FrameStats { position: self.frames.len() as u64, header, quality });
+        }
+        Ok(())
+    }
+
+    fn measure(&self, frame: &RgbaImage, header: &FrameHeader, chunk_size: usize) -> Result<FrameQuality> {
+        let generator = self.generator.for_header(header);
+        let estimates = generator.estimate_bytes(frame, chunk_size)?;
+        let mut data: Vec<u8> = estimates.iter().map(|v| v.round().clamp(0.0, 255.0) as u8).collect();
+
+        let payload_len = (header.payload_len as usize).min(chunk_size);
+        let crc_ok = header.verify(&data[..payload_len]);
+        let confidence = if payload_len == 0 {
+            1.0
+        } else {
+            let margin: f32 = estimates[..payload_len].iter().map(|v| 1.0 - 2.0 * (v - v.round()).abs()).sum();
+            margin / payload_len as f32
+        };
+
+        // The encoder zero-pads the last chunk
+        data[payload_len..].fill(0);
+        let ideal = generator.generate_from_data(&data)?;
+        let (mut errors, mut abs_sum, mut count) = (0u64, 0u64, 0u64);
+        for (x, y) in generator.data_pixels() {
+            let diff = frame.get_pixel(x, y)[0].abs_diff(ideal.get_pixel(x, y)[0]);
+            if diff > PIXEL_TOLERANCE {
+                errors += 1;
+            }
+            abs_sum += diff as u64;
+            count += 1;
+        }
+        let count = count.max(1) as f32;
+
+        Ok(FrameQuality {
+            crc_ok,
+            confidence,
+            pixel_error_rate: errors as f32 / count,
+            mean_abs_error: abs_sum as f32 / count,
+        })
+    }
+}
+
+/// Measure every frame of an encoded video (parameters from its manifest,
+/// or probed and inferred from frame headers without one)
+pub async fn analyze_video<P: AsRef<Path>>(video_path: P) -> Result<VideoStats> {
+    let path = video_path.as_ref();
+    let config = DecodeConfig::from_video(path)?;
+    let composer = VideoComposer::new(config.width, config.height, 30);
+
+    let mut analyzer = FrameAnalyzer::new(
+        GeometricArtGenerator::new(config.width, config.height, config.seed),
+        config.encoded_data_size.map(|_| config.chunk_size),
+    );
+    let frames = composer.extract_frames(path).await?;
+    debug!("📸 Analyzing {} frames", frames.len());
+    analyzer.process(&frames)?;
+
+    Ok(VideoStats {
+        width: config.width,
+        height: config.height,
+        chunk_size: analyzer.chunk_size.unwrap_or(config.chunk_size),
+        video_size_bytes: std::fs::metadata(path)?.len(),
+        frames: analyzer.frames,
+    })
+}
+
+/// Confidence of `SampleStats::max_damaged_share`
+pub const SAMPLE_CONFIDENCE: f64 = 0.95;
+
+/// Diagnostics for a random sample of a video's frames
+#[derive(Debug, Clone)]
+pub struct SampleStats {
+    /// Frames in the whole video
+    pub total_frames: u64,
+    /// The sampled frames only; `position` is their place in the video
+    pub stats: VideoStats,
+}
+
+impl SampleStats {
+    /// Share of sampled data frames that failed their CRC
+    pub fn damaged_share(&self) -> f64 {
+        match self.stats.data_frames() {
+            0 => 0.0,
+            n => self.stats.crc_failures() as f64 / n as f64,
+        }
+    }
+
+    /// With no failures in the sample, the largest share of damaged frames
+    /// still consistent with that at `SAMPLE_CONFIDENCE`
+    pub fn max_damaged_share(&self) -> Option<f64> {
+        let checked = self.stats.data_frames();
+        if checked == 0 || self.stats.crc_failures() > 0 {
+            return None;
+        }
+        // Chance of n clean draws with a damaged share p is (1 - p)^n
+        Some(1.0 - (1.0 - SAMPLE_CONFIDENCE).powf(1.0 / checked as f64))
+    }
+}
+
+/// Parse a sample rate: a percentage, with or without `%` (`5%` is 0.05)
+pub fn parse_sample_rate(text: &str) -> Result<f64> {
+    let percent = text.trim().trim_end_matches('%').trim().parse::<f64>().ok();
+    match percent {
+        Some(p) if p > 0.0 && p <= 100.0 => Ok(p / 100.0),
+        _ => Err(F2V2FError::ConfigError(format!(
+            "Invalid sample rate '{}' (expected a percentage between 0 and 100, e.g. 5%)",
+            text
+        ))),
+    }
+}
+
+/// Distinct frame positions for a `rate` share of `total` frames, ascending
+fn sample_positions(total: u64, rate: f64, rng: &mut impl Rng) -> Vec<u64> {
+    if total == 0 {
+        return Vec::new();
+    }
+    let count = ((total as f64 * rate).ceil() as u64).clamp(1, total);
+    let mut positions: Vec<u64> = rand::seq::index::sample(rng, total as usize, count as usize)
+        .into_iter()
+        .map(|i| i as u64)
+        .collect();
+    positions.sort_unstable();
+    positions
+}
+
+/// Measure a random `rate` share (0 to 1) of a video's frames
+pub async fn sample_video<P: AsRef<Path>>(video_path: P, rate: f64) -> Result<SampleStats> {
+    let path = video_path.as_ref();
+    if !(rate > 0.0 && rate <= 1.0) {
+        return Err(F2V2FError::ConfigError(format!("Sample rate {} is not between 0 and 1", rate)));
+    }
+    let config = DecodeConfig::from_video(path)?;
+    let composer = VideoComposer::new(config.width, config.height, 30);
+    let filters = composer.content_filters(path)?;
+    let (total_frames, fps) = VideoComposer::probe_frames(path)?;
+    let positions = sample_positions(total_frames, rate, &mut rand::thread_rng());
+    debug!("🎲 Sampling {} of {} frames", positions.len(), total_frames);
+
+    let mut analyzer = FrameAnalyzer::new(
+        GeometricArtGenerator::new(config.width, config.height, config.seed),
+        config.encoded_data_size.map(|_| config.chunk_size),
+    );
+    for position in positions {
+        // Half a frame early, so rounding can't land on the next frame
+        let seconds = (position as f64 - 0.5) / fps;
+        let Some(frame) = composer.extract_frame_at(path, &filters, seconds)? else {
+            continue;
+        };
+        analyzer.process(std::slice::from_ref(&frame))?;
+        if let Some(stats) = analyzer.frames.last_mut() {
+            stats.position = position;
+        }
+    }
+
+    Ok(SampleStats {
+        total_frames,
+        stats: VideoStats {
+            width: config.width,
+            height: config.height,
+            chunk_size: analyzer.chunk_size.unwrap_or(config.chunk_size),
+            video_size_bytes: std::fs::metadata(path)?.len(),
+            frames: analyzer.frames,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn test_frames(data: &[u8]) -> Result<Vec<RgbaImage>> {
+        let generator = GeometricArtGenerator::new(256, 256, 7);
+        data.chunks(1000)
+            .enumerate()
+            .map(|(i, chunk)| generator.generate_frame(&FrameHeader::new(i as u32, chunk), chunk))
+            .collect()
+    }
+
+    #[test]
+    fn test_clean_and_damaged_frames() -> Result<()> {
+        let data: Vec<u8> = (0..2500u32).map(|i| (i % 251) as u8).collect();
+        let mut frames = test_frames(&data)?;
+        // Heavy re-encoding damage on the middle frame
+        for (i, pixel) in frames[1].pixels_mut().skip(256 * 16).enumerate() {
+            if i % 3 == 0 {
+                pixel[0] = pixel[0].wrapping_add(40);
+            }
+        }
+        frames.push(RgbaImage::from_pixel(256, 256, image::Rgba([0, 0, 0, 255])));
+
+        let mut analyzer = FrameAnalyzer::new(GeometricArtGenerator::new(256, 256, 7), None);
+        analyzer.process(&frames)?;
+        let stats = VideoStats {
+            width: 256,
+            height: 256,
+            chunk_size: 1000,
+            video_size_bytes: 10_000,
+            frames: analyzer.frames,
+        };
+
+        assert_eq!((stats.data_frames(), stats.foreign_frames(), stats.transition_frames()), (3, 1, 0));
+        assert_eq!(stats.crc_failures(), 1);
+        assert_eq!(stats.payload_bytes(), 2500);
+        assert_eq!(stats.storage_efficiency(), 0.25);
+
+        let clean = stats.frames[0].quality.unwrap();
+        assert!(clean.crc_ok);
+        assert!(clean.confidence > 0.8);
+        assert_eq!(clean.pixel_error_rate, 0.0);
+
+        let damaged = stats.frames[1].quality.unwrap();
+        assert!(damaged.pixel_error_rate > 0.2);
+        assert!(damaged.mean_abs_error > clean.mean_abs_error);
+        assert!(damaged.confidence < clean.confidence);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sample_positions_and_bound() -> Result<()> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let positions = sample_positions(1000, 0.05, &mut rng);
+        assert_eq!(positions.len(), 50);
+        assert!(positions.windows(2).all(|w| w[0] < w[1]) && positions[49] < 1000);
+        assert_eq!(sample_positions(10, 0.01, &mut rng).len(), 1);
+        assert!(sample_positions(0, 0.5, &mut rng).is_empty());
+
+        assert_eq!(parse_sample_rate("5%")?, 0.05);
+        assert_eq!(parse_sample_rate("100")?, 1.0);
+        assert!(parse_sample_rate("0%").is_err());
+        assert!(parse_sample_rate("150%").is_err());
+
+        let data: Vec<u8> = (0..2000u32).map(|i| (i % 251) as u8).collect();
+        let mut analyzer = FrameAnalyzer::new(GeometricArtGenerator::new(256, 256, 7), None);
+        analyzer.process(&test_frames(&data)?)?;
+        let mut sample = SampleStats {
+            total_frames: 100,
+            stats: VideoStats { width: 256, height: 256, chunk_size: 1000, video_size_bytes: 0, frames: analyzer.frames },
+        };
+        // Two clean frames only rule out damage to most of the video
+        let bound = sample.max_damaged_share().unwrap();
+        assert!((bound - (1.0 - 0.05f64.sqrt())).abs() < 1e-9);
+        sample.stats.frames[1].quality.as_mut().unwrap().crc_ok = false;
+        assert_eq!(sample.max_damaged_share(), None);
+        assert_eq!(sample.damaged_share(), 0.5);
+        Ok(())
+    }
+}
@@ -0,0 +1,396 @@
+//! Where encode input comes from and decode output goes
+//!
+//! A `Source` is read once from start to end and a `Sink` is written once,
+//! so the encoder, decoder and CLI take any of them alike: local files
+//! (pipes and inherited descriptors included, see `stream`), stdin and
+//! stdout, HTTP(S) URLs, S3 objects and in-memory buffers. Ones backed by a
+//! local path expose it, so file-only features (sparse holes, the encode
+//! cache, replacing the output atomically) keep working for them. HTTP goes
+//! through `curl` and S3 through the `aws` CLI, the way video goes through
+//! ffmpeg. New integrations implement the two traits.
+
+use crate::backend::{forward_stderr, StderrForwarder};
+use crate::error::{F2V2FError, Result};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+/// Something an encode reads its input from
+pub trait Source: Send + Sync {
+    /// Name for logs and overlay labels
+    fn name(&self) -> String;
+
+    /// Local path the input can be read from directly, if there is one
+    fn local_path(&self) -> Option<&Path> {
+        None
+    }
+
+    fn open(&self) -> Result<Box<dyn Read + Send>>;
+}
+
+/// Something a decode writes its output to
+pub trait Sink: Send + Sync {
+    fn name(&self) -> String;
+
+    /// Local path the output can be written to directly, if there is one
+    fn local_path(&self) -> Option<&Path> {
+        None
+    }
+
+    fn create(&self) -> Result<Box<dyn SinkWriter>>;
+}
+
+/// Writer of a `Sink`; the output is only complete once `finish` succeeds
+pub trait SinkWriter: Write + Send {
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+/// A source or sink named on the command line
+///
+/// `-` is stdin or stdout, `http://` and `https://` URLs are fetched and
+/// uploaded (PUT) with curl, `s3://bucket/key` goes through the aws CLI, and
+/// anything else is a local path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    Local(PathBuf),
+    Stdio,
+    Http(String),
+    S3 { bucket: String, key: String },
+}
+
+impl Location {
+    /// Location of a command-line argument; paths that aren't UTF-8 are local
+    pub fn from_arg(arg: &Path) -> Result<Self> {
+        match arg.to_str() {
+            Some(s) => s.parse(),
+            None => Ok(Self::Local(arg.to_path_buf())),
+        }
+    }
+}
+
+impl FromStr for Location {
+    type Err = F2V2FError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s == "-" {
+            return Ok(Self::Stdio);
+        }
+        if s.starts_with("http://") || s.starts_with("https://") {
+            return Ok(Self::Http(s.to_string()));
+        }
+        if let Some(rest) = s.strip_prefix("s3://") {
+            return match rest.split_once('/') {
+                Some((bucket, key)) if !bucket.is_empty() && !key.is_empty() && !key.ends_with('/') => {
+                    Ok(Self::S3 { bucket: bucket.to_string(), key: key.to_string() })
+                }
+                _ => Err(F2V2FError::InvalidInput(format!("'{}' is not an S3 object (s3://bucket/key)", s))),
+            };
+        }
+        Ok(Self::Local(PathBuf::from(s)))
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Local(path) => write!(f, "{}", path.display()),
+            Self::Stdio => write!(f, "-"),
+            Self::Http(url) => write!(f, "{}", url),
+            Self::S3 { bucket, key } => write!(f, "s3://{}/{}", bucket, key),
+        }
+    }
+}
+
+impl From<PathBuf> for Location {
+    fn from(path: PathBuf) -> Self {
+        Self::Local(path)
+    }
+}
+
+impl From<&Path> for Location {
+    fn from(path: &Path) -> Self {
+        Self::Local(path.to_path_buf())
+    }
+}
+
+impl Source for Location {
+    fn name(&self) -> String {
+        self.to_string()
+    }
+
+    fn local_path(&self) -> Option<&Path> {
+        match self {
+            Self::Local(path) => Some(path),
+            #[cfg(unix)]
+            Self::Stdio => Some(Path::new("/dev/stdin")),
+            _ => None,
+        }
+    }
+
+    fn open(&self) -> Result<Box<dyn Read + Send>> {
+        match self {
+            Self::Local(path) => Ok(Box::new(std::fs::File::open(path)?)),
+            Self::Stdio => Ok(Box::new(io::stdin())),
+            Self::Http(url) => download("curl", &["-fsSL", url]),
+            Self::S3 { .. } => download("aws", &["s3", "cp", "--only-show-errors", &self.to_string(), "-"]),
+        }
+    }
+}
+
+impl Sink for Location {
+    fn name(&self) -> String {
+        self.to_string()
+    }
+
+    fn local_path(&self) -> Option<&Path> {
+        match self {
+            Self::Local(path) => Some(path),
+            #[cfg(unix)]
+            Self::Stdio => Some(Path::new("/dev/stdout")),
+            _ => None,
+        }
+    }
+
+    fn create(&self) -> Result<Box<dyn SinkWriter>> {
+        match self {
+            Self::Local(path) => Ok(Box::new(std::fs::File::create(path)?)),
+            Self::Stdio => Ok(Box::new(io::stdout())),
+            Self::Http(url) => upload("curl", &["-fsS", "-T", "-", url]),
+            Self::S3 { .. } => upload("aws", &["s3", "cp", "--only-show-errors", "-", &self.to_string()]),
+        }
+    }
+}
+
+impl SinkWriter for std::fs::File {
+    fn finish(self: Box<Self>) -> Result<()> {
+        self.sync_all()?;
+        Ok(())
+    }
+}
+
+impl SinkWriter for io::Stdout {
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.flush()?;
+        Ok(())
+    }
+}
+
+/// Bytes in memory, usable as both a source and a sink
+///
+/// Clones share the buffer, so a clone kept by the caller sees what a
+/// decode wrote.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryBuffer {
+    name: String,
+    data: Arc<Mutex<Vec<u8>>>,
+}
+
+impl MemoryBuffer {
+    pub fn new(name: impl Into<String>, data: Vec<u8>) -> Self {
+        Self { name: name.into(), data: Arc::new(Mutex::new(data)) }
+    }
+
+    pub fn contents(&self) -> Vec<u8> {
+        self.data.lock().map(|data| data.clone()).unwrap_or_default()
+    }
+}
+
+impl Source for MemoryBuffer {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn open(&self) -> Result<Box<dyn Read + Send>> {
+        Ok(Box::new(io::Cursor::new(self.contents())))
+    }
+}
+
+impl Sink for MemoryBuffer {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn create(&self) -> Result<Box<dyn SinkWriter>> {
+        Ok(Box::new(MemoryWriter { buffer: self.clone(), data: Vec::new() }))
+    }
+}
+
+/// Collects the output; the buffer only changes on `finish`
+struct MemoryWriter {
+    buffer: MemoryBuffer,
+    data: Vec<u8>,
+}
+
+impl Write for MemoryWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.data.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SinkWriter for MemoryWriter {
+    fn finish(self: Box<Self>) -> Result<()> {
+        let mut data = self
+            .buffer
+            .data
+            .lock()
+            .map_err(|_| F2V2FError::Io(format!("Memory buffer {} is poisoned", self.buffer.name)))?;
+        *data = self.data;
+        Ok(())
+    }
+}
+
+/// Read the stdout of `program`, failing at the end if it did
+fn download(program: &str, args: &[&str]) -> Result<Box<dyn Read + Send>> {
+    debug!("⬇️  {} {}", program, args.join(" "));
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| F2V2FError::Io(format!("Failed to start {}: {}", program, e)))?;
+    let stderr = forward_stderr(&mut child);
+    let stdout = child.stdout.take().ok_or_else(|| F2V2FError::Io(format!("{} has no stdout", program)))?;
+    Ok(Box::new(ProcessReader { program: program.to_string(), child, stdout, stderr: Some(stderr) }))
+}
+
+/// Feed the stdin of `program`
+fn upload(program: &str, args: &[&str]) -> Result<Box<dyn SinkWriter>> {
+    debug!("⬆️  {} {}", program, args.join(" "));
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| F2V2FError::Io(format!("Failed to start {}: {}", program, e)))?;
+    let stderr = forward_stderr(&mut child);
+    let stdin = child.stdin.take();
+    Ok(Box::new(ProcessWriter { program: program.to_string(), child, stdin, stderr }))
+}
+
+/// Exit status of a finished transfer as an error, with its last stderr lines
+fn check_exit(program: &str, child: &mut Child, stderr: StderrForwarder) -> io::Result<()> {
+    let status = child.wait()?;
+    let tail = stderr.join();
+    if status.success() {
+        return Ok(());
+    }
+    Err(io::Error::other(format!("{} failed ({}): {}", program, status, tail)))
+}
+
+struct ProcessReader {
+    program: String,
+    child: Child,
+    stdout: ChildStdout,
+    /// Taken once the transfer is checked
+    stderr: Option<StderrForwarder>,
+}
+
+impl Read for ProcessReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.stdout.read(buf)?;
+        // A failed download must not pass for a short input
+        if n == 0 && !buf.is_empty() {
+            if let Some(stderr) = self.stderr.take() {
+                check_exit(&self.program, &mut self.child, stderr)?;
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl Drop for ProcessReader {
+    fn drop(&mut self) {
+        if self.stderr.is_some() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+struct ProcessWriter {
+    program: String,
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stderr: StderrForwarder,
+}
+
+impl Write for ProcessWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.stdin {
+            Some(stdin) => stdin.write(buf),
+            None => Err(io::Error::new(io::ErrorKind::BrokenPipe, format!("{} has no stdin", self.program))),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdin.as_mut().map_or(Ok(()), |stdin| stdin.flush())
+    }
+}
+
+impl SinkWriter for ProcessWriter {
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        // Closing stdin ends the upload
+        drop(self.stdin.take());
+        let stderr = self.stderr.take();
+        check_exit(&self.program, &mut self.child, stderr)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_location() -> Result<()> {
+        assert_eq!("-".parse::<Location>()?, Location::Stdio);
+        assert_eq!("data/in.bin".parse::<Location>()?, Location::Local(PathBuf::from("data/in.bin")));
+        assert_eq!("https://example.com/a.bin".parse::<Location>()?, Location::Http("https://example.com/a.bin".into()));
+        let s3 = "s3://bucket/dir/a.bin".parse::<Location>()?;
+        assert_eq!(s3, Location::S3 { bucket: "bucket".into(), key: "dir/a.bin".into() });
+        assert_eq!(s3.to_string(), "s3://bucket/dir/a.bin");
+        for bad in ["s3://bucket", "s3://bucket/", "s3:///key"] {
+            assert!(bad.parse::<Location>().is_err(), "{}", bad);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_buffer_round_trip() -> Result<()> {
+        let buffer = MemoryBuffer::new("mem", b"abc".to_vec());
+        let mut read = Vec::new();
+        buffer.open()?.read_to_end(&mut read)?;
+        assert_eq!(read, b"abc");
+
+        let mut writer = buffer.create()?;
+        writer.write_all(b"xyz")?;
+        // Unfinished output isn't visible
+        assert_eq!(buffer.contents(), b"abc");
+        writer.finish()?;
+        assert_eq!(buffer.contents(), b"xyz");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_failed_download_is_an_error() -> Result<()> {
+        let mut read = Vec::new();
+        download("sh", &["-c", "printf partial; exit 3"])?.read_to_end(&mut read).unwrap_err();
+        assert_eq!(read, b"partial");
+
+        let mut writer = upload("sh", &["-c", "cat > /dev/null; exit 1"])?;
+        writer.write_all(b"data")?;
+        assert!(writer.finish().is_err());
+        Ok(())
+    }
+}
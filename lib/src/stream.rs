@@ -0,0 +1,99 @@
+//! Pipes and inherited file descriptors as encode input and decode output
+//!
+//! A path that exists but isn't a regular file (a named pipe, `/dev/stdin`,
+//! `/dev/fd/3`) is treated as a stream: the encoder reads it exactly once,
+//! without seeking, sparse-hole detection or the duplicate-encode cache, and
+//! the decoder writes straight into it instead of staging a file and
+//! renaming it over the target.
+
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Whether `path` names something that can only be read or written once
+pub fn is_stream<P: AsRef<Path>>(path: P) -> bool {
+    std::fs::metadata(path).is_ok_and(|m| !m.is_file() && !m.is_dir())
+}
+
+/// Path of an inherited file descriptor (`/dev/fd/N`)
+pub fn fd_path(fd: u32) -> PathBuf {
+    PathBuf::from(format!("/dev/fd/{}", fd))
+}
+
+/// Seekable wrapper for a stream: seeking forward writes zeros
+///
+/// Lets the sparse-file writer restore holes in output that can't seek.
+/// Seeking backwards fails.
+pub struct ZeroFill<W: Write> {
+    inner: W,
+    pos: u64,
+}
+
+impl<W: Write> ZeroFill<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner, pos: 0 }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for ZeroFill<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Seek for ZeroFill<W> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+            SeekFrom::End(_) => None,
+        };
+        let target = target.filter(|&t| t >= self.pos).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Unsupported, "Can only seek forward in a stream")
+        })?;
+        let zeros = [0u8; 64 * 1024];
+        while self.pos < target {
+            let n = (target - self.pos).min(zeros.len() as u64) as usize;
+            self.write_all(&zeros[..n])?;
+        }
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zero_fill_seeks_forward_only() -> io::Result<()> {
+        let mut out = ZeroFill::new(Vec::new());
+        out.write_all(b"ab")?;
+        assert_eq!(out.seek(SeekFrom::Current(3))?, 5);
+        out.write_all(b"c")?;
+        assert!(out.seek(SeekFrom::Start(1)).is_err());
+        assert_eq!(out.into_inner(), b"ab\0\0\0c");
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_stream() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("file");
+        std::fs::write(&file, b"x")?;
+        assert!(!is_stream(&file));
+        assert!(!is_stream(dir.path()));
+        assert!(!is_stream(dir.path().join("missing")));
+        #[cfg(unix)]
+        assert!(is_stream("/dev/null"));
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+//! Extra payload streams multiplexed into one video
+//!
+//! Besides the file itself (stream 0), a video can carry small independent
+//! streams — error correction, an embedded manifest, anything added later —
+//! as their own frame sequences, interleaved evenly between the file's
+//! frames. Each frame's header names its stream, and every stream counts
+//! its frames from 0. Extra streams are stored as-is: no compression,
+//! holes or dictionary. Decoders that don't know about streams skip their
+//! frames (see `FrameHeader`).
+
+use crate::error::{F2V2FError, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Stream holding the encoded file
+pub const MAIN_STREAM: u8 = 0;
+
+/// Stream holding the video's own manifest, written before the first and
+/// after the last data frame so either end of the video can restore it
+pub const MANIFEST_STREAM: u8 = 255;
+
+/// Stream of the single, payload-less frame following the file's last
+/// data frame; its header's `index` is the number of data frames, so a
+/// decoder knows where the file ends without a manifest
+pub const END_STREAM: u8 = 254;
+
+/// Chunk size of `MANIFEST_STREAM` frames (or the frame's chunk capacity,
+/// if smaller), whatever the file's chunk size: a video found without its
+/// sidecar only reveals chunk sizes the decoder's search tries
+pub const MANIFEST_CHUNK_SIZE: usize = 4096;
+
+fn is_reserved(id: u8) -> bool {
+    id == MAIN_STREAM || id == MANIFEST_STREAM || id == END_STREAM
+}
+
+/// Extra stream to encode, read from a file (`ID=PATH` on the command line)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtraStream {
+    pub id: u8,
+    pub path: PathBuf,
+}
+
+impl FromStr for ExtraStream {
+    type Err = F2V2FError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || F2V2FError::ConfigError(format!("Invalid stream '{}' (expected ID=PATH, ID 1-253)", s));
+        let (id, path) = s.split_once('=').ok_or_else(invalid)?;
+        let id = id.trim().parse::<u8>().map_err(|_| invalid())?;
+        if is_reserved(id) || path.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self { id, path: PathBuf::from(path) })
+    }
+}
+
+/// An extra stream as recorded in the manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StreamInfo {
+    pub id: u8,
+    pub size: u64,
+    pub num_frames: u64,
+    /// CRC32 of the stream's bytes
+    pub crc32: u32,
+}
+
+/// Check stream IDs are all extra streams and distinct
+pub fn validate(streams: &[ExtraStream]) -> Result<()> {
+    for (i, stream) in streams.iter().enumerate() {
+        if is_reserved(stream.id) {
+            return Err(F2V2FError::ConfigError(format!(
+                "Stream {} is reserved for the {}",
+                stream.id,
+                match stream.id {
+                    MAIN_STREAM => "file",
+                    MANIFEST_STREAM => "manifest",
+                    _ => "end marker",
+                }
+            )));
+        }
+        if streams[..i].iter().any(|s| s.id == stream.id) {
+            return Err(F2V2FError::ConfigError(format!("Stream {} is given twice", stream.id)));
+        }
+    }
+    Ok(())
+}
+
+/// Where each frame of the extra streams goes: (data frame it follows,
+/// stream index in `frame_counts`, frame index within the stream), in
+/// video order
+///
+/// A stream's frames are spread evenly over the `data_frames` frames of
+/// the file, so losing a stretch of the video costs every stream a little.
+pub fn interleave(data_frames: u64, frame_counts: &[u64]) -> Vec<(u64, usize, u64)> {
+    let mut slots: Vec<(u64, usize, u64)> = frame_counts
+        .iter()
+        .enumerate()
+        .flat_map(|(stream, &count)| (0..count).map(move |j| (j * data_frames / count, stream, j)))
+        .collect();
+    slots.sort();
+    slots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_validate() -> Result<()> {
+        let stream: ExtraStream = "2=parity.bin".parse()?;
+        assert_eq!(stream, ExtraStream { id: 2, path: PathBuf::from("parity.bin") });
+        assert!("0=x".parse::<ExtraStream>().is_err());
+        assert!("254=x".parse::<ExtraStream>().is_err());
+        assert!("255=x".parse::<ExtraStream>().is_err());
+        assert!("256=x".parse::<ExtraStream>().is_err());
+        assert!("parity.bin".parse::<ExtraStream>().is_err());
+        assert!(validate(&[stream.clone(), "3=y".parse()?]).is_ok());
+        assert!(validate(&[stream.clone(), stream]).is_err());
+        assert!(validate(&[ExtraStream { id: MANIFEST_STREAM, path: PathBuf::from("m") }]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_interleave_spreads_streams() {
+        let slots = interleave(10, &[2, 5]);
+        assert_eq!(slots.len(), 7);
+        let after = |stream: usize| slots.iter().filter(|s| s.1 == stream).map(|s| s.0).collect::<Vec<_>>();
+        assert_eq!(after(0), [0, 5]);
+        assert_eq!(after(1), [0, 2, 4, 6, 8]);
+        assert!(slots.windows(2).all(|w| w[0] <= w[1]));
+    }
+}
@@ -0,0 +1,112 @@
+//! Subtitle-track metadata channel
+//!
+//! With `EncodeConfig::subtitle_manifest`, the manifest and the CRC32 of
+//! every data frame travel in a subtitle track of their own, muxed from an
+//! SRT file handed to ffmpeg alongside the raw frame stream. Players ignore
+//! it unless asked to show it; the decoder reads it back when the sidecar,
+//! tag and embedded copies of the manifest are all gone.
+//!
+//! Subtitle text passes through ffmpeg's ASS conversion, which drops
+//! anything in braces, so the JSON is hex-encoded and split over cues short
+//! enough for any subtitle codec.
+
+use crate::error::{F2V2FError, Result};
+use crate::manifest::Manifest;
+use crate::payload::Payload;
+use serde::{Deserialize, Serialize};
+
+/// JSON bytes per cue (twice as many hex digits)
+const CUE_BYTES: usize = 1024;
+
+/// What the subtitle track holds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubtitleMetadata {
+    pub manifest: Manifest,
+    /// CRC32 of each data frame's payload, as in its frame header
+    pub frame_crcs: Vec<u32>,
+}
+
+impl SubtitleMetadata {
+    /// Render as an SRT document: consecutive one-millisecond cues from the
+    /// start of the video, each holding one line of hex
+    pub fn to_srt(&self) -> Result<String> {
+        let json = serde_json::to_vec(self)
+            .map_err(|e| F2V2FError::EncodingError(format!("Failed to serialize subtitle metadata: {}", e)))?;
+        let mut out = String::new();
+        for (i, cue) in json.chunks(CUE_BYTES).enumerate() {
+            out.push_str(&format!("{}\n{} --> {}\n{}\n\n", i + 1, timestamp(i), timestamp(i + 1), hex::encode(cue)));
+        }
+        Ok(out)
+    }
+
+    /// Parse an SRT document written by `to_srt` (as ffmpeg gives it back)
+    pub fn from_srt(srt: &str) -> Result<Self> {
+        let invalid = |what: &str| F2V2FError::DecodingError(format!("Invalid subtitle metadata: {}", what));
+        let mut json = Vec::new();
+        // A cue is a number, a timing line and its text
+        for cue in srt.replace("\r\n", "\n").split("\n\n").map(str::trim).filter(|c| !c.is_empty()) {
+            let text = cue.lines().skip(2).collect::<String>();
+            json.extend(hex::decode(text.trim()).map_err(|_| invalid("cue is not hex"))?);
+        }
+        serde_json::from_slice(&json).map_err(|e| invalid(&e.to_string()))
+    }
+}
+
+/// CRC32 of each `chunk_size` chunk of `payload`, the ones its frame
+/// headers will carry (an empty payload has one empty frame)
+pub fn frame_crcs(payload: &Payload, chunk_size: usize) -> Result<Vec<u32>> {
+    let mut reader = payload.reader()?;
+    let mut chunk = vec![0u8; chunk_size];
+    let mut crcs = Vec::new();
+    loop {
+        let n = crate::backend::read_chunk(&mut reader, &mut chunk)?;
+        if n == 0 && !crcs.is_empty() {
+            break;
+        }
+        crcs.push(crc32fast::hash(&chunk[..n]));
+        if n < chunk_size {
+            break;
+        }
+    }
+    Ok(crcs)
+}
+
+/// SRT timestamp of millisecond `ms`
+fn timestamp(ms: usize) -> String {
+    format!("{:02}:{:02}:{:02},{:03}", ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, ms % 1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_srt_roundtrip() {
+        let manifest = Manifest::from_json(
+            r#"{"format_version":3,"width":256,"height":256,"fps":30,"chunk_size":1024,"num_frames":600,
+                "original_size":614400,"encoded_size":614400,"compressed":false,"hash_algo":"sha256","checksum":"ab"}"#,
+        )
+        .unwrap();
+        let metadata = SubtitleMetadata { manifest, frame_crcs: (0..600).collect() };
+        let srt = metadata.to_srt().unwrap();
+        assert!(srt.starts_with("1\n00:00:00,000 --> 00:00:00,001\n"));
+        assert!(!srt.contains('{'));
+        assert_eq!(SubtitleMetadata::from_srt(&srt).unwrap(), metadata);
+        // CRLF line endings read the same
+        assert_eq!(SubtitleMetadata::from_srt(&srt.replace('\n', "\r\n")).unwrap(), metadata);
+        assert!(SubtitleMetadata::from_srt("1\n00:00:00,000 --> 00:00:00,001\nnot hex\n").is_err());
+    }
+
+    #[test]
+    fn test_frame_crcs() {
+        let payload = Payload::Memory(b"abcdefg".to_vec());
+        assert_eq!(frame_crcs(&payload, 3).unwrap(), vec![crc32fast::hash(b"abc"), crc32fast::hash(b"def"), crc32fast::hash(b"g")]);
+        assert_eq!(frame_crcs(&payload, 7).unwrap().len(), 1);
+        assert_eq!(frame_crcs(&Payload::Memory(Vec::new()), 3).unwrap(), vec![crc32fast::hash(b"")]);
+    }
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp(3_723_004), "01:02:03,004");
+    }
+}
@@ -0,0 +1,143 @@
+//! Sleep-based pacing for background encodes
+//!
+//! Long archival encodes can be slowed down so they don't saturate the
+//! machine: either to a payload throughput (bytes per second) or to a share
+//! of one core (sleep in proportion to the time spent working on a frame).
+
+use crate::error::{F2V2FError, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Pacing limit for the frame pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Throttle {
+    #[default]
+    Unlimited,
+    /// Maximum payload bytes per second
+    BytesPerSec(u64),
+    /// Maximum busy time per frame, as a percentage of wall time (1-99)
+    CpuPercent(u8),
+}
+
+impl FromStr for Throttle {
+    type Err = F2V2FError;
+
+    /// Parse `none`, `<N>MB/s`, `<N>KB/s` or `<N>%`
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim().to_ascii_lowercase();
+        let invalid = || {
+            F2V2FError::InvalidInput(format!(
+                "Invalid throttle '{}' (expected e.g. 20MB/s, 500KB/s or 50%)",
+                s
+            ))
+        };
+
+        if s == "none" || s == "off" {
+            return Ok(Throttle::Unlimited);
+        }
+        if let Some(percent) = s.strip_suffix('%') {
+            let percent: u8 = percent.trim().parse().map_err(|_| invalid())?;
+            if !(1..=99).contains(&percent) {
+                return Err(invalid());
+            }
+            return Ok(Throttle::CpuPercent(percent));
+        }
+
+        let (number, unit) = if let Some(n) = s.strip_suffix("mb/s") {
+            (n, 1024 * 1024)
+        } else if let Some(n) = s.strip_suffix("kb/s") {
+            (n, 1024)
+        } else {
+            return Err(invalid());
+        };
+        let rate: u64 = number.trim().parse().map_err(|_| invalid())?;
+        if rate == 0 {
+            return Err(invalid());
+        }
+        Ok(Throttle::BytesPerSec(rate * unit))
+    }
+}
+
+impl fmt::Display for Throttle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Throttle::Unlimited => write!(f, "none"),
+            Throttle::BytesPerSec(rate) => write!(f, "{}KB/s", rate / 1024),
+            Throttle::CpuPercent(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+/// Paces a loop according to a `Throttle`
+pub struct Pacer {
+    throttle: Throttle,
+    started: Instant,
+    bytes: u64,
+    frame_started: Instant,
+}
+
+impl Pacer {
+    pub fn new(throttle: Throttle) -> Self {
+        let now = Instant::now();
+        Self { throttle, started: now, bytes: 0, frame_started: now }
+    }
+
+    /// How long to sleep after a frame carrying `bytes` of payload
+    fn delay_after(&mut self, bytes: u64, now: Instant) -> Duration {
+        self.bytes += bytes;
+        match self.throttle {
+            Throttle::Unlimited => Duration::ZERO,
+            Throttle::BytesPerSec(rate) => {
+                let target = Duration::from_secs_f64(self.bytes as f64 / rate as f64);
+                target.saturating_sub(now - self.started)
+            }
+            Throttle::CpuPercent(percent) => {
+                let busy = now - self.frame_started;
+                busy.mul_f64((100 - percent) as f64 / percent as f64)
+            }
+        }
+    }
+
+    /// Call once per frame after its work is done; sleeps as needed
+    pub fn frame_done(&mut self, bytes: u64) {
+        let delay = self.delay_after(bytes, Instant::now());
+        if !delay.is_zero() {
+            std::thread::sleep(delay);
+        }
+        self.frame_started = Instant::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_throttle() {
+        assert_eq!("20MB/s".parse::<Throttle>().unwrap(), Throttle::BytesPerSec(20 * 1024 * 1024));
+        assert_eq!("512 kb/s".parse::<Throttle>().unwrap(), Throttle::BytesPerSec(512 * 1024));
+        assert_eq!("50%".parse::<Throttle>().unwrap(), Throttle::CpuPercent(50));
+        assert_eq!("none".parse::<Throttle>().unwrap(), Throttle::Unlimited);
+        assert!("0MB/s".parse::<Throttle>().is_err());
+        assert!("100%".parse::<Throttle>().is_err());
+        assert!("fast".parse::<Throttle>().is_err());
+    }
+
+    #[test]
+    fn test_pacer_delays() {
+        let mut pacer = Pacer::new(Throttle::BytesPerSec(1000));
+        let start = pacer.started;
+        // 500 bytes at 1000 B/s should take 0.5s; 0.1s have passed
+        let delay = pacer.delay_after(500, start + Duration::from_millis(100));
+        assert_eq!(delay, Duration::from_millis(400));
+
+        let mut pacer = Pacer::new(Throttle::CpuPercent(25));
+        let start = pacer.frame_started;
+        // 25% busy: 10ms of work is followed by 30ms of sleep
+        let delay = pacer.delay_after(0, start + Duration::from_millis(10));
+        assert_eq!(delay, Duration::from_millis(30));
+    }
+}
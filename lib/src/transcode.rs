@@ -0,0 +1,173 @@
+//! Platform re-encode simulator
+//!
+//! Puts a video through a lossy re-encode like the ones video platforms
+//! apply on upload (downscale, yuv420p chroma, a CRF), then tries to decode
+//! the result, so a user can tell whether an archive would survive before
+//! uploading it. Only the video stream is kept, as platforms drop tags,
+//! attachments and subtitles. The profiles are approximations; platforms
+//! change their settings without notice.
+
+use crate::backend::forward_stderr;
+use crate::config::DecodeConfig;
+use crate::decoder::Decoder;
+use crate::error::{F2V2FError, Result};
+use crate::manifest::Manifest;
+use serde::Serialize;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tracing::{debug, warn};
+
+/// Lossy re-encode settings resembling one platform's
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TranscodeProfile {
+    pub name: &'static str,
+    /// Videos taller than this are scaled down to it
+    pub max_height: u32,
+    /// ffmpeg encoder
+    pub codec: &'static str,
+    pub crf: u32,
+}
+
+pub const PROFILES: &[TranscodeProfile] = &[
+    TranscodeProfile { name: "youtube", max_height: 1080, codec: "libx264", crf: 23 },
+    TranscodeProfile { name: "vimeo", max_height: 1080, codec: "libx264", crf: 20 },
+    TranscodeProfile { name: "instagram", max_height: 1080, codec: "libx264", crf: 28 },
+    TranscodeProfile { name: "tiktok", max_height: 1080, codec: "libx264", crf: 28 },
+    TranscodeProfile { name: "twitter", max_height: 720, codec: "libx264", crf: 26 },
+    TranscodeProfile { name: "whatsapp", max_height: 480, codec: "libx264", crf: 30 },
+];
+
+impl TranscodeProfile {
+    /// Look up a profile by name (case insensitive)
+    pub fn parse(name: &str) -> Result<&'static TranscodeProfile> {
+        PROFILES.iter().find(|p| p.name.eq_ignore_ascii_case(name)).ok_or_else(|| {
+            let known: Vec<&str> = PROFILES.iter().map(|p| p.name).collect();
+            F2V2FError::ConfigError(format!("Unknown transcode profile '{}' (known: {})", name, known.join(", ")))
+        })
+    }
+
+    /// ffmpeg arguments re-encoding `input` to `output`
+    fn ffmpeg_args(&self, input: &Path, output: &Path) -> Vec<String> {
+        let mut args: Vec<String> = ["-y", "-i"].map(String::from).to_vec();
+        args.push(input.display().to_string());
+        args.extend(
+            [
+                "-map", "0:v:0",
+                "-map_metadata", "-1",
+                "-vf", &format!("scale=-2:'min(ih,{})'", self.max_height),
+                "-c:v", self.codec,
+                "-crf", &self.crf.to_string(),
+                "-pix_fmt", "yuv420p",
+            ]
+            .map(String::from),
+        );
+        args.push(output.display().to_string());
+        args
+    }
+}
+
+/// Re-encode `input` to `output` with `profile`
+pub fn transcode(input: &Path, output: &Path, profile: &TranscodeProfile) -> Result<()> {
+    debug!("📼 Re-encoding {} like {} ({}p, crf {})", input.display(), profile.name, profile.max_height, profile.crf);
+    let mut child = Command::new("/usr/local/bin/ffmpeg")
+        .args(profile.ffmpeg_args(input, output))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| F2V2FError::EncodingError(format!("Failed to start ffmpeg: {}", e)))?;
+    let stderr = forward_stderr(&mut child);
+    let status = child.wait().map_err(|e| F2V2FError::EncodingError(format!("Wait failed: {}", e)))?;
+    if !status.success() {
+        return Err(F2V2FError::EncodingError(format!(
+            "Re-encoding like {} failed (code {}): {}",
+            profile.name,
+            status.code().unwrap_or(-1),
+            stderr.join()
+        )));
+    }
+    Ok(())
+}
+
+/// Outcome of `simulate`
+#[derive(Debug, Clone, Serialize)]
+pub struct SurvivalReport {
+    pub profile: TranscodeProfile,
+    pub video_size: u64,
+    pub transcoded_size: u64,
+    /// The re-encoded video decoded and matched its checksum
+    pub survived: bool,
+    /// Why the full decode failed
+    pub error: Option<String>,
+    /// Bytes of the original file a partial decode got back
+    pub recovered_bytes: u64,
+    pub original_size: Option<u64>,
+    /// Data frames failing their CRC after the re-encode
+    pub frames_damaged: u64,
+}
+
+/// Re-encode `video` with `profile` in a scratch directory and try to decode it
+///
+/// With `keep_sidecar`, the video's sidecar manifest (if any) is kept next
+/// to the re-encoded copy, as the user would still have it after uploading.
+/// When the decode fails, a partial decode measures how much survives.
+pub async fn simulate(video: &Path, profile: &TranscodeProfile, keep_sidecar: bool) -> Result<SurvivalReport> {
+    let dir = tempfile::tempdir()?;
+    let transcoded = dir.path().join("transcoded.mp4");
+    transcode(video, &transcoded, profile)?;
+    if let Some(sidecar) = Manifest::find_sidecar(video).filter(|_| keep_sidecar) {
+        std::fs::copy(sidecar, Manifest::sidecar_path(&transcoded))?;
+    }
+
+    let mut report = SurvivalReport {
+        profile: *profile,
+        video_size: std::fs::metadata(video)?.len(),
+        transcoded_size: std::fs::metadata(&transcoded)?.len(),
+        survived: false,
+        error: None,
+        recovered_bytes: 0,
+        original_size: None,
+        frames_damaged: 0,
+    };
+    let decoder = Decoder::new(DecodeConfig::default())?;
+    match decoder.decode(&transcoded, &dir.path().join("decoded")).await {
+        Ok(info) => {
+            report.survived = true;
+            report.recovered_bytes = info.extracted_size;
+            report.original_size = Some(info.extracted_size);
+        }
+        Err(e) => {
+            warn!("Re-encoded video doesn't decode: {}", e);
+            report.error = Some(e.to_string());
+            if let Ok(partial) = decoder.decode_partial(&transcoded, &dir.path().join("partial")).await {
+                report.recovered_bytes = partial.recovered_bytes();
+                report.original_size = partial.original_size;
+                report.frames_damaged = partial.frames_damaged;
+            }
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_profile() {
+        assert_eq!(TranscodeProfile::parse("YouTube").unwrap().name, "youtube");
+        let err = TranscodeProfile::parse("myspace").unwrap_err().to_string();
+        assert!(err.contains("myspace") && err.contains("whatsapp"));
+    }
+
+    #[test]
+    fn test_ffmpeg_args() {
+        let profile = TranscodeProfile::parse("twitter").unwrap();
+        let args = profile.ffmpeg_args(Path::new("in.mp4"), Path::new("out.mp4"));
+        let after = |flag: &str| args[args.iter().position(|a| a == flag).unwrap() + 1].as_str();
+        assert_eq!(after("-i"), "in.mp4");
+        assert_eq!(after("-vf"), "scale=-2:'min(ih,720)'");
+        assert_eq!(after("-crf"), "26");
+        assert_eq!(args.last().unwrap(), "out.mp4");
+    }
+}
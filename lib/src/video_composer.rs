@@ -1,53 +1,396 @@
+use crate::atomic::AtomicOutput;
+use crate::backend::{is_matroska, read_chunk, Attachment, FfmpegBackend, FrameRequest, FrameSelection, FrameSink, OutputSpec, VideoBackend};
+use crate::chapters::{self, Chapter};
+use crate::container::HEADER_LEN;
 use crate::error::{F2V2FError, Result};
-use crate::image_generator::GeometricArtGenerator;
+use crate::events::FrameProgress;
+use crate::extract_format::ExtractFormat;
+use crate::operation::OperationHandle;
+use crate::throttle::{Pacer, Throttle};
+use crate::frame_header::{FrameHeader, FLAG_OVERLAY, FLAG_RAW, FLAG_SHOWCASE, FLAG_TRANSITION};
+use crate::image_generator::{GeometricArtGenerator, DEFAULT_SEED};
+use crate::index::FrameIndex;
+use crate::overlay::draw_overlay;
+use crate::payload::Payload;
+use crate::streams::{self, END_STREAM, MANIFEST_CHUNK_SIZE, MANIFEST_STREAM};
+use crate::warning::Warning;
 use image::ImageBuffer;
-use std::path::Path;
-use std::process::{Command, Stdio};
-use std::io::{Read, Write};
-use tracing::{info, warn, debug};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::io::Write;
+use tracing::{debug, warn};
+
+pub use crate::backend::{ContentRect, VIDEO_CODEC};
 
 /// Composes individual image frames into a video
 pub struct VideoComposer {
     width: u32,
     height: u32,
     fps: u32,
+    /// Art generator seed
+    seed: u64,
+    /// Pin ffmpeg flags for byte-identical output
+    deterministic: bool,
+    /// File name shown in the human-readable overlay (overlay disabled if None)
+    overlay_label: Option<String>,
+    /// Plain gray-level data frames instead of art
+    raw: bool,
+    /// Glow and vignette on top of the art
+    showcase: bool,
+    /// Crossfade frames inserted between consecutive data frames
+    transition_frames: u32,
+    /// Frames between keyframes (`-g`); 1 is all-intra, ffmpeg's default if None
+    keyframe_interval: Option<u32>,
+    /// Pattern complexity level per data frame (empty: unmodulated)
+    complexity_levels: Vec<u8>,
+    /// Chapter every this many data frames (see `with_chunk_chapters`)
+    chunk_chapters: Option<u32>,
+    /// Size of the file the payload holds as is, after its container
+    /// header, when chunk chapters can name its bytes
+    chapter_file_size: Option<u64>,
+    /// Extra streams (ID and data) interleaved with the payload's frames
+    streams: Vec<(u8, Vec<u8>)>,
+    /// Manifest JSON written as stream `MANIFEST_STREAM` at both ends of the video
+    manifest: Option<Vec<u8>>,
+    /// Container metadata tags (key, value)
+    tags: Vec<(String, String)>,
+    /// Files attached to the video (Matroska outputs only)
+    attachments: Vec<Attachment>,
+    /// SRT document muxed as a subtitle track
+    subtitles: Option<String>,
+    /// Called after each frame is handed to ffmpeg
+    progress: Option<FrameProgress>,
+    /// Pauses payload reads (and so frame feeding) when paused
+    operation: OperationHandle,
+    /// Sleep-based pacing between frames
+    throttle: Throttle,
+    /// Replace an existing output video
+    overwrite: bool,
+    /// Scratch directory for the partial video and ffmpeg metadata files
+    temp_dir: Option<PathBuf>,
+    /// Pixel format, scaler and color range of extracted frames
+    extract_format: ExtractFormat,
+    /// Keep decoding past corrupt or truncated input (`-err_detect ignore_err`)
+    ignore_errors: bool,
+    /// Recoverable anomalies from frame extraction, see `take_warnings`
+    warnings: Mutex<Vec<Warning>>,
+    /// Writes and reads the video container
+    backend: Arc<dyn VideoBackend>,
 }
 
 impl VideoComposer {
     pub fn new(width: u32, height: u32, fps: u32) -> Self {
-        Self { width, height, fps }
-    }
-
-    fn ffmpeg_encode(
-        width: u32,
-        height: u32,
-        fps: u32,
-        output_path: &str,
-    ) -> Result<std::process::Child> {
-        let cmd = Command::new("/usr/local/bin/ffmpeg")
-            .args(&[
-                "-y",  // Overwrite
-                "-f", "rawvideo",
-                "-pix_fmt", "rgba",
-                "-video_size", &format!("{}x{}", width, height),
-                "-framerate", &fps.to_string(),
-                "-i", "pipe:0",
-                "-c:v", "libx264",  // Use H.264 instead of H.265 for better compatibility
-                "-preset", "ultrafast",  // Faster encoding
-                "-qp", "0",  // LOSSLESS encoding - critical for data integrity!
-                "-pix_fmt", "yuv444p",  // Full chroma resolution (no subsampling)
-                "-movflags", "+faststart",
-                output_path,
-            ])
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|e| F2V2FError::EncodingError(format!("Failed to start ffmpeg: {}", e)))?;
-
-        Ok(cmd)
-    }
-   
+        Self {
+            width,
+            height,
+            fps,
+            seed: DEFAULT_SEED,
+            deterministic: false,
+            overlay_label: None,
+            raw: false,
+            showcase: false,
+            transition_frames: 0,
+            keyframe_interval: None,
+            complexity_levels: Vec::new(),
+            chunk_chapters: None,
+            chapter_file_size: None,
+            streams: Vec::new(),
+            manifest: None,
+            tags: Vec::new(),
+            attachments: Vec::new(),
+            subtitles: None,
+            progress: None,
+            operation: OperationHandle::new(),
+            throttle: Throttle::Unlimited,
+            overwrite: false,
+            temp_dir: None,
+            extract_format: ExtractFormat::default(),
+            ignore_errors: false,
+            warnings: Mutex::new(Vec::new()),
+            backend: Arc::new(FfmpegBackend),
+        }
+    }
+
+    /// Play the video at `fps` instead of the rate it was created with
+    pub fn with_fps(mut self, fps: u32) -> Self {
+        self.fps = fps;
+        self
+    }
+
+    /// Use a different generator seed (the decoder must use the same one)
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Mark a chapter every `every` data frames, to tell which part of
+    /// the file a damaged stretch of the video held
+    ///
+    /// Chapters are titled with the data frames they cover, or with the
+    /// file's bytes once `with_chapter_file_size` says where those are.
+    pub fn with_chunk_chapters(mut self, every: Option<u32>) -> Self {
+        self.chunk_chapters = every;
+        self
+    }
+
+    /// The payload is the `size`-byte file itself after its container
+    /// header (not compressed or sparse), so chunk chapters can be titled
+    /// with the file bytes they cover
+    pub fn with_chapter_file_size(mut self, size: Option<u64>) -> Self {
+        self.chapter_file_size = size;
+        self
+    }
+
+    /// Report (frames written, total frames) after every frame
+    pub fn with_progress(mut self, progress: FrameProgress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Let `handle` pause and resume feeding frames to ffmpeg
+    pub fn with_operation(mut self, handle: OperationHandle) -> Self {
+        self.operation = handle;
+        self
+    }
+
+    /// Replace the output video if it already exists (refused by default)
+    pub fn with_overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Write scratch files and the partial video to `dir` (default: system
+    /// temp dir, and next to the output for the video)
+    pub fn with_temp_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.temp_dir = dir;
+        self
+    }
+
+    /// Pace frame generation so background encodes don't saturate the machine
+    pub fn with_throttle(mut self, throttle: Throttle) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    /// Convert extracted frames with these ffmpeg settings
+    pub fn with_extract_format(mut self, format: ExtractFormat) -> Self {
+        self.extract_format = format;
+        self
+    }
+
+    /// Have ffmpeg decode what it can of a damaged or cut-off video instead
+    /// of stopping at the first error
+    pub fn with_ignore_errors(mut self, enabled: bool) -> Self {
+        self.ignore_errors = enabled;
+        self
+    }
+
+    /// Write and read videos through `backend` instead of ffmpeg
+    pub fn with_backend(mut self, backend: Arc<dyn VideoBackend>) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Warnings collected by frame extraction since the last call
+    pub fn take_warnings(&self) -> Vec<Warning> {
+        std::mem::take(&mut *self.warnings.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    fn push_warning(&self, warning: Warning) {
+        let mut warnings = self.warnings.lock().unwrap_or_else(|e| e.into_inner());
+        if !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
+    }
+
+    /// Video frame of every data frame, as `compose_from_payload_blocking`
+    /// lays them out, and the length of the whole video in frames
+    ///
+    /// Manifest frames come first and last; each data frame is preceded by
+    /// its crossfades and followed by the extra-stream frames slotted after
+    /// it (`slots`, see `streams::interleave`); the end marker follows the
+    /// last.
+    fn plan_layout(&self, chunk_size: u64, num_chunks: u64, manifest_frames: u64, slots: &[(u64, usize, u64)]) -> (FrameIndex, u64) {
+        let mut index = FrameIndex::new(chunk_size, self.transition_frames as u64 + 1);
+        let mut position = manifest_frames;
+        let mut slots = slots.iter().peekable();
+        for i in 0..num_chunks {
+            if i > 0 {
+                position += self.transition_frames as u64;
+            }
+            index.push(i, position);
+            position += 1;
+            while slots.next_if(|&&(after, _, _)| after == i).is_some() {
+                position += 1;
+            }
+        }
+        (index, position + 1 + manifest_frames)
+    }
+
+    /// Chapters at every `chunk_chapters` data frames, titled with the file
+    /// bytes they cover if known, otherwise with the data frames
+    fn chunk_chapters(&self, index: &FrameIndex) -> Vec<Chapter> {
+        let Some(every) = self.chunk_chapters.filter(|_| index.chunk_size > 0) else {
+            return Vec::new();
+        };
+        (0..index.frames)
+            .step_by(every as usize)
+            .map(|frame| {
+                let last = (frame + every as u64).min(index.frames) - 1;
+                let bytes = self.chapter_file_size.map(|size| {
+                    let file_offset = |frame: u64| (frame * index.chunk_size).saturating_sub(HEADER_LEN as u64).min(size);
+                    (file_offset(frame), file_offset(last + 1))
+                });
+                let title = match bytes {
+                    Some((start, end)) if start < end => format!("bytes {}-{}", start, end - 1),
+                    _ => format!("frames {}-{}", frame, last),
+                };
+                Chapter::new(frame, title)
+            })
+            .collect()
+    }
+
+    /// Write the chapter list to an FFMETADATA temp file for ffmpeg
+    fn chapter_metadata_file(&self, index: &FrameIndex, total_frames: u64) -> Result<Option<tempfile::NamedTempFile>> {
+        let chapters = self.chunk_chapters(index);
+        if chapters.is_empty() {
+            return Ok(None);
+        }
+        let mut file = match &self.temp_dir {
+            Some(dir) => tempfile::NamedTempFile::new_in(dir)?,
+            None => tempfile::NamedTempFile::new()?,
+        };
+        // Chapters are given in data frames; the index says where those went,
+        // past manifest frames, crossfades and extra-stream frames
+        let last = index.frames.saturating_sub(1);
+        let chapters: Vec<Chapter> = chapters
+            .iter()
+            .map(|c| Chapter::new(index.position(c.start_frame.min(last)).unwrap_or(0), c.title.clone()))
+            .collect();
+        file.write_all(chapters::to_ffmetadata(&chapters, self.fps, total_frames).as_bytes())?;
+        file.flush()?;
+        Ok(Some(file))
+    }
+
+    /// Write the subtitle track to an SRT temp file for ffmpeg
+    fn subtitle_file(&self) -> Result<Option<tempfile::NamedTempFile>> {
+        let Some(subtitles) = &self.subtitles else {
+            return Ok(None);
+        };
+        let mut file = match &self.temp_dir {
+            Some(dir) => tempfile::Builder::new().suffix(".srt").tempfile_in(dir)?,
+            None => tempfile::Builder::new().suffix(".srt").tempfile()?,
+        };
+        file.write_all(subtitles.as_bytes())?;
+        file.flush()?;
+        Ok(Some(file))
+    }
+
+    /// Render a text overlay (frame number, `label`, version) in a reserved
+    /// corner of every frame so the video identifies itself when found in the wild
+    pub fn with_overlay(mut self, label: impl Into<String>) -> Self {
+        self.overlay_label = Some(label.into());
+        self
+    }
+
+    /// Render data bytes as plain gray levels with no art pattern (flagged in
+    /// each frame header so the decoder follows)
+    pub fn with_raw(mut self, raw: bool) -> Self {
+        self.raw = raw;
+        self
+    }
+
+    /// Add highlight glow and a vignette to the art (flagged in each frame
+    /// header so the decoder divides them out)
+    pub fn with_showcase(mut self, showcase: bool) -> Self {
+        self.showcase = showcase;
+        self
+    }
+
+    /// Insert `frames` crossfade frames between consecutive data frames for
+    /// smoother playback (flagged so the decoder skips them)
+    pub fn with_transitions(mut self, frames: u32) -> Self {
+        self.transition_frames = frames;
+        self
+    }
+
+    /// Start a new group of pictures every `interval` frames; 1 makes every
+    /// frame a keyframe (all-intra), so any frame decodes on its own
+    pub fn with_keyframe_interval(mut self, interval: Option<u32>) -> Self {
+        self.keyframe_interval = interval;
+        self
+    }
+
+    /// Render data frame `i` with pattern complexity `levels[i]` (see
+    /// `entropy::EntropyMeter::frame_levels`); recorded in each frame header
+    pub fn with_complexity_levels(mut self, levels: Vec<u8>) -> Self {
+        self.complexity_levels = levels;
+        self
+    }
+
+    /// Interleave extra streams (see `streams`) with the payload's frames
+    pub fn with_streams(mut self, streams: Vec<(u8, Vec<u8>)>) -> Self {
+        self.streams = streams;
+        self
+    }
+
+    /// Embed `manifest` ahead of the first and after the last data frame, so
+    /// a video that lost its sidecar or either end still describes itself
+    pub fn with_manifest(mut self, manifest: Vec<u8>) -> Self {
+        self.manifest = Some(manifest);
+        self
+    }
+
+    /// Write the container metadata tag `key` (MP4 `udta`); it travels with
+    /// the file but may be stripped by re-encodes
+    pub fn with_tag(mut self, key: &str, value: String) -> Self {
+        self.tags.push((key.to_string(), value));
+        self
+    }
+
+    /// Attach `data` as the file `name`; unlike tags, attachments survive
+    /// remuxing, but only Matroska (`.mkv`) outputs can carry them
+    pub fn with_attachment(mut self, name: &str, mime: &str, data: Vec<u8>) -> Self {
+        self.attachments.push(Attachment { name: name.to_string(), mime: mime.to_string(), data });
+        self
+    }
+
+    /// Mux `srt` (an SRT document) as a subtitle track
+    pub fn with_subtitles(mut self, srt: String) -> Self {
+        self.subtitles = Some(srt);
+        self
+    }
+
+    /// Pin ffmpeg to settings that make the output byte-identical for
+    /// identical input and config (no timestamps or encoder version strings,
+    /// single-threaded x264)
+    pub fn with_deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Start writing a video to `path` through the backend
+    fn create_video(&self, path: &Path, metadata: Option<&Path>, subtitles: Option<&Path>) -> Result<Box<dyn FrameSink>> {
+        if !self.attachments.is_empty() && !is_matroska(path) {
+            return Err(F2V2FError::InvalidInput(format!(
+                "Only Matroska (.mkv) videos can hold attachments, not {}",
+                path.display()
+            )));
+        }
+        let spec = OutputSpec {
+            width: self.width,
+            height: self.height,
+            fps: self.fps,
+            keyframe_interval: self.keyframe_interval,
+            deterministic: self.deterministic,
+            metadata,
+            subtitles,
+            tags: &self.tags,
+            attachments: &self.attachments,
+        };
+        self.backend.create(path, &spec)
+    }
+
     /// Create video from sequence of frames
     pub fn compose_from_frames<P: AsRef<Path>>(
         &self,
@@ -55,7 +398,7 @@ impl VideoComposer {
         output_path: P,
     ) -> Result<()> {
         let output = output_path.as_ref();
-        info!(
+        debug!(
             "Composing video: {}x{} @ {} fps to {}",
             self.width,
             self.height,
@@ -63,27 +406,29 @@ impl VideoComposer {
             output.display()
         );
 
-        let mut child = Self::ffmpeg_encode(self.width, self.height, self.fps, &output.to_string_lossy())?;
-        let mut stdin = child.stdin.take().ok_or_else(|| F2V2FError::EncodingError("No stdin".to_string()))?;
+        // The backend writes a staging file that only replaces `output` on success
+        let staged = AtomicOutput::new_in(output, self.overwrite, self.temp_dir.as_deref())?;
+        // The frames are written as given, a data frame every `stride`
+        let mut index = FrameIndex::new(0, self.transition_frames as u64 + 1);
+        for i in 0..frame_data.len() as u64 {
+            index.push(i, i * index.stride);
+        }
+        let total_frames = (frame_data.len() as u64).saturating_sub(1) * index.stride + 1;
+        let metadata_file = self.chapter_metadata_file(&index, total_frames)?;
+        let subtitle_file = self.subtitle_file()?;
+        let mut sink = self.create_video(
+            staged.path(),
+            metadata_file.as_ref().map(|f| f.path()),
+            subtitle_file.as_ref().map(|f| f.path()),
+        )?;
 
         for frame in frame_data {
-            stdin.write_all(&frame)
+            sink.write_all(&frame)
                 .map_err(|e| F2V2FError::EncodingError(format!("Write failed: {}", e)))?;
         }
-        
-        drop(stdin);
-
-        let status = child.wait()
-            .map_err(|e| F2V2FError::EncodingError(format!("Wait failed: {}", e)))?;
-
-        if !status.success() {
-            let code = status.code().unwrap_or(-1);
-            return Err(F2V2FError::EncodingError(
-                format!("FFmpeg exited with code {}. This usually means: out of memory, invalid parameters, or disk full. For large files, try reducing chunk_size or lowering video resolution.", code)
-            ));
-        }
+        sink.finish()?;
 
-        Ok(())
+        staged.commit()
     }
 
     /// Create video from geometric art frames based on file data (BLOCKING)
@@ -93,67 +438,209 @@ impl VideoComposer {
         chunk_size: usize,
         output_path: P,
     ) -> Result<()> {
+        self.compose_from_payload_blocking(&Payload::Memory(file_data), chunk_size, output_path)?;
+        Ok(())
+    }
+
+    /// Create video from an encoded payload (BLOCKING)
+    ///
+    /// The payload is read sequentially one chunk at a time, so spilled
+    /// payloads are never loaded into memory as a whole. Returns where each
+    /// data frame went in the video.
+    pub fn compose_from_payload_blocking<P: AsRef<Path>>(
+        &self,
+        payload: &Payload,
+        chunk_size: usize,
+        output_path: P,
+    ) -> Result<FrameIndex> {
         let output = output_path.as_ref();
-        info!("Creating video from file data to {}", output.display());
+        debug!("Creating video from file data to {}", output.display());
+
+        // An empty payload still gets one marker frame with a zero-length header
+        let num_chunks = payload.len().div_ceil(chunk_size as u64).max(1) as usize;
+        let generator = GeometricArtGenerator::new(self.width, self.height, self.seed)
+            .with_overlay(self.overlay_label.is_some())
+            .with_raw(self.raw)
+            .with_showcase(self.showcase);
 
-        let num_chunks = (file_data.len() + chunk_size - 1) / chunk_size;
-        let generator = GeometricArtGenerator::new(self.width, self.height, 42);
+        let mut reader = self.operation.reader(payload.reader()?);
+        let mut chunk_buf = vec![0u8; chunk_size];
+        let mut pacer = Pacer::new(self.throttle);
+        // Every frame is rendered into one of these and handed to the sink
+        // as is: the data frame, the one before it (to fade from) and one for
+        // everything else
+        let frame_len = generator.frame_len();
+        let (mut frame, mut previous, mut scratch) = (vec![0u8; frame_len], vec![0u8; frame_len], vec![0u8; frame_len]);
+        let frame_counts: Vec<u64> = self
+            .streams
+            .iter()
+            .map(|(_, data)| (data.len() as u64).div_ceil(chunk_size as u64).max(1))
+            .collect();
+        let slots = streams::interleave(num_chunks as u64, &frame_counts);
+        let mut base_flags = 0;
+        if self.overlay_label.is_some() {
+            base_flags |= FLAG_OVERLAY;
+        }
+        if self.raw {
+            base_flags |= FLAG_RAW;
+        }
+        if self.showcase {
+            base_flags |= FLAG_SHOWCASE;
+        }
+        let mut manifest_buf = vec![0u8; MANIFEST_CHUNK_SIZE.min(generator.chunk_capacity())];
+        let manifest = self.manifest.as_deref().unwrap_or_default();
+        let manifest_frames = (manifest.len() as u64).div_ceil(manifest_buf.len() as u64);
+        // Chapters need the layout before the first frame is written
+        let (planned, total_frames) = self.plan_layout(chunk_size as u64, num_chunks as u64, manifest_frames, &slots);
+        let mut stream_slots = slots.into_iter().peekable();
 
-        let mut child = Self::ffmpeg_encode(self.width, self.height, self.fps, &output.to_string_lossy())?;
-        let mut stdin = child.stdin.take().ok_or_else(|| F2V2FError::EncodingError("No stdin".to_string()))?;
+        // The backend writes a staging file that only replaces `output` on success
+        let staged = AtomicOutput::new_in(output, self.overwrite, self.temp_dir.as_deref())?;
+        let metadata_file = self.chapter_metadata_file(&planned, total_frames)?;
+        let subtitle_file = self.subtitle_file()?;
+        let mut sink = self.create_video(
+            staged.path(),
+            metadata_file.as_ref().map(|f| f.path()),
+            subtitle_file.as_ref().map(|f| f.path()),
+        )?;
 
-        for (i, chunk) in file_data.chunks(chunk_size).enumerate() {
+        let mut write_manifest = |sink: &mut dyn Write, frame: &mut [u8], i: usize| -> Result<()> {
+            for j in 0..manifest_frames {
+                let stream = (MANIFEST_STREAM, manifest);
+                self.stream_frame(&generator, base_flags, stream, j, manifest_frames, &mut manifest_buf, frame)?;
+                write_frame(sink, frame, i, num_chunks)?;
+            }
+            Ok(())
+        };
+        write_manifest(&mut sink, &mut scratch, 0)?;
+        let mut index = FrameIndex::new(chunk_size as u64, self.transition_frames as u64 + 1);
+        let mut position = manifest_frames;
+
+        for i in 0..num_chunks {
             if (i + 1) % 100 == 0 || (i + 1) == num_chunks {
-                info!("  📹 Frame {}/{} ({:.1}%)", i + 1, num_chunks, 
+                debug!("  📹 Frame {}/{} ({:.1}%)", i + 1, num_chunks, 
                     ((i + 1) as f32 / num_chunks as f32) * 100.0);
             }
 
+            let len = read_chunk(&mut reader, &mut chunk_buf)?;
+
+            // Header records the real payload length and CRC before padding
+            let mut header = FrameHeader::new(i as u32, &chunk_buf[..len]);
+            header.flags |= base_flags;
+            let complexity = self.complexity_levels.get(i).copied().unwrap_or(0);
+            header.set_complexity(complexity);
+
             // Pad the last chunk with zeros if it's smaller than chunk_size
-            let mut padded_chunk = chunk.to_vec();
-            if padded_chunk.len() < chunk_size {
-                padded_chunk.resize(chunk_size, 0);
+            chunk_buf[len..].fill(0);
+
+            generator.with_complexity(complexity).render_frame(&header, &chunk_buf, &mut frame)?;
+            if let Some(label) = &self.overlay_label {
+                draw_overlay(&mut generator.frame_view(&mut frame)?, &[
+                    format!("f2v2f v{} - decode with f2v2f", env!("CARGO_PKG_VERSION")),
+                    format!("frame {}/{} {}", i + 1, num_chunks, label),
+                ]);
             }
 
-            {
-                let img = generator.generate_from_data(&padded_chunk)?;
-                let frame_bytes = img.into_raw();
-                
-                match stdin.write_all(&frame_bytes) {
-                    Ok(_) => {},
-                    Err(e) if e.raw_os_error() == Some(32) => {
-                        return Err(F2V2FError::EncodingError(
-                            format!("FFmpeg pipe broken at frame {}/{} - FFmpeg crashed or ran out of memory. Error: {}", i + 1, num_chunks, e)
-                        ));
-                    },
-                    Err(e) => return Err(F2V2FError::EncodingError(format!("Write failed at frame {}: {}", i + 1, e))),
+            if i > 0 && self.transition_frames > 0 {
+                let mut transition = FrameHeader::new(i as u32, &[]);
+                transition.flags = header.flags | FLAG_TRANSITION;
+                for step in 1..=self.transition_frames {
+                    let t = step as f32 / (self.transition_frames + 1) as f32;
+                    crossfade(&previous, &frame, t, &mut scratch);
+                    transition.write_to(&mut generator.frame_view(&mut scratch)?);
+                    write_frame(&mut sink, &scratch, i, num_chunks)?;
                 }
-                // frame_bytes and img are dropped here explicitly
+                position += self.transition_frames as u64;
+            }
+
+            write_frame(&mut sink, &frame, i, num_chunks)?;
+            index.push(i as u64, position);
+            position += 1;
+            // Kept around to fade from; its old contents are overwritten next
+            if self.transition_frames > 0 {
+                std::mem::swap(&mut frame, &mut previous);
             }
-            
-            // Explicit cleanup
-            padded_chunk.clear();
-        }
-        
-        drop(stdin);
 
-        // Read stderr to capture any FFmpeg errors
-        let mut stderr_output = Vec::new();
-        if let Some(mut stderr) = child.stderr.take() {
-            let _ = stderr.read_to_end(&mut stderr_output);
+            // Extra stream frames due after this one
+            while let Some((_, stream, j)) = stream_slots.next_if(|&(after, _, _)| after == i as u64) {
+                let (id, data) = &self.streams[stream];
+                let count = frame_counts[stream];
+                self.stream_frame(&generator, base_flags, (*id, data), j, count, &mut chunk_buf, &mut scratch)?;
+                write_frame(&mut sink, &scratch, i, num_chunks)?;
+                position += 1;
+            }
+
+            if let Some(progress) = &self.progress {
+                progress(i as u64 + 1, num_chunks as u64);
+            }
+            pacer.frame_done(len as u64);
         }
+        self.end_frame(&generator, base_flags, num_chunks as u32, &mut chunk_buf, &mut scratch)?;
+        write_frame(&mut sink, &scratch, num_chunks - 1, num_chunks)?;
+        // The trailer copy survives a video cut short at the start
+        write_manifest(&mut sink, &mut scratch, num_chunks - 1)?;
+        debug_assert_eq!(index, planned, "frames written differ from the planned layout");
+        debug_assert_eq!(position + 1 + manifest_frames, total_frames);
 
-        let status = child.wait()
-            .map_err(|e| F2V2FError::EncodingError(format!("Wait failed: {}", e)))?;
+        sink.finish()?;
+        staged.commit()?;
+        debug!("Video composition complete");
+        Ok(index)
+    }
 
-        if !status.success() {
-            let err_msg = String::from_utf8_lossy(&stderr_output).to_string();
-            debug!("FFmpeg stderr: {}", err_msg);
-            return Err(F2V2FError::EncodingError(
-                format!("FFmpeg exited with code {}. Details: {}", status.code().unwrap_or(-1), err_msg)
-            ));
+    /// Render frame `j` of `count` of an extra stream, cut into
+    /// `chunk_buf`-sized chunks, into `frame`
+    #[allow(clippy::too_many_arguments)]
+    fn stream_frame(
+        &self,
+        generator: &GeometricArtGenerator,
+        flags: u8,
+        (id, data): (u8, &[u8]),
+        j: u64,
+        count: u64,
+        chunk_buf: &mut [u8],
+        frame: &mut [u8],
+    ) -> Result<()> {
+        let chunk_size = chunk_buf.len();
+        let start = (j as usize * chunk_size).min(data.len());
+        let chunk = &data[start..(start + chunk_size).min(data.len())];
+        let mut header = FrameHeader::new(j as u32, chunk);
+        header.stream = id;
+        header.flags = flags;
+        chunk_buf[..chunk.len()].copy_from_slice(chunk);
+        chunk_buf[chunk.len()..].fill(0);
+        generator.render_frame(&header, chunk_buf, frame)?;
+        if let Some(label) = &self.overlay_label {
+            let name = if id == MANIFEST_STREAM { "manifest".to_string() } else { format!("stream {}", id) };
+            draw_overlay(&mut generator.frame_view(frame)?, &[
+                format!("f2v2f v{} - decode with f2v2f", env!("CARGO_PKG_VERSION")),
+                format!("{} frame {}/{} {}", name, j + 1, count, label),
+            ]);
         }
+        Ok(())
+    }
 
-        info!("Video composition complete");
+    /// Render the `END_STREAM` frame following the last of `data_frames` data
+    /// frames into `frame`
+    fn end_frame(
+        &self,
+        generator: &GeometricArtGenerator,
+        flags: u8,
+        data_frames: u32,
+        chunk_buf: &mut [u8],
+        frame: &mut [u8],
+    ) -> Result<()> {
+        let mut header = FrameHeader::new(data_frames, &[]);
+        header.stream = END_STREAM;
+        header.flags = flags;
+        chunk_buf.fill(0);
+        generator.render_frame(&header, chunk_buf, frame)?;
+        if let Some(label) = &self.overlay_label {
+            draw_overlay(&mut generator.frame_view(frame)?, &[
+                format!("f2v2f v{} - decode with f2v2f", env!("CARGO_PKG_VERSION")),
+                format!("end of {} frames {}", data_frames, label),
+            ]);
+        }
         Ok(())
     }
 
@@ -169,57 +656,189 @@ impl VideoComposer {
         self.compose_from_file_data_blocking(file_data, chunk_size, &output_path_str)
     }
 
+    /// Create video from an encoded payload
+    pub async fn compose_from_payload<P: AsRef<Path>>(
+        &self,
+        payload: &Payload,
+        chunk_size: usize,
+        output_path: P,
+    ) -> Result<FrameIndex> {
+        self.compose_from_payload_blocking(payload, chunk_size, output_path)
+    }
+
+    /// Probe the actual frame dimensions of a video's first video stream
+    pub fn probe_dimensions<P: AsRef<Path>>(video_path: P) -> Result<(u32, u32)> {
+        FfmpegBackend.probe_dimensions(video_path.as_ref())
+    }
+
+    /// Frame count and frame rate of a video's first video stream
+    ///
+    /// Counts packets rather than decoding, so it only reads the container.
+    pub fn probe_frames<P: AsRef<Path>>(video_path: P) -> Result<(u64, f64)> {
+        FfmpegBackend.probe_frames(video_path.as_ref())
+    }
+
+    /// Video encoders the installed ffmpeg was built with
+    pub fn probe_encoders() -> Result<Vec<String>> {
+        FfmpegBackend.probe_encoders()
+    }
+
+    /// Probe a video's duration in seconds with ffprobe
+    pub fn probe_duration<P: AsRef<Path>>(video_path: P) -> Result<f64> {
+        FfmpegBackend.probe_duration(video_path.as_ref())
+    }
+
+    /// Detect letterboxing/pillarboxing with ffmpeg's `cropdetect` filter
+    ///
+    /// Returns the content rectangle, or `None` if the picture fills the frame.
+    pub fn detect_content_rect<P: AsRef<Path>>(video_path: P) -> Result<Option<ContentRect>> {
+        FfmpegBackend.detect_content_rect(video_path.as_ref())
+    }
+
     /// Extract frames from video
     pub async fn extract_frames<P: AsRef<Path>>(
         &self,
         video_path: P,
     ) -> Result<Vec<ImageBuffer<image::Rgba<u8>, Vec<u8>>>> {
         let path = video_path.as_ref();
-        info!("Extracting frames from: {}", path.display());
-
-        let mut child = Command::new("/usr/local/bin/ffmpeg")
-            .args(&[
-                "-i", &path.to_string_lossy(),
-                "-f", "rawvideo",
-                "-pix_fmt", "rgba",
-                "-color_range", "pc",
-                "-",
-            ])
-            .stdout(Stdio::piped())
-            .stderr(Stdio::null())
-            .spawn()
-            .map_err(|e| F2V2FError::DecodingError(format!("Failed to start ffmpeg: {}", e)))?;
-
-        let mut stdout = child.stdout.take().ok_or_else(|| F2V2FError::DecodingError("No stdout".to_string()))?;
-        let mut frames = Vec::new();
-        let frame_size = (self.width * self.height * 4) as usize;
-        
-        loop {
-            let mut buffer = vec![0u8; frame_size];
-            match stdout.read_exact(&mut buffer) {
-                Ok(_) => {
-                    let img = ImageBuffer::from_raw(self.width, self.height, buffer)
-                        .ok_or_else(|| F2V2FError::DecodingError("Failed to create image from raw bytes".to_string()))?;
-                    frames.push(img);
-                }
-                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
-                Err(e) => return Err(F2V2FError::DecodingError(format!("Read failed: {}", e))),
-            }
-        }
+        debug!("Extracting frames from: {}", path.display());
 
-        let status = child.wait()
-            .map_err(|e| F2V2FError::DecodingError(format!("Wait failed: {}", e)))?;
+        let filters = self.content_filters(path)?;
+        let frames = self.read_all_frames(path, &filters)?;
+        debug!("Extracted {} frames", frames.len());
+        Ok(frames)
+    }
 
-        if !status.success() {
-            // It might fail if we read all frames but ffmpeg has more to say, or if it's not a video
-            warn!("ffmpeg exited with code {}", status.code().unwrap_or(-1));
+    /// Extract every frame with `filters` from `content_filters`
+    pub fn read_all_frames<P: AsRef<Path>>(
+        &self,
+        video_path: P,
+        filters: &[String],
+    ) -> Result<Vec<ImageBuffer<image::Rgba<u8>, Vec<u8>>>> {
+        self.read_frames(video_path.as_ref(), FrameSelection::All, filters)
+    }
+
+    /// Extract up to `count` frames starting at frame `start`
+    ///
+    /// `filters` comes from `content_filters`, so padding is detected once
+    /// rather than per window. ffmpeg still decodes from the start of the
+    /// video each time but stops after the window.
+    pub async fn extract_frame_window<P: AsRef<Path>>(
+        &self,
+        video_path: P,
+        filters: &[String],
+        start: u64,
+        count: u64,
+    ) -> Result<Vec<ImageBuffer<image::Rgba<u8>, Vec<u8>>>> {
+        self.read_frames(video_path.as_ref(), FrameSelection::Window { start, count }, filters)
+    }
+
+    /// Extract the single frame shown at `seconds`
+    ///
+    /// Seeks in the input, so ffmpeg only decodes from the nearest keyframe
+    /// instead of from the start of the video.
+    pub fn extract_frame_at<P: AsRef<Path>>(
+        &self,
+        video_path: P,
+        filters: &[String],
+        seconds: f64,
+    ) -> Result<Option<ImageBuffer<image::Rgba<u8>, Vec<u8>>>> {
+        let frames = self.read_frames(video_path.as_ref(), FrameSelection::At(seconds), filters)?;
+        Ok(frames.into_iter().next())
+    }
+
+    /// Extract the last `count` frames
+    ///
+    /// Seeks from the end of the input, so the rest of the video isn't
+    /// decoded.
+    pub fn extract_last_frames<P: AsRef<Path>>(
+        &self,
+        video_path: P,
+        filters: &[String],
+        count: u64,
+    ) -> Result<Vec<ImageBuffer<image::Rgba<u8>, Vec<u8>>>> {
+        self.read_frames(video_path.as_ref(), FrameSelection::Last(count), filters)
+    }
+
+    /// ffmpeg filters mapping the video's frames back onto the encoded grid
+    ///
+    /// Transcodes may pad the picture to another aspect ratio; crop to the
+    /// content so frame boundaries line up. A video of the encoded size is
+    /// never cropped, since crop detection would mistake dark data rows for
+    /// bars. Content of any other size than `width`x`height` is refused,
+    /// since resampling can't recover it.
+    pub fn content_filters(&self, path: &Path) -> Result<Vec<String>> {
+        let (mut content_width, mut content_height) = self.backend.probe_dimensions(path)?;
+        let mut filters = Vec::new();
+        if (content_width, content_height) == (self.width, self.height) {
+            return Ok(filters);
+        }
+        if let Some(rect) = self.backend.detect_content_rect(path)? {
+            warn!(
+                "Detected padding bars; cropping to {}x{} at ({}, {})",
+                rect.width, rect.height, rect.x, rect.y
+            );
+            filters.push(format!("crop={}:{}:{}:{}", rect.width, rect.height, rect.x, rect.y));
+            self.push_warning(Warning::PaddingCropped { width: rect.width, height: rect.height });
+            content_width = rect.width;
+            content_height = rect.height;
         }
+        if (content_width, content_height) != (self.width, self.height) {
+            return Err(F2V2FError::InvalidInput(format!(
+                "{} is {}x{} but decoding expects {}x{}",
+                path.display(),
+                content_width,
+                content_height,
+                self.width,
+                self.height
+            )));
+        }
+        Ok(filters)
+    }
 
-        info!("Extracted {} frames", frames.len());
+    /// Read the `selection` of frames through the backend
+    fn read_frames(
+        &self,
+        path: &Path,
+        selection: FrameSelection,
+        filters: &[String],
+    ) -> Result<Vec<ImageBuffer<image::Rgba<u8>, Vec<u8>>>> {
+        let request = FrameRequest {
+            width: self.width,
+            height: self.height,
+            selection,
+            filters: filters.to_vec(),
+            format: self.extract_format,
+            ignore_errors: self.ignore_errors,
+        };
+        let mut warnings = Vec::new();
+        let frames = self.backend.read_frames(path, &request, &mut warnings)?;
+        for warning in warnings {
+            self.push_warning(warning);
+        }
         Ok(frames)
     }
 }
 
+/// Hand one raw frame to the backend; `i` is the data frame it belongs to
+fn write_frame<W: Write + ?Sized>(sink: &mut W, frame_bytes: &[u8], i: usize, num_chunks: usize) -> Result<()> {
+    match sink.write_all(frame_bytes) {
+        Ok(_) => Ok(()),
+        Err(e) if e.raw_os_error() == Some(32) => Err(F2V2FError::EncodingError(format!(
+            "FFmpeg pipe broken at frame {}/{} - FFmpeg crashed or ran out of memory. Error: {}",
+            i + 1, num_chunks, e
+        ))),
+        Err(e) => Err(F2V2FError::EncodingError(format!("Write failed at frame {}: {}", i + 1, e))),
+    }
+}
+
+/// Blend two raw frames into `out`, `t` of the way from `from` to `to`
+fn crossfade(from: &[u8], to: &[u8], t: f32, out: &mut [u8]) {
+    for ((out, &a), &b) in out.iter_mut().zip(from).zip(to) {
+        *out = (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    }
+}
+
 /// Validates video file integrity
 pub struct VideoValidator;
 
@@ -260,6 +879,7 @@ impl VideoValidator {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::backend::MockBackend;
 
     #[test]
     fn test_composer_creation() {
@@ -269,6 +889,140 @@ mod tests {
         assert_eq!(composer.fps, 30);
     }
 
+    #[test]
+    fn test_crossfade() {
+        let black = [0, 0, 0, 255];
+        let white = [200, 200, 200, 255];
+        let mut out = [0; 4];
+        crossfade(&black, &white, 0.25, &mut out);
+        assert_eq!(out, [50, 50, 50, 255]);
+        crossfade(&black, &white, 1.0, &mut out);
+        assert_eq!(out, white);
+    }
+
+    #[test]
+    fn test_chunk_chapters_follow_layout() {
+        let composer = VideoComposer::new(256, 256, 30).with_transitions(1).with_chunk_chapters(Some(2));
+        // Two manifest frames at each end, a stream frame after data frame 1
+        let (index, total) = composer.plan_layout(100, 5, 2, &[(1, 0, 0)]);
+        let positions: Vec<_> = (0..5).map(|i| index.position(i).unwrap()).collect();
+        assert_eq!(positions, [2, 4, 7, 9, 11]);
+        assert_eq!(total, 12 + 1 + 2);
+
+        assert_eq!(composer.chunk_chapters(&index), [
+            Chapter::new(0, "frames 0-1"),
+            Chapter::new(2, "frames 2-3"),
+            Chapter::new(4, "frames 4-4"),
+        ]);
+        // File offsets start after the container header
+        let file_size = 450 - HEADER_LEN as u64;
+        assert_eq!(composer.with_chapter_file_size(Some(file_size)).chunk_chapters(&index), [
+            Chapter::new(0, format!("bytes 0-{}", 199 - HEADER_LEN)),
+            Chapter::new(2, format!("bytes {}-{}", 200 - HEADER_LEN, 399 - HEADER_LEN)),
+            Chapter::new(4, format!("bytes {}-{}", 400 - HEADER_LEN, file_size - 1)),
+        ]);
+        assert!(VideoComposer::new(256, 256, 30).chunk_chapters(&index).is_empty());
+    }
+
+    #[test]
+    fn test_chapters_land_on_their_data_frames() -> Result<()> {
+        let composer = VideoComposer::new(256, 256, 30).with_transitions(1).with_chunk_chapters(Some(2));
+        // Two manifest frames first: data frame 2 is video frame 6, not 4
+        let (index, total) = composer.plan_layout(100, 5, 2, &[]);
+        let file = composer.chapter_metadata_file(&index, total)?.unwrap();
+        assert!(std::fs::read_to_string(file.path())?.contains("START=6\n"));
+        Ok(())
+    }
+
+    /// `MockBackend` that keeps the chapter file ffmpeg would be handed
+    #[derive(Default)]
+    struct ChapterCapture(Mutex<String>);
+
+    impl VideoBackend for ChapterCapture {
+        fn create(&self, path: &Path, spec: &OutputSpec) -> Result<Box<dyn FrameSink>> {
+            if let Some(metadata) = spec.metadata {
+                *self.0.lock().unwrap() = std::fs::read_to_string(metadata)?;
+            }
+            MockBackend.create(path, spec)
+        }
+
+        fn read_frames(&self, path: &Path, request: &FrameRequest, warnings: &mut Vec<Warning>) -> Result<Vec<image::RgbaImage>> {
+            MockBackend.read_frames(path, request, warnings)
+        }
+
+        fn probe_dimensions(&self, path: &Path) -> Result<(u32, u32)> {
+            MockBackend.probe_dimensions(path)
+        }
+
+        fn probe_frames(&self, path: &Path) -> Result<(u64, f64)> {
+            MockBackend.probe_frames(path)
+        }
+
+        fn probe_duration(&self, path: &Path) -> Result<f64> {
+            MockBackend.probe_duration(path)
+        }
+
+        fn detect_content_rect(&self, path: &Path) -> Result<Option<ContentRect>> {
+            MockBackend.detect_content_rect(path)
+        }
+
+        fn read_tag(&self, path: &Path, key: &str) -> Result<Option<String>> {
+            MockBackend.read_tag(path, key)
+        }
+
+        fn read_attachment(&self, path: &Path, name: &str) -> Result<Option<Vec<u8>>> {
+            MockBackend.read_attachment(path, name)
+        }
+
+        fn read_subtitles(&self, path: &Path) -> Result<Option<String>> {
+            MockBackend.read_subtitles(path)
+        }
+    }
+
+    #[test]
+    fn test_chunk_chapters_start_at_indexed_frames() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let video = dir.path().join("chapters.f2v2f");
+        let capture = Arc::new(ChapterCapture::default());
+        // Manifest frames at the head, crossfades and an extra stream in between
+        let composer = VideoComposer::new(64, 64, 30)
+            .with_backend(capture.clone())
+            .with_raw(true)
+            .with_transitions(1)
+            .with_manifest(vec![b'm'; MANIFEST_CHUNK_SIZE + 1])
+            .with_streams(vec![(3, vec![1; 10])])
+            .with_chunk_chapters(Some(2));
+        let payload: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+        let index = composer.compose_from_payload_blocking(&Payload::Memory(payload), 200, &video)?;
+
+        let chapters = capture.0.lock().unwrap().clone();
+        let starts: Vec<u64> = chapters
+            .lines()
+            .filter_map(|line| line.strip_prefix("START="))
+            .map(|start| start.parse().unwrap())
+            .collect();
+        let expected: Vec<u64> = [0, 2, 4].iter().map(|&i| index.position(i).unwrap()).collect();
+        assert_eq!(starts, expected);
+        // The manifest takes at least two frames ahead of the data
+        assert!(starts[0] >= 2);
+
+        // Each chapter starts on the video frame holding its first data frame
+        for (data_frame, start) in [0u32, 2, 4].into_iter().zip(starts) {
+            let request = FrameRequest {
+                width: 64,
+                height: 64,
+                selection: FrameSelection::Window { start, count: 1 },
+                filters: Vec::new(),
+                format: ExtractFormat::default(),
+                ignore_errors: false,
+            };
+            let frame = MockBackend.read_frames(&video, &request, &mut Vec::new())?.remove(0);
+            let header = FrameHeader::read_from(&frame)?;
+            assert_eq!((header.stream, header.index), (crate::streams::MAIN_STREAM, data_frame));
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_compose_from_frames() -> Result<()> {
         let composer = VideoComposer::new(256, 256, 30);
@@ -0,0 +1,96 @@
+//! Recoverable anomalies reported alongside results
+//!
+//! Encode and decode either fail with an `F2V2FError` or succeed; things that
+//! went wrong without stopping them are collected as `Warning`s on
+//! `EncodedFileInfo` and `DecodedFileInfo` (and also logged), so callers can
+//! react to them programmatically.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Warning {
+    /// No manifest sidecar; parameters came from the config and frame headers
+    MissingManifest,
+    /// No manifest sidecar; the copy embedded in the video was used
+    EmbeddedManifest,
+    /// Chunk size found by searching frame CRCs (no manifest); `validated`
+    /// is false when no candidate passed and the header-based guess was used
+    InferredChunkSize { chunk_size: usize, validated: bool },
+    /// Padding bars around the picture were cropped off before decoding
+    PaddingCropped { width: u32, height: u32 },
+    /// Frames without an f2v2f header, or repeats, were skipped
+    SkippedFrames { count: usize },
+    /// Frames arrived out of order and were reassembled by their header index
+    ReorderedFrames { count: usize },
+    /// ffmpeg exited with an error after the frames were read
+    FfmpegExit { code: i32 },
+    /// The manifest's frame count differs from the data frames decoded
+    FrameCountMismatch { expected: u64, found: u64 },
+    /// More frames were needed than the `max_frames` target
+    ExceedsMaxFrames { frames: u64, target: u64 },
+    /// The video's duration couldn't be probed and was estimated
+    DurationEstimated { reason: String },
+    /// The finished encode couldn't be recorded in the duplicate-encode cache
+    CacheNotRecorded { reason: String },
+    /// The manifest was too large for a container tag and was left out
+    ManifestTagSkipped { size: usize },
+    /// No manifest sidecar; the copy in the container's metadata was used
+    TaggedManifest,
+    /// No manifest sidecar, tag or embedded copy; the copy in the subtitle
+    /// track was used
+    SubtitleManifest,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::MissingManifest => write!(f, "No manifest found; parameters were inferred"),
+            Warning::EmbeddedManifest => write!(f, "No manifest sidecar; used the copy embedded in the video"),
+            Warning::InferredChunkSize { chunk_size, validated } => write!(
+                f,
+                "Chunk size {} bytes was inferred{}",
+                chunk_size,
+                if *validated { " and checked against frame CRCs" } else { " but could not be verified" }
+            ),
+            Warning::PaddingCropped { width, height } => {
+                write!(f, "Cropped padding bars; content is {}x{}", width, height)
+            }
+            Warning::SkippedFrames { count } => write!(f, "Ignored {} foreign or repeated frames", count),
+            Warning::ReorderedFrames { count } => {
+                write!(f, "{} frames were out of order and reassembled by index", count)
+            }
+            Warning::FfmpegExit { code } => write!(f, "ffmpeg exited with code {}", code),
+            Warning::FrameCountMismatch { expected, found } => {
+                write!(f, "Manifest lists {} frames but {} were decoded", expected, found)
+            }
+            Warning::ExceedsMaxFrames { frames, target } => {
+                write!(f, "{} frames needed, more than the {} frame target", frames, target)
+            }
+            Warning::DurationEstimated { reason } => {
+                write!(f, "Could not probe video duration ({}); estimated from frame count", reason)
+            }
+            Warning::CacheNotRecorded { reason } => write!(f, "Could not record encode in cache: {}", reason),
+            Warning::ManifestTagSkipped { size } => {
+                write!(f, "Manifest is {} bytes, too large for a container tag; not tagged", size)
+            }
+            Warning::TaggedManifest => write!(f, "No manifest sidecar; used the copy in the video's metadata"),
+            Warning::SubtitleManifest => write!(f, "No manifest sidecar or embedded copy; used the copy in the subtitle track"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialized_with_kind_tag() {
+        let warning = Warning::SkippedFrames { count: 2 };
+        let json = serde_json::to_string(&warning).unwrap();
+        assert_eq!(json, r#"{"kind":"skipped_frames","count":2}"#);
+        assert_eq!(serde_json::from_str::<Warning>(&json).unwrap(), warning);
+        assert_eq!(warning.to_string(), "Ignored 2 foreign or repeated frames");
+    }
+}
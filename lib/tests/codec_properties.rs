@@ -0,0 +1,89 @@
+//! Property tests for reconstruction: random payloads and configs through
+//! the in-memory codec core (see `f2v2f::codec`), and manifests through JSON
+
+use f2v2f::checksum::HashAlgorithm;
+use f2v2f::codec::FrameCodec;
+use f2v2f::image_generator::GeometricArtGenerator;
+use f2v2f::manifest::{Manifest, MANIFEST_VERSION};
+use f2v2f::sparse::Hole;
+use proptest::prelude::*;
+
+proptest! {
+    // Rendering a frame is the slow part; keep the frames small
+    #![proptest_config(ProptestConfig::with_cases(48))]
+
+    #[test]
+    fn frames_round_trip(
+        data in prop::collection::vec(any::<u8>(), 0..6000),
+        width in 64u32..160,
+        height in 64u32..160,
+        seed in any::<u64>(),
+        chunk_share in 0.01f64..1.0,
+        raw in any::<bool>(),
+        level in prop::option::of(1i32..10),
+    ) {
+        let capacity = GeometricArtGenerator::new(width, height, seed).with_raw(raw).chunk_capacity();
+        let chunk_size = ((capacity as f64 * chunk_share) as usize).max(1);
+        let codec = FrameCodec::new(width, height, seed, chunk_size)?.with_raw(raw).with_compression(level);
+        let frames = codec.encode(&data)?;
+        prop_assert_eq!(codec.decode(&frames)?, data);
+    }
+
+    #[test]
+    fn frames_decode_in_any_order(
+        data in prop::collection::vec(any::<u8>(), 1..4000),
+        seed in any::<u64>(),
+        shuffle in any::<u64>(),
+    ) {
+        let codec = FrameCodec::new(96, 96, seed, 500)?;
+        let mut frames = codec.encode(&data)?;
+        // A repeat and a rotation: the decoder orders by header index
+        frames.push(frames[0].clone());
+        let by = shuffle as usize % frames.len();
+        frames.rotate_left(by);
+        prop_assert_eq!(codec.decode(&frames)?, data);
+    }
+
+    #[test]
+    fn manifest_json_round_trip(
+        chunk_size in 1usize..1 << 20,
+        num_frames in any::<u32>(),
+        original_size in any::<u64>(),
+        seed in any::<u64>(),
+        holes in prop::collection::vec((any::<u32>(), 1u32..u32::MAX), 0..4),
+        content_type in prop::option::of("[a-z]{1,10}/[a-z0-9.+-]{1,20}"),
+    ) {
+        let manifest = Manifest {
+            format_version: MANIFEST_VERSION,
+            width: 1920,
+            height: 1080,
+            fps: 30,
+            chunk_size,
+            num_frames: num_frames as u64,
+            original_size,
+            encoded_size: original_size / 2,
+            compressed: true,
+            compression_level: Some(11),
+            hash_algo: HashAlgorithm::Sha256,
+            checksum: "00".repeat(32),
+            dictionary_id: None,
+            seed,
+            holes: holes.into_iter().map(|(offset, len)| Hole { offset: offset as u64, len: len as u64 }).collect(),
+            link_target: None,
+            content_type,
+            transition_frames: 0,
+            video_checksum: None,
+            settings: None,
+            merkle: None,
+            streams: Vec::new(),
+            file: None,
+            index: None,
+        };
+        prop_assert_eq!(Manifest::from_json(&manifest.to_json()?)?, manifest);
+    }
+
+    #[test]
+    fn manifest_parser_never_panics(json in "\\PC*") {
+        let _ = Manifest::from_json(&json);
+    }
+}
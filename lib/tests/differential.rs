@@ -0,0 +1,117 @@
+//! Differential tests between video backends
+//!
+//! Every case is encoded and decoded through each backend available on this
+//! machine (`MockBackend` always, the ffmpeg CLI when installed), and the
+//! decoded files must be identical: to the input and to each other. A
+//! backend that mishandles colorspace, padding or odd frame sizes shows up
+//! as the first byte where its output differs.
+
+use f2v2f::backend::{FfmpegBackend, MockBackend, VideoBackend};
+use f2v2f::capabilities::capabilities;
+use f2v2f::config::{DecodeConfig, EncodeConfig};
+use f2v2f::image_generator::RAW_ART_STYLE;
+use f2v2f::{Decoder, FramePipeline, PayloadPipeline, Result};
+use rand::{RngCore, SeedableRng};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Backends to compare, by name; ffmpeg only when it runs here
+fn backends() -> Vec<(&'static str, Arc<dyn VideoBackend>)> {
+    let mut backends: Vec<(&'static str, Arc<dyn VideoBackend>)> = vec![("mock", Arc::new(MockBackend))];
+    let installed = capabilities(&EncodeConfig::default()).backends;
+    if installed.iter().filter(|b| b.name == "ffmpeg" || b.name == "ffprobe").all(|b| b.available) {
+        backends.push(("ffmpeg", Arc::new(FfmpegBackend)));
+    } else {
+        eprintln!("ffmpeg not found: comparing the mock backend only");
+    }
+    backends
+}
+
+struct Case {
+    name: &'static str,
+    data: Vec<u8>,
+    config: EncodeConfig,
+}
+
+fn random(len: usize, seed: u64) -> Vec<u8> {
+    let mut data = vec![0u8; len];
+    rand::rngs::StdRng::seed_from_u64(seed).fill_bytes(&mut data);
+    data
+}
+
+fn cases() -> Vec<Case> {
+    let base = EncodeConfig { width: 128, height: 128, chunk_size: 1024, ..EncodeConfig::default() };
+    vec![
+        Case { name: "empty", data: Vec::new(), config: base.clone() },
+        Case { name: "text", data: b"differential backends\n".repeat(200), config: base.clone() },
+        Case {
+            name: "incompressible",
+            data: random(10_000, 1),
+            config: EncodeConfig { use_compression: false, ..base.clone() },
+        },
+        Case {
+            name: "transitions",
+            data: random(5000, 2),
+            config: EncodeConfig { use_compression: false, transition_frames: 2, ..base.clone() },
+        },
+        Case {
+            name: "raw",
+            data: random(6000, 3),
+            config: EncodeConfig { art_style: RAW_ART_STYLE.to_string(), use_compression: false, ..base.clone() },
+        },
+        // Not a multiple of the codec's 16-pixel blocks, so padding must be cropped off
+        Case {
+            name: "odd-size",
+            data: random(4000, 4),
+            config: EncodeConfig { width: 136, height: 104, chunk_size: 700, use_compression: false, ..base },
+        },
+    ]
+}
+
+/// Encode `data` and decode it again through `backend`
+async fn round_trip(backend: Arc<dyn VideoBackend>, data: &[u8], config: &EncodeConfig, dir: &Path) -> Result<Vec<u8>> {
+    let (input, video, output) = (dir.join("in.bin"), dir.join("in.mp4"), dir.join("out.bin"));
+    std::fs::write(&input, data)?;
+    let (mut info, payload) = PayloadPipeline::new(config)?.run(&input)?;
+    FramePipeline::new(config).with_backend(backend.clone()).run(&payload, &mut info, &video)?;
+    Decoder::new(DecodeConfig::default())?.with_backend(backend).decode(&video, &output).await?;
+    Ok(std::fs::read(&output)?)
+}
+
+/// Where two decoded files first differ, for the failure message
+fn first_difference(a: &[u8], b: &[u8]) -> Option<usize> {
+    a.iter().zip(b).position(|(x, y)| x != y).or((a.len() != b.len()).then(|| a.len().min(b.len())))
+}
+
+#[tokio::test]
+async fn test_backends_decode_identically() -> Result<()> {
+    let backends = backends();
+    for case in cases() {
+        let mut outputs = Vec::new();
+        for (name, backend) in &backends {
+            let dir = tempfile::tempdir()?;
+            let output = round_trip(backend.clone(), &case.data, &case.config, dir.path())
+                .await
+                .unwrap_or_else(|e| panic!("{}: {} backend failed: {}", case.name, name, e));
+            outputs.push((*name, output));
+        }
+
+        // The input, then the first backend's output, are what every backend must match
+        let (reference, expected) = &outputs[0];
+        for (name, output) in &outputs {
+            if let Some(at) = first_difference(&case.data, output) {
+                panic!("{}: {} backend differs from the input at byte {} ({} vs {} bytes)",
+                    case.name, name, at, output.len(), case.data.len());
+            }
+            assert_eq!(first_difference(expected, output), None, "{}: {} vs {}", case.name, reference, name);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_first_difference() {
+    assert_eq!(first_difference(b"abc", b"abc"), None);
+    assert_eq!(first_difference(b"abc", b"abd"), Some(2));
+    assert_eq!(first_difference(b"abc", b"ab"), Some(2));
+}
@@ -0,0 +1,148 @@
+//! Format compatibility against the golden corpus in `tests/golden/`
+//!
+//! `tests/golden/vN/` holds tiny videos written by the encoder of format
+//! version N (see `MANIFEST_VERSION` for the policy), stored with
+//! `MockBackend` so they decode without ffmpeg. Each `CASE.f2v2fraw` sits
+//! next to its original, `CASE.in`, and usually its manifest sidecar.
+//!
+//! To write the corpus of a new format version, run
+//! `cargo test --test golden -- --ignored`.
+
+use f2v2f::backend::MockBackend;
+use f2v2f::config::{DecodeConfig, EncodeConfig};
+use f2v2f::image_generator::RAW_ART_STYLE;
+use f2v2f::manifest::{Manifest, MANIFEST_VERSION, OLDEST_MANIFEST_VERSION};
+use f2v2f::{Decoder, FramePipeline, PayloadPipeline, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const VIDEO_EXTENSION: &str = "f2v2fraw";
+
+fn corpus_dir(version: u32) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("v{}", version))
+}
+
+/// Pseudo-random bytes that don't compress
+fn noise(len: usize, seed: u32) -> Vec<u8> {
+    (0..len as u32).map(|i| (i.wrapping_add(seed).wrapping_mul(2_654_435_761) >> 17) as u8).collect()
+}
+
+/// Inputs and configs of the current format version's corpus:
+/// (name, input, config, write a sidecar)
+fn cases() -> Vec<(&'static str, Vec<u8>, EncodeConfig, bool)> {
+    let small = EncodeConfig {
+        width: 64,
+        height: 64,
+        chunk_size: 256,
+        deterministic: true,
+        embed_manifest: false,
+        ..EncodeConfig::default()
+    };
+    let text: String = (0..150).map(|i| format!("f2v2f golden corpus line {}\n", i)).collect();
+    vec![
+        ("compressed", text.into_bytes(), small.clone(), true),
+        (
+            "raw_transitions",
+            noise(1500, 1),
+            EncodeConfig {
+                art_style: RAW_ART_STYLE.to_string(),
+                use_compression: false,
+                transition_frames: 1,
+                chunk_size: 512,
+                ..small.clone()
+            },
+            true,
+        ),
+        (
+            "embedded_manifest",
+            noise(600, 2),
+            EncodeConfig { use_compression: false, embed_manifest: true, ..small },
+            false,
+        ),
+    ]
+}
+
+/// Encode `input` into `dir` as `name` with the current encoder
+fn encode(dir: &Path, name: &str, input: &[u8], config: &EncodeConfig, sidecar: bool) -> Result<PathBuf> {
+    let input_path = dir.join(format!("{}.in", name));
+    let video = dir.join(format!("{}.{}", name, VIDEO_EXTENSION));
+    std::fs::write(&input_path, input)?;
+    let (mut info, payload) = PayloadPipeline::new(config)?.run(&input_path)?;
+    FramePipeline::new(config).with_backend(Arc::new(MockBackend)).run(&payload, &mut info, &video)?;
+    if sidecar {
+        Manifest::new(&info, config).write_sidecar(&video)?;
+    }
+    Ok(video)
+}
+
+fn videos(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut videos: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .filter(|path| path.as_ref().map_or(true, |p| p.extension().is_some_and(|ext| ext == VIDEO_EXTENSION)))
+        .collect::<std::io::Result<_>>()?;
+    videos.sort();
+    Ok(videos)
+}
+
+#[test]
+fn test_corpus_exists_for_every_supported_version() {
+    for version in OLDEST_MANIFEST_VERSION..=MANIFEST_VERSION {
+        let dir = corpus_dir(version);
+        assert!(
+            videos(&dir).is_ok_and(|videos| !videos.is_empty()),
+            "No golden corpus for format version {} in {}; see MANIFEST_VERSION",
+            version,
+            dir.display()
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_every_golden_video_decodes() -> Result<()> {
+    let out = tempfile::tempdir()?;
+    for version in OLDEST_MANIFEST_VERSION..=MANIFEST_VERSION {
+        for video in videos(&corpus_dir(version))? {
+            let output = out.path().join(format!("v{}-{}", version, video.file_name().unwrap().to_string_lossy()));
+            let decoder = Decoder::new(DecodeConfig::default())?.with_backend(Arc::new(MockBackend));
+            decoder.decode(&video, &output).await?;
+            assert_eq!(
+                std::fs::read(&output)?,
+                std::fs::read(video.with_extension("in"))?,
+                "{} no longer decodes to its original",
+                video.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_encoder_reproduces_current_corpus() -> Result<()> {
+    let out = tempfile::tempdir()?;
+    let golden = corpus_dir(MANIFEST_VERSION);
+    for (name, input, config, sidecar) in cases() {
+        let video = encode(out.path(), name, &input, &config, sidecar)?;
+        let expected = golden.join(video.file_name().unwrap());
+        assert!(
+            std::fs::read(&video)? == std::fs::read(&expected)?,
+            "Encoder output for '{}' differs from {}: a format change needs a new MANIFEST_VERSION and corpus",
+            name,
+            expected.display()
+        );
+        assert_eq!(Manifest::read_sidecar(&video)?, Manifest::read_sidecar(&expected)?);
+    }
+    Ok(())
+}
+
+/// Write the corpus of the current format version (refuses to replace one)
+#[test]
+#[ignore]
+fn write_golden_corpus() -> Result<()> {
+    let dir = corpus_dir(MANIFEST_VERSION);
+    assert!(!dir.exists(), "{} exists; released corpora never change", dir.display());
+    std::fs::create_dir_all(&dir)?;
+    for (name, input, config, sidecar) in cases() {
+        encode(&dir, name, &input, &config, sidecar)?;
+    }
+    Ok(())
+}
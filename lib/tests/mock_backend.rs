@@ -0,0 +1,480 @@
+//! Encode and decode end to end through `MockBackend`, which needs no
+//! ffmpeg and stores frames losslessly
+
+use f2v2f::backend::{ContentRect, FrameRequest, FrameSelection, FrameSink, MockBackend, OutputSpec, VideoBackend};
+use f2v2f::chunk_manifest::ChunkEntry;
+use f2v2f::config::{DecodeConfig, EncodeConfig};
+use f2v2f::container::HEADER_LEN;
+use f2v2f::error::F2V2FError;
+use f2v2f::extract_format::ExtractFormat;
+use f2v2f::file_metadata::FileMetadata;
+use f2v2f::image_generator::RAW_ART_STYLE;
+use f2v2f::manifest::{Manifest, FILE_METADATA_ATTACHMENT, MANIFEST_ATTACHMENT, MANIFEST_TAG};
+use f2v2f::storage::MemoryBuffer;
+use f2v2f::subtitles::SubtitleMetadata;
+use f2v2f::warning::Warning;
+use f2v2f::{Decoder, Encoder, FramePipeline, PayloadPipeline, Result};
+use image::imageops::{self, FilterType};
+use image::RgbaImage;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+fn config() -> EncodeConfig {
+    EncodeConfig { width: 128, height: 128, chunk_size: 1024, ..EncodeConfig::default() }
+}
+
+/// Encode `input` to `video` and return its manifest
+fn encode(input: &Path, video: &Path, config: &EncodeConfig) -> Result<Manifest> {
+    let (mut info, payload) = PayloadPipeline::new(config)?.run(input)?;
+    FramePipeline::new(config).with_backend(Arc::new(MockBackend)).run(&payload, &mut info, video)?;
+    Ok(Manifest::new(&info, config))
+}
+
+fn decoder() -> Result<Decoder> {
+    Ok(Decoder::new(DecodeConfig::default())?.with_backend(Arc::new(MockBackend)))
+}
+
+#[tokio::test]
+async fn test_round_trip_from_embedded_manifest() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (input, video, output) = (dir.path().join("in.bin"), dir.path().join("in.f2v2f"), dir.path().join("out.bin"));
+    let data: Vec<u8> = (0..20_000u32).map(|i| (i * 7 % 253) as u8).collect();
+    std::fs::write(&input, &data)?;
+
+    let manifest = encode(&input, &video, &config())?;
+    let info = decoder()?.decode(&video, &output).await?;
+    assert_eq!(std::fs::read(&output)?, data);
+    assert_eq!(info.checksum, manifest.checksum);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_round_trip_from_manifest_tag() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (input, video, output) = (dir.path().join("in.bin"), dir.path().join("in.f2v2f"), dir.path().join("out.bin"));
+    let data: Vec<u8> = (0..20_000u32).map(|i| (i * 11 % 251) as u8).collect();
+    std::fs::write(&input, &data)?;
+
+    // No sidecar and no manifest frames: the container tag is all there is
+    let config = EncodeConfig { embed_manifest: false, manifest_tag: true, seed: 9, ..config() };
+    let manifest = encode(&input, &video, &config)?;
+    assert!(MockBackend.read_tag(&video, MANIFEST_TAG)?.is_some());
+    let info = decoder()?.decode(&video, &output).await?;
+    assert_eq!(std::fs::read(&output)?, data);
+    assert!(info.warnings.contains(&Warning::TaggedManifest));
+
+    let rebuilt = decoder()?.rebuild_manifest(&video).await?;
+    assert_eq!((rebuilt.seed, rebuilt.checksum), (9, manifest.checksum));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_round_trip_from_mkv_attachment() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (input, video, output) = (dir.path().join("in.bin"), dir.path().join("in.mkv"), dir.path().join("out.bin"));
+    let data: Vec<u8> = (0..20_000u32).map(|i| (i * 13 % 241) as u8).collect();
+    std::fs::write(&input, &data)?;
+
+    let config = EncodeConfig { embed_manifest: false, manifest_tag: true, preserve_metadata: true, ..config() };
+    encode(&input, &video, &config)?;
+    // Matroska takes the manifest as an attachment rather than a tag
+    assert!(MockBackend.read_tag(&video, MANIFEST_TAG)?.is_none());
+    assert!(MockBackend.read_attachment(&video, MANIFEST_ATTACHMENT)?.is_some());
+    let file = MockBackend.read_attachment(&video, FILE_METADATA_ATTACHMENT)?.unwrap();
+    let file: FileMetadata = serde_json::from_slice(&file).unwrap();
+    assert_eq!(file.name.as_deref(), Some("in.bin"));
+
+    let info = decoder()?.decode(&video, &output).await?;
+    assert_eq!(std::fs::read(&output)?, data);
+    assert!(info.warnings.contains(&Warning::TaggedManifest));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_round_trip_from_subtitle_track() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (input, video, output) = (dir.path().join("in.bin"), dir.path().join("in.f2v2f"), dir.path().join("out.bin"));
+    let data: Vec<u8> = (0..20_000u32).map(|i| (i * 17 % 239) as u8).collect();
+    std::fs::write(&input, &data)?;
+
+    let config = EncodeConfig { embed_manifest: false, subtitle_manifest: true, ..config() };
+    let manifest = encode(&input, &video, &config)?;
+    let metadata = SubtitleMetadata::from_srt(&MockBackend.read_subtitles(&video)?.unwrap())?;
+    assert_eq!(metadata.frame_crcs.len() as u64, manifest.num_frames);
+    assert_eq!(metadata.manifest.checksum, manifest.checksum);
+
+    let info = decoder()?.decode(&video, &output).await?;
+    assert_eq!(std::fs::read(&output)?, data);
+    assert!(info.warnings.contains(&Warning::SubtitleManifest));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_adaptive_fps_slows_small_videos() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (input, video, output) = (dir.path().join("in.bin"), dir.path().join("in.f2v2f"), dir.path().join("out.bin"));
+    let data: Vec<u8> = (0..5000u32).map(|i| (i % 241) as u8).collect();
+    std::fs::write(&input, &data)?;
+
+    let config = EncodeConfig { use_compression: false, adaptive_fps: true, ..config() };
+    let manifest = encode(&input, &video, &config)?;
+    // Five data frames can't fill ten seconds even at the slowest step
+    assert_eq!(manifest.fps, 1);
+    assert!(manifest.settings.as_ref().is_some_and(|s| s.adaptive_fps && s.fps == 30));
+    assert_eq!(MockBackend.probe_frames(&video)?.1, 1.0);
+
+    decoder()?.decode(&video, &output).await?;
+    assert_eq!(std::fs::read(&output)?, data);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_restore_file_metadata() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (input, video) = (dir.path().join("report.txt"), dir.path().join("videos/report.f2v2f"));
+    std::fs::create_dir(dir.path().join("videos"))?;
+    std::fs::write(&input, "quarterly numbers\n".repeat(100))?;
+    let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+    std::fs::File::options().write(true).open(&input)?.set_modified(modified)?;
+
+    // No sidecar: the metadata travels in the embedded manifest
+    let manifest = encode(&input, &video, &EncodeConfig { preserve_metadata: true, ..config() })?;
+    let output = Manifest::default_output_path(&video, Some(&manifest));
+    assert_eq!(output, dir.path().join("videos/report.txt"));
+    let config = DecodeConfig { restore_metadata: true, ..DecodeConfig::default() };
+    let info = Decoder::new(config)?.with_backend(Arc::new(MockBackend)).decode(&video, &output).await?;
+
+    assert_eq!(info.file_metadata.as_ref().and_then(|m| m.name.as_deref()), Some("report.txt"));
+    assert_eq!(std::fs::metadata(&output)?.modified()?, modified);
+    assert_eq!(FileMetadata::read(&output)?.mode, FileMetadata::read(&input)?.mode);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_checksum_mismatch_fails_the_decode() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (input, video, output) = (dir.path().join("in.bin"), dir.path().join("in.f2v2f"), dir.path().join("out.bin"));
+    std::fs::write(&input, b"checked on every decode".repeat(50))?;
+    let manifest = encode(&input, &video, &config())?;
+    Manifest { checksum: "0".repeat(64), ..manifest }.write_sidecar(&video)?;
+
+    let err = decoder()?.decode(&video, &output).await.unwrap_err();
+    assert!(matches!(&err, F2V2FError::IntegrityError(_, expected, _) if *expected == "0".repeat(64)), "{}", err);
+    assert!(!output.exists());
+
+    let unchecked = DecodeConfig { verify_checksum: false, ..DecodeConfig::default() };
+    Decoder::new(unchecked)?.with_backend(Arc::new(MockBackend)).decode(&video, &output).await?;
+    assert_eq!(std::fs::read(&output)?, std::fs::read(&input)?);
+    Ok(())
+}
+
+/// Rewrite `video` to `resized` with every frame scaled to `width`x`height`,
+/// as a platform downscaling an upload would
+fn resize_video(video: &Path, resized: &Path, width: u32, height: u32) -> Result<()> {
+    let (encoded_width, encoded_height) = MockBackend.probe_dimensions(video)?;
+    let request = FrameRequest {
+        width: encoded_width,
+        height: encoded_height,
+        selection: FrameSelection::All,
+        filters: Vec::new(),
+        format: ExtractFormat::default(),
+        ignore_errors: false,
+    };
+    let spec = OutputSpec { width, height, fps: 30, keyframe_interval: None, deterministic: true, metadata: None, subtitles: None, tags: &[], attachments: &[] };
+    let mut sink = MockBackend.create(resized, &spec)?;
+    for frame in MockBackend.read_frames(video, &request, &mut Vec::new())? {
+        sink.write_all(imageops::resize(&frame, width, height, FilterType::Triangle).as_raw())?;
+    }
+    sink.finish()
+}
+
+#[tokio::test]
+async fn test_resized_video_is_refused() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (input, video, output) = (dir.path().join("in.bin"), dir.path().join("in.f2v2f"), dir.path().join("out.bin"));
+    std::fs::write(&input, b"downscaled by the platform".repeat(40))?;
+    let manifest = encode(&input, &video, &config())?;
+
+    // The manifest knows the encoded size: an error naming both, not CRC failures
+    let resized = dir.path().join("resized.f2v2f");
+    resize_video(&video, &resized, 96, 96)?;
+    manifest.write_sidecar(&resized)?;
+    let err = decoder()?.decode(&resized, &output).await.unwrap_err();
+    assert!(matches!(&err, F2V2FError::InvalidInput(message) if message.contains("96x96 but decoding expects 128x128")), "{}", err);
+    assert!(!output.exists());
+
+    // Without it, the embedded manifest and frame headers don't survive either
+    let unlabelled = dir.path().join("unlabelled.f2v2f");
+    resize_video(&video, &unlabelled, 96, 96)?;
+    assert!(decoder()?.decode(&unlabelled, &output).await.is_err());
+    assert!(!output.exists());
+
+    // Same size, same frames: still decodes
+    let copy = dir.path().join("copy.f2v2f");
+    resize_video(&video, &copy, 128, 128)?;
+    manifest.write_sidecar(&copy)?;
+    decoder()?.decode(&copy, &output).await?;
+    assert_eq!(std::fs::read(&output)?, std::fs::read(&input)?);
+    Ok(())
+}
+
+/// `MockBackend` with crop detection like ffmpeg's `cropdetect=limit=16`:
+/// edge rows and columns that stay dark across the first frames count as
+/// bars
+struct CropDetecting;
+
+impl VideoBackend for CropDetecting {
+    fn create(&self, path: &Path, spec: &OutputSpec) -> Result<Box<dyn FrameSink>> {
+        MockBackend.create(path, spec)
+    }
+
+    fn read_frames(&self, path: &Path, request: &FrameRequest, warnings: &mut Vec<Warning>) -> Result<Vec<RgbaImage>> {
+        MockBackend.read_frames(path, request, warnings)
+    }
+
+    fn probe_dimensions(&self, path: &Path) -> Result<(u32, u32)> {
+        MockBackend.probe_dimensions(path)
+    }
+
+    fn probe_frames(&self, path: &Path) -> Result<(u64, f64)> {
+        MockBackend.probe_frames(path)
+    }
+
+    fn probe_duration(&self, path: &Path) -> Result<f64> {
+        MockBackend.probe_duration(path)
+    }
+
+    fn detect_content_rect(&self, path: &Path) -> Result<Option<ContentRect>> {
+        let (width, height) = self.probe_dimensions(path)?;
+        let request = FrameRequest {
+            width,
+            height,
+            selection: FrameSelection::Window { start: 0, count: 10 },
+            filters: Vec::new(),
+            format: ExtractFormat::default(),
+            ignore_errors: false,
+        };
+        let (mut rows, mut columns) = (vec![false; height as usize], vec![false; width as usize]);
+        for frame in self.read_frames(path, &request, &mut Vec::new())? {
+            for (x, y, pixel) in frame.enumerate_pixels() {
+                if pixel[0] > 16 {
+                    (rows[y as usize], columns[x as usize]) = (true, true);
+                }
+            }
+        }
+        let span = |lit: &[bool]| -> Option<(u32, u32)> {
+            let first = lit.iter().position(|&l| l)?;
+            let last = lit.iter().rposition(|&l| l)?;
+            Some((first as u32, (last - first + 1) as u32))
+        };
+        let (Some((y, h)), Some((x, w))) = (span(&rows), span(&columns)) else {
+            return Ok(None);
+        };
+        Ok(Some(ContentRect { width: w, height: h, x, y }).filter(|r| (r.width, r.height) != (width, height)))
+    }
+
+    fn read_tag(&self, path: &Path, key: &str) -> Result<Option<String>> {
+        MockBackend.read_tag(path, key)
+    }
+
+    fn read_attachment(&self, path: &Path, name: &str) -> Result<Option<Vec<u8>>> {
+        MockBackend.read_attachment(path, name)
+    }
+
+    fn read_subtitles(&self, path: &Path) -> Result<Option<String>> {
+        MockBackend.read_subtitles(path)
+    }
+}
+
+#[tokio::test]
+async fn test_dark_edge_rows_are_not_cropped() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (input, video, output) = (dir.path().join("in.bin"), dir.path().join("in.f2v2f"), dir.path().join("out.bin"));
+    // Raw frames show bytes as gray levels: zeros in the last two rows of
+    // every frame look like a letterbox bar
+    let config = EncodeConfig { art_style: RAW_ART_STYLE.to_string(), use_compression: false, embed_manifest: false, ..config() };
+    let capacity = config.frame_capacity();
+    let config = EncodeConfig { chunk_size: capacity, ..config };
+    let data: Vec<u8> = (0..12 * capacity - HEADER_LEN)
+        .map(|i| (i + HEADER_LEN) % capacity)
+        .map(|offset| if offset >= capacity - 2 * 128 { 0 } else { 100 + (offset % 100) as u8 })
+        .collect();
+    std::fs::write(&input, &data)?;
+    let manifest = encode(&input, &video, &config)?;
+    assert!(CropDetecting.detect_content_rect(&video)?.is_some_and(|r| r.height == 126));
+
+    let decoder = || -> Result<Decoder> { Ok(Decoder::new(DecodeConfig::default())?.with_backend(Arc::new(CropDetecting))) };
+    // Without a manifest the resolution is probed: the headers read at full size
+    assert_eq!(CropDetecting.probe_resolution(&video)?, (128, 128));
+    decoder()?.decode(&video, &output).await?;
+    assert_eq!(std::fs::read(&output)?, data);
+
+    // With one, the video already has the encoded size
+    std::fs::remove_file(&output)?;
+    manifest.write_sidecar(&video)?;
+    let info = decoder()?.decode(&video, &output).await?;
+    assert_eq!(std::fs::read(&output)?, data);
+    assert!(!info.warnings.iter().any(|w| matches!(w, Warning::PaddingCropped { .. })));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_round_trip_with_sidecar_and_transitions() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (input, video, output) = (dir.path().join("in.bin"), dir.path().join("in.f2v2f"), dir.path().join("out.bin"));
+    let data: Vec<u8> = (0..9000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect();
+    std::fs::write(&input, &data)?;
+
+    let config = EncodeConfig { use_compression: false, transition_frames: 2, ..config() };
+    let manifest = encode(&input, &video, &config)?;
+    manifest.write_sidecar(&video)?;
+    // 9 data frames, 2 fades between each, the end marker and the manifest at both ends
+    let (frames, _) = MockBackend.probe_frames(&video)?;
+    assert_eq!(frames, 9 + 8 * 2 + 1 + 2);
+
+    let info = decoder()?.decode(&video, &output).await?;
+    assert_eq!(std::fs::read(&output)?, data);
+    assert!(!info.was_compressed);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_verify_against_exported_chunks() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (input, video) = (dir.path().join("in.bin"), dir.path().join("in.f2v2f"));
+    let data: Vec<u8> = (0..9000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 11) as u8).collect();
+    std::fs::write(&input, &data)?;
+
+    let config = EncodeConfig { use_compression: false, transition_frames: 2, ..config() };
+    let (mut info, payload) = PayloadPipeline::new(&config)?.run(&input)?;
+    FramePipeline::new(&config).with_backend(Arc::new(MockBackend)).run(&payload, &mut info, &video)?;
+    let mut chunks = Encoder::new(config)?.export_manifest(&payload, &info)?;
+    assert_eq!(chunks.payload_size, payload.len());
+    // One manifest frame first, then a data frame every third frame
+    assert_eq!(chunks.chunks[2].frame, Some(1 + 2 * 3));
+
+    let verification = decoder()?.verify_against_manifest(&video, &chunks).await?;
+    assert!(verification.is_ok());
+    assert_eq!(verification.verified, chunks.chunks.len() as u64);
+
+    let last = chunks.chunks.len() as u64;
+    chunks.chunks[3].sha256 = "0".repeat(64);
+    chunks.chunks.push(ChunkEntry { index: last, offset: chunks.payload_size, size: 1, sha256: "0".repeat(64), frame: None });
+    let verification = decoder()?.verify_against_manifest(&video, &chunks).await?;
+    assert_eq!((verification.mismatched, verification.missing), (vec![3], vec![last]));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_read_range_through_the_frame_cache() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (input, video) = (dir.path().join("in.bin"), dir.path().join("in.f2v2f"));
+    let data: Vec<u8> = (0..9000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 11) as u8).collect();
+    std::fs::write(&input, &data)?;
+    let manifest = encode(&input, &video, &EncodeConfig { use_compression: false, transition_frames: 1, ..config() })?;
+
+    let cached = DecodeConfig { frame_cache_dir: Some(dir.path().join("frames")), ..DecodeConfig::default() };
+    let decoder = Decoder::new(cached)?.with_backend(Arc::new(MockBackend));
+    // Payload bytes 1500+8..4500+8: frames 1-4
+    let read = decoder.read_range(&video, 1500, 3000).await?;
+    assert_eq!(read.data, data[1500..4500]);
+    assert_eq!((read.frames_extracted, read.frames_cached), (4, 0));
+    let read = decoder.read_range(&video, 2000, 1000).await?;
+    assert_eq!(read.data, data[2000..3000]);
+    assert_eq!((read.frames_extracted, read.frames_cached), (0, 2));
+    // Cut short at the end of the file
+    let read = decoder.read_range(&video, 8000, 5000).await?;
+    assert_eq!(read.data, data[8000..]);
+
+    // The sidecar's frame index places the frames: one crossfade apart,
+    // after the embedded manifest
+    let index = manifest.index.clone().expect("sidecar manifests carry a frame index");
+    assert_eq!((index.stride, index.frames, index.runs.len()), (2, 9, 1));
+    manifest.write_sidecar(&video)?;
+    let read = decoder.read_range(&video, 5500, 1000).await?;
+    assert_eq!(read.data, data[5500..6500]);
+    assert_eq!((read.frames_extracted, read.frames_cached), (2, 0));
+
+    let compressed = dir.path().join("compressed.f2v2f");
+    encode(&input, &compressed, &config())?;
+    assert!(matches!(decoder.read_range(&compressed, 0, 10).await, Err(F2V2FError::InvalidInput(_))));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_partial_decode_of_cut_off_video() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (input, video, output) = (dir.path().join("in.bin"), dir.path().join("in.f2v2f"), dir.path().join("out.bin"));
+    // Ten frames of payload, container header included
+    let data: Vec<u8> = (0..(10_240 - HEADER_LEN) as u32).map(|i| (i % 249) as u8).collect();
+    std::fs::write(&input, &data)?;
+    let config = EncodeConfig { use_compression: false, ..config() };
+    encode(&input, &video, &config)?;
+
+    // Lose the trailing manifest, the end marker, the last three data frames
+    // and half of the one before
+    let frame = 128 * 128 * 4;
+    let file = std::fs::OpenOptions::new().write(true).open(&video)?;
+    file.set_len(file.metadata()?.len() - 5 * frame - frame / 2)?;
+    assert!(decoder()?.decode(&video, &output).await.is_err());
+
+    let info = decoder()?.decode_partial(&video, &output).await?;
+    assert_eq!(info.frames_recovered, 6);
+    let recovered = 6 * 1024 - HEADER_LEN;
+    assert_eq!(info.recovered_bytes(), recovered as u64);
+    assert_eq!(std::fs::read(&output)?[..recovered], data[..recovered]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rebuild_lost_sidecar() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (input, video, output) = (dir.path().join("in.txt"), dir.path().join("in.f2v2f"), dir.path().join("out.txt"));
+    let text: String = (0..2000).map(|i| format!("line {}\n", i)).collect();
+    std::fs::write(&input, &text)?;
+    let config = EncodeConfig { embed_manifest: false, transition_frames: 1, ..config() };
+    let mut original = encode(&input, &video, &config)?;
+    original.video_checksum = Some(f2v2f::checksum::hash_file(&video, original.hash_algo)?);
+
+    let rebuilt = decoder()?.rebuild_manifest(&video).await?;
+    for (field, same) in [
+        ("resolution", (rebuilt.width, rebuilt.height) == (original.width, original.height)),
+        ("fps", rebuilt.fps == original.fps),
+        ("chunk size", rebuilt.chunk_size == original.chunk_size),
+        ("frames", rebuilt.num_frames == original.num_frames),
+        ("sizes", (rebuilt.original_size, rebuilt.encoded_size) == (original.original_size, original.encoded_size)),
+        ("compression", rebuilt.compressed && original.compressed),
+        ("checksum", rebuilt.checksum == original.checksum),
+        ("content type", rebuilt.content_type == original.content_type),
+        ("transitions", rebuilt.transition_frames == original.transition_frames),
+        ("video checksum", rebuilt.video_checksum == original.video_checksum),
+    ] {
+        assert!(same, "Rebuilt manifest has the wrong {}", field);
+    }
+
+    rebuilt.write_sidecar(&video)?;
+    let info = decoder()?.decode(&video, &output).await?;
+    assert!(!info.warnings.contains(&Warning::MissingManifest));
+    assert_eq!(std::fs::read_to_string(&output)?, text);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_round_trip_between_memory_buffers() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let video = dir.path().join("in.f2v2f");
+    let data: Vec<u8> = (0..5000u32).map(|i| (i * 13 % 251) as u8).collect();
+    let (source, sink) = (MemoryBuffer::new("in", data.clone()), MemoryBuffer::default());
+
+    let config = config();
+    let (mut info, payload) = PayloadPipeline::new(&config)?.run_source(&source)?;
+    FramePipeline::new(&config).with_backend(Arc::new(MockBackend)).run(&payload, &mut info, &video)?;
+    Manifest::new(&info, &config).write_sidecar(&video)?;
+
+    let decoded = decoder()?.decode_to_sink(&video, &sink).await?;
+    assert_eq!(sink.contents(), data);
+    assert_eq!(decoded.checksum, info.checksum);
+    Ok(())
+}
@@ -0,0 +1,124 @@
+//! How much damage videos survive, per chunk density
+//!
+//! Videos are written through a `FaultyBackend` (feature `fault-injection`)
+//! and recovered with a partial decode. Run with
+//! `cargo test --features fault-injection --test robustness -- --nocapture`
+//! to see the tolerance table.
+
+use f2v2f::backend::MockBackend;
+use f2v2f::config::{DecodeConfig, EncodeConfig};
+use f2v2f::container::HEADER_LEN;
+use f2v2f::faults::{Faults, FaultyBackend};
+use f2v2f::image_generator::{GeometricArtGenerator, RAW_ART_STYLE};
+use f2v2f::manifest::Manifest;
+use f2v2f::partial::PartialDecodeInfo;
+use f2v2f::{Decoder, FramePipeline, PayloadPipeline, Result};
+use std::sync::Arc;
+
+const SIZE: u32 = 128;
+const DATA_FRAMES: usize = 8;
+
+/// Chunk sizes as a share of what a frame decodes reliably
+const DENSITIES: [f64; 3] = [1.0, 0.5, 0.25];
+
+/// Shares of pixels hit by noise
+const CORRUPTION: [f64; 6] = [0.0, 0.0005, 0.005, 0.05, 0.2, 0.5];
+
+fn config(density: f64, art_style: &str) -> EncodeConfig {
+    let raw = art_style == RAW_ART_STYLE;
+    let capacity = GeometricArtGenerator::new(SIZE, SIZE, 0).with_raw(raw).chunk_capacity();
+    EncodeConfig {
+        width: SIZE,
+        height: SIZE,
+        chunk_size: (capacity as f64 * density) as usize,
+        art_style: art_style.to_string(),
+        use_compression: false,
+        embed_manifest: false,
+        ..EncodeConfig::default()
+    }
+}
+
+/// Encode `DATA_FRAMES` frames of noise with `faults` and recover what survives
+async fn damage(config: &EncodeConfig, faults: Faults) -> Result<(PartialDecodeInfo, Vec<u8>, Vec<u8>)> {
+    let dir = tempfile::tempdir()?;
+    let (input, video, output) = (dir.path().join("in"), dir.path().join("video"), dir.path().join("out"));
+    // Payload fills exactly `DATA_FRAMES` frames, container header included
+    let data: Vec<u8> = (0..(config.chunk_size * DATA_FRAMES - HEADER_LEN) as u32)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 19) as u8)
+        .collect();
+    std::fs::write(&input, &data)?;
+
+    let (mut info, payload) = PayloadPipeline::new(config)?.run(&input)?;
+    let backend = FaultyBackend::new(Arc::new(MockBackend), faults)?;
+    FramePipeline::new(config).with_backend(Arc::new(backend)).run(&payload, &mut info, &video)?;
+    Manifest::new(&info, config).write_sidecar(&video)?;
+
+    let decoder = Decoder::new(DecodeConfig::default())?.with_backend(Arc::new(MockBackend));
+    let recovered = decoder.decode_partial(&video, &output).await?;
+    Ok((recovered, data, std::fs::read(&output)?))
+}
+
+/// Recovered bytes must match the original wherever they are reported
+fn check_ranges(info: &PartialDecodeInfo, data: &[u8], output: &[u8]) {
+    for range in &info.recovered {
+        let (start, end) = (range.start as usize, range.end as usize);
+        assert_eq!(output[start..end], data[start..end], "Bytes {}..{} reported recovered but wrong", start, end);
+    }
+}
+
+#[tokio::test]
+async fn test_corruption_tolerance_by_density() -> Result<()> {
+    // Any-value noise, then mild noise like a lossy codec's
+    for (art_style, amplitude) in [("geometric", 255), (RAW_ART_STYLE, 255), ("geometric", 24), (RAW_ART_STYLE, 24)] {
+        println!("{} frames, noise up to ±{}, share of the payload recovered:", art_style, amplitude);
+        println!("density  {}", CORRUPTION.map(|c| format!("{:>7.2}%", c * 100.0)).join(""));
+        let mut tolerated = Vec::new();
+        for density in DENSITIES {
+            let config = config(density, art_style);
+            let mut shares = Vec::new();
+            for corruption in CORRUPTION {
+                // Past some point no frame survives and the decode fails
+                let share = match damage(&config, Faults::default().with_corrupt_pixels(corruption).with_amplitude(amplitude)).await {
+                    Ok((info, data, output)) => {
+                        check_ranges(&info, &data, &output);
+                        info.recovered_bytes() as f64 / data.len() as f64
+                    }
+                    Err(_) if corruption > 0.0 => 0.0,
+                    Err(e) => return Err(e),
+                };
+                shares.push(share);
+            }
+            println!("{:>7.2}  {}", density, shares.iter().map(|s| format!("{:>7.1}%", s * 100.0)).collect::<String>());
+            // Undamaged videos always come back whole
+            assert_eq!(shares[0], 1.0);
+            tolerated.push(shares.iter().take_while(|&&s| s == 1.0).count());
+        }
+        // Spreading each byte over more pixels never tolerates less damage
+        assert!(tolerated.windows(2).all(|w| w[0] <= w[1]), "{} tolerance by density: {:?}", art_style, tolerated);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_dropped_frames_leave_gaps() -> Result<()> {
+    let config = config(0.5, "geometric");
+    let (info, data, output) = damage(&config, Faults::default().with_drop_frames(0.3).with_seed(5)).await?;
+    check_ranges(&info, &data, &output);
+    assert!(info.frames_recovered > 0 && info.frames_recovered < DATA_FRAMES as u64);
+    // The first frame also carries the container header
+    let header = if info.recovered.first().is_some_and(|r| r.start == 0) { HEADER_LEN } else { 0 };
+    assert_eq!(info.recovered_bytes(), info.frames_recovered * config.chunk_size as u64 - header as u64);
+    assert_eq!(info.frames_damaged, 0);
+    assert!(!info.is_complete());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_truncated_video_keeps_its_prefix() -> Result<()> {
+    let config = config(0.5, "geometric");
+    let (info, data, output) = damage(&config, Faults::default().with_truncate_after(5)).await?;
+    check_ranges(&info, &data, &output);
+    assert_eq!(info.recovered.len(), 1);
+    assert_eq!(info.recovered_bytes(), (5 * config.chunk_size - HEADER_LEN) as u64);
+    Ok(())
+}